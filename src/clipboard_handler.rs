@@ -4,12 +4,9 @@ use std::sync::{
 	Arc, Condvar, Mutex,
 };
 
-use gelatin::image::imageops::{
-	flip_horizontal_in_place, flip_vertical_in_place, rotate180_in_place, rotate270, rotate90,
-};
-
-use crate::image_cache::image_loader::{
-	complex_load_image, ImageLoaderError, LoadResult, Orientation,
+use crate::image_cache::{
+	image_loader::{complex_load_image, ImageLoaderError, LoadResult},
+	pixel_ops,
 };
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -107,38 +104,9 @@ impl ClipboardHandler {
 				}
 			}
 			let result = complex_load_image(&request_path, false, 0, |frame| {
-				if let LoadResult::Frame { mut image, orientation, .. } = frame {
+				if let LoadResult::Frame { image, orientation, .. } = frame {
 					if let Ok(clipboard) = &mut clipboard {
-						// Note: the imageops functions use clockwise rotation whereas the
-						// `Orientation` type describes counter-clockwise rotation.
-						image = match orientation {
-							Orientation::Deg0 => image,
-							Orientation::Deg0HorFlip => {
-								flip_horizontal_in_place(&mut image);
-								image
-							}
-							Orientation::Deg90 => rotate270(&image),
-							Orientation::Deg90VerFlip => {
-								let mut result = rotate270(&image);
-								flip_vertical_in_place(&mut result);
-								result
-							}
-							Orientation::Deg180 => {
-								rotate180_in_place(&mut image);
-								image
-							}
-							Orientation::Deg180HorFlip => {
-								// This is identical to just a vertical flip with no rotation.
-								flip_vertical_in_place(&mut image);
-								image
-							}
-							Orientation::Deg270 => rotate90(&image),
-							Orientation::Deg270VerFlip => {
-								let mut result = rotate90(&image);
-								flip_vertical_in_place(&mut result);
-								result
-							}
-						};
+						let image = pixel_ops::bake_orientation(image, orientation);
 						let (w, h) = image.dimensions();
 						let cb_image = arboard::ImageData {
 							width: w as usize,
@@ -1,10 +1,186 @@
+use crate::image_cache::{
+	directory::{NameFilter, StartAt},
+	image_loader,
+};
+use crate::ipc::Request;
+use crate::playback_manager::DEFAULT_PRESENT_INTERVAL;
+use crate::playlist::Playlist;
 use crate::Version;
 use clap::{parser::ValueSource, value_parser, Arg, Command};
-use std::path::Path;
+use gelatin::image::RgbaImage;
+use std::env;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 pub struct Args {
-	pub file_path: Option<String>,
+	/// When there's a single path, its folder is browsed. When there are more, exactly the listed
+	/// files (and the files in the listed directories) are browsed.
+	pub file_paths: Vec<String>,
+	/// The path was `-`, the image should be read from the standard input
+	pub read_stdin: bool,
+	/// A file with one path on each line, or `-` for the standard input. See `read_file_list`
+	pub file_list: Option<String>,
+	/// Only the files matching this are browsed
+	pub name_filter: Option<NameFilter>,
 	pub displayed_folders: Option<u32>,
+	pub software: bool,
+	/// Set when started as `emulsion convert ...`, in this case no window is opened
+	pub convert: Option<ConvertArgs>,
+	/// Print diagnostic information for bug reports instead of opening a window
+	pub doctor: bool,
+	/// Print information about the files in `file_paths` instead of opening a window
+	pub info: bool,
+	/// Register the file associations with Windows instead of opening a window, see
+	/// `shell_integration`
+	pub register_associations: bool,
+	/// Remove what `register_associations` registered
+	pub unregister_associations: bool,
+	/// Write the default config file, see `configuration::default_file_text`
+	pub write_default_config: bool,
+	/// Start by showing the image on the clipboard
+	pub clipboard: bool,
+	/// Open a new window even when `single_instance` is enabled in the config
+	pub new_instance: bool,
+	/// Open the paths in a new window of the running instance, see `viewer_window`
+	pub new_window: bool,
+	/// Present the images in a second window, see `presenter`
+	pub presenter: bool,
+	/// The number of the monitor to present on, starting from 1
+	pub presenter_monitor: Option<u32>,
+	/// Start in fullscreen, regardless of `start_fullscreen` in the config
+	pub fullscreen: bool,
+	/// Start presenting the images, showing each one for this long. Set by `--slideshow` and by
+	/// `--shuffle`.
+	pub slideshow: Option<Duration>,
+	/// Present the images in a random order
+	pub shuffle: bool,
+	/// The image of the folder to show first
+	pub start_at: Option<StartAt>,
+	/// Overrides the size and the position of the window that were saved in the cache
+	pub geometry: Option<Geometry>,
+	/// Start maximized, regardless of the saved window state
+	pub maximized: bool,
+	/// Read and write the config from this file instead of the default location
+	pub config_path: Option<PathBuf>,
+	/// Read and write the cache from this file instead of the default location
+	pub cache_path: Option<PathBuf>,
+	/// Store the config and the cache next to the executable
+	pub portable: bool,
+	/// The name of the profile to start with, see `configuration::Profile`
+	pub profile: Option<String>,
+	/// Set when started as `emulsion remote ...`, in this case the request is sent to the running
+	/// instance and no window is opened
+	pub remote: Option<Request>,
+	/// Set when started with `--verify`, in this case no window is opened
+	pub verify: Option<VerifyArgs>,
+}
+
+/// See `editing::convert` and `editing::convert_into_folder`
+pub struct ConvertArgs {
+	pub inputs: Vec<PathBuf>,
+	/// A folder when there are several inputs or when it's an existing folder
+	pub output: PathBuf,
+	/// The extension of the images converted into a folder, the input's extension by default
+	pub format: Option<String>,
+	pub max_size: Option<u32>,
+	pub jpeg_quality: Option<u8>,
+}
+
+/// The size and the position of the window in physical pixels, parsed from `WxH+X+Y`, `WxH` or
+/// `+X+Y`. The position is relative to the top left corner of the screen and may be negative,
+/// like `+-1920+0`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Geometry {
+	pub size: Option<(u32, u32)>,
+	pub pos: Option<(i32, i32)>,
+}
+
+impl Geometry {
+	pub fn parse(text: &str) -> Result<Geometry, String> {
+		let invalid = || format!("{:?} is not like WxH+X+Y, WxH or +X+Y", text);
+		let text = text.trim();
+		let (size, pos) = match text.find(['+', '-']) {
+			Some(index) => (&text[..index], Some(&text[index..])),
+			None => (text, None),
+		};
+		let size = if size.is_empty() {
+			None
+		} else {
+			let (w, h) = size.split_once(['x', 'X']).ok_or_else(invalid)?;
+			let w = w.parse::<u32>().ok().filter(|w| *w > 0).ok_or_else(invalid)?;
+			let h = h.parse::<u32>().ok().filter(|h| *h > 0).ok_or_else(invalid)?;
+			Some((w, h))
+		};
+		let pos = match pos {
+			Some(pos) => {
+				// Y starts at the first sign after the digits of X, like the '-' in "+10-20"
+				let digits_start = pos.find(|c: char| c.is_ascii_digit()).ok_or_else(invalid)?;
+				let y_start = pos[digits_start..].find(['+', '-']).ok_or_else(invalid)?;
+				let y_start = digits_start + y_start;
+				let coordinate = |text: &str| {
+					let text = text.strip_prefix('+').unwrap_or(text);
+					text.parse::<i32>().map_err(|_| invalid())
+				};
+				Some((coordinate(&pos[..y_start])?, coordinate(&pos[y_start..])?))
+			}
+			None => None,
+		};
+		if size.is_none() && pos.is_none() {
+			return Err(invalid());
+		}
+		Ok(Geometry { size, pos })
+	}
+}
+
+/// See `verify::run`
+pub struct VerifyArgs {
+	pub paths: Vec<PathBuf>,
+	pub report: Option<PathBuf>,
+}
+
+/// The images to browse at startup
+pub enum StartupPaths {
+	None,
+	/// A single path. The folder of the image (or the folder itself) is browsed.
+	Single(String),
+	/// Only these files are browsed, see `Directory::set_file_list`
+	List(Playlist),
+}
+
+impl Args {
+	/// Reads the file list if there's one, see `read_file_list`
+	pub fn startup_paths(&self) -> StartupPaths {
+		if let Some(source) = &self.file_list {
+			return match read_file_list(source) {
+				Some(playlist) => StartupPaths::List(playlist),
+				None => StartupPaths::None,
+			};
+		}
+		StartupPaths::from_paths(self.file_paths.iter().map(PathBuf::from).collect())
+	}
+}
+
+impl StartupPaths {
+	pub fn from_paths(paths: Vec<PathBuf>) -> StartupPaths {
+		match paths.as_slice() {
+			[] => StartupPaths::None,
+			[path] => StartupPaths::Single(path.to_string_lossy().into_owned()),
+			_ => StartupPaths::List(Playlist { paths, current: None }),
+		}
+	}
+
+	/// The paths made absolute, so that they can be opened by an other process too
+	pub fn absolute_paths(&self) -> Vec<PathBuf> {
+		let current_dir = env::current_dir().unwrap_or_default();
+		match self {
+			StartupPaths::None => Vec::new(),
+			StartupPaths::Single(path) => vec![current_dir.join(path)],
+			StartupPaths::List(playlist) => {
+				playlist.paths.iter().map(|path| current_dir.join(path)).collect()
+			}
+		}
+	}
 }
 
 /// Parses the command-line arguments and returns the file path
@@ -12,7 +188,10 @@ pub fn parse_args(config_path: &Path, cache_path: &Path) -> Args {
 	// It's okay to leak this, because this code should only be executed once.
 	let config: &'static str = Box::leak(
 		format!(
-			"CONFIGURATION:\n    config file: {}\n    cache file:  {}",
+			"CONFIGURATION:\n    config file: {}\n    cache file:  {}\n\n\
+			Both are stored in the folder given in the EMULSION_CONFIG_DIR environment variable if \
+			it's set, or next to the executable with --portable or when a file called 'portable' is \
+			there.",
 			config_path.to_string_lossy(),
 			cache_path.to_string_lossy(),
 		)
@@ -45,19 +224,537 @@ pub fn parse_args(config_path: &Path, cache_path: &Path) -> Args {
 				.num_args(0)
 				.conflicts_with("FOLDER_COUNT"),
 		)
-		.arg(Arg::new("PATH").help("The file path of the image").index(1))
+		.arg(
+			Arg::new("software")
+				.long("software")
+				.help("Render on the CPU instead of using OpenGL")
+				.num_args(0),
+		)
+		.arg(
+			Arg::new("doctor")
+				.long("doctor")
+				.help("Print information about the system and the configuration for bug reports")
+				.num_args(0),
+		)
+		.arg(
+			Arg::new("register_associations")
+				.long("register-associations")
+				.help(
+					"Register Emulsion for the image files of the current user and add \"Open with \
+					Emulsion\" to the context menu of images and folders. Only on Windows, when \
+					built with the shell-integration feature",
+				)
+				.num_args(0),
+		)
+		.arg(
+			Arg::new("unregister_associations")
+				.long("unregister-associations")
+				.help("Remove what --register-associations registered")
+				.num_args(0)
+				.conflicts_with("register_associations"),
+		)
+		.arg(
+			Arg::new("info")
+				.long("info")
+				.help(
+					"Print the format, the dimensions, the color type, the rating and the EXIF \
+					summary of the given files, without opening a window",
+				)
+				.num_args(0)
+				.requires("PATH")
+				.conflicts_with_all(["clipboard", "FILE_LIST", "presenter", "verify"]),
+		)
+		.arg(
+			Arg::new("write_default_config")
+				.long("write-default-config")
+				.help(
+					"Write a config file that lists every option and the default keys of every \
+					action, all commented out, to the location given below or by --config. An \
+					existing file is never overwritten.",
+				)
+				.num_args(0),
+		)
+		.arg(
+			Arg::new("clipboard")
+				.long("clipboard")
+				.help("Show the image on the clipboard")
+				.num_args(0)
+				.conflicts_with_all(["PATH", "FILE_LIST"]),
+		)
+		.arg(
+			Arg::new("new_instance")
+				.long("new-instance")
+				.help("Open a new window even if `single_instance` is enabled in the config")
+				.num_args(0),
+		)
+		.arg(
+			Arg::new("new_window")
+				.long("new-window")
+				.help(
+					"Open the images in a new window of the emulsion that's already running, \
+					which needs `single_instance` or `remote_control` in its config",
+				)
+				.num_args(0)
+				.conflicts_with("new_instance"),
+		)
+		.arg(
+			Arg::new("presenter")
+				.long("presenter")
+				.value_name("MONITOR")
+				.help(
+					"Present the images as a fullscreen slideshow on an other monitor, while this \
+					window shows the next image and the elapsed time. The monitors are numbered \
+					from 1, like --presenter=2.",
+				)
+				.num_args(0..=1)
+				.require_equals(true)
+				.value_parser(value_parser!(u32).range(1..))
+				.conflicts_with_all(["clipboard", "software"]),
+		)
+		.arg(
+			Arg::new("fullscreen")
+				.long("fullscreen")
+				.help("Start in fullscreen")
+				.num_args(0)
+				.conflicts_with("software"),
+		)
+		.arg(
+			Arg::new("slideshow")
+				.long("slideshow")
+				.value_name("SECONDS")
+				.help(
+					"Start presenting the images, showing each one for the given number of \
+					seconds, like --slideshow=10. Each one is shown for 6 seconds by default.",
+				)
+				.num_args(0..=1)
+				.require_equals(true)
+				.value_parser(parse_seconds)
+				.conflicts_with_all(["clipboard", "software"]),
+		)
+		.arg(
+			Arg::new("shuffle")
+				.long("shuffle")
+				.help("Start presenting the images in a random order, see --slideshow")
+				.num_args(0)
+				.conflicts_with_all(["clipboard", "software"]),
+		)
+		.arg(
+			Arg::new("CONFIG")
+				.long("config")
+				.help("Read the configuration from this file instead of the one listed below")
+				.num_args(1)
+				.value_parser(value_parser!(PathBuf)),
+		)
+		.arg(
+			Arg::new("CACHE")
+				.long("cache")
+				.help(
+					"Store the window state and the other things that are remembered in this file \
+					instead of the one listed below",
+				)
+				.num_args(1)
+				.value_parser(value_parser!(PathBuf)),
+		)
+		.arg(
+			Arg::new("portable")
+				.long("portable")
+				.help(
+					"Store the configuration and the cache next to the executable, for running \
+					from a USB stick. Creating a file called 'portable' there does the same.",
+				)
+				.num_args(0),
+		)
+		.arg(
+			Arg::new("PROFILE")
+				.long("profile")
+				.help("Start with this profile from the [profiles] section of the configuration")
+				.num_args(1),
+		)
+		.arg(
+			Arg::new("GEOMETRY")
+				.long("geometry")
+				.help(
+					"The size and the position of the window in pixels, like 1280x720+100+50, \
+					1280x720 or +100+50. A negative position like -1920+0 is left of or above the \
+					top left corner of the main screen, it's not measured from the right or the \
+					bottom edge like in X11. Overrides the window area that was used last.",
+				)
+				.num_args(1)
+				.value_parser(|text: &str| Geometry::parse(text)),
+		)
+		.arg(
+			Arg::new("maximized")
+				.long("maximized")
+				.help("Start with the window maximized")
+				.num_args(0)
+				.conflicts_with("GEOMETRY"),
+		)
+		.arg(
+			Arg::new("START_AT")
+				.long("start-at")
+				.help(
+					"Open the folder at this image. Either its position in the folder starting \
+					from 1, its file name, or a pattern like '*.png' that picks the first \
+					matching image.",
+				)
+				.num_args(1)
+				.conflicts_with("clipboard")
+				.value_parser(|text: &str| Ok::<_, String>(StartAt::parse(text))),
+		)
+		.arg(
+			Arg::new("verify")
+				.long("verify")
+				.help(
+					"Decode the given files and the images in the given folders completely and \
+					report the damaged ones, without opening a window",
+				)
+				.num_args(0)
+				.requires("PATH")
+				.conflicts_with_all(["clipboard", "FILE_LIST", "presenter"]),
+		)
+		.arg(
+			Arg::new("REPORT")
+				.long("report")
+				.help("Write the report of --verify to this file instead of the standard output")
+				.num_args(1)
+				.requires("verify")
+				.value_parser(value_parser!(PathBuf)),
+		)
+		.arg(
+			Arg::new("PATH")
+				.help(
+					"The file path of the image, or - to read the image from the standard input. \
+					When multiple files or folders are given, only those are browsed.",
+				)
+				.num_args(0..)
+				.index(1),
+		)
+		.arg(
+			Arg::new("FILE_LIST")
+				.long("file-list")
+				.help(
+					"Browse the paths listed in this file, one on each line. \
+					Use - to read the list from the standard input.",
+				)
+				.num_args(1)
+				.conflicts_with("PATH"),
+		)
+		.arg(
+			Arg::new("FILTER")
+				.long("filter")
+				.help(
+					"Browse only the files with names matching one of these comma separated \
+					patterns, like \"*.png,*.jpg\"",
+				)
+				.num_args(1)
+				.value_parser(|text: &str| NameFilter::parse(text)),
+		)
+		.subcommand(
+			Command::new("convert")
+				.about("Convert images to the format of the output file, without opening a window")
+				.arg(
+					Arg::new("INPUT")
+						.help(
+							"The images to convert, or - to read a single image from the standard \
+							input",
+						)
+						.required(true)
+						.num_args(1..)
+						.value_parser(value_parser!(PathBuf)),
+				)
+				.arg(
+					Arg::new("OUTPUT")
+						.help(
+							"Where to save the result. The extension specifies the format. When \
+							there are several images or this is a folder, the images are saved \
+							into this folder with their names.",
+						)
+						.required(true)
+						.value_parser(value_parser!(PathBuf)),
+				)
+				.arg(
+					Arg::new("FORMAT")
+						.long("format")
+						.help(
+							"The extension, like png, of the images that are saved into a folder. \
+							They keep their format by default.",
+						)
+						.num_args(1),
+				)
+				.arg(
+					Arg::new("SIZE")
+						.long("size")
+						.help("Shrink the image so that neither of its sides are longer than this")
+						.num_args(1)
+						.value_parser(value_parser!(u32).range(1..)),
+				)
+				.arg(
+					Arg::new("QUALITY")
+						.long("quality")
+						.help("The quality of JPEG outputs, from 1 to 100")
+						.num_args(1)
+						.value_parser(value_parser!(u8).range(1..=100)),
+				),
+		)
+		.subcommand(
+			Command::new("remote")
+				.about(
+					"Control the emulsion window that's already open and print its response. \
+					Requires `remote_control` or `single_instance` in the config.",
+				)
+				.subcommand_required(true)
+				.subcommand(Command::new("next").about("Show the next image"))
+				.subcommand(Command::new("prev").about("Show the previous image"))
+				.subcommand(
+					Command::new("open")
+						.about("Open images like they were passed to emulsion")
+						.arg(
+							Arg::new("PATH")
+								.required(true)
+								.num_args(1..)
+								.value_parser(value_parser!(PathBuf)),
+						),
+				)
+				.subcommand(
+					Command::new("zoom")
+						.about("Set the zoom, 1 is the original size")
+						.arg(Arg::new("FACTOR").required(true).value_parser(value_parser!(f32))),
+				)
+				.subcommand(
+					Command::new("fullscreen")
+						.about("Enter or leave fullscreen, toggles it when no state is given")
+						.arg(Arg::new("STATE").value_parser(["on", "off"])),
+				)
+				.subcommand(
+					Command::new("overlay")
+						.about(
+							"Enter or leave the overlay mode of `toggle_overlay`, toggles it when \
+							no state is given",
+						)
+						.arg(Arg::new("STATE").value_parser(["on", "off"])),
+				)
+				.subcommand(
+					Command::new("query-current")
+						.about("Print the path, the index, and the zoom of the shown image"),
+				),
+		)
 		.get_matches();
 
-	let file_path = matches.get_one::<String>("PATH").cloned();
+	let convert = matches.subcommand_matches("convert").map(|matches| ConvertArgs {
+		inputs: matches.get_many::<PathBuf>("INPUT").unwrap().cloned().collect(),
+		output: matches.get_one::<PathBuf>("OUTPUT").unwrap().clone(),
+		format: matches
+			.get_one::<String>("FORMAT")
+			.map(|format| format.trim_start_matches('.').to_owned()),
+		max_size: matches.get_one::<u32>("SIZE").copied(),
+		jpeg_quality: matches.get_one::<u8>("QUALITY").copied(),
+	});
+
+	let remote = matches.subcommand_matches("remote").map(|matches| match matches.subcommand() {
+		Some(("next", _)) => Request::Next,
+		Some(("prev", _)) => Request::Prev,
+		Some(("open", matches)) => {
+			let current_dir = env::current_dir().unwrap_or_default();
+			let paths = matches.get_many::<PathBuf>("PATH").unwrap();
+			Request::Open { paths: paths.map(|path| current_dir.join(path)).collect() }
+		}
+		Some(("zoom", matches)) => {
+			Request::Zoom { factor: *matches.get_one::<f32>("FACTOR").unwrap() }
+		}
+		Some(("fullscreen", matches)) => Request::Fullscreen {
+			enabled: matches.get_one::<String>("STATE").map(|state| state == "on"),
+		},
+		Some(("overlay", matches)) => Request::Overlay {
+			enabled: matches.get_one::<String>("STATE").map(|state| state == "on"),
+		},
+		Some(("query-current", _)) => Request::QueryCurrent,
+		_ => unreachable!(),
+	});
+
+	let mut file_paths: Vec<String> = matches
+		.get_many::<String>("PATH")
+		.map(|paths| paths.cloned().collect())
+		.unwrap_or_default();
+	let read_stdin = file_paths == ["-"];
+	if read_stdin {
+		file_paths.clear();
+	}
+	// The desktop passes URIs when it's told to with `%U` in the desktop file
+	#[cfg(target_os = "linux")]
+	for path in &mut file_paths {
+		if let Some(uri_path) = crate::file_manager::path_from_uri(path) {
+			*path = uri_path.to_string_lossy().into_owned();
+		}
+	}
+
+	let file_list = matches.get_one::<String>("FILE_LIST").cloned();
+	let name_filter = matches.get_one::<NameFilter>("FILTER").cloned();
 
 	let is_absolute = matches.value_source("absolute") == Some(ValueSource::CommandLine);
 	let displayed_folders = if is_absolute {
 		// Subtract one because we later want to add one to this value, and we don't want
 		// an overflow
-		Some(std::u32::MAX - 1)
+		Some(u32::MAX - 1)
 	} else {
 		matches.get_one::<u32>("FOLDER_COUNT").copied()
 	};
 
-	Args { file_path, displayed_folders }
+	let software = matches.value_source("software") == Some(ValueSource::CommandLine);
+	let doctor = matches.value_source("doctor") == Some(ValueSource::CommandLine);
+	let info = matches.value_source("info") == Some(ValueSource::CommandLine);
+	let register_associations =
+		matches.value_source("register_associations") == Some(ValueSource::CommandLine);
+	let unregister_associations =
+		matches.value_source("unregister_associations") == Some(ValueSource::CommandLine);
+	let write_default_config =
+		matches.value_source("write_default_config") == Some(ValueSource::CommandLine);
+	let clipboard = matches.value_source("clipboard") == Some(ValueSource::CommandLine);
+	let new_instance = matches.value_source("new_instance") == Some(ValueSource::CommandLine);
+	let new_window = matches.value_source("new_window") == Some(ValueSource::CommandLine);
+	let presenter = matches.value_source("presenter") == Some(ValueSource::CommandLine);
+	let presenter_monitor = matches.get_one::<u32>("presenter").copied();
+	let fullscreen = matches.value_source("fullscreen") == Some(ValueSource::CommandLine);
+	let shuffle = matches.value_source("shuffle") == Some(ValueSource::CommandLine);
+	let slideshow = if matches.value_source("slideshow") == Some(ValueSource::CommandLine) {
+		Some(matches.get_one::<Duration>("slideshow").copied().unwrap_or(DEFAULT_PRESENT_INTERVAL))
+	} else if shuffle {
+		Some(DEFAULT_PRESENT_INTERVAL)
+	} else {
+		None
+	};
+	let verify =
+		(matches.value_source("verify") == Some(ValueSource::CommandLine)).then(|| VerifyArgs {
+			paths: file_paths.iter().map(PathBuf::from).collect(),
+			report: matches.get_one::<PathBuf>("REPORT").cloned(),
+		});
+
+	Args {
+		file_paths,
+		read_stdin,
+		file_list,
+		name_filter,
+		displayed_folders,
+		software,
+		convert,
+		doctor,
+		info,
+		register_associations,
+		unregister_associations,
+		write_default_config,
+		clipboard,
+		new_instance,
+		new_window,
+		presenter,
+		presenter_monitor,
+		fullscreen,
+		slideshow,
+		shuffle,
+		start_at: matches.get_one::<StartAt>("START_AT").cloned(),
+		geometry: matches.get_one::<Geometry>("GEOMETRY").copied(),
+		maximized: matches.value_source("maximized") == Some(ValueSource::CommandLine),
+		config_path: matches.get_one::<PathBuf>("CONFIG").map(|path| absolute_path(path)),
+		cache_path: matches.get_one::<PathBuf>("CACHE").map(|path| absolute_path(path)),
+		portable: matches.value_source("portable") == Some(ValueSource::CommandLine),
+		profile: matches.get_one::<String>("PROFILE").cloned(),
+		remote,
+		verify,
+	}
+}
+
+/// Reads and decodes the image that's piped to emulsion, like in `some-command | emulsion -`
+pub fn read_stdin_image() -> Option<RgbaImage> {
+	let mut bytes = Vec::new();
+	if let Err(e) = std::io::stdin().read_to_end(&mut bytes) {
+		log::error!("Could not read the standard input: {}", e);
+		return None;
+	}
+	match image_loader::load_image_from_memory(&bytes) {
+		Ok(image) => Some(image),
+		Err(e) => {
+			log::error!("Could not decode the image from the standard input: {}", e);
+			None
+		}
+	}
+}
+
+/// Reads the paths listed in `source`, which is either a playlist file or `-` for the standard
+/// input, like in `find . -name '*.png' | emulsion --file-list -`. See the `playlist` module for
+/// the format.
+pub fn read_file_list(source: &str) -> Option<Playlist> {
+	if source == "-" {
+		let mut text = String::new();
+		if let Err(e) = std::io::stdin().read_to_string(&mut text) {
+			log::error!("Could not read the file list from the standard input: {}", e);
+			return None;
+		}
+		return Some(Playlist::parse(&text, None));
+	}
+	match Playlist::load(Path::new(source)) {
+		Ok(playlist) => Some(playlist),
+		Err(e) => {
+			log::error!("Could not read the file list {:?}: {}", source, e);
+			None
+		}
+	}
+}
+
+/// Parses a positive number of seconds, like "2.5"
+fn parse_seconds(text: &str) -> Result<Duration, String> {
+	match text.parse::<f32>() {
+		Ok(seconds) if seconds.is_finite() && seconds > 0.0 => Ok(Duration::from_secs_f32(seconds)),
+		_ => Err(format!("{:?} is not a positive number of seconds", text)),
+	}
+}
+
+/// Resolves a relative path from the current folder
+fn absolute_path(path: &Path) -> PathBuf {
+	env::current_dir().map(|dir| dir.join(path)).unwrap_or_else(|_| path.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn geometry_size() {
+		let geometry = Geometry::parse("1280x720").unwrap();
+		assert_eq!(geometry, Geometry { size: Some((1280, 720)), pos: None });
+		assert_eq!(Geometry::parse(" 640X480 ").unwrap().size, Some((640, 480)));
+	}
+
+	#[test]
+	fn geometry_size_and_position() {
+		let geometry = Geometry::parse("1280x720+100+50").unwrap();
+		assert_eq!(geometry, Geometry { size: Some((1280, 720)), pos: Some((100, 50)) });
+		let geometry = Geometry::parse("+100+50").unwrap();
+		assert_eq!(geometry, Geometry { size: None, pos: Some((100, 50)) });
+	}
+
+	#[test]
+	fn geometry_negative_position() {
+		assert_eq!(Geometry::parse("800x600-1920+0").unwrap().pos, Some((-1920, 0)));
+		assert_eq!(Geometry::parse("+-1920+0").unwrap().pos, Some((-1920, 0)));
+		assert_eq!(Geometry::parse("+10-20").unwrap().pos, Some((10, -20)));
+		assert_eq!(Geometry::parse("-10-20").unwrap().pos, Some((-10, -20)));
+	}
+
+	#[test]
+	fn geometry_malformed() {
+		for text in [
+			"",
+			"1280",
+			"x720",
+			"1280x",
+			"0x720",
+			"1280x0",
+			"axb",
+			"1280x720+100",
+			"+100",
+			"+a+b",
+			"1280x720+",
+			"+100+50x",
+		] {
+			assert!(Geometry::parse(text).is_err(), "{:?} was accepted", text);
+		}
+	}
 }
@@ -0,0 +1,42 @@
+//! Reloading the config file when it's changed, so that new bindings and settings can be tried
+//! without restarting.
+//!
+//! Like `system_theme`, the file is polled on a background thread, which is simple and works the
+//! same way on every platform.
+
+use std::{
+	fs,
+	path::{Path, PathBuf},
+	thread,
+	time::{Duration, SystemTime},
+};
+
+use gelatin::winit::event_loop::EventLoopProxy;
+
+use crate::EmulsionEvent;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+	fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// Starts a background thread that sends `EmulsionEvent::ConfigChanged` through `proxy` whenever
+/// the file at `path` is modified, created or removed. The thread stops when the event loop is
+/// gone.
+pub fn watch(path: PathBuf, proxy: EventLoopProxy<EmulsionEvent>) {
+	thread::spawn(move || {
+		let mut prev = modified_time(&path);
+		loop {
+			thread::sleep(POLL_INTERVAL);
+			let curr = modified_time(&path);
+			if curr == prev {
+				continue;
+			}
+			prev = curr;
+			if proxy.send_event(EmulsionEvent::ConfigChanged).is_err() {
+				break;
+			}
+		}
+	});
+}
@@ -10,11 +10,139 @@
 use std::borrow::Cow;
 use std::collections::BTreeMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use serde::{Deserialize, Serialize};
 
+use crate::input_handling::{action_key_hint, DEFAULT_BINDINGS, EDIT_CONFIG_NAME};
+use crate::stats::{CacheStatsSection, ConfigStatsSection};
+
+/// The default config file without the bindings, which documents every option. Everything is commented
+/// out, so the defaults still apply until a line is uncommented. See `default_file_text`.
+const OPTIONS_TEXT: &str = r#"# The configuration of emulsion. The lines starting with '#' are ignored, remove the '#' to
+# change a setting. The changes apply as soon as the file is saved. The values shown are the
+# defaults, or examples where there's no default.
+
+[window]
+# theme = "auto"                # "light", "dark" or "auto" to follow the system, the last used
+#                               # theme by default
+# start_fullscreen = false
+# start_maximized = false
+# show_bottom_bar = true
+# adaptive_background = false   # the background follows the brightness of the image
+# ambient_background = false    # a blurred copy of the image fills the space around it
+# borderless = false            # no title bar, drag the strip at the top to move the window
+# always_on_top = false         # the window floats above the other windows
+# opacity = 100                 # in percent, the windows behind show through below 100
+# overlay_opacity = 50          # in percent, the opacity of the overlay mode of toggle_overlay
+# tray_icon = false             # an icon in the system tray with a menu, only on Linux
+# minimize_to_tray = false      # minimizing hides the window until the tray icon is clicked
+# use_last_window_area = true   # with false, the window starts at win_x, win_y, win_w, win_h
+# win_x = 64
+# win_y = 64
+# win_w = 580
+# win_h = 558
+# single_instance = false       # open images in the window that's already open
+# remote_control = false        # let other programs control the viewer, like `emulsion remote`
+# snap_to_integer_scale = false
+# fullscreen_monitor = 1        # the monitor that the window is on by default
+# fullscreen_insets = { top = 0.0, right = 0.0, bottom = 0.0, left = 0.0 }
+
+[image]
+# scaling = "auto"              # "auto", "nearest" or "linear", the last used one by default
+# downscaling = "fast"          # "high_quality" is sharper when zoomed out, but slower
+# on_file_removed = "next"      # "next", "placeholder" or "close"
+# use_trash = true
+# extra_extensions = ["raw"]
+# excluded_extensions = ["ico"]
+# remember_view = false
+# difference_gain = 8
+# onion_skin_opacity = 50
+# view_filters = []             # "grayscale", "sepia" and "invert"
+
+[title]
+# displayed_folders = 0
+# show_program_name = true
+
+[sequence]
+# fps = 25
+# loop = true
+# export_format = "gif"         # "gif" or "apng"
+# slideshow_seconds = 6
+
+# [updates]
+# check_updates = false
+
+[stats]
+# enabled = false
+
+# System-wide hotkeys, with the `hotkeys` feature
+[hotkeys]
+# show_clipboard = "Ctrl+Shift+V"
+# toggle_overlay = "Ctrl+Shift+O"
+# img_next = "Ctrl+Alt+Right"
+# img_prev = "Ctrl+Alt+Left"
+# toggle_slideshow = "Ctrl+Alt+Space"
+
+# The program of `open_with_editor`, instead of the "open with" dialog of the system
+# [editor]
+# program = "gimp"
+# args = ["${img}"]
+
+# [[commands]]
+# name = "Open in GIMP"         # shown in the context menu
+# input = ["G"]
+# program = "gimp"
+# args = ["${img}"]             # also ${folder}, ${filename}, ${index} and more
+# envs = [{ name = "LANG", value = "C" }]
+# cwd = "${folder}"
+# shell = false
+# wait = true
+# capture_output = false
+# reload_after = false
+
+# [[sort_folders]]
+# input = ["Alt+1"]
+# folder = "keep"               # relative to the folder of the image
+# copy = false
+
+# [[export_presets]]
+# name = "web"
+# format = "jpg"
+# quality = 85
+# max_size = 1920
+
+# [[view_rules]]
+# pattern = "*/sprites/*.png"
+# antialiasing = "nearest"
+# scaling = "original"          # "fit", "fit_stretch" or "original"
+# checkerboard = true
+
+# Profiles override some of the settings while they're active. One is chosen with --profile, and
+# cycle_profile switches to the next one.
+# [profiles.pixel-art]
+# theme = "dark"
+# antialiasing = "nearest"
+# bindings = { img_next = ["Space"] }
+
+# The keys of each action. Setting an action replaces all of its default keys.
+[bindings]
+"#;
+
+/// The default config file, with every option and every default binding commented out. It's
+/// written by `edit_config` when the file doesn't exist and by `--write-default-config`.
+pub fn default_file_text() -> String {
+	let mut text = OPTIONS_TEXT.to_owned();
+	let mut bindings = DEFAULT_BINDINGS.iter().collect::<Vec<_>>();
+	bindings.sort();
+	for (action, keys) in bindings {
+		let keys = keys.iter().map(|key| format!("{:?}", key)).collect::<Vec<_>>();
+		text.push_str(&format!("# {} = [{}]\n", action, keys.join(", ")));
+	}
+	text
+}
+
 #[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum Theme {
@@ -30,24 +158,121 @@ impl Theme {
 	}
 }
 
+/// The `theme` value of the config
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ThemeSetting {
+	Light,
+	Dark,
+	/// Follow the light or dark appearance of the operating system
+	Auto,
+}
+
+/// The filter that's used when the image is scaled
 #[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum Antialias {
+	/// Linear while the pixels of the image are smaller than a few pixels of the screen, nearest
+	/// when zoomed in further
 	#[default]
 	Auto,
+	/// Linear at every zoom level
+	#[serde(alias = "linear")]
 	Always,
+	/// Nearest neighbour at every zoom level, which keeps pixel art sharp
+	#[serde(alias = "nearest")]
 	Never,
 }
 
+/// How zoomed out images are drawn
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Downscaling {
+	/// One sample of the closest mipmap level
+	#[default]
+	Fast,
+	/// Several samples of a finer mipmap level, which is sharper but slower
+	HighQuality,
+}
+
+/// A change of the colors that the image is shown with, which helps to see its luminance
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ViewFilter {
+	Grayscale,
+	/// Brownish grayscale, like an old photo. It replaces `Grayscale`.
+	Sepia,
+	Invert,
+}
+
+/// The format of the animations written by `export_animation`
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnimationFormat {
+	#[default]
+	Gif,
+	Apng,
+}
+impl AnimationFormat {
+	pub fn extension(self) -> &'static str {
+		match self {
+			AnimationFormat::Gif => "gif",
+			AnimationFormat::Apng => "png",
+		}
+	}
+}
+
+/// What happens when the file of the displayed image is removed by an other program
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileRemovedAction {
+	/// Show the image that took the place of the removed one in the folder
+	#[default]
+	Next,
+	/// Show nothing until an other image is opened, but keep the position in the folder
+	Placeholder,
+	/// Close emulsion
+	Close,
+}
+
 #[derive(Debug, Default, Eq, PartialEq, Clone, Serialize, Deserialize)]
 pub struct CacheImageSection {
 	pub fit_stretches: bool,
 	pub antialiasing: Antialias,
+	#[serde(default)]
+	pub two_page_spread: bool,
+	#[serde(default)]
+	pub right_to_left: bool,
 }
 
 #[derive(Debug, Default, Eq, PartialEq, Clone, Deserialize)]
 pub struct ConfigImageSection {
+	/// `"auto"`, `"nearest"` or `"linear"`. It replaces `antialiasing`, which is still read when
+	/// this isn't set. Without either, the last choice of `toggle_antialias` is used.
+	pub scaling: Option<Antialias>,
 	pub antialiasing: Option<String>,
+	/// `"fast"` by default, see `Downscaling`
+	pub downscaling: Option<Downscaling>,
+	pub on_file_removed: Option<FileRemovedAction>,
+	/// `img_del` moves the file to the trash unless this is `false`, in which case it deletes the
+	/// file permanently like `img_del_permanent`
+	pub use_trash: Option<bool>,
+	/// Files with these extensions are browsed even if the format isn't recognized, they are
+	/// decoded according to their contents
+	pub extra_extensions: Option<Vec<String>>,
+	/// Files with these extensions are never browsed
+	pub excluded_extensions: Option<Vec<String>>,
+	/// Returning to an image that was already shown restores its zoom and position
+	pub remember_view: Option<bool>,
+	/// How much the differences are multiplied by in the amplified difference view and the
+	/// heatmap, 8 by default
+	pub difference_gain: Option<u32>,
+	/// How opaque the adjacent image is when it's blended over the shown one by `onion_skin`, in
+	/// percent, 50 by default
+	pub onion_skin_opacity: Option<u32>,
+	/// The filters that the images are shown with at the start, which the `toggle_grayscale`,
+	/// `toggle_sepia` and `toggle_invert` actions switch
+	pub view_filters: Option<Vec<ViewFilter>>,
 }
 
 #[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
@@ -70,23 +295,97 @@ pub struct ConfigWindowSection {
 	pub start_fullscreen: Option<bool>,
 	pub start_maximized: Option<bool>,
 	pub show_bottom_bar: Option<bool>,
-	pub theme: Option<Theme>,
+	pub theme: Option<ThemeSetting>,
+	pub adaptive_background: Option<bool>,
+	/// A blurred copy of the image is stretched behind it to fill the rest of the window
+	pub ambient_background: Option<bool>,
+	/// The window has no title bar and no borders, it's moved by dragging a strip at its top
+	pub borderless: Option<bool>,
+	/// The window floats above the other windows, which `toggle_on_top` switches
+	pub always_on_top: Option<bool>,
+	/// How opaque the window is in percent, 100 by default. Below 100 the windows behind it show
+	/// through, for using the image as a reference over an other program.
+	pub opacity: Option<u32>,
+	/// The opacity of the window in percent in the overlay mode of `toggle_overlay`. Setting it
+	/// creates the window so that it can be made translucent.
+	pub overlay_opacity: Option<u32>,
+	/// An icon in the system tray with a menu, only on Linux
+	pub tray_icon: Option<bool>,
+	/// Minimizing the window hides it until the tray icon is clicked, which implies `tray_icon`
+	pub minimize_to_tray: Option<bool>,
 	pub use_last_window_area: Option<bool>,
+	/// Opening images sends them to the window that's already open instead of opening a new one
+	pub single_instance: Option<bool>,
+	/// Lets other processes control the viewer, see `ipc` and `dbus`
+	pub remote_control: Option<bool>,
+	/// Resizing the window snaps to the sizes at which the fitted image is shown at a whole scale
+	pub snap_to_integer_scale: Option<bool>,
+	/// Space left empty at the edges of the screen in fullscreen, for displays with a notch or
+	/// rounded corners
+	pub fullscreen_insets: Option<Insets>,
+	/// The number of the monitor used in fullscreen, starting from 1. By default it's the monitor
+	/// that the window is on.
+	pub fullscreen_monitor: Option<u32>,
 	pub win_w: Option<u32>,
 	pub win_h: Option<u32>,
 	pub win_x: Option<i32>,
 	pub win_y: Option<i32>,
 }
 
-#[derive(Debug, Eq, PartialEq, Clone, Deserialize)]
+/// Sizes in logical pixels for each edge
+#[derive(Debug, Default, Copy, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Insets {
+	pub top: f32,
+	pub right: f32,
+	pub bottom: f32,
+	pub left: f32,
+}
+
+/// System-wide hotkeys, see `global_hotkeys`
+#[derive(Debug, Default, Eq, PartialEq, Clone, Deserialize)]
+pub struct ConfigHotkeysSection {
+	/// Brings the window to the front and shows the image on the clipboard
+	pub show_clipboard: Option<String>,
+	/// Enters or leaves the overlay mode, see `toggle_overlay`
+	pub toggle_overlay: Option<String>,
+	/// Like the `img_next` action, for controlling a slideshow while an other program is focused
+	pub img_next: Option<String>,
+	pub img_prev: Option<String>,
+	/// Pauses the presentation, or resumes it when it's paused
+	pub toggle_slideshow: Option<String>,
+}
+
+/// The program of the `open_with_editor` action. Without this section the "open with" dialog of
+/// the system is shown, where there's one.
+#[derive(Debug, Default, Eq, PartialEq, Clone, Deserialize)]
+pub struct ConfigEditorSection {
+	pub program: String,
+	/// The arguments may contain the same variables as those of the custom commands. The default
+	/// is `["${img}"]`.
+	pub args: Option<Vec<String>>,
+}
+
+/// Playing the images of the folder one after the other like the frames of an animation, with the
+/// `play_anim` action
+#[derive(Debug, Default, Eq, PartialEq, Clone, Deserialize)]
+pub struct ConfigSequenceSection {
+	/// The number of images shown per second, 25 by default
+	pub fps: Option<u32>,
+	/// Playing starts over after the last image unless this is `false`
+	#[serde(rename = "loop")]
+	pub looping: Option<bool>,
+	/// The format of the animations written by `export_animation`, GIF by default
+	pub export_format: Option<AnimationFormat>,
+	/// How many seconds each image is shown during a presentation (`play_present`), 6 by default
+	pub slideshow_seconds: Option<u32>,
+}
+
+/// Checking for updates is opt-in
+#[derive(Debug, Default, Eq, PartialEq, Clone, Deserialize)]
 pub struct ConfigUpdateSection {
 	pub check_updates: bool,
 }
-impl Default for ConfigUpdateSection {
-	fn default() -> Self {
-		Self { check_updates: true }
-	}
-}
 
 #[derive(Default, Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
 pub struct CacheUpdateSection {
@@ -115,6 +414,7 @@ struct IncompleteCache {
 	pub window: Option<CacheWindowSection>,
 	pub updates: Option<CacheUpdateSection>,
 	pub image: Option<CacheImageSection>,
+	pub stats: Option<CacheStatsSection>,
 }
 
 #[derive(Debug, Default, Eq, PartialEq, Clone, Serialize)]
@@ -122,6 +422,7 @@ pub struct Cache {
 	pub window: CacheWindowSection,
 	pub updates: CacheUpdateSection,
 	pub image: CacheImageSection,
+	pub stats: CacheStatsSection,
 }
 impl From<IncompleteCache> for Cache {
 	fn from(cache: IncompleteCache) -> Self {
@@ -129,6 +430,7 @@ impl From<IncompleteCache> for Cache {
 			window: cache.window.unwrap_or_default(),
 			updates: cache.updates.unwrap_or_default(),
 			image: cache.image.unwrap_or_default(),
+			stats: cache.stats.unwrap_or_default(),
 		}
 	}
 }
@@ -172,9 +474,110 @@ pub struct EnvVar {
 #[derive(Debug, Default, Eq, PartialEq, Clone, Deserialize)]
 pub struct Command {
 	pub input: Vec<String>,
+	/// The name of the command in the context menu, which is the program if this is missing
+	pub name: Option<String>,
 	pub program: String,
 	pub args: Option<Vec<String>>,
 	pub envs: Option<Vec<EnvVar>>,
+	/// The working directory of the program, which may contain the same variables as `args`
+	pub cwd: Option<String>,
+	/// Run `program` and `args` as a command line of the shell, `sh -c` or `cmd /C` on Windows
+	pub shell: Option<bool>,
+	/// Emulsion waits for the command to finish unless this is false
+	pub wait: Option<bool>,
+	/// Show the last line of the output of the command in the title
+	pub capture_output: Option<bool>,
+	/// Reload the shown image when the command finishes, for commands that edit it
+	pub reload_after: Option<bool>,
+}
+
+/// A folder that the shown image is moved (or copied) to by pressing `input`, for sorting photos
+#[derive(Debug, Default, Eq, PartialEq, Clone, Deserialize)]
+pub struct SortFolder {
+	pub input: Vec<String>,
+	/// A relative path is relative to the folder of the image. It's created if needed.
+	pub folder: PathBuf,
+	/// Copy the image instead of moving it
+	pub copy: Option<bool>,
+}
+
+/// A way of saving the shown image with `export_as`, which is chosen by its name
+#[derive(Debug, Default, Eq, PartialEq, Clone, Deserialize)]
+pub struct ExportPreset {
+	pub name: String,
+	/// The extension of the exported file, like "jpg", which also sets its format
+	pub format: String,
+	/// The quality of JPEGs from 1 to 100. The other formats are lossless.
+	pub quality: Option<u8>,
+	/// Larger images are shrunk so that neither of their sides are longer than this
+	pub max_size: Option<u32>,
+}
+impl ExportPreset {
+	/// The presets that are offered when none are configured
+	pub fn defaults() -> Vec<ExportPreset> {
+		let preset = |name: &str, quality| ExportPreset {
+			name: name.to_owned(),
+			format: name.to_owned(),
+			quality,
+			max_size: None,
+		};
+		vec![preset("png", None), preset("jpg", Some(90)), preset("webp", None)]
+	}
+}
+
+/// A named set of settings that override the config while it's active. It's chosen with
+/// `--profile` or by cycling through the profiles with `cycle_profile`.
+#[derive(Debug, Default, Eq, PartialEq, Clone, Deserialize)]
+pub struct Profile {
+	pub theme: Option<ThemeSetting>,
+	pub antialiasing: Option<Antialias>,
+	/// Replaces the keys of the listed actions, on top of the `[bindings]` section
+	pub bindings: Option<BTreeMap<String, Vec<String>>>,
+}
+
+/// How a view rule sizes the image
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ViewScaling {
+	/// Shrink the image to fit the window, but never enlarge it
+	Fit,
+	/// Shrink or enlarge the image to fit the window
+	FitStretch,
+	/// Show the image at its original size
+	Original,
+}
+
+/// View settings for the images that match `pattern`, applied whenever such an image is shown.
+/// The first rule that matches an image is used.
+#[derive(Debug, Default, Eq, PartialEq, Clone, Deserialize)]
+pub struct ViewRule {
+	/// A glob pattern, like `*.jpg`. Patterns with a `/` are matched against the whole path (like
+	/// `*/icons/*.png`), the others against the file name only. Case is ignored.
+	pub pattern: String,
+	pub antialiasing: Option<Antialias>,
+	pub scaling: Option<ViewScaling>,
+	/// Show a checkerboard behind the transparent parts of the image, this is the default
+	pub checkerboard: Option<bool>,
+}
+impl ViewRule {
+	pub fn matches(&self, path: &Path) -> bool {
+		let pattern = match glob::Pattern::new(&self.pattern) {
+			Ok(pattern) => pattern,
+			Err(e) => {
+				log::error!("Invalid pattern {:?} in a view rule: {}", self.pattern, e);
+				return false;
+			}
+		};
+		let options = glob::MatchOptions { case_sensitive: false, ..Default::default() };
+		if self.pattern.contains('/') {
+			// Windows paths are matched with forward slashes too
+			let path = path.to_string_lossy().replace('\\', "/");
+			pattern.matches_with(&path, options)
+		} else {
+			let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+			pattern.matches_with(&file_name, options)
+		}
+	}
 }
 
 #[derive(Debug, Default, Eq, PartialEq, Clone, Deserialize)]
@@ -223,14 +626,125 @@ pub struct Configuration {
 	pub title: Option<TitleSection>,
 	pub image: Option<ConfigImageSection>,
 	pub window: Option<ConfigWindowSection>,
+	pub hotkeys: Option<ConfigHotkeysSection>,
+	pub editor: Option<ConfigEditorSection>,
+	pub stats: Option<ConfigStatsSection>,
+	pub sequence: Option<ConfigSequenceSection>,
+	pub view_rules: Option<Vec<ViewRule>>,
+	pub sort_folders: Option<Vec<SortFolder>>,
+	pub export_presets: Option<Vec<ExportPreset>>,
+	pub profiles: Option<BTreeMap<String, Profile>>,
+
+	/// The name of the active profile, see `Profile`
+	#[serde(skip)]
+	pub profile: Option<String>,
+	/// The file that the config was loaded from
+	#[serde(skip)]
+	pub file_path: PathBuf,
+	/// Set when the config file couldn't be parsed and the default config is used instead
+	#[serde(skip)]
+	pub parse_error: Option<String>,
 }
 impl Configuration {
-	pub fn load<P: AsRef<Path>>(file_path: P) -> Result<Configuration, String> {
+	/// Loads the config from `file_path`. The default config is returned when the file can't be
+	/// read (e.g. because it doesn't exist yet).
+	///
+	/// When the file isn't valid, a copy of it is saved next to it with a `.broken` extension and
+	/// the default config is returned with `parse_error` set, so that the error can be shown.
+	pub fn load<P: AsRef<Path>>(file_path: P) -> Configuration {
 		let file_path = file_path.as_ref();
-		let cfg_str = fs::read_to_string(file_path)
-			.map_err(|_| format!("Could not read config from {:?}", file_path))?;
-		let result = toml::from_str(cfg_str.as_ref()).map_err(|e| format!("{}", e))?;
-		//println!("Read config from file:\n{:#?}", result);
-		Ok(result)
+		let mut config = match fs::read_to_string(file_path) {
+			Ok(cfg_str) => match toml::from_str(cfg_str.as_ref()) {
+				Ok(config) => config,
+				Err(e) => {
+					log::error!("Could not parse the config file {:?}\n{}", file_path, e);
+					let mut backup_path = file_path.as_os_str().to_owned();
+					backup_path.push(".broken");
+					if let Err(e) = fs::write(&backup_path, cfg_str) {
+						log::error!("Could not back up the config to {:?}: {}", backup_path, e);
+					}
+					Configuration { parse_error: Some(e.to_string()), ..Default::default() }
+				}
+			},
+			Err(_) => Configuration::default(),
+		};
+		config.file_path = file_path.to_owned();
+		config
+	}
+
+	/// Reads the config from `file_path` again after it was changed. Unlike `load`, an invalid file
+	/// isn't backed up, because it's likely still being edited. The error is returned instead.
+	pub fn reload(file_path: &Path) -> Result<Configuration, String> {
+		let mut config = match fs::read_to_string(file_path) {
+			Ok(cfg_str) => toml::from_str(&cfg_str).map_err(|e| e.to_string())?,
+			Err(_) => Configuration::default(),
+		};
+		config.file_path = file_path.to_owned();
+		Ok(config)
+	}
+
+	/// Opens the config file with the default program for editing it. The file is created with
+	/// `default_file_text` first if it doesn't exist yet.
+	pub fn open_in_editor(&self) {
+		if !self.file_path.exists() {
+			if let Some(folder) = self.file_path.parent() {
+				let _ = fs::create_dir_all(folder);
+			}
+			if let Err(e) = fs::write(&self.file_path, default_file_text()) {
+				log::error!("Could not create the config file {:?}: {}", self.file_path, e);
+				return;
+			}
+		}
+		if let Err(e) = open::that(&self.file_path) {
+			log::error!("Could not open the config file {:?}: {}", self.file_path, e);
+		}
+	}
+
+	pub fn active_profile(&self) -> Option<&Profile> {
+		self.profiles.as_ref()?.get(self.profile.as_ref()?)
+	}
+
+	/// The profile after the active one in the alphabetical order, or `None` after the last one,
+	/// which means that no profile is used
+	pub fn next_profile(&self) -> Option<String> {
+		let mut names = self.profiles.iter().flat_map(|profiles| profiles.keys());
+		match &self.profile {
+			Some(profile) => names.skip_while(|name| *name != profile).nth(1).cloned(),
+			None => names.next().cloned(),
+		}
+	}
+
+	/// The keys bound to the action in the active profile or in the `[bindings]` section, or
+	/// `None` if the default keys are used
+	pub fn bindings_of(&self, action_name: &str) -> Option<&Vec<String>> {
+		let profile_bindings = self.active_profile().and_then(|p| p.bindings.as_ref());
+		match profile_bindings.and_then(|bindings| bindings.get(action_name)) {
+			Some(keys) => Some(keys),
+			None => self.bindings.as_ref()?.get(action_name),
+		}
+	}
+
+	/// The `theme` of the active profile or of the `[window]` section
+	pub fn theme_setting(&self) -> Option<ThemeSetting> {
+		let profile_theme = self.active_profile().and_then(|profile| profile.theme);
+		profile_theme.or_else(|| self.window.as_ref()?.theme)
+	}
+
+	/// The first view rule that matches `path`
+	pub fn view_rule(&self, path: &Path) -> Option<&ViewRule> {
+		self.view_rules.as_ref()?.iter().find(|rule| rule.matches(path))
+	}
+
+	/// Returns a title prefix that tells about the `parse_error`, or an empty string if the
+	/// config is valid.
+	pub fn format_parse_error(&self) -> String {
+		let error = match &self.parse_error {
+			Some(error) => error.lines().next().unwrap_or_default(),
+			None => return String::new(),
+		};
+		match action_key_hint(self, EDIT_CONFIG_NAME) {
+			Some(key) => format!("[ CONFIG ERROR: {}, press {} to edit ] ", error, key),
+			None => format!("[ CONFIG ERROR: {} ] ", error),
+		}
 	}
 }
@@ -0,0 +1,199 @@
+//! A D-Bus service on Linux that offers the requests of the `ipc` module to desktop automation
+//! tools, and a signal when the shown image changes.
+//!
+//! The service is published on the session bus when `remote_control` is enabled in the config.
+//! For example `busctl --user call io.github.arturkovacs.emulsion /io/github/arturkovacs/emulsion
+//! io.github.arturkovacs.emulsion.Viewer Next` shows the next image.
+//!
+//! The `org.freedesktop.Application` interface is published when `single_instance` is enabled, so
+//! that desktops that start applications over D-Bus, and Flatpak through the portals, can pass the
+//! opened files as URIs to the emulsion that's running. It isn't published otherwise, because the
+//! desktop would then send the files to the first window instead of opening a new one.
+
+use std::{
+	collections::HashMap,
+	path::{Path, PathBuf},
+	sync::Mutex,
+};
+
+use gelatin::winit::event_loop::EventLoopProxy;
+use zbus::{
+	blocking::{connection, Connection},
+	fdo, interface,
+	object_server::SignalContext,
+	zvariant::OwnedValue,
+};
+
+use crate::{
+	file_manager,
+	ipc::{self, Request},
+	EmulsionEvent,
+};
+
+const BUS_NAME: &str = "io.github.arturkovacs.emulsion";
+const OBJECT_PATH: &str = "/io/github/arturkovacs/emulsion";
+
+/// Keeps the service published until it and its clones are dropped
+#[derive(Clone)]
+pub struct DbusService {
+	connection: Connection,
+	signal_context: SignalContext<'static>,
+}
+
+impl DbusService {
+	/// Publishes the service, with the `org.freedesktop.Application` interface when
+	/// `single_instance` is set and the `Viewer` interface when `remote_control` is set. Fails when
+	/// there's no session bus or when an other emulsion window has already published it.
+	pub fn start(
+		proxy: EventLoopProxy<EmulsionEvent>,
+		single_instance: bool,
+		remote_control: bool,
+	) -> zbus::Result<DbusService> {
+		let mut builder = connection::Builder::session()?.name(BUS_NAME)?;
+		if single_instance {
+			let application = Application { proxy: Mutex::new(proxy.clone()) };
+			builder = builder.serve_at(OBJECT_PATH, application)?;
+		}
+		if remote_control {
+			builder = builder.serve_at(OBJECT_PATH, Viewer { proxy: Mutex::new(proxy) })?;
+		}
+		let connection = builder.build()?;
+		let signal_context = SignalContext::new(connection.inner(), OBJECT_PATH)?;
+		Ok(DbusService { connection, signal_context })
+	}
+
+	/// Emits the `ImageChanged` signal. This doesn't block, the signal is sent in the background.
+	pub fn image_changed(&self, path: &Path) {
+		let path = path.to_string_lossy().into_owned();
+		let signal_context = self.signal_context.clone();
+		let executor = self.connection.inner().executor();
+		let emit = async move {
+			if let Err(e) = Viewer::image_changed(&signal_context, &path).await {
+				log::warn!("Could not emit the ImageChanged D-Bus signal: {}", e);
+			}
+		};
+		executor.spawn(emit, "emit ImageChanged").detach();
+	}
+}
+
+struct Viewer {
+	proxy: Mutex<EventLoopProxy<EmulsionEvent>>,
+}
+
+impl Viewer {
+	/// Waits for the response on a thread of the `blocking` pool, so that the other D-Bus
+	/// messages are handled in the meantime
+	async fn request(&self, request: Request) -> fdo::Result<ipc::Response> {
+		let proxy = self.proxy.lock().unwrap().clone();
+		let response = blocking::unblock(move || ipc::dispatch(&proxy, request))
+			.await
+			.ok_or_else(|| fdo::Error::Failed("The window is closed".to_owned()))?;
+		match response.error {
+			Some(error) => Err(fdo::Error::Failed(error)),
+			None => Ok(response),
+		}
+	}
+}
+
+#[interface(name = "io.github.arturkovacs.emulsion.Viewer")]
+impl Viewer {
+	/// Opens absolute paths like they were passed on the command line
+	async fn open(&self, paths: Vec<String>) -> fdo::Result<()> {
+		let paths = paths.into_iter().map(PathBuf::from).collect();
+		self.request(Request::Open { paths }).await.map(drop)
+	}
+
+	async fn next(&self) -> fdo::Result<()> {
+		self.request(Request::Next).await.map(drop)
+	}
+
+	async fn prev(&self) -> fdo::Result<()> {
+		self.request(Request::Prev).await.map(drop)
+	}
+
+	async fn zoom(&self, factor: f64) -> fdo::Result<()> {
+		self.request(Request::Zoom { factor: factor as f32 }).await.map(drop)
+	}
+
+	async fn set_fullscreen(&self, enabled: bool) -> fdo::Result<()> {
+		self.request(Request::Fullscreen { enabled: Some(enabled) }).await.map(drop)
+	}
+
+	async fn toggle_fullscreen(&self) -> fdo::Result<()> {
+		self.request(Request::Fullscreen { enabled: None }).await.map(drop)
+	}
+
+	async fn set_overlay(&self, enabled: bool) -> fdo::Result<()> {
+		self.request(Request::Overlay { enabled: Some(enabled) }).await.map(drop)
+	}
+
+	async fn toggle_overlay(&self) -> fdo::Result<()> {
+		self.request(Request::Overlay { enabled: None }).await.map(drop)
+	}
+
+	/// The path is empty and the index and the count are -1 when they aren't known
+	#[zbus(out_args("path", "index", "count", "zoom", "fullscreen"))]
+	async fn query_current(&self) -> fdo::Result<(String, i64, i64, f64, bool)> {
+		let current = match self.request(Request::QueryCurrent).await?.current {
+			Some(current) => current,
+			None => return Err(fdo::Error::Failed("No current image in the response".to_owned())),
+		};
+		let path = current.path.map(|path| path.to_string_lossy().into_owned());
+		Ok((
+			path.unwrap_or_default(),
+			current.index.map_or(-1, |index| index as i64),
+			current.count.map_or(-1, |count| count as i64),
+			current.zoom as f64,
+			current.fullscreen,
+		))
+	}
+
+	#[zbus(signal)]
+	async fn image_changed(signal_context: &SignalContext<'_>, path: &str) -> zbus::Result<()>;
+}
+
+/// The platform data of the methods of `org.freedesktop.Application`, like the activation token
+type PlatformData = HashMap<String, OwnedValue>;
+
+struct Application {
+	proxy: Mutex<EventLoopProxy<EmulsionEvent>>,
+}
+
+impl Application {
+	/// Brings the window to the front and opens `paths` in it, if there are any
+	fn open_paths(&self, paths: Vec<PathBuf>) {
+		// This fails when the event loop is already gone, in which case there's nothing to open
+		let _ = self.proxy.lock().unwrap().send_event(EmulsionEvent::OpenPaths(paths));
+	}
+}
+
+#[interface(name = "org.freedesktop.Application")]
+impl Application {
+	fn activate(&self, _platform_data: PlatformData) {
+		self.open_paths(Vec::new());
+	}
+
+	/// Opens the `file://` URIs like the paths passed on the command line
+	fn open(&self, uris: Vec<String>, _platform_data: PlatformData) {
+		let paths = uris
+			.iter()
+			.filter_map(|uri| {
+				let path = file_manager::path_from_uri(uri);
+				if path.is_none() {
+					log::warn!("Only local files can be opened, skipping {}", uri);
+				}
+				path
+			})
+			.collect();
+		self.open_paths(paths);
+	}
+
+	fn activate_action(
+		&self,
+		action_name: String,
+		_parameter: Vec<OwnedValue>,
+		_platform_data: PlatformData,
+	) -> fdo::Result<()> {
+		Err(fdo::Error::NotSupported(format!("There's no action called {}", action_name)))
+	}
+}
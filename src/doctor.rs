@@ -0,0 +1,126 @@
+//! The `--doctor` report, which collects everything that's usually needed to investigate a bug
+//! report into a single block of text.
+
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use gelatin::image::ImageFormat;
+
+use crate::configuration::{Cache, Configuration};
+use crate::Version;
+
+/// Prints the report to the standard output
+pub fn run(config_path: &Path, cache_path: &Path) {
+	println!("{}", report(config_path, cache_path));
+}
+
+fn report(config_path: &Path, cache_path: &Path) -> String {
+	let mut out = String::new();
+	// Writing to a String can't fail
+	let _ = writeln!(out, "emulsion {}", Version::cargo_pkg_version());
+	let _ = writeln!(out, "os: {} {}", std::env::consts::OS, std::env::consts::ARCH);
+	if let Ok(release) = sys_info::os_release() {
+		let _ = writeln!(out, "os release: {}", release);
+	}
+	let _ = writeln!(out, "features: {}", enabled_features());
+
+	let _ = writeln!(out, "\n[opengl]");
+	match gelatin::window::probe_opengl() {
+		Ok(info) => {
+			let _ = writeln!(out, "vendor: {}", info.vendor);
+			let _ = writeln!(out, "renderer: {}", info.renderer);
+			let _ = writeln!(out, "version: {}", info.version);
+			let _ = writeln!(out, "max texture size: {}", info.max_texture_size);
+		}
+		Err(e) => {
+			let _ = writeln!(out, "unavailable, emulsion falls back to software rendering ({})", e);
+		}
+	}
+
+	let _ = writeln!(out, "\n[files]");
+	let _ = writeln!(out, "config: {:?} ({})", config_path, config_status(config_path));
+	let cache_status = match (cache_path.exists(), Cache::load(cache_path)) {
+		(false, _) => "not found".to_owned(),
+		(true, Ok(_)) => "valid".to_owned(),
+		(true, Err(e)) => format!("invalid: {}", first_line(&e)),
+	};
+	let _ = writeln!(out, "cache: {:?} ({})", cache_path, cache_status);
+
+	let _ = writeln!(out, "\n[decoders]");
+	let mut formats = ImageFormat::all()
+		.filter(ImageFormat::reading_enabled)
+		.map(|format| format!("{:?}", format))
+		.collect::<Vec<_>>();
+	formats.push("Svg".to_owned());
+	let _ = writeln!(out, "{}", formats.join(", "));
+
+	let _ = writeln!(out, "\n[integrations]");
+	let clipboard = match arboard::Clipboard::new() {
+		Ok(_) => "available".to_owned(),
+		Err(e) => format!("unavailable ({})", e),
+	};
+	let _ = writeln!(out, "clipboard: {}", clipboard);
+	let _ = writeln!(out, "trash: {}", trash_status());
+	#[cfg(feature = "video")]
+	{
+		use crate::image_cache::video::program_status;
+		let _ = writeln!(out, "ffmpeg: {}", program_status("ffmpeg"));
+		let _ = writeln!(out, "ffprobe: {}", program_status("ffprobe"));
+	}
+	out
+}
+
+fn enabled_features() -> String {
+	let features = [
+		("networking", cfg!(feature = "networking")),
+		("avif", cfg!(feature = "avif")),
+		("benchmark", cfg!(feature = "benchmark")),
+		("video", cfg!(feature = "video")),
+	];
+	let enabled = features.iter().filter(|(_, on)| *on).map(|(name, _)| *name).collect::<Vec<_>>();
+	if enabled.is_empty() {
+		"none".to_owned()
+	} else {
+		enabled.join(", ")
+	}
+}
+
+/// Unlike `Configuration::load` this doesn't back up invalid files
+fn config_status(config_path: &Path) -> String {
+	let text = match fs::read_to_string(config_path) {
+		Ok(text) => text,
+		Err(_) => return "not found, using the defaults".to_owned(),
+	};
+	match toml::from_str::<Configuration>(&text) {
+		Ok(_) => "valid".to_owned(),
+		Err(e) => format!("invalid: {}", first_line(&e.to_string())),
+	}
+}
+
+fn first_line(text: &str) -> &str {
+	text.lines().next().unwrap_or_default()
+}
+
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+fn trash_status() -> String {
+	match trash::os_limited::trash_folders() {
+		Ok(folders) if folders.is_empty() => "no trash folders found".to_owned(),
+		Ok(folders) => format!("freedesktop, {} trash folder(s)", folders.len()),
+		Err(e) => format!("unavailable ({})", e),
+	}
+}
+
+#[cfg(not(all(
+	unix,
+	not(target_os = "macos"),
+	not(target_os = "ios"),
+	not(target_os = "android")
+)))]
+fn trash_status() -> String {
+	if cfg!(windows) {
+		"Windows recycle bin".to_owned()
+	} else {
+		"macOS trash".to_owned()
+	}
+}
@@ -0,0 +1,77 @@
+//! Dragging the shown file out of the window, for the `drag_out` action, so that it can be dropped
+//! into a chat, an e-mail or a file manager.
+//!
+//! Winit can only receive drops, so the drag is started by a small helper window that shows up at
+//! the cursor, and the file is dragged from there. On Linux that's dragon-drop or ripdrag, which
+//! have to be installed. On Windows the window is made with Windows Forms through PowerShell.
+//! There's no such helper on macOS.
+
+use std::{io, path::Path, thread};
+
+/// Shows the helper window that the file can be dragged from. This doesn't block, the helper runs
+/// in the background and the errors are logged.
+pub fn start(path: &Path) {
+	let path = path.to_owned();
+	thread::spawn(move || {
+		if let Err(e) = start_blocking(&path) {
+			log::error!("Could not drag {:?} out of the window: {}", path, e);
+		}
+	});
+}
+
+#[cfg(target_os = "linux")]
+fn start_blocking(path: &Path) -> io::Result<()> {
+	use std::process::Command;
+
+	// Both close their window after the file was dropped somewhere
+	let status = match Command::new("dragon-drop").arg("--and-exit").arg(path).status() {
+		Err(e) if e.kind() == io::ErrorKind::NotFound => {
+			Command::new("ripdrag").arg("--and-exit").arg(path).status()
+		}
+		status => status,
+	};
+	match status {
+		Err(e) if e.kind() == io::ErrorKind::NotFound => Err(io::Error::new(
+			io::ErrorKind::NotFound,
+			"dragging needs dragon-drop or ripdrag to be installed",
+		)),
+		status => status.map(|_| ()),
+	}
+}
+
+#[cfg(target_os = "windows")]
+fn start_blocking(path: &Path) -> io::Result<()> {
+	use std::process::Command;
+
+	// The path is passed in the environment so that it doesn't have to be quoted in the script
+	const SCRIPT: &str = "\
+		Add-Type -AssemblyName System.Windows.Forms, System.Drawing
+		$path = $env:EMULSION_DRAG_PATH
+		$files = New-Object System.Collections.Specialized.StringCollection
+		[void]$files.Add($path)
+		$data = New-Object System.Windows.Forms.DataObject
+		$data.SetFileDropList($files)
+		$form = New-Object System.Windows.Forms.Form
+		$form.Text = 'Drag the file'
+		$form.TopMost = $true
+		$form.StartPosition = 'Manual'
+		$form.Location = [System.Windows.Forms.Cursor]::Position
+		$form.Size = New-Object System.Drawing.Size(280, 90)
+		$label = New-Object System.Windows.Forms.Label
+		$label.Dock = 'Fill'
+		$label.TextAlign = 'MiddleCenter'
+		$label.Text = [IO.Path]::GetFileName($path)
+		$label.Add_MouseDown({ [void]$label.DoDragDrop($data, 'Copy'); $form.Close() })
+		$form.Controls.Add($label)
+		[void]$form.ShowDialog()";
+	Command::new("powershell")
+		.args(["-NoProfile", "-Sta", "-Command", SCRIPT])
+		.env("EMULSION_DRAG_PATH", path)
+		.status()?;
+	Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+fn start_blocking(_path: &Path) -> io::Result<()> {
+	Err(io::Error::new(io::ErrorKind::Unsupported, "dragging files out isn't supported here"))
+}
@@ -0,0 +1,103 @@
+//! Encoding a sequence of images into an animated GIF or APNG, for the `export_animation` action.
+//!
+//! The images are decoded one at a time and written to the file right away, so long sequences
+//! don't have to fit into memory. Images of a different size than the first one are stretched to
+//! its size.
+
+use std::{
+	fs::File,
+	io::BufWriter,
+	path::{Path, PathBuf},
+	sync::mpsc,
+	thread,
+};
+
+use gelatin::image::{
+	self,
+	codecs::gif::{GifEncoder, Repeat},
+	imageops::{self, FilterType},
+	Delay, Frame, RgbaImage,
+};
+
+use super::EditError;
+use crate::{configuration::AnimationFormat, image_cache::image_loader};
+
+/// Decodes the image at `path` and stretches it to `size` if that's given
+fn load_frame(path: &Path, size: Option<(u32, u32)>) -> Result<RgbaImage, EditError> {
+	let image = image_loader::load_first_frame(path)?;
+	match size {
+		Some((w, h)) if image.dimensions() != (w, h) => {
+			Ok(imageops::resize(&image, w, h, FilterType::Triangle))
+		}
+		_ => Ok(image),
+	}
+}
+
+/// Writes the images at `paths` to `target` as an animation that shows `fps` images per second
+/// and loops forever
+pub fn export(
+	paths: &[PathBuf],
+	target: &Path,
+	fps: u32,
+	format: AnimationFormat,
+) -> Result<(), EditError> {
+	let fps = fps.clamp(1, u16::MAX as u32);
+	let first = match paths.first() {
+		Some(path) => load_frame(path, None)?,
+		None => return Ok(()),
+	};
+	let size = first.dimensions();
+	let frames = std::iter::once(Ok(first))
+		.chain(paths[1..].iter().map(|path| load_frame(path, Some(size))));
+	let file = BufWriter::new(File::create(target).map_err(image::ImageError::IoError)?);
+	match format {
+		AnimationFormat::Gif => {
+			let mut encoder = GifEncoder::new(file);
+			encoder.set_repeat(Repeat::Infinite)?;
+			for frame in frames {
+				let delay = Delay::from_numer_denom_ms(1000, fps);
+				encoder.encode_frame(Frame::from_parts(frame?, 0, 0, delay))?;
+			}
+		}
+		AnimationFormat::Apng => {
+			let mut encoder = png::Encoder::new(file, size.0, size.1);
+			encoder.set_color(png::ColorType::Rgba);
+			encoder.set_depth(png::BitDepth::Eight);
+			// Zero plays means that it loops forever
+			encoder.set_animated(paths.len() as u32, 0)?;
+			encoder.set_frame_delay(1, fps as u16)?;
+			let mut writer = encoder.write_header()?;
+			for frame in frames {
+				writer.write_image_data(&frame?)?;
+			}
+			writer.finish()?;
+		}
+	}
+	Ok(())
+}
+
+/// Exports an animation on an other thread
+pub struct BackgroundExport {
+	receiver: mpsc::Receiver<Result<PathBuf, String>>,
+}
+
+impl BackgroundExport {
+	pub fn start(
+		paths: Vec<PathBuf>,
+		target: PathBuf,
+		fps: u32,
+		format: AnimationFormat,
+	) -> BackgroundExport {
+		let (sender, receiver) = mpsc::channel();
+		thread::spawn(move || {
+			let result = export(&paths, &target, fps, format);
+			let _ = sender.send(result.map(|()| target).map_err(|e| e.to_string()));
+		});
+		BackgroundExport { receiver }
+	}
+
+	/// Returns the path of the animation, or the error, once the export has finished
+	pub fn try_finish(&self) -> Option<Result<PathBuf, String>> {
+		self.receiver.try_recv().ok()
+	}
+}
@@ -0,0 +1,160 @@
+use std::mem;
+
+use gelatin::cgmath::Vector2;
+
+/// A rectangle of texels in the displayed (ie oriented) image
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct TexelRect {
+	pub x: u32,
+	pub y: u32,
+	pub w: u32,
+	pub h: u32,
+}
+
+#[derive(Debug, Copy, Clone)]
+enum Drag {
+	None,
+	/// The edges that are set to true follow the cursor
+	Edges {
+		left: bool,
+		right: bool,
+		top: bool,
+		bottom: bool,
+	},
+	/// The whole selection follows the cursor. `offset` points from the top left corner of the
+	/// selection to the cursor.
+	Move {
+		offset: Vector2<f32>,
+	},
+}
+
+/// The selection of the crop tool.
+///
+/// All positions are in texel coordinates of the displayed image and are not rounded, so that
+/// the selection can be adjusted smoothly when zoomed out. Rounding and clamping to the image
+/// only happens in `selection`.
+#[derive(Debug, Clone)]
+pub struct CropTool {
+	/// Top left corner
+	min: Vector2<f32>,
+	/// Bottom right corner
+	max: Vector2<f32>,
+	has_selection: bool,
+	drag: Drag,
+}
+
+impl CropTool {
+	pub fn new() -> Self {
+		CropTool {
+			min: Vector2::new(0.0, 0.0),
+			max: Vector2::new(0.0, 0.0),
+			has_selection: false,
+			drag: Drag::None,
+		}
+	}
+
+	pub fn is_dragging(&self) -> bool {
+		!matches!(self.drag, Drag::None)
+	}
+
+	pub fn clear(&mut self) {
+		self.has_selection = false;
+		self.drag = Drag::None;
+	}
+
+	/// Starts dragging at `pos`.
+	///
+	/// Grabbing an edge (or a corner) of the current selection adjusts it, grabbing the inside
+	/// moves it and anything else starts a new selection. `grab_distance` is how far the cursor
+	/// may be from an edge to grab it, in texels.
+	pub fn begin_drag(&mut self, pos: Vector2<f32>, grab_distance: f32) {
+		if self.has_selection {
+			let near = |a: f32, b: f32| (a - b).abs() <= grab_distance;
+			let within_x =
+				pos.x >= self.min.x - grab_distance && pos.x <= self.max.x + grab_distance;
+			let within_y =
+				pos.y >= self.min.y - grab_distance && pos.y <= self.max.y + grab_distance;
+			let left = within_y && near(pos.x, self.min.x);
+			let right = within_y && !left && near(pos.x, self.max.x);
+			let top = within_x && near(pos.y, self.min.y);
+			let bottom = within_x && !top && near(pos.y, self.max.y);
+			if left || right || top || bottom {
+				self.drag = Drag::Edges { left, right, top, bottom };
+				return;
+			}
+			let inside = pos.x > self.min.x
+				&& pos.x < self.max.x
+				&& pos.y > self.min.y
+				&& pos.y < self.max.y;
+			if inside {
+				self.drag = Drag::Move { offset: pos - self.min };
+				return;
+			}
+		}
+		self.min = pos;
+		self.max = pos;
+		self.has_selection = true;
+		self.drag = Drag::Edges { left: false, right: true, top: false, bottom: true };
+	}
+
+	pub fn drag_to(&mut self, pos: Vector2<f32>) {
+		match &mut self.drag {
+			Drag::None => {}
+			Drag::Move { offset } => {
+				let size = self.max - self.min;
+				self.min = pos - *offset;
+				self.max = self.min + size;
+			}
+			Drag::Edges { left, right, top, bottom } => {
+				if *left {
+					self.min.x = pos.x;
+				}
+				if *right {
+					self.max.x = pos.x;
+				}
+				if *top {
+					self.min.y = pos.y;
+				}
+				if *bottom {
+					self.max.y = pos.y;
+				}
+				// Dragging an edge over the opposite one flips the selection, after which the
+				// opposite edge is the one being dragged.
+				if self.min.x > self.max.x {
+					mem::swap(&mut self.min.x, &mut self.max.x);
+					mem::swap(left, right);
+				}
+				if self.min.y > self.max.y {
+					mem::swap(&mut self.min.y, &mut self.max.y);
+					mem::swap(top, bottom);
+				}
+			}
+		}
+	}
+
+	pub fn end_drag(&mut self) {
+		self.drag = Drag::None;
+	}
+
+	/// Returns the selection clamped to an image of `w` x `h` texels or `None` if there's no
+	/// selection or if it doesn't cover any texels of the image.
+	pub fn selection(&self, w: u32, h: u32) -> Option<TexelRect> {
+		if !self.has_selection {
+			return None;
+		}
+		let x0 = self.min.x.round().clamp(0.0, w as f32) as u32;
+		let y0 = self.min.y.round().clamp(0.0, h as f32) as u32;
+		let x1 = self.max.x.round().clamp(0.0, w as f32) as u32;
+		let y1 = self.max.y.round().clamp(0.0, h as f32) as u32;
+		if x1 <= x0 || y1 <= y0 {
+			return None;
+		}
+		Some(TexelRect { x: x0, y: y0, w: x1 - x0, h: y1 - y0 })
+	}
+}
+
+impl Default for CropTool {
+	fn default() -> Self {
+		CropTool::new()
+	}
+}
@@ -0,0 +1,219 @@
+//! Operations that produce new images out of the one that is being viewed.
+//!
+//! The original file is never modified, results are either written to a new file next to the
+//! original or copied to the clipboard. Images can also be pasted from the clipboard or converted
+//! to a different format from the command line.
+
+use std::fs::{self, File};
+use std::io::{BufWriter, Read};
+use std::path::{Path, PathBuf};
+
+use gelatin::image::{
+	self, codecs::jpeg::JpegEncoder, imageops, imageops::FilterType, DynamicImage, ImageFormat,
+	RgbaImage,
+};
+
+use crate::image_cache::{
+	image_loader::{self, ImageLoaderError, Orientation},
+	pixel_ops,
+};
+
+pub mod animation;
+pub mod crop;
+
+pub use crop::{CropTool, TexelRect};
+
+#[derive(Debug, thiserror::Error)]
+pub enum EditError {
+	#[error("Failed to encode the image: {0}")]
+	Image(#[from] image::ImageError),
+
+	#[error("Failed to access the clipboard: {0}")]
+	Clipboard(#[from] arboard::Error),
+
+	#[error("The clipboard image has an unexpected size")]
+	InvalidClipboardImage,
+
+	#[error("Failed to load the image: {0}")]
+	Load(#[from] ImageLoaderError),
+
+	#[error("Failed to encode the APNG: {0}")]
+	Apng(#[from] png::EncodingError),
+}
+
+/// Cuts `rect` out of an image that is displayed with `orientation`.
+///
+/// `rect` is in the coordinates of the displayed image while `pixels` are the stored ones, so only
+/// the region that is needed gets reoriented.
+pub fn crop(pixels: &RgbaImage, orientation: Orientation, rect: TexelRect) -> RgbaImage {
+	let (w, h) = pixels.dimensions();
+	let corner_a = pixel_ops::oriented_to_raw(orientation, w, h, rect.x, rect.y);
+	let corner_b =
+		pixel_ops::oriented_to_raw(orientation, w, h, rect.x + rect.w - 1, rect.y + rect.h - 1);
+	let x = corner_a.0.min(corner_b.0);
+	let y = corner_a.1.min(corner_b.1);
+	let raw_w = corner_a.0.max(corner_b.0) - x + 1;
+	let raw_h = corner_a.1.max(corner_b.1) - y + 1;
+	let cropped = imageops::crop_imm(pixels, x, y, raw_w, raw_h).to_image();
+	pixel_ops::bake_orientation(cropped, orientation)
+}
+
+/// Returns a path next to `original` that doesn't exist yet, like "photo-cropped.jpg" or
+/// "photo-cropped-2.jpg".
+pub fn derived_file_path(original: &Path, suffix: &str) -> PathBuf {
+	let stem = original.file_stem().map(|s| s.to_string_lossy()).unwrap_or_default();
+	// Keep the format of the original if it can store an 8 bit RGB(A) image of any size
+	let extension = match ImageFormat::from_path(original) {
+		Ok(
+			ImageFormat::Png
+			| ImageFormat::Jpeg
+			| ImageFormat::Bmp
+			| ImageFormat::Tiff
+			| ImageFormat::Tga
+			| ImageFormat::WebP
+			| ImageFormat::Pnm,
+		) => original.extension().unwrap_or_default().to_string_lossy().into_owned(),
+		_ => "png".to_owned(),
+	};
+	let folder = original.parent().unwrap_or_else(|| Path::new(""));
+	unused_file_path(folder, &format!("{}-{}", stem, suffix), &extension)
+}
+
+/// Returns a path in `folder` that doesn't exist yet, like "pasted.png" or "pasted-2.png".
+pub fn unused_file_path(folder: &Path, stem: &str, extension: &str) -> PathBuf {
+	let mut candidate = folder.join(format!("{}.{}", stem, extension));
+	let mut counter = 2;
+	while candidate.exists() {
+		candidate = folder.join(format!("{}-{}.{}", stem, counter, extension));
+		counter += 1;
+	}
+	candidate
+}
+
+/// Saves the image in the format that the extension of `path` specifies. The alpha channel is
+/// dropped for formats that can't store it.
+pub fn save_image(image: RgbaImage, path: &Path) -> Result<(), EditError> {
+	save_image_with_quality(image, path, None)
+}
+
+/// Like `save_image`, but JPEGs are encoded with `jpeg_quality` (1 to 100) if it's specified.
+pub fn save_image_with_quality(
+	image: RgbaImage,
+	path: &Path,
+	jpeg_quality: Option<u8>,
+) -> Result<(), EditError> {
+	let format = ImageFormat::from_path(path)?;
+	let image = DynamicImage::ImageRgba8(image);
+	match (format, jpeg_quality) {
+		(ImageFormat::Jpeg, Some(quality)) => {
+			let file = File::create(path).map_err(image::ImageError::IoError)?;
+			let encoder = JpegEncoder::new_with_quality(BufWriter::new(file), quality);
+			DynamicImage::ImageRgb8(image.into_rgb8()).write_with_encoder(encoder)?
+		}
+		(ImageFormat::Jpeg | ImageFormat::Pnm, _) => {
+			DynamicImage::ImageRgb8(image.into_rgb8()).save_with_format(path, format)?
+		}
+		_ => image.save_with_format(path, format)?,
+	}
+	Ok(())
+}
+
+/// Decodes `input` (or the standard input if it's `-`) and saves it to `output` in the format that
+/// the extension of `output` specifies. Only the first frame of animations is converted and the
+/// EXIF orientation is applied to the pixels.
+///
+/// When `max_size` is specified, the image is shrunk so that neither of its sides are longer than
+/// `max_size`. See `save_image_with_quality` for `jpeg_quality`.
+pub fn convert(
+	input: &Path,
+	output: &Path,
+	max_size: Option<u32>,
+	jpeg_quality: Option<u8>,
+) -> Result<(), EditError> {
+	let image = if input == Path::new("-") {
+		let mut bytes = Vec::new();
+		std::io::stdin().read_to_end(&mut bytes).map_err(ImageLoaderError::from)?;
+		image_loader::load_image_from_memory(&bytes)?
+	} else {
+		image_loader::load_first_frame(input)?
+	};
+	save_image_with_quality(shrink_to_fit(image, max_size), output, jpeg_quality)
+}
+
+/// Converts each of `inputs` with `convert` to a file of the same name in `folder`, which is
+/// created if needed. The extension is replaced with `extension` if it's specified. The paths of
+/// the converted images are printed, the errors are printed and skipped.
+///
+/// Returns false if any of the images couldn't be converted.
+pub fn convert_into_folder(
+	inputs: &[PathBuf],
+	folder: &Path,
+	extension: Option<&str>,
+	max_size: Option<u32>,
+	jpeg_quality: Option<u8>,
+) -> bool {
+	if let Err(e) = fs::create_dir_all(folder) {
+		eprintln!("Could not create {:?}: {}", folder, e);
+		return false;
+	}
+	let mut all_converted = true;
+	for input in inputs {
+		let name = match input.file_name() {
+			Some(name) if input != Path::new("-") => name,
+			_ => {
+				eprintln!("Only files can be converted into a folder, skipping {:?}", input);
+				all_converted = false;
+				continue;
+			}
+		};
+		let mut output = folder.join(name);
+		if let Some(extension) = extension {
+			output.set_extension(extension);
+		}
+		// Converting into the folder of the images mustn't overwrite them
+		if output.exists() && fs::canonicalize(&output).ok() == fs::canonicalize(input).ok() {
+			eprintln!("Skipping {:?}, it would be overwritten", input);
+			all_converted = false;
+			continue;
+		}
+		match convert(input, &output, max_size, jpeg_quality) {
+			Ok(()) => println!("{}", output.display()),
+			Err(e) => {
+				eprintln!("Could not convert {:?}: {}", input, e);
+				all_converted = false;
+			}
+		}
+	}
+	all_converted
+}
+
+/// Shrinks the image so that neither of its sides are longer than `max_size`, if it's specified
+pub fn shrink_to_fit(image: RgbaImage, max_size: Option<u32>) -> RgbaImage {
+	let (w, h) = image.dimensions();
+	match max_size {
+		Some(max_size) if w.max(h) > max_size => {
+			let scale = max_size as f64 / w.max(h) as f64;
+			let new_w = ((w as f64 * scale).round() as u32).max(1);
+			let new_h = ((h as f64 * scale).round() as u32).max(1);
+			imageops::resize(&image, new_w, new_h, FilterType::Lanczos3)
+		}
+		_ => image,
+	}
+}
+
+pub fn copy_image_to_clipboard(image: RgbaImage) -> Result<(), EditError> {
+	let (w, h) = image.dimensions();
+	let image_data = arboard::ImageData {
+		width: w as usize,
+		height: h as usize,
+		bytes: image.into_raw().into(),
+	};
+	arboard::Clipboard::new()?.set_image(image_data)?;
+	Ok(())
+}
+
+pub fn paste_image_from_clipboard() -> Result<RgbaImage, EditError> {
+	let image_data = arboard::Clipboard::new()?.get_image()?;
+	let (w, h) = (image_data.width as u32, image_data.height as u32);
+	RgbaImage::from_raw(w, h, image_data.bytes.into_owned()).ok_or(EditError::InvalidClipboardImage)
+}
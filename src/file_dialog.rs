@@ -0,0 +1,88 @@
+//! The file dialog of the system, for opening images and folders without the command line, and for
+//! saving and opening playlists.
+//!
+//! On Linux the file chooser of the XDG desktop portal is shown, which is the dialog of the
+//! desktop and also works inside a Flatpak. The dialogs are shown from a background thread, so
+//! the window keeps drawing while they're open.
+
+use std::{path::PathBuf, thread};
+
+use gelatin::winit::event_loop::EventLoopProxy;
+use rfd::AsyncFileDialog;
+
+use crate::{playlist::Playlist, EmulsionEvent};
+
+/// The extensions that are listed by default, the others can still be chosen with "All files"
+const IMAGE_EXTENSIONS: &[&str] = &[
+	"jpg", "jpeg", "png", "apng", "gif", "webp", "tif", "tiff", "tga", "bmp", "ico", "hdr", "pbm",
+	"pam", "ppm", "pgm", "avif", "svg",
+];
+
+const PLAYLIST_EXTENSIONS: &[&str] = &["m3u", "m3u8", "txt"];
+
+/// Lets the user choose images and sends `EmulsionEvent::OpenPaths` through `proxy` with them.
+/// This doesn't block. Nothing is sent when the dialog is cancelled.
+pub fn pick_images(proxy: EventLoopProxy<EmulsionEvent>) {
+	thread::spawn(move || {
+		let dialog = AsyncFileDialog::new()
+			.set_title("Open")
+			.add_filter("Images", IMAGE_EXTENSIONS)
+			.add_filter("All files", &["*"]);
+		let files = pollster::block_on(dialog.pick_files()).unwrap_or_default();
+		let paths: Vec<PathBuf> = files.iter().map(|file| file.path().to_owned()).collect();
+		if !paths.is_empty() {
+			// This fails when the event loop is already gone, in which case there's nothing to do
+			let _ = proxy.send_event(EmulsionEvent::OpenPaths(paths));
+		}
+	});
+}
+
+/// Lets the user choose a folder and sends `EmulsionEvent::OpenFolder` through `proxy` with it,
+/// like `pick_images`
+pub fn pick_folder(proxy: EventLoopProxy<EmulsionEvent>) {
+	thread::spawn(move || {
+		let dialog = AsyncFileDialog::new().set_title("Open Folder");
+		if let Some(folder) = pollster::block_on(dialog.pick_folder()) {
+			let _ = proxy.send_event(EmulsionEvent::OpenFolder(folder.path().to_owned()));
+		}
+	});
+}
+
+/// Lets the user choose a playlist file and sends `EmulsionEvent::OpenPlaylist` through `proxy`
+/// with its contents, like `pick_images`
+pub fn pick_playlist(proxy: EventLoopProxy<EmulsionEvent>) {
+	thread::spawn(move || {
+		let dialog = AsyncFileDialog::new()
+			.set_title("Open Playlist")
+			.add_filter("Playlists", PLAYLIST_EXTENSIONS)
+			.add_filter("All files", &["*"]);
+		let file = match pollster::block_on(dialog.pick_file()) {
+			Some(file) => file,
+			None => return,
+		};
+		match Playlist::load(file.path()) {
+			Ok(playlist) => {
+				let _ = proxy.send_event(EmulsionEvent::OpenPlaylist(playlist));
+			}
+			Err(e) => log::error!("Could not read the playlist {:?}: {}", file.path(), e),
+		}
+	});
+}
+
+/// Lets the user choose where `playlist` is saved and saves it there. This doesn't block.
+pub fn save_playlist(playlist: Playlist) {
+	thread::spawn(move || {
+		let dialog = AsyncFileDialog::new()
+			.set_title("Save Playlist")
+			.set_file_name("playlist.m3u")
+			.add_filter("Playlists", PLAYLIST_EXTENSIONS);
+		let file = match pollster::block_on(dialog.save_file()) {
+			Some(file) => file,
+			None => return,
+		};
+		match playlist.save(file.path()) {
+			Ok(()) => log::info!("Saved the playlist to {:?}", file.path()),
+			Err(e) => log::error!("Could not save the playlist to {:?}: {}", file.path(), e),
+		}
+	});
+}
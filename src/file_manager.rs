@@ -0,0 +1,214 @@
+//! Showing a file in the file manager of the system with the file selected, for the
+//! `reveal_in_file_manager` action, and the "open with" dialog of the system for the
+//! `open_with_editor` action.
+//!
+//! Explorer and Finder are started with the arguments that select the file. On Linux the file
+//! manager is asked over D-Bus with the `org.freedesktop.FileManager1` interface, which most file
+//! managers implement. Where that's not available, the folder of the file is opened without
+//! selecting it.
+//!
+//! The "open with" dialog is the one of the shell on Windows and the application chooser of the
+//! XDG desktop portal on Linux. Elsewhere the file is opened with its default program instead.
+
+use std::{io, path::Path, thread};
+
+/// Shows the file in the file manager. This doesn't block, the file manager is started in the
+/// background and the errors are logged.
+pub fn reveal(path: &Path) {
+	let path = path.to_owned();
+	thread::spawn(move || {
+		if let Err(e) = reveal_blocking(&path) {
+			log::error!("Could not show {:?} in the file manager: {}", path, e);
+		}
+	});
+}
+
+/// Lets the user choose the program that opens the file. Like `reveal`, this doesn't block.
+pub fn open_with_dialog(path: &Path) {
+	let path = path.to_owned();
+	thread::spawn(move || {
+		if let Err(e) = open_with_dialog_blocking(&path) {
+			log::error!("Could not open {:?}: {}", path, e);
+		}
+	});
+}
+
+#[cfg(target_os = "windows")]
+fn open_with_dialog_blocking(path: &Path) -> io::Result<()> {
+	std::process::Command::new("rundll32.exe")
+		.arg("shell32.dll,OpenAs_RunDLL")
+		.arg(path)
+		.status()?;
+	Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn open_with_dialog_blocking(path: &Path) -> io::Result<()> {
+	match open_uri_asking(path) {
+		Ok(()) => Ok(()),
+		Err(e) => {
+			log::warn!("Could not show the application chooser, opening the file: {}", e);
+			open::that(path)
+		}
+	}
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+fn open_with_dialog_blocking(path: &Path) -> io::Result<()> {
+	open::that(path)
+}
+
+#[cfg(target_os = "windows")]
+fn reveal_blocking(path: &Path) -> io::Result<()> {
+	use std::{os::windows::process::CommandExt, process::Command};
+
+	// Explorer doesn't follow the usual quoting rules, the path has to be quoted after the comma.
+	// It also exits with 1 even when it succeeds, so the status isn't checked.
+	Command::new("explorer").raw_arg(format!("/select,\"{}\"", path.display())).status()?;
+	Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn reveal_blocking(path: &Path) -> io::Result<()> {
+	let status = std::process::Command::new("open").arg("-R").arg(path).status()?;
+	if !status.success() {
+		return Err(io::Error::other(format!("open failed with {}", status)));
+	}
+	Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn reveal_blocking(path: &Path) -> io::Result<()> {
+	match show_items(path) {
+		Ok(()) => Ok(()),
+		Err(e) => {
+			log::warn!("Could not select the file over D-Bus, opening its folder: {}", e);
+			open_folder(path)
+		}
+	}
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+fn reveal_blocking(path: &Path) -> io::Result<()> {
+	open_folder(path)
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn open_folder(path: &Path) -> io::Result<()> {
+	open::that(path.parent().unwrap_or(path))
+}
+
+/// Calls `ShowItems` of `org.freedesktop.FileManager1`, which starts the file manager if needed
+#[cfg(target_os = "linux")]
+fn show_items(path: &Path) -> zbus::Result<()> {
+	const FILE_MANAGER: &str = "org.freedesktop.FileManager1";
+
+	let path = path.canonicalize().unwrap_or_else(|_| path.to_owned());
+	let uris = vec![file_uri(&path)];
+	let connection = zbus::blocking::Connection::session()?;
+	// The second argument is the startup id, which can be left empty
+	connection.call_method(
+		Some(FILE_MANAGER),
+		"/org/freedesktop/FileManager1",
+		Some(FILE_MANAGER),
+		"ShowItems",
+		&(uris, ""),
+	)?;
+	Ok(())
+}
+
+/// Calls `OpenURI` of the XDG desktop portal with `ask` set, which makes the portal show the
+/// application chooser
+#[cfg(target_os = "linux")]
+fn open_uri_asking(path: &Path) -> zbus::Result<()> {
+	use std::collections::HashMap;
+	use zbus::zvariant::Value;
+
+	let path = path.canonicalize().unwrap_or_else(|_| path.to_owned());
+	let mut options = HashMap::new();
+	options.insert("ask", Value::from(true));
+	let connection = zbus::blocking::Connection::session()?;
+	// The first argument is the parent window, which can be left empty
+	connection.call_method(
+		Some("org.freedesktop.portal.Desktop"),
+		"/org/freedesktop/portal/desktop",
+		Some("org.freedesktop.portal.OpenURI"),
+		"OpenURI",
+		&("", file_uri(&path), options),
+	)?;
+	Ok(())
+}
+
+/// The path of a `file://` URI, with the percent-encoded bytes decoded. Returns `None` for other
+/// URIs and for files on other hosts.
+#[cfg(target_os = "linux")]
+pub fn path_from_uri(uri: &str) -> Option<std::path::PathBuf> {
+	use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+	let path = uri.strip_prefix("file://")?;
+	let path = path.strip_prefix("localhost").unwrap_or(path);
+	if !path.starts_with('/') {
+		return None;
+	}
+	let encoded = path.as_bytes();
+	let mut bytes = Vec::with_capacity(encoded.len());
+	let mut i = 0;
+	while i < encoded.len() {
+		let hex = encoded.get(i + 1..i + 3).and_then(|hex| std::str::from_utf8(hex).ok());
+		match hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+			Some(byte) if encoded[i] == b'%' => {
+				bytes.push(byte);
+				i += 3;
+			}
+			_ => {
+				bytes.push(encoded[i]);
+				i += 1;
+			}
+		}
+	}
+	Some(OsStr::from_bytes(&bytes).into())
+}
+
+/// A `file://` URI of the absolute path, in which the bytes other than the unreserved characters
+/// and the slashes are percent-encoded
+#[cfg(target_os = "linux")]
+pub fn file_uri(path: &Path) -> String {
+	use std::os::unix::ffi::OsStrExt;
+
+	let mut uri = String::from("file://");
+	for &byte in path.as_os_str().as_bytes() {
+		if byte.is_ascii_alphanumeric() || b"/-._~".contains(&byte) {
+			uri.push(byte as char);
+		} else {
+			uri.push_str(&format!("%{:02X}", byte));
+		}
+	}
+	uri
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+	use std::path::PathBuf;
+
+	use super::*;
+
+	#[test]
+	fn uri_to_path() {
+		let path = path_from_uri("file:///home/me/a%20b%25c%C3%A9.png").unwrap();
+		assert_eq!(path, PathBuf::from("/home/me/a b%cé.png"));
+		let path = path_from_uri("file://localhost/tmp/100%/%zz%4").unwrap();
+		assert_eq!(path, PathBuf::from("/tmp/100%/%zz%4"));
+		assert_eq!(path_from_uri("file://server/share/a.png"), None);
+		assert_eq!(path_from_uri("https://example.com/a.png"), None);
+		assert_eq!(path_from_uri("/home/me/a.png"), None);
+		assert_eq!(path_from_uri("FILE:///a.png"), None);
+	}
+
+	#[test]
+	fn path_to_uri() {
+		let path = Path::new("/home/me/a b#?%é~_-.png");
+		let uri = file_uri(path);
+		assert_eq!(uri, "file:///home/me/a%20b%23%3F%25%C3%A9~_-.png");
+		assert_eq!(path_from_uri(&uri).as_deref(), Some(path));
+	}
+}
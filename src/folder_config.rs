@@ -0,0 +1,130 @@
+//! Settings for the images of a single folder, read from a `.emulsion.toml` file in that folder.
+//!
+//! They override the config while the folder is browsed, for folders that are always viewed the
+//! same way. For example a folder of sprites can be shown without antialiasing on a checkerboard:
+//!
+//! ```toml
+//! antialiasing = "never"
+//! checkerboard = true
+//! background = "#202020"
+//! ```
+
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::configuration::{Antialias, ViewRule, ViewScaling};
+use crate::image_cache::directory::NameFilter;
+
+pub const FILE_NAME: &str = ".emulsion.toml";
+
+/// The order in which the images of the folder are browsed
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+	/// By file name, with the numbers in the names compared by their values
+	#[default]
+	Name,
+	/// The least recently modified first
+	Modified,
+	/// The smallest file first
+	Size,
+}
+
+/// What starts when the folder is opened
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StartMode {
+	Fullscreen,
+	/// Presenting the images like `play_present`
+	Slideshow,
+	/// Playing the images like the frames of an animation, like `play_anim`
+	Play,
+}
+
+#[derive(Debug, Default, Clone, Eq, PartialEq, Deserialize)]
+pub struct FolderConfig {
+	pub sort: Option<SortOrder>,
+	/// Browse the images in the opposite order of `sort`
+	pub reverse: Option<bool>,
+	/// Comma separated file name patterns like `--filter`, only the matching images are browsed
+	pub filter: Option<String>,
+	/// The color behind the image, like `"#202020"`
+	pub background: Option<String>,
+	pub antialiasing: Option<Antialias>,
+	pub scaling: Option<ViewScaling>,
+	pub checkerboard: Option<bool>,
+	pub start: Option<StartMode>,
+}
+
+impl FolderConfig {
+	/// Reads the `.emulsion.toml` in `folder`. The default is returned when there's none or when
+	/// it's not valid.
+	pub fn load(folder: &Path) -> FolderConfig {
+		let path = folder.join(FILE_NAME);
+		let text = match fs::read_to_string(&path) {
+			Ok(text) => text,
+			Err(_) => return FolderConfig::default(),
+		};
+		match toml::from_str(&text) {
+			Ok(config) => config,
+			Err(e) => {
+				log::error!("Could not parse the folder config {:?}\n{}", path, e);
+				FolderConfig::default()
+			}
+		}
+	}
+
+	pub fn name_filter(&self) -> Option<NameFilter> {
+		let text = self.filter.as_deref()?;
+		match NameFilter::parse(text) {
+			Ok(filter) => Some(filter),
+			Err(e) => {
+				log::error!("Invalid filter {:?} in a folder config: {}", text, e);
+				None
+			}
+		}
+	}
+
+	/// The `background` as an RGBA color. Both `#rrggbb` and `#rgb` are accepted.
+	pub fn background_color(&self) -> Option<[f32; 4]> {
+		let text = self.background.as_deref()?;
+		let color = parse_hex_color(text);
+		if color.is_none() {
+			log::error!("Invalid background {:?} in a folder config, expected #rrggbb", text);
+		}
+		color
+	}
+
+	/// Overrides the view settings of `rule` with those of the folder
+	pub fn override_view_rule(&self, rule: Option<ViewRule>) -> Option<ViewRule> {
+		if self.antialiasing.is_none() && self.scaling.is_none() && self.checkerboard.is_none() {
+			return rule;
+		}
+		let rule = rule.unwrap_or_default();
+		Some(ViewRule {
+			antialiasing: self.antialiasing.or(rule.antialiasing),
+			scaling: self.scaling.or(rule.scaling),
+			checkerboard: self.checkerboard.or(rule.checkerboard),
+			..rule
+		})
+	}
+}
+
+fn parse_hex_color(text: &str) -> Option<[f32; 4]> {
+	let hex = text.trim().strip_prefix('#')?;
+	if !hex.is_ascii() {
+		return None;
+	}
+	let digits = match hex.len() {
+		3 => hex.chars().map(|c| c.to_string().repeat(2)).collect::<Vec<_>>(),
+		6 => (0..3).map(|i| hex[i * 2..i * 2 + 2].to_owned()).collect(),
+		_ => return None,
+	};
+	let mut color = [1.0; 4];
+	for (channel, digits) in color.iter_mut().zip(&digits) {
+		*channel = u8::from_str_radix(digits, 16).ok()? as f32 / 255.0;
+	}
+	Some(color)
+}
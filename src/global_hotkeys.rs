@@ -0,0 +1,115 @@
+//! System-wide hotkeys, which work even when emulsion isn't focused.
+//!
+//! These are only available when emulsion is built with the `hotkeys` feature. The hotkeys are
+//! configured in the `[hotkeys]` section of the config and are written like the key bindings,
+//! for example `"CmdCtrl+Shift+V"`.
+
+use gelatin::winit::event_loop::EventLoopProxy;
+
+use crate::{
+	configuration::{ConfigHotkeysSection, Configuration},
+	EmulsionEvent,
+};
+
+/// Creates the event that a hotkey sends
+type MakeEvent = fn() -> EmulsionEvent;
+
+/// Keeps the hotkeys registered until it's dropped
+#[cfg_attr(not(feature = "hotkeys"), allow(dead_code))]
+pub struct GlobalHotkeys {
+	#[cfg(feature = "hotkeys")]
+	_manager: global_hotkey::GlobalHotKeyManager,
+}
+
+/// Registers the hotkeys from the config. Sends the event of each hotkey through `proxy` when it's
+/// pressed, for example `EmulsionEvent::ShowClipboardImage` for `show_clipboard`.
+#[cfg(feature = "hotkeys")]
+pub fn register(
+	config: &Configuration,
+	proxy: EventLoopProxy<EmulsionEvent>,
+) -> Option<GlobalHotkeys> {
+	use std::{collections::HashMap, sync::Mutex};
+
+	use global_hotkey::{GlobalHotKeyEvent, GlobalHotKeyManager, HotKeyState};
+
+	let bindings = bindings(config.hotkeys.as_ref()?);
+	if bindings.is_empty() {
+		return None;
+	}
+	let manager = match GlobalHotKeyManager::new() {
+		Ok(manager) => manager,
+		Err(e) => {
+			log::error!("Global hotkeys are not supported on this system: {}", e);
+			return None;
+		}
+	};
+	let mut events = HashMap::new();
+	for (binding, event) in bindings {
+		let hotkey = match parse_hotkey(binding) {
+			Some(hotkey) => hotkey,
+			None => continue,
+		};
+		if let Err(e) = manager.register(hotkey) {
+			log::error!("Could not register the global hotkey {:?}: {}", binding, e);
+			continue;
+		}
+		events.insert(hotkey.id(), event);
+	}
+	if events.is_empty() {
+		return None;
+	}
+	let proxy = Mutex::new(proxy);
+	GlobalHotKeyEvent::set_event_handler(Some(move |event: GlobalHotKeyEvent| {
+		if event.state != HotKeyState::Pressed {
+			return;
+		}
+		if let Some(make_event) = events.get(&event.id) {
+			// This fails when the event loop is already gone, in which case there's nothing to do
+			let _ = proxy.lock().unwrap().send_event(make_event());
+		}
+	}));
+	Some(GlobalHotkeys { _manager: manager })
+}
+
+/// The configured hotkeys with the events that they send
+fn bindings(section: &ConfigHotkeysSection) -> Vec<(&str, MakeEvent)> {
+	let all: Vec<(&Option<String>, MakeEvent)> = vec![
+		(&section.show_clipboard, || EmulsionEvent::ShowClipboardImage),
+		(&section.toggle_overlay, || EmulsionEvent::ToggleOverlay),
+		(&section.img_next, || EmulsionEvent::NextImage),
+		(&section.img_prev, || EmulsionEvent::PrevImage),
+		(&section.toggle_slideshow, || EmulsionEvent::ToggleSlideshow),
+	];
+	all.into_iter().filter_map(|(binding, event)| Some((binding.as_deref()?, event))).collect()
+}
+
+#[cfg(feature = "hotkeys")]
+fn parse_hotkey(binding: &str) -> Option<global_hotkey::hotkey::HotKey> {
+	// The bindings of emulsion call the modifier `CmdCtrl` while `global_hotkey` calls it `CmdOrCtrl`
+	let hotkey_str = binding
+		.split('+')
+		.map(|part| if part.eq_ignore_ascii_case("cmdctrl") { "CmdOrCtrl" } else { part })
+		.collect::<Vec<_>>()
+		.join("+");
+	match hotkey_str.parse() {
+		Ok(hotkey) => Some(hotkey),
+		Err(e) => {
+			log::error!("Invalid global hotkey {:?}: {}", binding, e);
+			None
+		}
+	}
+}
+
+/// Only warns about the configured hotkeys without the `hotkeys` feature
+#[cfg(not(feature = "hotkeys"))]
+pub fn register(
+	config: &Configuration,
+	_proxy: EventLoopProxy<EmulsionEvent>,
+) -> Option<GlobalHotkeys> {
+	if config.hotkeys.as_ref().is_some_and(|section| !bindings(section).is_empty()) {
+		log::warn!(
+			"Global hotkeys are configured but emulsion was built without the `hotkeys` feature"
+		);
+	}
+	None
+}
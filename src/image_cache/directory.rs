@@ -1,12 +1,17 @@
-use std::ffi::{OsStr, OsString};
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
 use std::fmt;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
 
 use log::debug;
 
 use super::image_loader::is_file_supported;
+use crate::configuration::Configuration;
+use crate::folder_config::{FolderConfig, SortOrder};
 use crate::parallel_action::ParallelAction;
+use crate::sidecar;
 
 #[derive(Debug)]
 pub enum Error {
@@ -24,6 +29,10 @@ impl fmt::Display for Error {
 	}
 }
 impl std::error::Error for Error {}
+
+/// The last request id that was given to a file. The ids are unique among the directories of all
+/// windows, because their caches share the loader and `PRIORITY_REQUEST_ID`.
+static LAST_REQUEST_ID: AtomicU32 = AtomicU32::new(0);
 impl From<std::io::Error> for Error {
 	fn from(e: std::io::Error) -> Self {
 		Error::Other(format!("{}", e))
@@ -31,8 +40,7 @@ impl From<std::io::Error> for Error {
 }
 impl From<Error> for std::io::Error {
 	fn from(value: Error) -> Self {
-		use std::io::ErrorKind;
-		std::io::Error::new(ErrorKind::Other, format!("directory::Error: {}", value))
+		std::io::Error::other(format!("directory::Error: {}", value))
 	}
 }
 
@@ -41,7 +49,7 @@ pub type Result<T> = std::result::Result<T, Error>;
 macro_rules! step_to_next_img {
 	($this:ident, $iter:ident) => {
 		for (i, file) in $iter {
-			if is_file_supported(&file.path) {
+			if $this.is_browsed(i, &file.path) {
 				$this.curr_file_idx = i;
 				$this.set_image_index_from_file_index();
 				return;
@@ -59,6 +67,140 @@ pub struct DirItem {
 	pub request_id: u32,
 }
 
+/// Comma separated file name patterns, like `*.png,*.jpg`. The case doesn't matter.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct NameFilter {
+	text: String,
+	patterns: Vec<glob::Pattern>,
+}
+
+impl NameFilter {
+	pub fn parse(text: &str) -> std::result::Result<NameFilter, glob::PatternError> {
+		let patterns = text
+			.split(',')
+			.map(str::trim)
+			.filter(|pattern| !pattern.is_empty())
+			.map(glob::Pattern::new)
+			.collect::<std::result::Result<_, _>>()?;
+		Ok(NameFilter { text: text.trim().to_owned(), patterns })
+	}
+
+	pub fn text(&self) -> &str {
+		&self.text
+	}
+
+	pub fn matches(&self, path: &Path) -> bool {
+		let name = match path.file_name() {
+			Some(name) => name.to_string_lossy(),
+			None => return false,
+		};
+		let options = glob::MatchOptions { case_sensitive: false, ..Default::default() };
+		self.patterns.iter().any(|pattern| pattern.matches_with(&name, options))
+	}
+}
+
+/// The image that's shown first, set with `--start-at`
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum StartAt {
+	/// The position of the image in the folder, starting from 1
+	Number(usize),
+	/// The exact file name, or a pattern like `*.png`
+	Name(String),
+}
+
+impl StartAt {
+	pub fn parse(text: &str) -> StartAt {
+		match text.trim().parse::<usize>() {
+			Ok(number) => StartAt::Number(number),
+			Err(_) => StartAt::Name(text.to_owned()),
+		}
+	}
+
+	/// Returns the index of the image in `paths` to start at. A name is matched exactly if
+	/// possible, otherwise the first image that the pattern matches is chosen.
+	pub fn find(&self, paths: &[PathBuf]) -> Option<usize> {
+		match self {
+			StartAt::Number(_) if paths.is_empty() => None,
+			StartAt::Number(number) => Some(number.saturating_sub(1).min(paths.len() - 1)),
+			StartAt::Name(name) => {
+				let exact = paths.iter().position(|path| path.file_name() == Some(name.as_ref()));
+				exact.or_else(|| {
+					let filter = NameFilter::parse(name).ok()?;
+					paths.iter().position(|path| filter.matches(path))
+				})
+			}
+		}
+	}
+}
+
+impl fmt::Display for StartAt {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			StartAt::Number(number) => write!(f, "{}", number),
+			StartAt::Name(name) => write!(f, "{}", name),
+		}
+	}
+}
+
+/// Decides which of the supported images are browsed
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct BrowseFilter {
+	/// Only the files with matching names are browsed
+	pub name: Option<NameFilter>,
+	/// The `filter` of the folder config, which is applied on top of `name`. It's set by
+	/// `Directory` whenever a folder is opened.
+	pub folder_name: Option<NameFilter>,
+	/// Lowercase extensions of files that are browsed even if their format isn't recognized
+	pub extra_extensions: Vec<String>,
+	/// Lowercase extensions of files that are never browsed
+	pub excluded_extensions: Vec<String>,
+	/// Only the images rated at least this many stars are browsed, 0 browses all images
+	pub min_rating: u8,
+	/// Only the images with this tag are browsed, the case doesn't matter
+	pub tag: Option<String>,
+}
+
+impl BrowseFilter {
+	/// Browses all images, with the extensions listed in the `[image]` section of the config
+	/// taken into account
+	pub fn from_config(config: &Configuration) -> BrowseFilter {
+		let image = config.image.as_ref();
+		let normalize = |extensions: Option<&Vec<String>>| {
+			let extensions = extensions.into_iter().flatten();
+			extensions.map(|ext| ext.trim().trim_start_matches('.').to_lowercase()).collect()
+		};
+		BrowseFilter {
+			extra_extensions: normalize(image.and_then(|i| i.extra_extensions.as_ref())),
+			excluded_extensions: normalize(image.and_then(|i| i.excluded_extensions.as_ref())),
+			..Default::default()
+		}
+	}
+
+	pub fn accepts(&self, path: &Path) -> bool {
+		if !self.name.as_ref().is_none_or(|name| name.matches(path)) {
+			return false;
+		}
+		if !self.folder_name.as_ref().is_none_or(|name| name.matches(path)) {
+			return false;
+		}
+		let extension = path.extension().map(|ext| ext.to_string_lossy().to_lowercase());
+		let supported = match &extension {
+			Some(ext) if self.excluded_extensions.contains(ext) => false,
+			Some(ext) if self.extra_extensions.contains(ext) => true,
+			_ => is_file_supported(path),
+		};
+		if !supported {
+			return false;
+		}
+		if self.min_rating == 0 && self.tag.is_none() {
+			return true;
+		}
+		let metadata = sidecar::read(path);
+		metadata.rating.unwrap_or(0) >= self.min_rating
+			&& self.tag.as_ref().is_none_or(|tag| metadata.has_tag(tag))
+	}
+}
+
 // enum FilterState {
 //     Idle,
 //     Processing(Arc<Vec<usize>>),
@@ -67,6 +209,8 @@ pub struct DirItem {
 
 pub struct Directory {
 	path: PathBuf,
+	/// When set, these are browsed instead of the contents of `path`
+	file_list: Option<Vec<PathBuf>>,
 	files: Vec<DirItem>,
 
 	/// Maps image indicies to indicies for the `files` vector.
@@ -76,11 +220,12 @@ pub struct Directory {
 	/// Maps file indicies to indicies for the `curr_image_idx`.
 	/// This is relevant when the current image is given by its name
 	/// when it will first be located by its file index.
+	/// It has an item for every file once the files are filtered.
 	file_i_to_img_i: Vec<Option<u32>>,
 
-	/// A monotonically increasing integer used for identifying
-	/// each load request
-	current_req_id: u32,
+	filter: BrowseFilter,
+	/// The `.emulsion.toml` of `path`, or the default when browsing a file list
+	folder_config: FolderConfig,
 
 	/// current file index
 	/// This must never be exposed to users of this object.
@@ -91,36 +236,101 @@ pub struct Directory {
 	curr_image_idx: usize,
 
 	//filter_state: Arc<Mutex<FilterState>>,
-	filter_action: ParallelAction<Vec<DirItem>, Vec<usize>>,
+	filter_action: ParallelAction<(Vec<DirItem>, BrowseFilter), Vec<usize>>,
 }
 
-fn get_action() -> impl FnMut(Vec<DirItem>) -> Vec<usize> {
-	|input: Vec<DirItem>| {
+fn get_action() -> impl FnMut((Vec<DirItem>, BrowseFilter)) -> Vec<usize> {
+	|(input, filter): (Vec<DirItem>, BrowseFilter)| {
 		input
 			.into_iter()
 			.enumerate()
-			.filter_map(|(i, item)| if is_file_supported(&item.path) { Some(i) } else { None })
+			.filter_map(|(i, item)| if filter.accepts(&item.path) { Some(i) } else { None })
 			.collect()
 	}
 }
 
+/// Returns the files (and symlinks) in `dir` in the given order
+fn list_files(dir: &Path, order: SortOrder, reverse: bool) -> Result<Vec<PathBuf>> {
+	let mut files: Vec<_> = fs::read_dir(dir)?
+		.filter_map(|x| match x {
+			Ok(entry) => match entry.file_type() {
+				Ok(file_type) if file_type.is_file() || file_type.is_symlink() => {
+					Some(entry.path())
+				}
+				_ => None,
+			},
+			Err(_) => None,
+		})
+		.collect();
+	files.sort_unstable_by(|a, b| {
+		lexical_sort::natural_lexical_cmp(
+			&a.file_name().unwrap().to_string_lossy(),
+			&b.file_name().unwrap().to_string_lossy(),
+		)
+	});
+	// The sort is stable, so the files with the same time or size stay sorted by their names
+	match order {
+		SortOrder::Name => (),
+		SortOrder::Modified => files.sort_by_cached_key(|path| {
+			fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+		}),
+		SortOrder::Size => {
+			files.sort_by_cached_key(|path| fs::metadata(path).map(|m| m.len()).unwrap_or(0))
+		}
+	}
+	if reverse {
+		files.reverse();
+	}
+	Ok(files)
+}
+
+/// Replaces the directories in `file_list` with their contents. Paths that can't be accessed and
+/// files that were already listed are skipped.
+fn expand_file_list(file_list: &[PathBuf]) -> Vec<PathBuf> {
+	let mut files = Vec::with_capacity(file_list.len());
+	let mut listed = HashSet::new();
+	for path in file_list {
+		let path = match path.canonicalize() {
+			Ok(path) => path,
+			Err(e) => {
+				log::error!("Skipping {:?}: {}", path, e);
+				continue;
+			}
+		};
+		if path.is_dir() {
+			match list_files(&path, SortOrder::Name, false) {
+				Ok(dir_files) => {
+					files.extend(dir_files.into_iter().filter(|file| listed.insert(file.clone())))
+				}
+				Err(e) => log::error!("Could not list the files in {:?}: {}", path, e),
+			}
+		} else if listed.insert(path.clone()) {
+			files.push(path);
+		}
+	}
+	files
+}
+
 impl Directory {
 	pub fn new() -> Self {
 		Directory {
 			path: PathBuf::new(),
+			file_list: None,
 			files: Vec::new(),
 			img_i_to_file_i: Vec::new(),
 			file_i_to_img_i: Vec::new(),
+			filter: BrowseFilter::default(),
+			folder_config: FolderConfig::default(),
 			curr_file_idx: 0,
 			curr_image_idx: 0,
-			current_req_id: 0,
 			filter_action: ParallelAction::new(get_action()),
 		}
 	}
 
 	pub fn change_directory(&mut self, path: &Path) -> Result<()> {
-		if self.path != path {
+		if self.path != path || self.file_list.is_some() {
 			path.clone_into(&mut self.path);
+			self.file_list = None;
 			self.collect_directory()
 		} else {
 			Ok(())
@@ -143,8 +353,60 @@ impl Directory {
 		Err(Error::Other(format!("Could not find file {:?} in directory {:?}", filename, path)))
 	}
 
-	pub fn curr_filename(&self) -> Option<OsString> {
-		self.files.get(self.curr_file_idx).and_then(|x| x.path.file_name().map(|x| x.to_owned()))
+	/// Browses the given files instead of the contents of a directory. Directories in `paths` are
+	/// replaced with the files in them.
+	pub fn set_file_list(&mut self, paths: Vec<PathBuf>) -> Result<()> {
+		self.path = PathBuf::new();
+		self.file_list = Some(paths);
+		self.collect_directory()
+	}
+
+	/// Replaces `old_path` with `new_path` in the list of browsed files, after the file was renamed
+	pub fn rename_listed_file(&mut self, old_path: &Path, new_path: &Path) {
+		if let Some(file_list) = &mut self.file_list {
+			for path in file_list.iter_mut().filter(|path| *path == old_path) {
+				*path = new_path.to_owned();
+			}
+		}
+	}
+
+	/// Browses only the images accepted by `filter`. The files are filtered again, the current file
+	/// stays the same.
+	pub fn set_filter(&mut self, filter: BrowseFilter) -> Result<()> {
+		if self.filter == filter {
+			return Ok(());
+		}
+		self.filter = filter;
+		if self.path.as_os_str().is_empty() && self.file_list.is_none() {
+			// Nothing was opened yet
+			return Ok(());
+		}
+		self.update_directory()
+	}
+
+	pub fn filter(&self) -> &BrowseFilter {
+		&self.filter
+	}
+
+	pub fn folder_config(&self) -> &FolderConfig {
+		&self.folder_config
+	}
+
+	pub fn is_file_list(&self) -> bool {
+		self.file_list.is_some()
+	}
+
+	/// Makes `path` the current file. Returns false if it isn't one of the listed files.
+	pub fn select_file(&mut self, path: &Path) -> bool {
+		match self.files.iter().position(|desc| desc.path == path) {
+			Some(index) => {
+				self.curr_file_idx = index;
+				self.set_image_index_from_file_index();
+				self.check_filter_ready();
+				true
+			}
+			None => false,
+		}
 	}
 
 	pub fn curr_descriptor(&self) -> Option<&DirItem> {
@@ -219,18 +481,26 @@ impl Directory {
 		}
 	}
 
+	/// Returns the paths of the images in the order of their indices, or None if the images
+	/// haven't been filtered yet
+	pub fn image_paths(&mut self) -> Option<Vec<PathBuf>> {
+		if !self.check_filter_ready() {
+			return None;
+		}
+		Some(self.img_i_to_file_i.iter().map(|&i| self.files[i].path.clone()).collect())
+	}
+
 	pub fn update_directory(&mut self) -> Result<()> {
-		let curr_filename = self.curr_filename();
-		let curr_filename = curr_filename.as_deref();
+		let curr_path = self.curr_descriptor().map(|desc| desc.path.clone());
 		let curr_index = self.curr_file_idx;
 		debug!(
-			"Directory: `update_directory`. Current filename: {:?}, curr_index: {:?}",
-			curr_filename, curr_index
+			"Directory: `update_directory`. Current path: {:?}, curr_index: {:?}",
+			curr_path, curr_index
 		);
 		self.collect_directory()?;
-		if curr_filename.is_some() {
+		if let Some(curr_path) = curr_path {
 			for (index, desc) in self.files.iter().enumerate() {
-				if desc.path.file_name() == curr_filename {
+				if desc.path == curr_path {
 					debug!("Found file the previously 'current' file in the directory.");
 					self.curr_file_idx = index;
 					self.set_image_index_from_file_index();
@@ -240,9 +510,9 @@ impl Directory {
 			}
 		}
 		debug!("Previously 'current' file not found, skipping to next supported.");
-		// if is_file_supported, preserve index of previous file or its following files
+		// if the file is browsed, preserve index of previous file or its following files
 		for (index, desc) in self.files.iter().enumerate().skip(curr_index) {
-			if is_file_supported(&desc.path) {
+			if self.filter.accepts(&desc.path) {
 				debug!("Next supported file found. Index {:?}, name {:?}.", index, desc.path);
 				self.curr_file_idx = index;
 				self.set_image_index_from_file_index();
@@ -258,38 +528,42 @@ impl Directory {
 	}
 
 	pub fn collect_directory(&mut self) -> Result<()> {
-		let mut dir_files: Vec<_> = fs::read_dir(&self.path)?
-			.filter_map(|x| match x {
-				Ok(entry) => match entry.file_type() {
-					Ok(file_type) => {
-						if file_type.is_file() || file_type.is_symlink() {
-							self.current_req_id += 1;
-							Some(DirItem { path: entry.path(), request_id: self.current_req_id })
-						} else {
-							None
-						}
-					}
-					Err(_) => None,
-				},
-				Err(_) => None,
+		// The folder config is read again with the files, so that changes to it apply too
+		self.folder_config = match &self.file_list {
+			Some(_) => FolderConfig::default(),
+			None => FolderConfig::load(&self.path),
+		};
+		self.filter.folder_name = self.folder_config.name_filter();
+		let paths = match &self.file_list {
+			Some(file_list) => expand_file_list(file_list),
+			None => {
+				let sort = self.folder_config.sort.unwrap_or_default();
+				list_files(&self.path, sort, self.folder_config.reverse.unwrap_or(false))?
+			}
+		};
+		// Files that were already listed keep their request id, so that their cached textures
+		// stay valid when the same directory is collected again.
+		let prev_request_ids: HashMap<PathBuf, u32> =
+			self.files.drain(..).map(|item| (item.path, item.request_id)).collect();
+		let dir_files: Vec<_> = paths
+			.into_iter()
+			.map(|path| {
+				let request_id = match prev_request_ids.get(&path) {
+					Some(&request_id) => request_id,
+					None => LAST_REQUEST_ID.fetch_add(1, Ordering::Relaxed) + 1,
+				};
+				DirItem { path, request_id }
 			})
 			.collect();
 
-		dir_files.sort_unstable_by(|a, b| {
-			lexical_sort::natural_lexical_cmp(
-				&a.path.file_name().unwrap().to_string_lossy(),
-				&b.path.file_name().unwrap().to_string_lossy(),
-			)
-		});
-
 		// Set the current file index to the first image
 		for (i, item) in dir_files.iter().enumerate() {
-			if is_file_supported(&item.path) {
+			if self.filter.accepts(&item.path) {
 				self.curr_file_idx = i;
 				break;
 			}
 		}
-		self.filter_action.give_input(dir_files.clone());
+		self.filter_action.give_input((dir_files.clone(), self.filter.clone()));
 		self.img_i_to_file_i.clear();
 		self.file_i_to_img_i.clear();
 		self.files = dir_files;
@@ -307,13 +581,27 @@ impl Directory {
 			self.file_i_to_img_i.push(Some(curr_img_i as u32));
 			last_file_i = curr_file_i as isize;
 		}
+		self.file_i_to_img_i.resize(self.files.len(), None);
 		self.set_image_index_from_file_index();
 	}
 
+	/// The current file might not be browsed when it was opened directly while a filter is set.
+	/// Then the next browsed image is used as the current image.
 	fn set_image_index_from_file_index(&mut self) {
-		if let Some(img_idx) = self.file_i_to_img_i.get(self.curr_file_idx) {
-			self.curr_image_idx = img_idx.unwrap() as usize;
+		let following = self.file_i_to_img_i.iter().skip(self.curr_file_idx).find_map(|i| *i);
+		let img_idx = following.or_else(|| self.file_i_to_img_i.iter().rev().find_map(|i| *i));
+		if let Some(img_idx) = img_idx {
+			self.curr_image_idx = img_idx as usize;
+		}
+	}
+
+	/// Returns true if the file is one of the browsed images. The filter is only applied here
+	/// until the files are filtered on the background thread.
+	fn is_browsed(&self, file_idx: usize, path: &Path) -> bool {
+		if self.file_i_to_img_i.len() == self.files.len() {
+			return self.file_i_to_img_i[file_idx].is_some();
 		}
+		self.filter.accepts(path)
 	}
 
 	fn check_filter_ready(&mut self) -> bool {
@@ -325,3 +613,33 @@ impl Directory {
 		self.filter_action.is_ready()
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn name_filter_matches() {
+		let filter = NameFilter::parse(" *.png, IMG_*.jpg ,,").unwrap();
+		assert_eq!(filter.text(), "*.png, IMG_*.jpg ,,");
+		assert!(filter.matches(Path::new("a.png")));
+		assert!(filter.matches(Path::new("folder/B.PNG")));
+		assert!(filter.matches(Path::new("img_0001.JPG")));
+		assert!(!filter.matches(Path::new("0001.jpg")));
+		assert!(!filter.matches(Path::new("png/a.gif")));
+		assert!(!filter.matches(Path::new("/")));
+		assert!(NameFilter::parse("[*.png").is_err());
+	}
+
+	#[test]
+	fn start_at_find() {
+		let paths: Vec<PathBuf> = ["a.png", "b.jpg", "c.png"].iter().map(PathBuf::from).collect();
+		assert_eq!(StartAt::parse("2").find(&paths), Some(1));
+		assert_eq!(StartAt::parse("0").find(&paths), Some(0));
+		assert_eq!(StartAt::parse("9").find(&paths), Some(2));
+		assert_eq!(StartAt::parse("c.png").find(&paths), Some(2));
+		assert_eq!(StartAt::parse("*.jpg").find(&paths), Some(1));
+		assert_eq!(StartAt::parse("*.gif").find(&paths), None);
+		assert_eq!(StartAt::parse("1").find(&[]), None);
+	}
+}
@@ -1,9 +1,9 @@
 use std::borrow::Cow;
 use std::fs;
-use std::io::{BufReader, Read};
+use std::io::{BufReader, Cursor, Read};
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
-use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::sync::mpsc::{channel, Receiver, Sender};
 use std::sync::{Arc, Mutex};
 use std::thread;
 
@@ -14,6 +14,8 @@ use gelatin::image::{
 };
 use usvg::fontdb;
 
+use super::pixel_ops;
+
 #[derive(Debug, thiserror::Error)]
 #[error("error happened in the image_loader: {description}")]
 pub struct ImageLoaderError {
@@ -42,8 +44,13 @@ pub type Result<T> = std::result::Result<T, ImageLoaderError>;
 /// which will only carry out the request if the focused request id matches their request or
 /// if the focused is set to `NON_EXISTENT_REQUEST_ID`
 pub static PRIORITY_REQUEST_ID: AtomicU32 = AtomicU32::new(0); // The first request usually
-pub const NON_EXISTENT_REQUEST_ID: u32 = std::u32::MAX;
+pub const NON_EXISTENT_REQUEST_ID: u32 = u32::MAX;
+
+/// The longest side of the images that the loader threads send to the cache. Larger images are
+/// halved until they fit, their textures would take more memory than most GPUs have.
+pub const MAX_LOADED_SIDE: u32 = 16384;
 
+#[derive(Debug, Copy, Clone)]
 pub enum ImgFormat {
 	Image(ImageFormat),
 	Svg,
@@ -55,7 +62,7 @@ pub enum ImgFormat {
 /// image. This is represented by the value `Deg0`. All other cases must be interpreted as relative
 /// to this. The rotation part is counter-clockwise. When there's a flip it's always interpreted as
 /// if it happened after the rotation.
-#[derive(Default, Debug, Copy, Clone)]
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq)]
 pub enum Orientation {
 	/// Exif 1
 	#[default]
@@ -103,6 +110,56 @@ pub fn detect_format(path: &Path) -> Result<ImgFormat> {
 	Ok(ImgFormat::Image(ImageFormat::from_path(path)?))
 }
 
+/// What can be known about an image file without decoding it
+#[derive(Debug, Clone)]
+pub struct ImageHeader {
+	/// Width and height after applying the EXIF orientation. `None` for SVGs because those are
+	/// rendered at an arbitrary size.
+	pub dimensions: Option<(u32, u32)>,
+	pub format: ImgFormat,
+}
+
+impl ImageHeader {
+	/// Returns a short description like "4032×3024 JPEG"
+	pub fn describe(&self) -> String {
+		let format = self.describe_format();
+		match self.dimensions {
+			Some((w, h)) => format!("{}×{} {}", w, h, format),
+			None => format,
+		}
+	}
+
+	/// Returns the name of the format like "JPEG"
+	pub fn describe_format(&self) -> String {
+		match self.format {
+			ImgFormat::Image(format) => format!("{:?}", format).to_uppercase(),
+			ImgFormat::Svg => "SVG".to_owned(),
+		}
+	}
+}
+
+/// Reads the format and the dimensions of an image from the beginning of the file, which is much
+/// cheaper than decoding it.
+pub fn read_header(path: &Path) -> Result<ImageHeader> {
+	let format = detect_format(path)?;
+	let dimensions = match format {
+		ImgFormat::Image(image_format) => {
+			let reader = BufReader::new(fs::File::open(path)?);
+			let (w, h) = image::ImageReader::with_format(reader, image_format).into_dimensions()?;
+			let orientation = detect_orientation(path).unwrap_or(Orientation::Deg0);
+			match orientation {
+				Orientation::Deg90
+				| Orientation::Deg90VerFlip
+				| Orientation::Deg270
+				| Orientation::Deg270VerFlip => Some((h, w)),
+				_ => Some((w, h)),
+			}
+		}
+		ImgFormat::Svg => None,
+	};
+	Ok(ImageHeader { dimensions, format })
+}
+
 pub fn detect_orientation(path: &Path) -> std::result::Result<Orientation, exif::Error> {
 	let file = std::fs::File::open(path)?;
 	let mut bufreader = std::io::BufReader::new(&file);
@@ -111,21 +168,7 @@ pub fn detect_orientation(path: &Path) -> std::result::Result<Orientation, exif:
 	if let Some(orientation) = exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY) {
 		if let exif::Value::Short(ref shorts) = orientation.value {
 			if let Some(&exif_orientation) = shorts.first() {
-				// According to page 30 of http://www.cipa.jp/std/documents/e/DC-008-2012_E.pdf
-				match exif_orientation {
-					1 => Ok(Orientation::Deg0),
-					2 => Ok(Orientation::Deg0HorFlip),
-					3 => Ok(Orientation::Deg180),
-					4 => Ok(Orientation::Deg180HorFlip),
-					5 => Ok(Orientation::Deg90VerFlip),
-					6 => Ok(Orientation::Deg270),
-					7 => Ok(Orientation::Deg270VerFlip),
-					8 => Ok(Orientation::Deg90),
-					_ => {
-						eprintln!("Invalid Exif orientation. Using default orientation.");
-						Ok(Orientation::Deg0)
-					}
-				}
+				Ok(orientation_from_exif(exif_orientation as u32))
 			} else {
 				Ok(Orientation::Deg0)
 			}
@@ -137,11 +180,29 @@ pub fn detect_orientation(path: &Path) -> std::result::Result<Orientation, exif:
 	}
 }
 
+fn orientation_from_exif(exif_orientation: u32) -> Orientation {
+	// According to page 30 of http://www.cipa.jp/std/documents/e/DC-008-2012_E.pdf
+	match exif_orientation {
+		1 => Orientation::Deg0,
+		2 => Orientation::Deg0HorFlip,
+		3 => Orientation::Deg180,
+		4 => Orientation::Deg180HorFlip,
+		5 => Orientation::Deg90VerFlip,
+		6 => Orientation::Deg270,
+		7 => Orientation::Deg270VerFlip,
+		8 => Orientation::Deg90,
+		_ => {
+			eprintln!("Invalid Exif orientation. Using default orientation.");
+			Orientation::Deg0
+		}
+	}
+}
+
 pub fn simple_load_image(path: &Path, image_format: ImageFormat) -> Result<image::RgbaImage> {
 	let reader = BufReader::new(fs::File::open(path)?);
 	let mut image_reader = image::ImageReader::with_format(reader, image_format);
 	image_reader.no_limits();
-	Ok(image_reader.decode()?.into_rgba8())
+	Ok(pixel_ops::expand_to_rgba8(image_reader.decode()?))
 }
 
 /// Returns an iterator over the animation frames of a GIF file
@@ -155,18 +216,20 @@ pub fn load_gif(path: &Path, req_id: u32) -> Result<impl Iterator<Item = Result<
 /// Parse, render and gather an SVG into a ImageBuffer<Rgba>
 pub fn load_svg(path: &std::path::Path) -> Result<image::RgbaImage> {
 	let svg_data = fs::read(path)?;
+	let resources_dir =
+		std::fs::canonicalize(path).ok().and_then(|p| p.parent().map(|p| p.to_path_buf()));
+	render_svg(&svg_data, resources_dir)
+}
+
+/// `resources_dir` is where relative paths of the SVG are resolved from
+fn render_svg(svg_data: &[u8], resources_dir: Option<PathBuf>) -> Result<image::RgbaImage> {
 	let rtree = {
-		let opt = usvg::Options {
-			resources_dir: std::fs::canonicalize(path)
-				.ok()
-				.and_then(|p| p.parent().map(|p| p.to_path_buf())),
-			..Default::default()
-		};
+		let opt = usvg::Options { resources_dir, ..Default::default() };
 
 		let mut fontdb = fontdb::Database::new();
 		fontdb.load_system_fonts();
 
-		usvg::Tree::from_data(&svg_data, &opt, &fontdb)?
+		usvg::Tree::from_data(svg_data, &opt, &fontdb)?
 	};
 	let width = rtree.size().width();
 	let height = rtree.size().height();
@@ -180,6 +243,41 @@ pub fn load_svg(path: &std::path::Path) -> Result<image::RgbaImage> {
 	Ok(image::RgbaImage::from_raw(width, height, pixmap.take()).unwrap())
 }
 
+/// Decodes an image that is already in memory, for example one that was piped to emulsion. Only
+/// the first frame of animations is loaded and the EXIF orientation is baked into the result.
+pub fn load_image_from_memory(bytes: &[u8]) -> Result<image::RgbaImage> {
+	let format = match image::guess_format(bytes) {
+		Ok(format) => format,
+		// There's no magic number for SVGs, so just try to parse it
+		Err(_) => return render_svg(bytes, None),
+	};
+	let mut image_reader = image::ImageReader::with_format(Cursor::new(bytes), format);
+	image_reader.no_limits();
+	let image = pixel_ops::expand_to_rgba8(image_reader.decode()?);
+	let orientation = exif::Reader::new()
+		.read_from_container(&mut Cursor::new(bytes))
+		.ok()
+		.and_then(|exif| {
+			let field = exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)?;
+			field.value.get_uint(0)
+		})
+		.map(orientation_from_exif)
+		.unwrap_or_default();
+	Ok(pixel_ops::bake_orientation(image, orientation))
+}
+
+/// Decodes the first frame of the image file and bakes its EXIF orientation into the result.
+pub fn load_first_frame(path: &Path) -> Result<image::RgbaImage> {
+	let mut first_frame = None;
+	complex_load_image(path, false, 0, |result| {
+		if let LoadResult::Frame { image, orientation, .. } = result {
+			first_frame = Some(pixel_ops::bake_orientation(image, orientation));
+		}
+		Ok(())
+	})?;
+	first_frame.ok_or(ImageLoaderError { description: "The image has no frames".into() })
+}
+
 pub fn complex_load_image<F>(
 	path: &Path,
 	allow_animation: bool,
@@ -189,6 +287,10 @@ pub fn complex_load_image<F>(
 where
 	F: FnMut(LoadResult) -> Result<()>,
 {
+	#[cfg(feature = "video")]
+	if super::video::is_video(path) {
+		return super::video::load(path, allow_animation, req_id, process_image);
+	}
 	let image_format = detect_format(path)?;
 	let orientation = detect_orientation(path).unwrap_or(Orientation::Deg0);
 
@@ -268,6 +370,8 @@ pub fn is_file_supported(filename: &Path) -> bool {
 				}
 				#[cfg(feature = "avif")]
 				"avif" => return true,
+				#[cfg(feature = "video")]
+				ext if super::video::EXTENSIONS.contains(&ext) => return true,
 				_ => (),
 			}
 		}
@@ -279,6 +383,8 @@ pub fn is_file_supported(filename: &Path) -> bool {
 pub struct LoadRequest {
 	pub req_id: u32,
 	pub path: PathBuf,
+	/// Where the results are sent, each cache that uses the loader has its own channel
+	pub reply: Sender<LoadResult>,
 }
 
 pub enum LoadResult {
@@ -313,48 +419,37 @@ impl LoadResult {
 	}
 }
 
+/// The threads that decode the images. The results of each request are sent to the channel of the
+/// request, so one loader can serve the caches of several windows.
 pub struct ImageLoader {
 	running: Arc<AtomicBool>,
 	join_handles: Option<Vec<thread::JoinHandle<()>>>,
-	image_rx: Receiver<LoadResult>,
 	path_tx: Sender<LoadRequest>,
 }
 
 impl ImageLoader {
-	/// # Arguemnts
-	/// * `capacity` - Number of bytes. The last image loaded will be the one at which the allocated memory reaches or exceeds capacity
 	pub fn new(threads: u32) -> ImageLoader {
 		let running = Arc::new(AtomicBool::from(true));
 		let (load_request_tx, load_request_rx) = channel();
 		let load_request_rx = Arc::new(Mutex::new(load_request_rx));
 
-		let (loaded_img_tx, loaded_img_rx) = channel();
-
 		let mut join_handles = Vec::new();
 		for _ in 0..threads {
 			let running = running.clone();
 			let request_recv = load_request_rx.clone();
 			let request_send = load_request_tx.clone();
-			let img_sender = loaded_img_tx.clone();
 			join_handles.push(thread::spawn(move || {
-				Self::thread_loop(running, request_recv, request_send, img_sender);
+				Self::thread_loop(running, request_recv, request_send);
 			}));
 		}
 
-		ImageLoader {
-			running,
-			join_handles: Some(join_handles),
-
-			image_rx: loaded_img_rx,
-			path_tx: load_request_tx,
-		}
+		ImageLoader { running, join_handles: Some(join_handles), path_tx: load_request_tx }
 	}
 
 	fn thread_loop(
 		running: Arc<AtomicBool>,
 		request_recv: Arc<Mutex<Receiver<LoadRequest>>>,
 		request_send: Sender<LoadRequest>,
-		img_sender: Sender<LoadResult>,
 	) {
 		// The size was an arbitrary choice made with the argument that this should be
 		// enough to fit enough image file info to determine the format.
@@ -378,41 +473,45 @@ impl ImageLoader {
 					continue;
 				}
 			};
-			Self::load_and_send(&img_sender, request);
+			Self::load_and_send(request);
 		}
 	}
 
-	pub fn try_recv_prefetched(&mut self) -> std::result::Result<LoadResult, TryRecvError> {
-		self.image_rx.try_recv()
-	}
-
-	pub fn send_load_request(&mut self, request: LoadRequest) {
+	pub fn send_load_request(&self, request: LoadRequest) {
 		self.path_tx.send(request).unwrap();
 	}
 
-	fn load_and_send(img_sender: &Sender<LoadResult>, request: LoadRequest) {
-		fn try_load_and_send(img_sender: &Sender<LoadResult>, request: &LoadRequest) -> Result<()> {
+	/// The results of a cache that was dropped in the meantime, because its window was closed, are
+	/// thrown away
+	fn load_and_send(request: LoadRequest) {
+		fn try_load_and_send(request: &LoadRequest) -> Result<()> {
+			let img_sender = &request.reply;
 			let metadata = fs::metadata(&request.path)?;
-			img_sender.send(LoadResult::Start { req_id: request.req_id, metadata }).unwrap();
-			complex_load_image(&request.path, true, request.req_id, |frame| {
-				img_sender.send(frame).unwrap();
+			let _ = img_sender.send(LoadResult::Start { req_id: request.req_id, metadata });
+			complex_load_image(&request.path, true, request.req_id, |result| {
+				let result = match result {
+					LoadResult::Frame { req_id, image, delay_nano, orientation } => {
+						let image = pixel_ops::downscale_to_fit(image, MAX_LOADED_SIDE);
+						LoadResult::Frame { req_id, image, delay_nano, orientation }
+					}
+					other => other,
+				};
+				let _ = img_sender.send(result);
 				Ok(())
 			})?;
 			Ok(())
 		}
 
-		img_sender
-			.send(match try_load_and_send(img_sender, &request) {
-				Ok(()) => LoadResult::Done { req_id: request.req_id },
-				Err(error) => {
-					eprintln!(
-						"Request #{}: Error occurred while loading file {:?}\n    {}",
-						request.req_id, request.path, error,
-					);
-					LoadResult::Failed { req_id: request.req_id }
-				}
-			})
-			.unwrap();
+		let _ = request.reply.send(match try_load_and_send(&request) {
+			Ok(()) => LoadResult::Done { req_id: request.req_id },
+			Err(error) => {
+				eprintln!(
+					"Request #{}: Error occurred while loading file {:?}\n    {}",
+					request.req_id, request.path, error,
+				);
+				LoadResult::Failed { req_id: request.req_id }
+			}
+		});
 	}
 }
 
@@ -420,8 +519,11 @@ impl Drop for ImageLoader {
 	fn drop(&mut self) {
 		self.running.store(false, Ordering::Release);
 		if let Some(join_handles) = self.join_handles.take() {
+			let (reply, _) = channel();
 			for _ in join_handles.iter() {
-				self.path_tx.send(LoadRequest { req_id: 0, path: PathBuf::from("") }).unwrap();
+				let request =
+					LoadRequest { req_id: 0, path: PathBuf::from(""), reply: reply.clone() };
+				self.path_tx.send(request).unwrap();
 			}
 
 			for handle in join_handles.into_iter() {
@@ -1,11 +1,15 @@
 use std::{
 	borrow::Cow,
+	cell::Cell,
 	collections::BTreeMap,
 	ffi::{OsStr, OsString},
 	fs, mem,
 	path::{Path, PathBuf},
 	rc::Rc,
-	sync::atomic::Ordering,
+	sync::{
+		atomic::Ordering,
+		mpsc::{channel, Receiver, Sender},
+	},
 	time::SystemTime,
 };
 
@@ -18,9 +22,13 @@ use gelatin::{
 		CapabilitiesSource,
 	},
 	image,
+	picture_view::PictureTextures,
 };
 
 pub mod image_loader;
+pub mod pixel_ops;
+#[cfg(feature = "video")]
+pub mod video;
 
 use self::{directory::DirItem, image_loader::*};
 
@@ -28,7 +36,9 @@ mod pending_requests;
 use pending_requests::PendingRequests;
 
 pub mod directory;
-use directory::Directory;
+use directory::{BrowseFilter, Directory};
+
+use crate::folder_config::FolderConfig;
 
 #[derive(Debug, thiserror::Error)]
 pub enum TextureError {
@@ -82,8 +92,8 @@ pub fn get_image_size_estimate(width: u32, height: u32) -> isize {
 	// In an RGBA image, each pixel is 4 bytes.
 	// counting all the mipmaps would add an additionnal multiplier of around ~1.6
 	// but only the gpu textures have mip maps so just multiply by 1.5
-	// 4 x 1.5 gives the factor 6.
-	(width * height * 6) as isize
+	// 4 x 1.5 gives the factor 6. The CPU side copy that is kept for sampling adds another 4.
+	(width as isize) * (height as isize) * 10
 }
 
 pub fn get_anim_size_estimate(frames: &[AnimationFrameTexture]) -> isize {
@@ -109,7 +119,7 @@ impl<'a> RequestKind<'a> {
 }
 
 pub struct TextureGridItem {
-	pub tex: SrgbTexture2d,
+	pub tex: Rc<SrgbTexture2d>,
 	pub col: u32,
 	pub row: u32,
 }
@@ -126,12 +136,13 @@ pub struct AnimationFrameTexture {
 	/// of the cell at the 3rd column and 2nd row is
 	/// (3*cell_step_size, 2*cell_step_size)
 	pub cell_step_size: u32,
-	pub grid_rows: u32,
-	pub grid_cols: u32,
 
 	pub delay_nano: u64,
 	pub orientation: Orientation,
 
+	/// The decoded pixels, kept around so that individual texels can be sampled on the CPU
+	pub pixels: Rc<image::RgbaImage>,
+
 	/// The total width of the image. This equals to the sum of the widths of the
 	/// textures from a single row of the grid
 	pub w: u32,
@@ -147,7 +158,8 @@ impl AnimationFrameTexture {
 		orientation: Orientation,
 	) -> TextureResult<Self> {
 		let (w, h) = image.dimensions();
-		let img_bytes = image.into_raw();
+		let pixels = Rc::new(image);
+		let img_bytes = pixels.as_raw();
 		let mut tex_grid = Vec::new();
 
 		// The reasoning behind dividing by 2 and taking the min with 4*1024, is
@@ -170,9 +182,9 @@ impl AnimationFrameTexture {
 				let cell_w = (w - offset_x).min(max_size);
 				let cell_h = (h - offset_y).min(max_size);
 				let tex = texture_from_img_rect(
-					display, w, h, &img_bytes, offset_x, offset_y, cell_w, cell_h,
+					display, w, h, img_bytes, offset_x, offset_y, cell_w, cell_h,
 				)?;
-				let item = TextureGridItem { tex, col, row };
+				let item = TextureGridItem { tex: Rc::new(tex), col, row };
 				tex_grid.push(item);
 			}
 		}
@@ -181,14 +193,24 @@ impl AnimationFrameTexture {
 			tex_grid: Rc::new(tex_grid),
 			delay_nano,
 			orientation,
+			pixels,
 			w,
 			h,
 			cell_step_size: max_size,
-			grid_rows,
-			grid_cols,
 		})
 	}
 
+	/// Returns the color of the texel at `x`, `y` as it appears on screen, ie after the
+	/// orientation is applied.
+	pub fn oriented_texel(&self, x: u32, y: u32) -> Option<[u8; 4]> {
+		let (w, h) = self.oriented_dimensions();
+		if x >= w || y >= h {
+			return None;
+		}
+		let (x, y) = pixel_ops::oriented_to_raw(self.orientation, self.w, self.h, x, y);
+		Some(self.pixels.get_pixel(x, y).0)
+	}
+
 	pub fn oriented_dimensions(&self) -> (u32, u32) {
 		use Orientation::*;
 		match self.orientation {
@@ -196,6 +218,28 @@ impl AnimationFrameTexture {
 			Deg90 | Deg90VerFlip | Deg270 | Deg270VerFlip => (self.h, self.w),
 		}
 	}
+
+	/// The textures of the grid for drawing them with a `PictureView`. They are shared with this
+	/// frame, not copied.
+	pub fn picture_textures(&self) -> PictureTextures {
+		use Orientation::*;
+		let tiles = self.tex_grid.iter().map(|cell| {
+			let offset = (cell.col * self.cell_step_size, cell.row * self.cell_step_size);
+			(cell.tex.clone(), offset)
+		});
+		// `PictureTextures` mirrors the picture before turning it clockwise
+		let (quarter_turns, mirrored) = match self.orientation {
+			Deg0 => (0, false),
+			Deg0HorFlip => (0, true),
+			Deg180 => (2, false),
+			Deg180HorFlip => (2, true),
+			Deg90 => (3, false),
+			Deg90VerFlip => (3, true),
+			Deg270 => (1, false),
+			Deg270VerFlip => (1, true),
+		};
+		PictureTextures { tiles: tiles.collect(), size: (self.w, self.h), quarter_turns, mirrored }
+	}
 }
 
 /// img_bytes has to be an rgba8 buffer.
@@ -210,17 +254,16 @@ fn texture_from_img_rect(
 	cell_w: u32,
 	cell_h: u32,
 ) -> TextureResult<SrgbTexture2d> {
-	let raw_image;
-	if img_w == cell_w {
+	let raw_image = if img_w == cell_w {
 		assert!(offset_x == 0);
 		let start = (offset_y as usize * img_w as usize) * 4;
 		let end = start + (cell_h as usize * cell_w as usize * 4);
-		raw_image = RawImage2d {
+		RawImage2d {
 			data: Cow::Borrowed(&img_bytes[start..end]),
 			format: glium::texture::ClientFormat::U8U8U8U8,
 			width: cell_w,
 			height: cell_h,
-		};
+		}
 	} else {
 		let cell_size = cell_w as usize * cell_h as usize * 4;
 		let mut cell_pixels = Vec::with_capacity(cell_size);
@@ -231,8 +274,8 @@ fn texture_from_img_rect(
 			let end = start + (cell_w as usize * 4);
 			cell_pixels.extend_from_slice(&img_bytes[start..end]);
 		}
-		raw_image = RawImage2d::from_raw_rgba(cell_pixels, (cell_w, cell_h));
-	}
+		RawImage2d::from_raw_rgba(cell_pixels, (cell_w, cell_h))
+	};
 
 	let x_pow = 31 - img_w.leading_zeros();
 	let y_pow = 31 - img_h.leading_zeros();
@@ -292,12 +335,56 @@ pub struct ImageCache {
 	current_frame_idx: usize,
 
 	remaining_capacity: isize,
-	total_capacity: isize,
 	curr_est_size: isize,
 
 	pending_requests: PendingRequests,
 	texture_cache: BTreeMap<u32, CachedTexture>,
+	loader: Rc<SharedLoader>,
+	/// The loader sends the results of the requests of this cache here
+	image_tx: Sender<LoadResult>,
+	image_rx: Receiver<LoadResult>,
+}
+
+/// The loader threads and the memory budget that the image caches of all windows share. Only the
+/// textures are kept by each cache, because the windows don't share their OpenGL contexts.
+pub struct SharedLoader {
 	loader: ImageLoader,
+	/// Number of bytes, split evenly between the caches
+	capacity: isize,
+	cache_count: Cell<isize>,
+}
+
+impl SharedLoader {
+	pub fn new(capacity: isize, threads: u32) -> SharedLoader {
+		SharedLoader { loader: ImageLoader::new(threads), capacity, cache_count: Cell::new(0) }
+	}
+
+	/// Uses an eighth of the memory of the system and a thread per core, but at least 2 and at most
+	/// 4 threads
+	pub fn for_system() -> SharedLoader {
+		let capacity = match sys_info::mem_info() {
+			Ok(value) => {
+				// value originally reported in KiB
+				((value.total / 8) * 1024) as isize
+			}
+			_ => {
+				eprintln!("Could not get system memory size, using default value");
+				// bytes
+				500_000_000
+			}
+		};
+
+		let thread_count = match sys_info::cpu_num() {
+			Ok(value) => value.clamp(2, 4),
+			_ => 4,
+		};
+		SharedLoader::new(capacity, thread_count)
+	}
+
+	/// The part of the memory budget of each cache
+	fn cache_capacity(&self) -> isize {
+		self.capacity / self.cache_count.get().max(1)
+	}
 }
 
 /// This is a store for the supported images loaded from a folder
@@ -307,30 +394,35 @@ impl ImageCache {
 	// Originally 5
 	const MAX_PENDING_REQUESTS: usize = 1;
 
-	/// # Arguments
-	/// * `capacity` - Number of bytes. The last image loaded will be the one at which the allocated memory reaches or exceeds capacity
-	pub fn new(capacity: isize, threads: u32) -> ImageCache {
+	/// The cache gets an equal part of the capacity of `loader` as the other caches that use it.
+	/// The last image loaded will be the one at which the allocated memory reaches or exceeds that.
+	pub fn new(loader: Rc<SharedLoader>) -> ImageCache {
+		loader.cache_count.set(loader.cache_count.get() + 1);
+		let capacity = loader.cache_capacity();
+		let (image_tx, image_rx) = channel();
 		ImageCache {
 			dir: Directory::new(),
 			//current_file_idx: 0,
 			current_frame_idx: 0,
 
 			remaining_capacity: capacity,
-			total_capacity: capacity,
 			curr_est_size: 1000, // 1 kb, an optimistic estimate for the image size before anything is loaded
 
 			pending_requests: PendingRequests::new(),
 			texture_cache: BTreeMap::new(),
-			loader: ImageLoader::new(threads),
+			loader,
+			image_tx,
+			image_rx,
 		}
 	}
 
-	pub fn current_filename(&self) -> Option<OsString> {
-		self.dir.curr_filename()
+	/// Changes when windows are opened or closed, see `SharedLoader`
+	fn total_capacity(&self) -> isize {
+		self.loader.cache_capacity()
 	}
 
 	pub fn current_file_path(&self) -> Option<PathBuf> {
-		self.current_filename().map(|name| self.dir.path().join(name))
+		self.dir.curr_descriptor().map(|desc| desc.path.clone())
 	}
 
 	/// Returns `None` when the directory hasn't finished filtering image files.
@@ -343,6 +435,11 @@ impl ImageCache {
 		self.dir.image_count()
 	}
 
+	/// Returns `None` when the directory hasn't finished filtering image files.
+	pub fn image_paths(&mut self) -> Option<Vec<PathBuf>> {
+		self.dir.image_paths()
+	}
+
 	fn curr_dir_item(&self) -> Option<DirItem> {
 		self.dir.curr_descriptor().cloned()
 	}
@@ -359,6 +456,40 @@ impl ImageCache {
 		false
 	}
 
+	/// Returns the first frame of the image at `index` without making it the current image. If
+	/// it isn't uploaded to the GPU yet, a load request is sent for it and `WaitingOnLoader` is
+	/// returned.
+	pub fn texture_at_index(&mut self, index: usize) -> TextureResult<AnimationFrameTexture> {
+		let DirItem { path, request_id } =
+			self.dir.image_by_index(index).cloned().ok_or(TextureError::WaitingOnLoader)?;
+		if let Some(texture) = self.texture_cache.get(&request_id) {
+			if texture.failed {
+				return Err(TextureError::from_failed_request(request_id));
+			}
+			return texture.frames.first().cloned().ok_or(TextureError::WaitingOnLoader);
+		}
+		self.send_request_for_file(path, request_id, RequestKind::NonPriority);
+		Err(TextureError::WaitingOnLoader)
+	}
+
+	/// See `Directory::rename_listed_file`
+	pub fn rename_listed_file(&mut self, old_path: &Path, new_path: &Path) {
+		self.dir.rename_listed_file(old_path, new_path);
+	}
+
+	/// See `Directory::set_filter`
+	pub fn set_browse_filter(&mut self, filter: BrowseFilter) -> directory::Result<()> {
+		self.dir.set_filter(filter)
+	}
+
+	pub fn browse_filter(&self) -> &BrowseFilter {
+		self.dir.filter()
+	}
+
+	pub fn folder_config(&self) -> &FolderConfig {
+		self.dir.folder_config()
+	}
+
 	/// Fetches the contents of the folder and stores the list of image filenames to know which
 	/// files will be the next and previous.
 	///
@@ -368,8 +499,10 @@ impl ImageCache {
 	/// the index to 0 making the current file the first one in the folder.
 	///
 	/// Returns the error that might occure while fetching the files from the directory. Otherwise
-	/// returns `Ok(())`
-	pub fn update_directory(&mut self) -> directory::Result<()> {
+	/// returns true if the current image is affected, ie if it's a different file now or if the
+	/// file was modified. The textures of the unaffected images stay valid.
+	pub fn update_directory(&mut self) -> directory::Result<bool> {
+		let prev_path = self.current_file_path();
 		self.dir.update_directory()?;
 
 		// indicate that the an update directory
@@ -379,7 +512,22 @@ impl ImageCache {
 			texture.needs_update = true;
 		}
 
-		Ok(())
+		Ok(prev_path != self.current_file_path() || self.current_file_modified())
+	}
+
+	/// Returns true if the current file was modified since it was loaded or if it isn't loaded.
+	fn current_file_modified(&self) -> bool {
+		let DirItem { path, request_id } = match self.dir.curr_descriptor() {
+			Some(item) => item,
+			None => return false,
+		};
+		match self.texture_cache.get(request_id) {
+			Some(CachedTexture { mod_time: Some(mod_time), .. }) => {
+				let new_mod_time = fs::metadata(path).ok().and_then(|m| m.modified().ok());
+				new_mod_time != Some(*mod_time)
+			}
+			_ => true,
+		}
 	}
 
 	pub fn load_at_index(
@@ -410,30 +558,34 @@ impl ImageCache {
 		trace!("Begin `load_specific`");
 		self.receive_prefetched();
 		trace!("Receive prefetched done");
-		let target_file_name;
-		let parent;
-		if path.is_dir() {
-			parent = path.to_owned();
-			target_file_name = None;
-		} else {
-			let filename_and_parent = get_file_name_and_parent(path)?;
-			target_file_name = Some(filename_and_parent.0);
-			parent = filename_and_parent.1;
-		}
-
 		let prev_img_index = self.dir.curr_img_index();
-		if let Some(target_file_name) = target_file_name {
-			self.change_directory_with_filename(&parent, &target_file_name)?;
-		} else {
-			self.change_directory(&parent)?;
-			self.current_frame_idx = 0;
-		}
-		if self.dir.path() != parent {
-			let DirItem { path, request_id } = self.curr_dir_item().ok_or_else(|| {
-				TextureError::Other("Could not get path for current image".into())
-			})?;
-			self.send_request_for_file(path, request_id, RequestKind::Priority { display });
-			return Err(TextureError::WaitingOnLoader);
+		// When browsing a file list, the listed files are opened without leaving the list
+		let listed = self.dir.is_file_list() && self.dir.select_file(path);
+		if !listed {
+			let target_file_name;
+			let parent;
+			if path.is_dir() {
+				parent = path.to_owned();
+				target_file_name = None;
+			} else {
+				let filename_and_parent = get_file_name_and_parent(path)?;
+				target_file_name = Some(filename_and_parent.0);
+				parent = filename_and_parent.1;
+			}
+
+			if let Some(target_file_name) = target_file_name {
+				self.change_directory_with_filename(&parent, &target_file_name)?;
+			} else {
+				self.change_directory(&parent)?;
+				self.current_frame_idx = 0;
+			}
+			if self.dir.path() != parent {
+				let DirItem { path, request_id } = self.curr_dir_item().ok_or_else(|| {
+					TextureError::Other("Could not get path for current image".into())
+				})?;
+				self.send_request_for_file(path, request_id, RequestKind::Priority { display });
+				return Err(TextureError::WaitingOnLoader);
+			}
 		}
 		if let Some(img_index) = self.dir.curr_img_index() {
 			self.dir.set_curr_img_index(img_index)?;
@@ -469,7 +621,7 @@ impl ImageCache {
 			let mut sorted_files: Vec<_> = cache.into_iter().enumerate().collect();
 			sorted_files
 				.sort_unstable_by_key(|&(index, _)| (index as isize - curr_index as isize).abs());
-			self.remaining_capacity = self.total_capacity;
+			self.remaining_capacity = self.total_capacity();
 			sorted_files.retain(|(_, (_, texture))| {
 				// TODO consider retaining individual frames.
 				let all_frames_size = get_anim_size_estimate(&texture.frames);
@@ -540,7 +692,7 @@ impl ImageCache {
 	fn receive_prefetched(&mut self) {
 		use std::sync::mpsc::TryRecvError;
 		loop {
-			match self.loader.try_recv_prefetched() {
+			match self.image_rx.try_recv() {
 				Ok(load_result) => {
 					self.pending_requests.add_load_result(load_result);
 				}
@@ -627,12 +779,8 @@ impl ImageCache {
 			if get_from_cache {
 				let count = tex.frames.len() as isize;
 				if tex.fully_loaded || (frame_id >= 0 && frame_id < count) {
-					let wrapped_id;
-					if frame_id < 0 {
-						wrapped_id = count + (frame_id % count);
-					} else {
-						wrapped_id = frame_id % count;
-					}
+					let wrapped_id =
+						if frame_id < 0 { count + (frame_id % count) } else { frame_id % count };
 					if let Some(frame) = tex.frames.get(wrapped_id as usize) {
 						self.current_frame_idx = wrapped_id as usize;
 						return Ok(frame.clone());
@@ -823,18 +971,29 @@ impl ImageCache {
 		if self.pending_requests.contains(&req_id) {
 			return false;
 		}
-		let request = LoadRequest { req_id, path: file_path };
+		let request = LoadRequest { req_id, path: file_path, reply: self.image_tx.clone() };
 		self.pending_requests.add_request(request.clone());
-		self.loader.send_load_request(request);
+		self.loader.loader.send_load_request(request);
 		true
 	}
 
+	/// Browses the given files instead of a directory, see `Directory::set_file_list`
+	pub fn open_file_list(&mut self, paths: Vec<PathBuf>) -> directory::Result<()> {
+		self.texture_cache.clear();
+		self.remaining_capacity = self.total_capacity();
+		for (_, request) in self.pending_requests.iter_mut() {
+			request.cancel();
+		}
+		self.current_frame_idx = 0;
+		self.dir.set_file_list(paths)
+	}
+
 	fn change_directory(&mut self, dir_path: &Path) -> directory::Result<()> {
 		if self.dir.path() == dir_path {
 			return Ok(());
 		}
 		self.texture_cache.clear();
-		self.remaining_capacity = self.total_capacity;
+		self.remaining_capacity = self.total_capacity();
 
 		// Cancel all pending load requests
 		for (_, request) in self.pending_requests.iter_mut() {
@@ -889,16 +1048,20 @@ impl ImageCache {
 	// }
 }
 
+impl Drop for ImageCache {
+	fn drop(&mut self) {
+		let cache_count = &self.loader.cache_count;
+		cache_count.set(cache_count.get() - 1);
+	}
+}
+
 fn get_file_name_and_parent(path: &Path) -> std::io::Result<(OsString, PathBuf)> {
 	use std::io;
 
 	let file_name = match path.file_name() {
 		Some(f) => f.to_owned(),
 		None => {
-			return Err(io::Error::new(
-				io::ErrorKind::Other,
-				format!("Could not get file name from path {:?}", path),
-			))
+			return Err(io::Error::other(format!("Could not get file name from path {:?}", path)))
 		}
 	};
 	let parent = match path.parent() {
@@ -912,10 +1075,10 @@ fn get_file_name_and_parent(path: &Path) -> std::io::Result<(OsString, PathBuf)>
 		None => {
 			let mut path = path.canonicalize()?;
 			if !path.pop() {
-				return Err(io::Error::new(
-					io::ErrorKind::Other,
-					format!("Could not get parent directory of {:?}", path),
-				));
+				return Err(io::Error::other(format!(
+					"Could not get parent directory of {:?}",
+					path
+				)));
 			}
 			path
 		}
@@ -923,3 +1086,72 @@ fn get_file_name_and_parent(path: &Path) -> std::io::Result<(OsString, PathBuf)>
 
 	Ok((file_name, parent))
 }
+
+#[cfg(test)]
+mod tests {
+	use std::{env, fs::File, time::Duration};
+
+	use super::*;
+
+	/// Lists `dir` with `file_name` as the current image, which is marked as uploaded like after
+	/// it was shown
+	fn cache_showing(dir: &Path, file_name: &str) -> ImageCache {
+		let mut cache = ImageCache::new(Rc::new(SharedLoader::new(0, 1)));
+		cache.dir.change_directory_with_filename(dir, file_name.as_ref()).unwrap();
+		let DirItem { path, request_id } = cache.dir.curr_descriptor().unwrap().clone();
+		let mod_time = fs::metadata(path).unwrap().modified().ok();
+		let texture = CachedTexture {
+			_req_id: request_id,
+			needs_update: false,
+			mod_time,
+			fully_loaded: true,
+			failed: false,
+			frames: Vec::new(),
+		};
+		cache.texture_cache.insert(request_id, texture);
+		cache
+	}
+
+	fn current_request_id(cache: &ImageCache) -> u32 {
+		cache.dir.curr_descriptor().unwrap().request_id
+	}
+
+	#[test]
+	fn caches_split_the_capacity_of_the_loader() {
+		let loader = Rc::new(SharedLoader::new(1000, 1));
+		let first = ImageCache::new(loader.clone());
+		assert_eq!(first.total_capacity(), 1000);
+		let second = ImageCache::new(loader);
+		assert_eq!(first.total_capacity(), 500);
+		assert_eq!(second.remaining_capacity, 500);
+		drop(second);
+		assert_eq!(first.total_capacity(), 1000);
+	}
+
+	/// Toggling fullscreen or resizing the window makes it regain focus on some platforms, which
+	/// lists the folder again. That must not reload the shown image or upload it again.
+	#[test]
+	fn update_directory_keeps_the_unchanged_image() {
+		let dir = env::temp_dir().join(format!("emulsion-update-directory-{}", std::process::id()));
+		fs::create_dir_all(&dir).unwrap();
+		for name in ["a.png", "b.png"] {
+			image::RgbaImage::new(1, 1).save(dir.join(name)).unwrap();
+		}
+		let mut cache = cache_showing(&dir, "a.png");
+		let request_id = current_request_id(&cache);
+
+		assert!(!cache.update_directory().unwrap());
+		// A new file in the folder doesn't affect the shown image either
+		image::RgbaImage::new(1, 1).save(dir.join("c.png")).unwrap();
+		assert!(!cache.update_directory().unwrap());
+		assert_eq!(current_request_id(&cache), request_id);
+		assert!(cache.texture_cache.contains_key(&request_id));
+
+		let modified = SystemTime::now() + Duration::from_secs(60);
+		let file = File::options().write(true).open(dir.join("a.png")).unwrap();
+		file.set_modified(modified).unwrap();
+		assert!(cache.update_directory().unwrap());
+
+		fs::remove_dir_all(&dir).unwrap();
+	}
+}
@@ -0,0 +1,685 @@
+//! CPU-side pixel conversions that happen between decoding an image and handing it over to the
+//! GPU (or the clipboard): expanding it to RGBA, applying the orientation and shrinking images that
+//! are too large to show.
+//!
+//! Every conversion has a portable scalar implementation and, on x86_64, a SIMD implementation
+//! that is selected at runtime based on the features the CPU reports. Building with the
+//! `benchmark` feature runs both paths on every call and prints how long each one took.
+
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+use gelatin::image::{imageops, DynamicImage, RgbaImage};
+
+use super::image_loader::Orientation;
+
+/// Converts a decoded image into an 8-bit RGBA buffer.
+///
+/// 8-bit RGB and grayscale images (by far the most common outputs of the JPEG decoder) are
+/// expanded with SIMD when possible, everything else goes through the `image` crate.
+pub fn expand_to_rgba8(image: DynamicImage) -> RgbaImage {
+	match image {
+		DynamicImage::ImageRgba8(image) => image,
+		DynamicImage::ImageRgb8(image) => {
+			let (w, h) = image.dimensions();
+			#[cfg(feature = "benchmark")]
+			let fallback_time = measure(|| DynamicImage::ImageRgb8(image.clone()).into_rgba8());
+			#[cfg(feature = "benchmark")]
+			let start = std::time::Instant::now();
+			let mut rgba = vec![0; w as usize * h as usize * 4];
+			rgb_to_rgba(image.as_raw(), &mut rgba);
+			#[cfg(feature = "benchmark")]
+			report("RGB to RGBA expansion", w, h, start.elapsed(), fallback_time);
+			RgbaImage::from_raw(w, h, rgba).unwrap()
+		}
+		DynamicImage::ImageLuma8(image) => {
+			let (w, h) = image.dimensions();
+			#[cfg(feature = "benchmark")]
+			let fallback_time = measure(|| DynamicImage::ImageLuma8(image.clone()).into_rgba8());
+			#[cfg(feature = "benchmark")]
+			let start = std::time::Instant::now();
+			let mut rgba = vec![0; w as usize * h as usize * 4];
+			luma_to_rgba(image.as_raw(), &mut rgba);
+			#[cfg(feature = "benchmark")]
+			report("Luma to RGBA expansion", w, h, start.elapsed(), fallback_time);
+			RgbaImage::from_raw(w, h, rgba).unwrap()
+		}
+		other => other.into_rgba8(),
+	}
+}
+
+/// Applies the orientation to the pixels of the image so that the result can be displayed
+/// without any further transformation.
+pub fn bake_orientation(image: RgbaImage, orientation: Orientation) -> RgbaImage {
+	if let Orientation::Deg0 = orientation {
+		return image;
+	}
+	let (transpose, flip_x, flip_y) = orientation_as_flips(orientation);
+	let (w, h) = image.dimensions();
+	let (dst_w, dst_h) = if transpose { (h, w) } else { (w, h) };
+	let remap = Remap { w: w as usize, h: h as usize, transpose, flip_x, flip_y };
+
+	#[cfg(feature = "benchmark")]
+	let fallback_time = measure(|| {
+		let mut dst = vec![0; image.as_raw().len()];
+		remap.apply_scalar(image.as_raw(), &mut dst);
+		dst
+	});
+	#[cfg(feature = "benchmark")]
+	let start = std::time::Instant::now();
+	let mut dst = vec![0; image.as_raw().len()];
+	remap.apply(image.as_raw(), &mut dst);
+	#[cfg(feature = "benchmark")]
+	report("Orientation bake", w, h, start.elapsed(), fallback_time);
+
+	RgbaImage::from_raw(dst_w, dst_h, dst).unwrap()
+}
+
+/// Halves the image until neither of its sides is longer than `max_side`. Each pixel of a halved
+/// image is the average of a 2x2 block, an odd last row or column is left out.
+pub fn downscale_to_fit(mut image: RgbaImage, max_side: u32) -> RgbaImage {
+	loop {
+		let (w, h) = image.dimensions();
+		if w.max(h) <= max_side.max(1) {
+			return image;
+		}
+		let (dst_w, dst_h) = ((w / 2).max(1), (h / 2).max(1));
+		let (w, h) = (w as usize, h as usize);
+
+		#[cfg(feature = "benchmark")]
+		let fallback_time = measure(|| {
+			let mut dst = vec![0; dst_w as usize * dst_h as usize * 4];
+			for y in 0..dst_h as usize {
+				halve_row_scalar(image.as_raw(), w, h, &mut dst, y, 0);
+			}
+			dst
+		});
+		#[cfg(feature = "benchmark")]
+		let start = std::time::Instant::now();
+		let mut dst = vec![0; dst_w as usize * dst_h as usize * 4];
+		halve(image.as_raw(), w, h, &mut dst);
+		#[cfg(feature = "benchmark")]
+		report("Halving", w as u32, h as u32, start.elapsed(), fallback_time);
+
+		image = RgbaImage::from_raw(dst_w, dst_h, dst).unwrap();
+	}
+}
+
+/// Returns the position of the pixel in the stored (not yet oriented) image that is displayed at
+/// `x`, `y` once the orientation is applied. `w` and `h` are the dimensions of the stored image.
+pub fn oriented_to_raw(orientation: Orientation, w: u32, h: u32, x: u32, y: u32) -> (u32, u32) {
+	let (transpose, flip_x, flip_y) = orientation_as_flips(orientation);
+	let (oriented_w, oriented_h) = if transpose { (h, w) } else { (w, h) };
+	let x = if flip_x { oriented_w - 1 - x } else { x };
+	let y = if flip_y { oriented_h - 1 - y } else { y };
+	if transpose {
+		(y, x)
+	} else {
+		(x, y)
+	}
+}
+
+/// Returns the average luminance of the image between 0 and 1, weighted by the alpha of each
+/// pixel. Only a grid of at most `MAX_SAMPLES` x `MAX_SAMPLES` pixels is considered, so this is
+/// cheap even for large images. Returns `None` if the image is empty or fully transparent.
+pub fn average_luminance(image: &RgbaImage) -> Option<f32> {
+	const MAX_SAMPLES: u32 = 64;
+	let (w, h) = image.dimensions();
+	let step_x = (w / MAX_SAMPLES).max(1);
+	let step_y = (h / MAX_SAMPLES).max(1);
+	let mut sum = 0.0;
+	let mut weight = 0.0;
+	for y in (0..h).step_by(step_y as usize) {
+		for x in (0..w).step_by(step_x as usize) {
+			let [r, g, b, a] = image.get_pixel(x, y).0;
+			let alpha = a as f32 / 255.0;
+			let luma = (0.2126 * r as f32 + 0.7152 * g as f32 + 0.0722 * b as f32) / 255.0;
+			sum += luma * alpha;
+			weight += alpha;
+		}
+	}
+	if weight > 0.0 {
+		Some(sum / weight)
+	} else {
+		None
+	}
+}
+
+/// Shrinks the image to at most `MAX_SIZE` pixels on its longer side, blurs it heavily and applies
+/// the orientation. It's drawn stretched behind the image with `ambient_background`, which blurs it
+/// further.
+pub fn ambient_image(image: &RgbaImage, orientation: Orientation) -> RgbaImage {
+	const MAX_SIZE: u32 = 32;
+	const BLUR_SIGMA: f32 = 2.0;
+	let (w, h) = image.dimensions();
+	let scale = (MAX_SIZE as f32 / w.max(h).max(1) as f32).min(1.0);
+	let small_w = ((w as f32 * scale).round() as u32).max(1);
+	let small_h = ((h as f32 * scale).round() as u32).max(1);
+	let small = imageops::thumbnail(image, small_w, small_h);
+	bake_orientation(imageops::blur(&small, BLUR_SIGMA), orientation)
+}
+
+/// Expresses the orientation as a transpose followed by horizontal and vertical flips.
+fn orientation_as_flips(orientation: Orientation) -> (bool, bool, bool) {
+	match orientation {
+		Orientation::Deg0 => (false, false, false),
+		Orientation::Deg0HorFlip => (false, true, false),
+		Orientation::Deg180 => (false, true, true),
+		Orientation::Deg180HorFlip => (false, false, true),
+		Orientation::Deg90 => (true, false, true),
+		Orientation::Deg90VerFlip => (true, false, false),
+		Orientation::Deg270 => (true, true, false),
+		Orientation::Deg270VerFlip => (true, true, true),
+	}
+}
+
+fn rgb_to_rgba(src: &[u8], dst: &mut [u8]) {
+	#[cfg(target_arch = "x86_64")]
+	{
+		if is_x86_feature_detected!("ssse3") {
+			// SAFETY: the required CPU feature was detected above
+			unsafe { rgb_to_rgba_ssse3(src, dst) };
+			return;
+		}
+	}
+	rgb_to_rgba_scalar(src, dst);
+}
+
+fn rgb_to_rgba_scalar(src: &[u8], dst: &mut [u8]) {
+	for (rgb, rgba) in src.chunks_exact(3).zip(dst.chunks_exact_mut(4)) {
+		rgba[..3].copy_from_slice(rgb);
+		rgba[3] = u8::MAX;
+	}
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "ssse3")]
+unsafe fn rgb_to_rgba_ssse3(src: &[u8], dst: &mut [u8]) {
+	let pixel_count = src.len() / 3;
+	assert!(dst.len() >= pixel_count * 4);
+	let shuffle = _mm_setr_epi8(0, 1, 2, -1, 3, 4, 5, -1, 6, 7, 8, -1, 9, 10, 11, -1);
+	let alpha = _mm_set1_epi32(0xFF00_0000u32 as i32);
+
+	// Each iteration consumes 12 bytes but loads 16, so stop early enough to never read past the
+	// end of `src`.
+	let mut i = 0;
+	while (i + 6) * 3 <= src.len() {
+		let rgb = _mm_loadu_si128(src.as_ptr().add(i * 3) as *const __m128i);
+		let rgba = _mm_or_si128(_mm_shuffle_epi8(rgb, shuffle), alpha);
+		_mm_storeu_si128(dst.as_mut_ptr().add(i * 4) as *mut __m128i, rgba);
+		i += 4;
+	}
+	rgb_to_rgba_scalar(&src[i * 3..], &mut dst[i * 4..]);
+}
+
+fn luma_to_rgba(src: &[u8], dst: &mut [u8]) {
+	#[cfg(target_arch = "x86_64")]
+	{
+		if is_x86_feature_detected!("ssse3") {
+			// SAFETY: the required CPU feature was detected above
+			unsafe { luma_to_rgba_ssse3(src, dst) };
+			return;
+		}
+	}
+	luma_to_rgba_scalar(src, dst);
+}
+
+fn luma_to_rgba_scalar(src: &[u8], dst: &mut [u8]) {
+	for (&luma, rgba) in src.iter().zip(dst.chunks_exact_mut(4)) {
+		rgba.copy_from_slice(&[luma, luma, luma, u8::MAX]);
+	}
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "ssse3")]
+unsafe fn luma_to_rgba_ssse3(src: &[u8], dst: &mut [u8]) {
+	assert!(dst.len() >= src.len() * 4);
+	let shuffles = [
+		_mm_setr_epi8(0, 0, 0, -1, 1, 1, 1, -1, 2, 2, 2, -1, 3, 3, 3, -1),
+		_mm_setr_epi8(4, 4, 4, -1, 5, 5, 5, -1, 6, 6, 6, -1, 7, 7, 7, -1),
+		_mm_setr_epi8(8, 8, 8, -1, 9, 9, 9, -1, 10, 10, 10, -1, 11, 11, 11, -1),
+		_mm_setr_epi8(12, 12, 12, -1, 13, 13, 13, -1, 14, 14, 14, -1, 15, 15, 15, -1),
+	];
+	let alpha = _mm_set1_epi32(0xFF00_0000u32 as i32);
+
+	let mut i = 0;
+	while i + 16 <= src.len() {
+		let luma = _mm_loadu_si128(src.as_ptr().add(i) as *const __m128i);
+		for (j, shuffle) in shuffles.iter().enumerate() {
+			let rgba = _mm_or_si128(_mm_shuffle_epi8(luma, *shuffle), alpha);
+			_mm_storeu_si128(dst.as_mut_ptr().add((i + j * 4) * 4) as *mut __m128i, rgba);
+		}
+		i += 16;
+	}
+	luma_to_rgba_scalar(&src[i..], &mut dst[i * 4..]);
+}
+
+/// Averages each 2x2 block of the `w`x`h` RGBA image in `src` into a pixel of `dst`
+fn halve(src: &[u8], w: usize, h: usize, dst: &mut [u8]) {
+	let (dst_w, dst_h) = ((w / 2).max(1), (h / 2).max(1));
+	assert!(src.len() >= w * h * 4 && dst.len() >= dst_w * dst_h * 4);
+	for y in 0..dst_h {
+		// SSE2 is part of the x86_64 baseline so there's nothing to detect here
+		#[cfg(target_arch = "x86_64")]
+		// SAFETY: the buffer sizes were checked above
+		let done = unsafe { halve_row_sse2(src, w, h, dst, y) };
+		#[cfg(not(target_arch = "x86_64"))]
+		let done = 0;
+		halve_row_scalar(src, w, h, dst, y, done);
+	}
+}
+
+/// Halves the source rows of the row `y` of `dst`, from the column `start_x` of `dst`
+fn halve_row_scalar(src: &[u8], w: usize, h: usize, dst: &mut [u8], y: usize, start_x: usize) {
+	let dst_w = (w / 2).max(1);
+	let top = 2 * y;
+	let bottom = (2 * y + 1).min(h - 1);
+	for x in start_x..dst_w {
+		let left = 2 * x;
+		let right = (2 * x + 1).min(w - 1);
+		for channel in 0..4 {
+			let at = |x: usize, y: usize| src[(y * w + x) * 4 + channel];
+			let even = average(at(left, top), at(left, bottom));
+			let odd = average(at(right, top), at(right, bottom));
+			dst[(y * dst_w + x) * 4 + channel] = average(even, odd);
+		}
+	}
+}
+
+/// Rounds up like `_mm_avg_epu8`, so that the scalar and the SIMD results are the same
+fn average(a: u8, b: u8) -> u8 {
+	(a as u16 + b as u16).div_ceil(2) as u8
+}
+
+/// Halves the source rows of the row `y` of `dst` four pixels at a time, and returns how many
+/// pixels of the row are done
+#[cfg(target_arch = "x86_64")]
+unsafe fn halve_row_sse2(src: &[u8], w: usize, h: usize, dst: &mut [u8], y: usize) -> usize {
+	let dst_w = (w / 2).max(1);
+	let top = src.as_ptr().add(2 * y * w * 4);
+	let bottom = src.as_ptr().add((2 * y + 1).min(h - 1) * w * 4);
+	let dst_ptr = dst.as_mut_ptr().add(y * dst_w * 4);
+	let mut x = 0;
+	// Each iteration reads 8 pixels of both source rows, which exist as long as 4 pixels of the
+	// destination row are left
+	while x + 4 <= dst_w {
+		let load = |row: *const u8, offset: usize| {
+			_mm_loadu_si128(row.add((2 * x + offset) * 4) as *const __m128i)
+		};
+		let first = _mm_castsi128_ps(_mm_avg_epu8(load(top, 0), load(bottom, 0)));
+		let second = _mm_castsi128_ps(_mm_avg_epu8(load(top, 4), load(bottom, 4)));
+		let even = _mm_castps_si128(_mm_shuffle_ps(first, second, 0b10_00_10_00));
+		let odd = _mm_castps_si128(_mm_shuffle_ps(first, second, 0b11_01_11_01));
+		_mm_storeu_si128(dst_ptr.add(x * 4) as *mut __m128i, _mm_avg_epu8(even, odd));
+		x += 4;
+	}
+	x
+}
+
+/// Describes how each pixel of a `w`x`h` RGBA image moves to a destination buffer.
+///
+/// The source is first transposed (if `transpose` is set) and then the result is mirrored along
+/// the axes specified by `flip_x` and `flip_y`.
+#[derive(Clone, Copy)]
+struct Remap {
+	w: usize,
+	h: usize,
+	transpose: bool,
+	flip_x: bool,
+	flip_y: bool,
+}
+
+impl Remap {
+	/// Size of the square tiles used when transposing, in pixels. Working in tiles keeps both the
+	/// source rows and the destination rows of a tile in the cache.
+	const TILE_SIZE: usize = 64;
+
+	fn dst_dimensions(&self) -> (usize, usize) {
+		if self.transpose {
+			(self.h, self.w)
+		} else {
+			(self.w, self.h)
+		}
+	}
+
+	fn dst_index(&self, x: usize, y: usize) -> usize {
+		let (dst_w, dst_h) = self.dst_dimensions();
+		let (x, y) = if self.transpose { (y, x) } else { (x, y) };
+		let x = if self.flip_x { dst_w - 1 - x } else { x };
+		let y = if self.flip_y { dst_h - 1 - y } else { y };
+		(y * dst_w + x) * 4
+	}
+
+	fn copy_pixel(&self, src: &[u8], dst: &mut [u8], x: usize, y: usize) {
+		let src_index = (y * self.w + x) * 4;
+		let dst_index = self.dst_index(x, y);
+		dst[dst_index..dst_index + 4].copy_from_slice(&src[src_index..src_index + 4]);
+	}
+
+	fn apply(&self, src: &[u8], dst: &mut [u8]) {
+		assert!(src.len() >= self.w * self.h * 4 && dst.len() >= self.w * self.h * 4);
+		// SSE2 is part of the x86_64 baseline so there's nothing to detect here
+		#[cfg(target_arch = "x86_64")]
+		{
+			// SAFETY: the buffer sizes were checked above
+			unsafe { self.apply_sse2(src, dst) };
+		}
+		#[cfg(not(target_arch = "x86_64"))]
+		{
+			self.apply_scalar(src, dst);
+		}
+	}
+
+	fn apply_scalar(&self, src: &[u8], dst: &mut [u8]) {
+		if !self.transpose && !self.flip_x {
+			// Only the row order changes so whole rows can be copied at once
+			let row_len = self.w * 4;
+			for y in 0..self.h {
+				let dst_start = self.dst_index(0, y);
+				dst[dst_start..dst_start + row_len]
+					.copy_from_slice(&src[y * row_len..(y + 1) * row_len]);
+			}
+			return;
+		}
+		for y in 0..self.h {
+			for x in 0..self.w {
+				self.copy_pixel(src, dst, x, y);
+			}
+		}
+	}
+
+	#[cfg(target_arch = "x86_64")]
+	unsafe fn apply_sse2(&self, src: &[u8], dst: &mut [u8]) {
+		if !self.transpose {
+			if !self.flip_x {
+				return self.apply_scalar(src, dst);
+			}
+			let src_ptr = src.as_ptr();
+			let dst_ptr = dst.as_mut_ptr();
+			for y in 0..self.h {
+				let mut x = 0;
+				while x + 4 <= self.w {
+					let pixels =
+						_mm_loadu_si128(src_ptr.add((y * self.w + x) * 4) as *const __m128i);
+					// The rightmost of the four pixels is the one that lands at the lowest address
+					let reversed = _mm_shuffle_epi32(pixels, 0b00_01_10_11);
+					let dst_index = self.dst_index(x + 3, y);
+					_mm_storeu_si128(dst_ptr.add(dst_index) as *mut __m128i, reversed);
+					x += 4;
+				}
+				for x in x..self.w {
+					self.copy_pixel(src, dst, x, y);
+				}
+			}
+			return;
+		}
+
+		let full_w = self.w - self.w % 4;
+		let full_h = self.h - self.h % 4;
+		for tile_y in (0..full_h).step_by(Self::TILE_SIZE) {
+			for tile_x in (0..full_w).step_by(Self::TILE_SIZE) {
+				let end_y = (tile_y + Self::TILE_SIZE).min(full_h);
+				let end_x = (tile_x + Self::TILE_SIZE).min(full_w);
+				for y in (tile_y..end_y).step_by(4) {
+					for x in (tile_x..end_x).step_by(4) {
+						self.transpose_block_sse2(src, dst, x, y);
+					}
+				}
+			}
+		}
+		// The pixels along the right and bottom edges that don't fill a whole 4x4 block
+		for y in 0..self.h {
+			let start_x = if y < full_h { full_w } else { 0 };
+			for x in start_x..self.w {
+				self.copy_pixel(src, dst, x, y);
+			}
+		}
+	}
+
+	/// Transposes the 4x4 pixel block whose top left corner is at `x`, `y` in the source.
+	#[cfg(target_arch = "x86_64")]
+	#[inline(always)]
+	unsafe fn transpose_block_sse2(&self, src: &[u8], dst: &mut [u8], x: usize, y: usize) {
+		let src_ptr = src.as_ptr();
+		let load = |row: usize| {
+			_mm_loadu_si128(src_ptr.add(((y + row) * self.w + x) * 4) as *const __m128i)
+		};
+		let (r0, r1, r2, r3) = (load(0), load(1), load(2), load(3));
+
+		let t0 = _mm_unpacklo_epi32(r0, r1);
+		let t1 = _mm_unpacklo_epi32(r2, r3);
+		let t2 = _mm_unpackhi_epi32(r0, r1);
+		let t3 = _mm_unpackhi_epi32(r2, r3);
+		let columns = [
+			_mm_unpacklo_epi64(t0, t1),
+			_mm_unpackhi_epi64(t0, t1),
+			_mm_unpacklo_epi64(t2, t3),
+			_mm_unpackhi_epi64(t2, t3),
+		];
+
+		let dst_ptr = dst.as_mut_ptr();
+		for (i, column) in columns.iter().enumerate() {
+			// Each column of the source block is a row in the destination
+			let (column, dst_index) = if self.flip_x {
+				(_mm_shuffle_epi32(*column, 0b00_01_10_11), self.dst_index(x + i, y + 3))
+			} else {
+				(*column, self.dst_index(x + i, y))
+			};
+			_mm_storeu_si128(dst_ptr.add(dst_index) as *mut __m128i, column);
+		}
+	}
+}
+
+#[cfg(feature = "benchmark")]
+fn measure<T>(fun: impl FnOnce() -> T) -> std::time::Duration {
+	let start = std::time::Instant::now();
+	let result = fun();
+	let elapsed = start.elapsed();
+	drop(result);
+	elapsed
+}
+
+#[cfg(feature = "benchmark")]
+fn report(
+	name: &str,
+	w: u32,
+	h: u32,
+	elapsed: std::time::Duration,
+	fallback_elapsed: std::time::Duration,
+) {
+	println!(
+		"{} of a {}x{} image took {:.2}ms, the fallback took {:.2}ms ({:.1}x speedup)",
+		name,
+		w,
+		h,
+		elapsed.as_secs_f64() * 1000.0,
+		fallback_elapsed.as_secs_f64() * 1000.0,
+		fallback_elapsed.as_secs_f64() / elapsed.as_secs_f64()
+	);
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::{Duration, Instant};
+
+	use super::*;
+
+	const ORIENTATIONS: [Orientation; 8] = [
+		Orientation::Deg0,
+		Orientation::Deg0HorFlip,
+		Orientation::Deg180,
+		Orientation::Deg180HorFlip,
+		Orientation::Deg90,
+		Orientation::Deg90VerFlip,
+		Orientation::Deg270,
+		Orientation::Deg270VerFlip,
+	];
+
+	/// Includes odd sizes and sizes that aren't a multiple of the block or the tile size, so that
+	/// the edges handled by the scalar code are covered too
+	const SIZES: [(usize, usize); 8] =
+		[(1, 1), (3, 1), (1, 5), (4, 4), (5, 7), (17, 9), (67, 130), (131, 65)];
+
+	fn test_pixels(len: usize) -> Vec<u8> {
+		(0..len).map(|i| (i * 7 + i / 251) as u8).collect()
+	}
+
+	fn remap(orientation: Orientation, w: usize, h: usize) -> Remap {
+		let (transpose, flip_x, flip_y) = orientation_as_flips(orientation);
+		Remap { w, h, transpose, flip_x, flip_y }
+	}
+
+	#[test]
+	fn remap_matches_scalar() {
+		for &orientation in &ORIENTATIONS {
+			for &(w, h) in &SIZES {
+				let remap = remap(orientation, w, h);
+				let src = test_pixels(w * h * 4);
+				let mut expected = vec![0; src.len()];
+				remap.apply_scalar(&src, &mut expected);
+				let mut actual = vec![0; src.len()];
+				remap.apply(&src, &mut actual);
+				assert!(expected == actual, "{:?} of a {}x{} image differs", orientation, w, h);
+			}
+		}
+	}
+
+	#[test]
+	fn bake_orientation_matches_oriented_to_raw() {
+		for &orientation in &ORIENTATIONS {
+			for &(w, h) in &SIZES {
+				let (w, h) = (w as u32, h as u32);
+				let image = RgbaImage::from_raw(w, h, test_pixels((w * h * 4) as usize)).unwrap();
+				let baked = bake_orientation(image.clone(), orientation);
+				for (x, y, pixel) in baked.enumerate_pixels() {
+					let (raw_x, raw_y) = oriented_to_raw(orientation, w, h, x, y);
+					assert_eq!(pixel, image.get_pixel(raw_x, raw_y), "{:?}", orientation);
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn rgb_to_rgba_matches_scalar() {
+		for pixel_count in (0..40).chain([1001, 1023]) {
+			let src = test_pixels(pixel_count * 3);
+			let mut expected = vec![0; pixel_count * 4];
+			rgb_to_rgba_scalar(&src, &mut expected);
+			let mut actual = vec![0; pixel_count * 4];
+			rgb_to_rgba(&src, &mut actual);
+			assert!(expected == actual, "{} pixels differ", pixel_count);
+		}
+	}
+
+	#[test]
+	fn luma_to_rgba_matches_scalar() {
+		for pixel_count in (0..40).chain([1001, 1023]) {
+			let src = test_pixels(pixel_count);
+			let mut expected = vec![0; pixel_count * 4];
+			luma_to_rgba_scalar(&src, &mut expected);
+			let mut actual = vec![0; pixel_count * 4];
+			luma_to_rgba(&src, &mut actual);
+			assert!(expected == actual, "{} pixels differ", pixel_count);
+		}
+	}
+
+	fn halve_scalar(src: &[u8], w: usize, h: usize, dst: &mut [u8]) {
+		for y in 0..(h / 2).max(1) {
+			halve_row_scalar(src, w, h, dst, y, 0);
+		}
+	}
+
+	#[test]
+	fn halve_matches_scalar() {
+		for &(w, h) in SIZES.iter().chain(&[(2, 2), (8, 3), (16, 16), (1000, 2)]) {
+			let src = test_pixels(w * h * 4);
+			let dst_len = (w / 2).max(1) * (h / 2).max(1) * 4;
+			let mut expected = vec![0; dst_len];
+			halve_scalar(&src, w, h, &mut expected);
+			let mut actual = vec![0; dst_len];
+			halve(&src, w, h, &mut actual);
+			assert!(expected == actual, "halving a {}x{} image differs", w, h);
+		}
+	}
+
+	#[test]
+	fn downscale_to_fit_averages_blocks() {
+		let image = RgbaImage::from_raw(5, 2, test_pixels(5 * 2 * 4)).unwrap();
+		assert_eq!(downscale_to_fit(image.clone(), 5).dimensions(), (5, 2));
+		let halved = downscale_to_fit(image.clone(), 4);
+		assert_eq!(halved.dimensions(), (2, 1));
+		let pixel = |x, y| image.get_pixel(x, y).0;
+		for channel in 0..4 {
+			let left = average(pixel(2, 0)[channel], pixel(2, 1)[channel]);
+			let right = average(pixel(3, 0)[channel], pixel(3, 1)[channel]);
+			assert_eq!(halved.get_pixel(1, 0).0[channel], average(left, right));
+		}
+		assert_eq!(downscale_to_fit(image, 1).dimensions(), (1, 1));
+	}
+
+	/// Returns the fastest of a few runs of `fun`
+	fn fastest(mut fun: impl FnMut()) -> Duration {
+		(0..5)
+			.map(|_| {
+				let start = Instant::now();
+				fun();
+				start.elapsed()
+			})
+			.min()
+			.unwrap()
+	}
+
+	/// Compares the SIMD paths to the scalar ones on a 24 megapixel image, and checks that they
+	/// process a loaded photo at least twice as fast: expanding it to RGBA, rotating it and halving
+	/// it. Timings of unoptimized builds mean nothing, so run it with
+	/// `cargo test --release -- --nocapture benchmark`.
+	#[test]
+	#[cfg_attr(debug_assertions, ignore)]
+	fn benchmark() {
+		const W: usize = 6000;
+		const H: usize = 4000;
+		let print = |name: &str, simd: Duration, scalar: Duration| {
+			println!(
+				"{}: {:.2}ms, scalar: {:.2}ms ({:.1}x speedup)",
+				name,
+				simd.as_secs_f64() * 1000.0,
+				scalar.as_secs_f64() * 1000.0,
+				scalar.as_secs_f64() / simd.as_secs_f64()
+			);
+		};
+		let mut rgba = vec![0; W * H * 4];
+		let mut halved = vec![0; W / 2 * H / 2 * 4];
+
+		let rgb = test_pixels(W * H * 3);
+		let rgb_simd = fastest(|| rgb_to_rgba(&rgb, &mut rgba));
+		let rgb_scalar = fastest(|| rgb_to_rgba_scalar(&rgb, &mut rgba));
+		print("RGB to RGBA", rgb_simd, rgb_scalar);
+
+		let luma = test_pixels(W * H);
+		let simd = fastest(|| luma_to_rgba(&luma, &mut rgba));
+		let scalar = fastest(|| luma_to_rgba_scalar(&luma, &mut rgba));
+		print("Luma to RGBA", simd, scalar);
+
+		let src = test_pixels(W * H * 4);
+		let (mut rotate_simd, mut rotate_scalar) = Default::default();
+		for &orientation in &ORIENTATIONS[1..] {
+			let remap = remap(orientation, W, H);
+			let simd = fastest(|| remap.apply(&src, &mut rgba));
+			let scalar = fastest(|| remap.apply_scalar(&src, &mut rgba));
+			print(&format!("{:?}", orientation), simd, scalar);
+			if let Orientation::Deg90 = orientation {
+				rotate_simd = simd;
+				rotate_scalar = scalar;
+			}
+		}
+
+		let halving_simd = fastest(|| halve(&src, W, H, &mut halved));
+		let halving_scalar = fastest(|| halve_scalar(&src, W, H, &mut halved));
+		print("Halving", halving_simd, halving_scalar);
+
+		let simd = rgb_simd + rotate_simd + halving_simd;
+		let scalar = rgb_scalar + rotate_scalar + halving_scalar;
+		print("Loading a rotated photo", simd, scalar);
+		assert!(scalar >= simd * 2, "the SIMD paths are less than twice as fast");
+	}
+}
@@ -0,0 +1,177 @@
+//! Decoding short video clips with the `ffmpeg` program, for the `video` feature. The frames are
+//! passed on like the frames of an animated image, so clips play, pause and step like animations.
+//!
+//! The `ffmpeg` and `ffprobe` programs have to be installed, `--doctor` reports whether they are
+//! found. Every frame is kept in memory, so the clips are scaled down to `MAX_WIDTH` and only the
+//! first `MAX_FRAMES` frames are decoded, or fewer if they would take more than `MAX_BYTES`.
+
+use std::{
+	ffi::OsString,
+	io::{self, Read},
+	path::Path,
+	process::{Command, Stdio},
+};
+
+use gelatin::image::RgbaImage;
+
+use super::image_loader::{ImageLoaderError, LoadResult, Orientation, Result};
+
+/// Files with these extensions are decoded as videos
+pub const EXTENSIONS: &[&str] = &["mp4", "m4v", "webm", "mkv", "mov"];
+const MAX_WIDTH: u32 = 960;
+const MAX_FRAMES: usize = 300;
+/// The most memory that the decoded frames of a clip may take
+const MAX_BYTES: usize = 256 * 1024 * 1024;
+/// Used when the frame rate of the video is unknown
+const DEFAULT_FRAME_DELAY_NANO: u64 = 1_000_000_000 / 30;
+
+pub fn is_video(path: &Path) -> bool {
+	let extension = path.extension().and_then(|ext| ext.to_str()).map(str::to_lowercase);
+	extension.is_some_and(|ext| EXTENSIONS.contains(&ext.as_str()))
+}
+
+/// What `ffprobe` tells about the first video stream
+struct VideoInfo {
+	/// The size of the frames as they are shown, after the rotation
+	width: u32,
+	height: u32,
+	frame_delay_nano: u64,
+}
+
+impl VideoInfo {
+	/// The size that the frames are decoded at
+	fn decoded_size(&self) -> (u32, u32) {
+		if self.width <= MAX_WIDTH {
+			return (self.width, self.height);
+		}
+		let height = (self.height as u64 * MAX_WIDTH as u64 / self.width as u64).max(1);
+		(MAX_WIDTH, height as u32)
+	}
+}
+
+fn not_found_as_missing_program(program: &str, error: io::Error) -> ImageLoaderError {
+	if error.kind() == io::ErrorKind::NotFound {
+		let description = format!("{} is needed for videos but it's not installed", program);
+		ImageLoaderError { description: description.into() }
+	} else {
+		error.into()
+	}
+}
+
+/// The argument that names `path` as the input. With the `file:` protocol a name starting with `-`
+/// isn't taken for an option, and a name like `http:clip.mp4` isn't taken for an other protocol.
+fn input_arg(path: &Path) -> OsString {
+	let mut arg = OsString::from("file:");
+	arg.push(path);
+	arg
+}
+
+/// The first line of `program -version`, or why it couldn't be run
+pub fn program_status(program: &str) -> String {
+	let output = Command::new(program).arg("-version").stdin(Stdio::null()).output();
+	match output {
+		Ok(output) if output.status.success() => {
+			let version = String::from_utf8_lossy(&output.stdout);
+			version.lines().next().unwrap_or_default().to_owned()
+		}
+		Ok(output) => format!("failed with {}", output.status),
+		Err(e) if e.kind() == io::ErrorKind::NotFound => "not found".to_owned(),
+		Err(e) => format!("could not be run ({})", e),
+	}
+}
+
+fn probe(path: &Path) -> Result<VideoInfo> {
+	let output = Command::new("ffprobe")
+		.args(["-v", "error", "-select_streams", "v:0"])
+		.args(["-show_entries", "stream=width,height,avg_frame_rate:stream_side_data=rotation"])
+		.args(["-of", "default=noprint_wrappers=1", "-i"])
+		.arg(input_arg(path))
+		.stdin(Stdio::null())
+		.output()
+		.map_err(|e| not_found_as_missing_program("ffprobe", e))?;
+	if !output.status.success() {
+		let error = String::from_utf8_lossy(&output.stderr);
+		let description = format!("ffprobe failed: {}", error.trim());
+		return Err(ImageLoaderError { description: description.into() });
+	}
+	let mut width = None;
+	let mut height = None;
+	let mut frame_delay_nano = DEFAULT_FRAME_DELAY_NANO;
+	let mut rotation = 0;
+	for line in String::from_utf8_lossy(&output.stdout).lines() {
+		match line.split_once('=') {
+			Some(("width", value)) => width = value.parse::<u32>().ok(),
+			Some(("height", value)) => height = value.parse::<u32>().ok(),
+			Some(("rotation", value)) => rotation = value.parse::<i32>().unwrap_or(0),
+			Some(("avg_frame_rate", value)) => {
+				// Like "30000/1001", or "0/0" when it's unknown
+				let rate = value.split_once('/').and_then(|(numerator, denominator)| {
+					Some((numerator.parse::<u64>().ok()?, denominator.parse::<u64>().ok()?))
+				});
+				if let Some((numerator, denominator)) = rate.filter(|(n, d)| *n > 0 && *d > 0) {
+					frame_delay_nano = 1_000_000_000 * denominator / numerator;
+				}
+			}
+			_ => (),
+		}
+	}
+	let (width, height) = match (width, height) {
+		(Some(width), Some(height)) if width > 0 && height > 0 => (width, height),
+		_ => return Err(ImageLoaderError { description: "The file has no video stream".into() }),
+	};
+	// ffmpeg rotates the frames according to the metadata of the stream
+	let (width, height) =
+		if rotation.rem_euclid(180) == 90 { (height, width) } else { (width, height) };
+	Ok(VideoInfo { width, height, frame_delay_nano })
+}
+
+/// Decodes the frames of the video at `path`, or only the first one unless `allow_animation` is
+/// set, and passes them to `process_image`
+pub fn load<F>(path: &Path, allow_animation: bool, req_id: u32, mut process_image: F) -> Result<()>
+where
+	F: FnMut(LoadResult) -> Result<()>,
+{
+	let info = probe(path)?;
+	let (width, height) = info.decoded_size();
+	let frame_len = width as usize * height as usize * 4;
+	let max_frames = if allow_animation { MAX_FRAMES.min(MAX_BYTES / frame_len).max(1) } else { 1 };
+	let mut child = Command::new("ffmpeg")
+		.args(["-v", "error", "-nostdin", "-i"])
+		.arg(input_arg(path))
+		.args(["-an", "-frames:v", &max_frames.to_string()])
+		.args(["-vf", &format!("scale={}:{}", width, height)])
+		.args(["-f", "rawvideo", "-pix_fmt", "rgba", "-"])
+		.stdin(Stdio::null())
+		.stdout(Stdio::piped())
+		.stderr(Stdio::null())
+		.spawn()
+		.map_err(|e| not_found_as_missing_program("ffmpeg", e))?;
+	// The output was piped, so it's there
+	let mut stdout = child.stdout.take().unwrap();
+	let mut result = Ok(());
+	let mut frame_count = 0;
+	while frame_count < max_frames {
+		let mut pixels = vec![0; frame_len];
+		if stdout.read_exact(&mut pixels).is_err() {
+			// The end of the video
+			break;
+		}
+		// The buffer has the right size for the dimensions
+		let image = RgbaImage::from_raw(width, height, pixels).unwrap();
+		let delay_nano = info.frame_delay_nano;
+		let frame = LoadResult::Frame { req_id, image, delay_nano, orientation: Orientation::Deg0 };
+		result = process_image(frame);
+		if result.is_err() {
+			break;
+		}
+		frame_count += 1;
+	}
+	drop(stdout);
+	// ffmpeg may still be decoding when the frames that are needed were read
+	let _ = child.kill();
+	let _ = child.wait();
+	if result.is_ok() && frame_count == 0 {
+		return Err(ImageLoaderError { description: "ffmpeg could not decode the video".into() });
+	}
+	result
+}
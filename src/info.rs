@@ -0,0 +1,81 @@
+//! The `--info` report, which prints what's known about image files without opening a window, for
+//! scripts.
+
+use std::fmt::Write;
+use std::fs;
+use std::io::BufReader;
+use std::path::Path;
+
+use gelatin::image::{self, ImageDecoder};
+
+use crate::image_cache::image_loader::{self, ImgFormat};
+use crate::sidecar;
+
+/// The EXIF fields that are printed, with their labels
+const EXIF_FIELDS: &[(exif::Tag, &str)] = &[
+	(exif::Tag::Make, "camera make"),
+	(exif::Tag::Model, "camera model"),
+	(exif::Tag::LensModel, "lens"),
+	(exif::Tag::DateTimeOriginal, "taken"),
+	(exif::Tag::ExposureTime, "exposure time"),
+	(exif::Tag::FNumber, "f-number"),
+	(exif::Tag::PhotographicSensitivity, "iso"),
+	(exif::Tag::FocalLength, "focal length"),
+	(exif::Tag::Orientation, "orientation"),
+	(exif::Tag::Software, "software"),
+];
+
+/// Prints the report of each file to the standard output. Returns false if any of them couldn't
+/// be read.
+pub fn run(paths: &[&Path]) -> bool {
+	let mut all_read = true;
+	for (index, path) in paths.iter().enumerate() {
+		if index > 0 {
+			println!();
+		}
+		match report(path) {
+			Ok(report) => print!("{}", report),
+			Err(e) => {
+				eprintln!("Could not read {:?}: {}", path, e);
+				all_read = false;
+			}
+		}
+	}
+	all_read
+}
+
+fn report(path: &Path) -> image_loader::Result<String> {
+	let header = image_loader::read_header(path)?;
+	let mut out = String::new();
+	// Writing to a String can't fail
+	let _ = writeln!(out, "file: {}", path.display());
+	let _ = writeln!(out, "size: {} bytes", fs::metadata(path)?.len());
+	let _ = writeln!(out, "format: {}", header.describe_format());
+	if let Some((w, h)) = header.dimensions {
+		let _ = writeln!(out, "dimensions: {}x{}", w, h);
+	}
+	if let ImgFormat::Image(format) = header.format {
+		let reader = BufReader::new(fs::File::open(path)?);
+		let decoder = image::ImageReader::with_format(reader, format).into_decoder()?;
+		let _ = writeln!(out, "color type: {:?}", decoder.color_type());
+	}
+
+	let metadata = sidecar::read(path);
+	if let Some(rating) = metadata.rating {
+		let _ = writeln!(out, "rating: {}", rating);
+	}
+	if !metadata.tags.is_empty() {
+		let _ = writeln!(out, "tags: {}", metadata.tags.join(", "));
+	}
+
+	let mut reader = BufReader::new(fs::File::open(path)?);
+	if let Ok(exif) = exif::Reader::new().read_from_container(&mut reader) {
+		for (tag, label) in EXIF_FIELDS {
+			if let Some(field) = exif.get_field(*tag, exif::In::PRIMARY) {
+				let value = field.display_value().with_unit(&exif).to_string();
+				let _ = writeln!(out, "{}: {}", label, value.trim_matches('"'));
+			}
+		}
+	}
+	Ok(out)
+}
@@ -1,24 +1,43 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
+use std::iter;
+use std::path::Path;
 use std::process::Command;
 use std::rc::Rc;
+use std::sync::{
+	atomic::{AtomicUsize, Ordering},
+	mpsc, Arc,
+};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use crate::configuration::Configuration;
-use gelatin::winit::keyboard::ModifiersState;
+use crate::configuration::{self, Configuration};
+use crate::editing::TexelRect;
+use crate::file_manager;
+use crate::image_cache::image_loader::Orientation;
+use gelatin::winit::{event::MouseButton, keyboard::ModifiersState};
 use lazy_static::lazy_static;
 
 pub static TOGGLE_FULLSCREEN_NAME: &str = "toggle_fullscreen";
 pub static ESCAPE_NAME: &str = "escape";
 pub static IMG_NEXT_NAME: &str = "img_next";
 pub static IMG_PREV_NAME: &str = "img_prev";
+pub static IMG_FIRST_NAME: &str = "img_first";
+pub static IMG_LAST_NAME: &str = "img_last";
 pub static IMG_ORIG_NAME: &str = "img_orig";
 pub static IMG_FIT_NAME: &str = "img_fit";
 pub static IMG_FIT_BEST_NAME: &str = "img_fit_best";
+pub static TOGGLE_FIT_NAME: &str = "toggle_fit";
 pub static IMG_DEL_NAME: &str = "img_del";
+pub static IMG_DEL_PERMANENT_NAME: &str = "img_del_permanent";
 pub static IMG_COPY_NAME: &str = "img_copy";
+pub static COPY_PATH_NAME: &str = "copy_path";
+pub static PASTE_NAME: &str = "paste";
+pub static SAVE_NAME: &str = "save";
 pub static PAN_NAME: &str = "pan";
 pub static PAN_VERT_NAME: &str = "pan_vert"; // Vertical panning
 pub static PAN_HOR_NAME: &str = "pan_hor"; // Horizontal panning
+pub static PAN_HINT_NAME: &str = "pan_hint";
 pub static PLAY_ANIM_NAME: &str = "play_anim";
 pub static PLAY_PRESENT_NAME: &str = "play_present";
 pub static PLAY_PRESENT_RND_NAME: &str = "play_present_rnd";
@@ -30,29 +49,223 @@ pub static PAN_LEFT_NAME: &str = "pan_left";
 pub static PAN_RIGHT_NAME: &str = "pan_right";
 pub static PAN_UP_NAME: &str = "pan_up";
 pub static PAN_DOWN_NAME: &str = "pan_down";
+pub static PIXEL_INSPECTOR_NAME: &str = "pixel_inspector";
+pub static CROP_NAME: &str = "crop";
+pub static CROP_SAVE_NAME: &str = "crop_save";
+pub static EDIT_CONFIG_NAME: &str = "edit_config";
+pub static SAVE_PLAYLIST_NAME: &str = "save_playlist";
+pub static OPEN_PLAYLIST_NAME: &str = "open_playlist";
+pub static TOGGLE_TWO_PAGE_SPREAD_NAME: &str = "toggle_two_page_spread";
+pub static TOGGLE_RIGHT_TO_LEFT_NAME: &str = "toggle_right_to_left";
+pub static SHOW_STATS_NAME: &str = "show_stats";
+pub static UNDO_NAME: &str = "undo";
+pub static VERIFY_NAME: &str = "verify";
+pub static RENAME_NAME: &str = "rename";
+pub static RATE_NAMES: [&str; 6] = ["rate_0", "rate_1", "rate_2", "rate_3", "rate_4", "rate_5"];
+pub static RATING_FILTER_NAME: &str = "rating_filter";
+pub static EDIT_TAGS_NAME: &str = "edit_tags";
+pub static TAG_FILTER_NAME: &str = "tag_filter";
+pub static NAME_FILTER_NAME: &str = "name_filter";
+pub static LOCK_ZOOM_NAME: &str = "lock_zoom";
+pub static READING_MODE_NAME: &str = "reading_mode";
+pub static SCROLL_ZOOM_NAME: &str = "scroll_zoom";
+pub static TOGGLE_BOTTOM_BAR_NAME: &str = "toggle_bottom_bar";
+pub static TOGGLE_HELP_NAME: &str = "toggle_help";
+pub static TOGGLE_THEME_NAME: &str = "toggle_theme";
+pub static CONTEXT_MENU_NAME: &str = "context_menu";
+pub static REVEAL_IN_FILE_MANAGER_NAME: &str = "reveal_in_file_manager";
+pub static OPEN_WITH_EDITOR_NAME: &str = "open_with_editor";
+pub static SET_WALLPAPER_NAME: &str = "set_wallpaper";
+pub static CYCLE_MONITOR_NAME: &str = "cycle_monitor";
+pub static OPEN_FILE_NAME: &str = "open_file";
+pub static OPEN_FOLDER_NAME: &str = "open_folder";
+pub static DRAG_OUT_NAME: &str = "drag_out";
+pub static SHARE_NAME: &str = "share";
+pub static NEW_WINDOW_NAME: &str = "new_window";
+pub static COMPARE_SIDE_BY_SIDE_NAME: &str = "compare_side_by_side";
+pub static PIN_A_NAME: &str = "pin_a";
+pub static FLICKER_A_B_NAME: &str = "flicker_a_b";
+pub static DIFFERENCE_VIEW_NAME: &str = "difference_view";
+pub static ONION_SKIN_NAME: &str = "onion_skin";
+pub static STEP_FORWARD_NAME: &str = "step_forward";
+pub static STEP_BACKWARD_NAME: &str = "step_backward";
+pub static PLAYBACK_FASTER_NAME: &str = "playback_faster";
+pub static PLAYBACK_SLOWER_NAME: &str = "playback_slower";
+pub static TOGGLE_PLAYBACK_LOOP_NAME: &str = "toggle_playback_loop";
+pub static TOGGLE_ANIMATION_NAME: &str = "toggle_animation";
+pub static NEXT_FRAME_NAME: &str = "next_frame";
+pub static PREV_FRAME_NAME: &str = "prev_frame";
+pub static EXPORT_AS_NAME: &str = "export_as";
+pub static SNAPSHOT_NAME: &str = "snapshot";
+pub static MARK_RANGE_NAME: &str = "mark_range";
+pub static EXPORT_ANIMATION_NAME: &str = "export_animation";
+pub static CYCLE_PROFILE_NAME: &str = "cycle_profile";
+pub static BRIGHTNESS_UP_NAME: &str = "brightness_up";
+pub static BRIGHTNESS_DOWN_NAME: &str = "brightness_down";
+pub static CONTRAST_UP_NAME: &str = "contrast_up";
+pub static CONTRAST_DOWN_NAME: &str = "contrast_down";
+pub static GAMMA_UP_NAME: &str = "gamma_up";
+pub static GAMMA_DOWN_NAME: &str = "gamma_down";
+pub static RESET_ADJUSTMENTS_NAME: &str = "reset_adjustments";
+pub static TOGGLE_GRAYSCALE_NAME: &str = "toggle_grayscale";
+pub static TOGGLE_SEPIA_NAME: &str = "toggle_sepia";
+pub static TOGGLE_INVERT_NAME: &str = "toggle_invert";
+pub static TOGGLE_ON_TOP_NAME: &str = "toggle_on_top";
+pub static TOGGLE_OVERLAY_NAME: &str = "toggle_overlay";
+pub static WHEEL_INPUT_KEY: &str = "wheel";
+pub static DOUBLE_TAP_INPUT_KEY: &str = "doubletap";
 
 lazy_static! {
 	pub static ref DEFAULT_BINDINGS: HashMap<&'static str, Vec<&'static str>> = {
 		let mut m = HashMap::new();
-		m.insert(TOGGLE_FULLSCREEN_NAME, vec!["F11", "Return"]);
-		m.insert(ESCAPE_NAME, vec!["Escape", "Q"]);
-		m.insert(IMG_NEXT_NAME, vec!["D", "Right", "PageDown"]);
-		m.insert(IMG_PREV_NAME, vec!["A", "Left", "PageUp"]);
+		m.insert(TOGGLE_FULLSCREEN_NAME, vec!["F11", "Return", "MouseLeftDouble", "GestureUp"]);
+		m.insert(ESCAPE_NAME, vec!["Escape", "Q", "GestureDown"]);
+		m.insert(
+			IMG_NEXT_NAME,
+			vec!["D", "Right", "PageDown", "GestureRight", "MouseForward", "SwipeLeft"],
+		);
+		m.insert(
+			IMG_PREV_NAME,
+			vec!["A", "Left", "PageUp", "GestureLeft", "MouseBack", "SwipeRight"],
+		);
+		m.insert(IMG_FIRST_NAME, vec!["Home", "G G"]);
+		m.insert(IMG_LAST_NAME, vec!["End"]);
 		m.insert(IMG_ORIG_NAME, vec!["1"]);
 		m.insert(IMG_FIT_NAME, vec!["F"]);
 		m.insert(IMG_FIT_BEST_NAME, vec!["E"]);
+		m.insert(TOGGLE_FIT_NAME, vec!["DoubleTap"]);
 		m.insert(IMG_DEL_NAME, vec!["Delete"]);
+		m.insert(IMG_DEL_PERMANENT_NAME, vec!["CmdCtrl+Delete"]);
 		m.insert(IMG_COPY_NAME, vec!["CmdCtrl+C"]);
-		m.insert(PAN_NAME, vec!["Space"]);
+		m.insert(COPY_PATH_NAME, vec!["CmdCtrl+Alt+C"]);
+		m.insert(PASTE_NAME, vec!["CmdCtrl+V"]);
+		m.insert(SAVE_NAME, vec!["CmdCtrl+S"]);
+		m.insert(PAN_NAME, vec!["Space", "MouseLeft"]);
+		m.insert(PAN_HINT_NAME, vec!["MouseRight"]);
+		m.insert(CONTEXT_MENU_NAME, vec!["MouseRightClick"]);
 		m.insert(PLAY_ANIM_NAME, vec!["Alt+A", "Alt+V"]);
 		m.insert(PLAY_PRESENT_NAME, vec!["P"]);
 		m.insert(PLAY_PRESENT_RND_NAME, vec!["Alt+P"]);
 		m.insert(TOGGLE_ANTIALIAS_NAME, vec!["S"]);
 		m.insert(SET_AUTOMATIC_ANTIALIAS_NAME, vec!["Alt+S"]);
+		m.insert(PIXEL_INSPECTOR_NAME, vec!["I"]);
+		m.insert(CROP_NAME, vec!["C"]);
+		m.insert(CROP_SAVE_NAME, vec!["CmdCtrl+S"]);
+		m.insert(EDIT_CONFIG_NAME, vec!["CmdCtrl+,"]);
+		m.insert(SAVE_PLAYLIST_NAME, vec!["CmdCtrl+L"]);
+		m.insert(OPEN_PLAYLIST_NAME, vec!["CmdCtrl+Alt+L"]);
+		m.insert(TOGGLE_TWO_PAGE_SPREAD_NAME, vec!["B"]);
+		m.insert(TOGGLE_RIGHT_TO_LEFT_NAME, vec!["Alt+B"]);
+		m.insert(SHOW_STATS_NAME, vec!["CmdCtrl+Alt+S"]);
+		m.insert(UNDO_NAME, vec!["CmdCtrl+Z"]);
+		m.insert(VERIFY_NAME, vec!["CmdCtrl+Alt+V"]);
+		m.insert(RENAME_NAME, vec!["F2"]);
+		m.insert(RATE_NAMES[0], vec!["CmdCtrl+0"]);
+		m.insert(RATE_NAMES[1], vec!["CmdCtrl+1"]);
+		m.insert(RATE_NAMES[2], vec!["CmdCtrl+2"]);
+		m.insert(RATE_NAMES[3], vec!["CmdCtrl+3"]);
+		m.insert(RATE_NAMES[4], vec!["CmdCtrl+4"]);
+		m.insert(RATE_NAMES[5], vec!["CmdCtrl+5"]);
+		m.insert(RATING_FILTER_NAME, vec!["Alt+R"]);
+		m.insert(EDIT_TAGS_NAME, vec!["T"]);
+		m.insert(TAG_FILTER_NAME, vec!["Alt+T"]);
+		m.insert(NAME_FILTER_NAME, vec!["Alt+F"]);
+		m.insert(LOCK_ZOOM_NAME, vec!["L"]);
+		m.insert(READING_MODE_NAME, vec!["R"]);
+		m.insert(SCROLL_ZOOM_NAME, vec!["Wheel", "Ctrl+Wheel"]);
+		m.insert(TOGGLE_BOTTOM_BAR_NAME, vec!["H"]);
+		m.insert(TOGGLE_HELP_NAME, vec!["F1"]);
+		m.insert(TOGGLE_THEME_NAME, vec!["CmdCtrl+Alt+T"]);
+		m.insert(REVEAL_IN_FILE_MANAGER_NAME, vec!["CmdCtrl+Alt+R"]);
+		m.insert(OPEN_WITH_EDITOR_NAME, vec!["CmdCtrl+E"]);
+		m.insert(SET_WALLPAPER_NAME, vec!["CmdCtrl+Alt+W"]);
+		m.insert(CYCLE_MONITOR_NAME, vec!["Shift+F11"]);
+		m.insert(NEW_WINDOW_NAME, vec!["CmdCtrl+N"]);
+		m.insert(OPEN_FILE_NAME, vec!["CmdCtrl+O"]);
+		m.insert(OPEN_FOLDER_NAME, vec!["CmdCtrl+Alt+F"]);
+		m.insert(DRAG_OUT_NAME, vec!["CmdCtrl+MouseLeft"]);
+		m.insert(COMPARE_SIDE_BY_SIDE_NAME, vec!["Alt+C"]);
+		m.insert(PIN_A_NAME, vec!["Alt+X"]);
+		m.insert(FLICKER_A_B_NAME, vec!["X"]);
+		m.insert(DIFFERENCE_VIEW_NAME, vec!["Alt+D"]);
+		m.insert(ONION_SKIN_NAME, vec!["O"]);
+		m.insert(STEP_FORWARD_NAME, vec!["."]);
+		m.insert(STEP_BACKWARD_NAME, vec![","]);
+		m.insert(PLAYBACK_FASTER_NAME, vec!["]"]);
+		m.insert(PLAYBACK_SLOWER_NAME, vec!["["]);
+		m.insert(TOGGLE_PLAYBACK_LOOP_NAME, vec!["Alt+L"]);
+		m.insert(TOGGLE_ANIMATION_NAME, vec!["K"]);
+		m.insert(NEXT_FRAME_NAME, vec!["Alt+."]);
+		m.insert(PREV_FRAME_NAME, vec!["Alt+,"]);
+		m.insert(EXPORT_AS_NAME, vec!["Alt+E"]);
+		m.insert(SNAPSHOT_NAME, vec!["F12"]);
+		m.insert(MARK_RANGE_NAME, vec!["M"]);
+		m.insert(EXPORT_ANIMATION_NAME, vec!["CmdCtrl+Alt+E"]);
+		m.insert(CYCLE_PROFILE_NAME, vec!["CmdCtrl+Alt+P"]);
+		m.insert(BRIGHTNESS_UP_NAME, vec!["Alt+Up"]);
+		m.insert(BRIGHTNESS_DOWN_NAME, vec!["Alt+Down"]);
+		m.insert(CONTRAST_UP_NAME, vec!["Alt+Right"]);
+		m.insert(CONTRAST_DOWN_NAME, vec!["Alt+Left"]);
+		m.insert(GAMMA_UP_NAME, vec!["Alt+PageUp"]);
+		m.insert(GAMMA_DOWN_NAME, vec!["Alt+PageDown"]);
+		m.insert(RESET_ADJUSTMENTS_NAME, vec!["Alt+0"]);
+		m.insert(TOGGLE_GRAYSCALE_NAME, vec!["Alt+G"]);
+		m.insert(TOGGLE_SEPIA_NAME, vec!["Alt+Y"]);
+		m.insert(TOGGLE_INVERT_NAME, vec!["Alt+I"]);
+		m.insert(TOGGLE_ON_TOP_NAME, vec!["CmdCtrl+T"]);
+		m.insert(TOGGLE_OVERLAY_NAME, vec!["CmdCtrl+Alt+O"]);
 		m
 	};
 }
 
+/// The longest time between the keys of a key sequence
+const KEY_SEQUENCE_TIMEOUT: Duration = Duration::from_secs(1);
+/// The number of keys that are remembered for matching key sequences
+const MAX_KEY_SEQUENCE_LEN: usize = 8;
+
+/// The recently pressed keys, for the bindings that are sequences of keys like `"G G"`. The keys of
+/// a sequence have to be pressed within a second of each other.
+#[derive(Debug, Default)]
+pub struct KeyHistory {
+	/// The most recent press is the last
+	presses: Vec<(String, ModifiersState, Instant)>,
+	/// Set when the most recent press completed a sequence, so that it doesn't start the next one
+	completed_sequence: Cell<bool>,
+}
+
+impl KeyHistory {
+	pub fn push(&mut self, input_key: &str, modifiers: ModifiersState) {
+		let now = Instant::now();
+		let timed_out = self
+			.presses
+			.last()
+			.is_some_and(|(_, _, time)| now.duration_since(*time) > KEY_SEQUENCE_TIMEOUT);
+		if self.completed_sequence.take() || timed_out {
+			self.presses.clear();
+		}
+		if self.presses.len() == MAX_KEY_SEQUENCE_LEN {
+			self.presses.remove(0);
+		}
+		self.presses.push((input_key.to_owned(), modifiers, now));
+	}
+
+	/// Whether the most recent presses are the `steps` of a sequence
+	fn ends_with(&self, steps: &[String]) -> bool {
+		let start = match self.presses.len().checked_sub(steps.len()) {
+			Some(start) => start,
+			None => return false,
+		};
+		let matched = steps
+			.iter()
+			.zip(&self.presses[start..])
+			.all(|(step, (key, modifiers, _))| key_matches(step, key, *modifiers));
+		if matched {
+			self.completed_sequence.set(true);
+		}
+		matched
+	}
+}
+
 pub fn char_to_input_key(ch: &str) -> String {
 	let mut input_key = String::with_capacity(8);
 	if ch == " " {
@@ -67,6 +280,58 @@ pub fn char_to_input_key(ch: &str) -> String {
 	input_key
 }
 
+/// The input key of a mouse button, like `mouseleft`. Mouse buttons are bound like keys, with
+/// `Double` appended for double clicks, as in `MouseLeftDouble`, and with `Click` appended for
+/// releasing the button without moving the cursor, as in `MouseRightClick`. The thumb buttons are
+/// `MouseBack` and `MouseForward`, other buttons are numbered like `Mouse10`.
+pub fn mouse_button_to_input_key(button: MouseButton) -> String {
+	match button {
+		MouseButton::Left => "mouseleft".into(),
+		MouseButton::Right => "mouseright".into(),
+		MouseButton::Middle => "mousemiddle".into(),
+		MouseButton::Back => "mouseback".into(),
+		MouseButton::Forward => "mouseforward".into(),
+		MouseButton::Other(id) => format!("mouse{}", id),
+	}
+}
+
+/// The input key of a mouse gesture, which is made by moving the cursor by `dx` and `dy` with the
+/// right button held down. The gestures are bound as `GestureLeft`, `GestureRight`, `GestureUp`
+/// and `GestureDown`.
+pub fn gesture_input_key(dx: f32, dy: f32) -> String {
+	format!("gesture{}", direction_name(dx, dy))
+}
+
+/// The input key of swiping with one finger by `dx` and `dy` on a touchscreen. Swipes are bound as
+/// `SwipeLeft`, `SwipeRight`, `SwipeUp` and `SwipeDown`.
+pub fn swipe_input_key(dx: f32, dy: f32) -> String {
+	format!("swipe{}", direction_name(dx, dy))
+}
+
+/// The main direction of a movement by `dx` and `dy`
+fn direction_name(dx: f32, dy: f32) -> &'static str {
+	if dx.abs() >= dy.abs() {
+		if dx < 0.0 {
+			"left"
+		} else {
+			"right"
+		}
+	} else if dy < 0.0 {
+		"up"
+	} else {
+		"down"
+	}
+}
+
+/// The input key of one step of the mouse wheel
+pub fn wheel_input_key(up: bool) -> &'static str {
+	if up {
+		"wheelup"
+	} else {
+		"wheeldown"
+	}
+}
+
 fn substitute_command_parameters(string: &str, var_map: &HashMap<&str, &str>) -> String {
 	let mut result = String::from(string);
 	for (&var_name, &substitute) in var_map.iter() {
@@ -75,82 +340,400 @@ fn substitute_command_parameters(string: &str, var_map: &HashMap<&str, &str>) ->
 	result
 }
 
-/// Execute all custom commands that were triggered by the input key and modifier set.
-/// Note: img_path and folder_path both have to be str instead of Path because we
-/// wouldn't be able to construct a command from them if they cannot be converted to
+/// How the image is displayed at the time a command is triggered
+pub struct ViewState {
+	/// Size of a texel in physical pixels, 1.0 means 100%
+	pub zoom: f32,
+	pub orientation: Orientation,
+	/// The crop selection or if there's no selection, the visible part of the image. In texel
+	/// coordinates of the displayed (ie oriented) image.
+	pub region: TexelRect,
+}
+
+impl ViewState {
+	/// Returns the clockwise rotation in degrees that's applied to the image file for displaying
+	/// it and whether it's flipped horizontally after the rotation.
+	pub fn rotation_and_flip(&self) -> (u32, bool) {
+		match self.orientation {
+			Orientation::Deg0 => (0, false),
+			Orientation::Deg0HorFlip => (0, true),
+			Orientation::Deg180 => (180, false),
+			Orientation::Deg180HorFlip => (180, true),
+			Orientation::Deg270 => (90, false),
+			Orientation::Deg90VerFlip => (90, true),
+			Orientation::Deg90 => (270, false),
+			Orientation::Deg270VerFlip => (270, true),
+		}
+	}
+}
+
+/// What happened when a custom command finished
+pub struct CommandReport {
+	/// Shown in the title
+	pub message: Option<String>,
+	/// The command has `reload_after` set, so the shown image should be reloaded
+	pub reload: bool,
+}
+
+/// The reports about the custom commands. The commands that don't wait send them from the
+/// background.
+pub struct CommandMessages {
+	sender: mpsc::Sender<CommandReport>,
+	receiver: mpsc::Receiver<CommandReport>,
+	/// The number of commands that are running in the background
+	running: Arc<AtomicUsize>,
+}
+
+impl CommandMessages {
+	pub fn new() -> CommandMessages {
+		let (sender, receiver) = mpsc::channel();
+		CommandMessages { sender, receiver, running: Default::default() }
+	}
+
+	pub fn try_recv(&self) -> Option<CommandReport> {
+		self.receiver.try_recv().ok()
+	}
+
+	pub fn is_running(&self) -> bool {
+		self.running.load(Ordering::SeqCst) > 0
+	}
+}
+
+/// The shown image at the time a command is triggered. The paths are `str` instead of `Path`
+/// because we wouldn't be able to construct a command from them if they cannot be converted to
 /// valid UTF-8.
+pub struct CommandTarget<'a> {
+	pub img_path: &'a str,
+	pub folder_path: &'a str,
+	/// The index of the image in the folder, starting from 0
+	pub index: Option<usize>,
+	/// The number of images in the folder
+	pub total: Option<usize>,
+	pub view: Option<ViewState>,
+}
+
+/// Execute all custom commands that were triggered by the input key and modifier set.
+///
+/// Besides `${img}` and `${folder}` the arguments may contain `${filename}`, `${index}` (starting
+/// from 1) and `${total}`, as well as `${zoom}`, `${rotation}`, `${flip}` and `${crop_rect}` which
+/// describe the view. `${crop_rect}` is formatted as an ImageMagick geometry (`WxH+X+Y`) and refers
+/// to the image after applying the rotation and the flip. These are substituted with empty strings
+/// when they are not known. The `cwd` of a command may contain the same variables.
 pub fn execute_triggered_commands(
 	config: Rc<RefCell<Configuration>>,
 	input_key: &str,
 	modifiers: ModifiersState,
-	img_path: &str,
-	folder_path: &str,
+	history: &KeyHistory,
+	target: &CommandTarget,
+	messages: &CommandMessages,
 ) {
 	let config = config.borrow();
-	if let Some(ref commands) = config.commands {
-		let mut var_map = HashMap::with_capacity(2);
-		var_map.insert("${img}", img_path);
-		var_map.insert("${folder}", folder_path);
-		for command in commands.iter() {
-			if keys_triggered(&command.input, input_key, modifiers) {
-				let mut cmd = Command::new(&command.program);
-				if let Some(ref args) = command.args {
-					cmd.args(args.iter().map(|arg| substitute_command_parameters(arg, &var_map)));
-				}
-				if let Some(ref envs) = command.envs {
-					cmd.envs(
-						envs.iter().map(|env_var| (env_var.name.as_str(), env_var.value.as_str())),
-					);
-				}
-				if let Err(e) = cmd.status() {
-					eprintln!("Error while executing the following user command. See the error below.\n{:?}\nError: {:?}", command, e);
-				}
+	let commands = config.commands.iter().flatten();
+	execute_commands(
+		commands.filter(|command| keys_triggered(&command.input, input_key, modifiers, history)),
+		target,
+		messages,
+	);
+}
+
+/// Executes the custom command at `index` of the configuration, like when it's chosen from the
+/// context menu
+pub fn execute_command(
+	config: &Configuration,
+	index: usize,
+	target: &CommandTarget,
+	messages: &CommandMessages,
+) {
+	execute_commands(config.commands.iter().flatten().skip(index).take(1), target, messages);
+}
+
+/// Opens the image with the program of the `[editor]` section, or shows the "open with" dialog of
+/// the system if there's no such section
+pub fn open_with_editor(
+	config: &Configuration,
+	target: &CommandTarget,
+	messages: &CommandMessages,
+) {
+	let editor = match &config.editor {
+		Some(editor) => editor,
+		None => {
+			file_manager::open_with_dialog(Path::new(target.img_path));
+			return;
+		}
+	};
+	let command = configuration::Command {
+		program: editor.program.clone(),
+		args: Some(editor.args.clone().unwrap_or_else(|| vec!["${img}".to_owned()])),
+		wait: Some(false),
+		..Default::default()
+	};
+	execute_commands(std::iter::once(&command), target, messages);
+}
+
+fn execute_commands<'a>(
+	commands: impl Iterator<Item = &'a configuration::Command>,
+	target: &CommandTarget,
+	messages: &CommandMessages,
+) {
+	let mut commands = commands.peekable();
+	if commands.peek().is_none() {
+		return;
+	}
+	let (zoom, rotation, flip, crop_rect) = match &target.view {
+		Some(view) => {
+			let (rotation, flip) = view.rotation_and_flip();
+			let rect = view.region;
+			(
+				format!("{:.4}", view.zoom),
+				rotation.to_string(),
+				flip.to_string(),
+				format!("{}x{}+{}+{}", rect.w, rect.h, rect.x, rect.y),
+			)
+		}
+		None => Default::default(),
+	};
+	let file_name = Path::new(target.img_path).file_name().and_then(|name| name.to_str());
+	let index = target.index.map(|index| (index + 1).to_string()).unwrap_or_default();
+	let total = target.total.map(|total| total.to_string()).unwrap_or_default();
+	let mut var_map = HashMap::with_capacity(9);
+	var_map.insert("${img}", target.img_path);
+	var_map.insert("${folder}", target.folder_path);
+	var_map.insert("${filename}", file_name.unwrap_or_default());
+	var_map.insert("${index}", index.as_str());
+	var_map.insert("${total}", total.as_str());
+	var_map.insert("${zoom}", zoom.as_str());
+	var_map.insert("${rotation}", rotation.as_str());
+	var_map.insert("${flip}", flip.as_str());
+	var_map.insert("${crop_rect}", crop_rect.as_str());
+	for command in commands {
+		let mut cmd = if command.shell == Some(true) {
+			shell_command(command, &var_map)
+		} else {
+			let mut cmd = Command::new(&command.program);
+			if let Some(ref args) = command.args {
+				cmd.args(args.iter().map(|arg| substitute_command_parameters(arg, &var_map)));
 			}
+			cmd
+		};
+		if let Some(ref cwd) = command.cwd {
+			cmd.current_dir(substitute_command_parameters(cwd, &var_map));
+		}
+		if let Some(ref envs) = command.envs {
+			cmd.envs(envs.iter().map(|env_var| (env_var.name.as_str(), env_var.value.as_str())));
+		}
+		let capture_output = command.capture_output == Some(true);
+		let reload = command.reload_after == Some(true);
+		if command.wait == Some(false) {
+			let program = command.program.clone();
+			let sender = messages.sender.clone();
+			let running = messages.running.clone();
+			running.fetch_add(1, Ordering::SeqCst);
+			thread::spawn(move || {
+				let message = run_command(cmd, &program, capture_output);
+				send_report(&sender, message, reload);
+				running.fetch_sub(1, Ordering::SeqCst);
+			});
+		} else {
+			let message = run_command(cmd, &command.program, capture_output);
+			send_report(&messages.sender, message, reload);
 		}
 	}
 }
 
-pub fn keys_triggered<S: AsRef<str>>(
-	keys: &[S],
-	input_key: &str,
-	modifiers: ModifiersState,
-) -> bool {
-	for key in keys {
-		let complex_key = key.as_ref();
-		let parts = complex_key.split('+').map(|s| s.trim().to_lowercase()).collect::<Vec<_>>();
-		if parts.is_empty() {
-			continue;
+/// Runs the command and returns the message to show about it: the reason if it failed, or the
+/// last line of its output if that's captured
+fn run_command(mut cmd: Command, program: &str, capture_output: bool) -> Option<String> {
+	let result = if capture_output {
+		cmd.output().map(|output| (output.status, Some(output)))
+	} else {
+		cmd.status().map(|status| (status, None))
+	};
+	match result {
+		Ok((status, output)) if status.success() => {
+			output.and_then(|output| last_line(&output.stdout))
 		}
-		let key = parts.last().unwrap();
-		if input_key != *key {
+		Ok((status, output)) => {
+			let error = output.and_then(|output| last_line(&output.stderr));
+			eprintln!("The user command {:?} failed with {}", program, status);
+			match error {
+				Some(error) => Some(format!("{} failed: {}", program, error)),
+				None => Some(format!("{} failed with {}", program, status)),
+			}
+		}
+		Err(e) => {
+			eprintln!("Error while executing the user command {:?}: {:?}", program, e);
+			Some(format!("Could not run {}: {}", program, e))
+		}
+	}
+}
+
+fn send_report(sender: &mpsc::Sender<CommandReport>, message: Option<String>, reload: bool) {
+	if message.is_some() || reload {
+		let _ = sender.send(CommandReport { message, reload });
+	}
+}
+
+fn last_line(output: &[u8]) -> Option<String> {
+	let output = String::from_utf8_lossy(output);
+	output.lines().map(str::trim).rfind(|line| !line.is_empty()).map(str::to_owned)
+}
+
+/// Joins the program and the arguments of `command` into a command line that's run by the shell,
+/// `sh` or `cmd` on Windows. The substituted values are quoted, so that paths with spaces remain a
+/// single argument.
+fn shell_command(command: &configuration::Command, var_map: &HashMap<&str, &str>) -> Command {
+	let quoted = var_map
+		.iter()
+		.map(|(&var_name, &substitute)| (var_name, quote_for_shell(substitute)))
+		.collect::<Vec<_>>();
+	let quoted_map = quoted.iter().map(|(var_name, quoted)| (*var_name, quoted.as_str())).collect();
+	let mut line = substitute_command_parameters(&command.program, &quoted_map);
+	for arg in command.args.iter().flatten() {
+		line.push(' ');
+		line.push_str(&substitute_command_parameters(arg, &quoted_map));
+	}
+	#[cfg(windows)]
+	{
+		use std::os::windows::process::CommandExt;
+		let mut cmd = Command::new("cmd");
+		cmd.arg("/C").raw_arg(line);
+		cmd
+	}
+	#[cfg(not(windows))]
+	{
+		let mut cmd = Command::new("sh");
+		cmd.arg("-c").arg(line);
+		cmd
+	}
+}
+
+/// Quotes a substituted value, so that the shell passes it to the program as a single argument
+/// without expanding anything in it
+fn quote_for_shell(value: &str) -> String {
+	if cfg!(windows) {
+		quote_for_cmd(value)
+	} else {
+		quote_for_sh(value)
+	}
+}
+
+fn quote_for_sh(value: &str) -> String {
+	format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// Quotes the value the way programs split their command line on Windows, then escapes the
+/// characters that `cmd` interprets with `^`. The quotes are escaped too, because `cmd` would
+/// still expand variables like `%PATH%` between quotes.
+fn quote_for_cmd(value: &str) -> String {
+	let mut quoted = String::from("\"");
+	let mut backslashes = 0;
+	for c in value.chars() {
+		if c == '\\' {
+			backslashes += 1;
 			continue;
 		}
-		let mut has_alt = false;
-		let mut has_ctrl = false;
-		let mut has_logo = false;
-		for mod_str in parts.iter().take(parts.len() - 1) {
-			match mod_str.as_ref() {
-				"alt" => has_alt = true,
-				"ctrl" => has_ctrl = true,
-				"logo" => has_logo = true,
-				"cmdctrl" => {
-					if cfg!(target_os = "macos") {
-						has_logo = true;
-					} else {
-						has_ctrl = true;
-					}
+		// Backslashes are only special before a quote, where they are doubled, and the quote
+		// itself is escaped with one more
+		let count = if c == '"' { backslashes * 2 + 1 } else { backslashes };
+		quoted.extend(iter::repeat_n('\\', count));
+		quoted.push(c);
+		backslashes = 0;
+	}
+	// The backslashes before the closing quote
+	quoted.extend(iter::repeat_n('\\', backslashes * 2));
+	quoted.push('"');
+
+	let mut escaped = String::with_capacity(quoted.len() * 2);
+	for c in quoted.chars() {
+		if "()%!^\"<>&|".contains(c) {
+			escaped.push('^');
+		}
+		escaped.push(c);
+	}
+	escaped
+}
+
+/// The steps of a key sequence like `"G G"` or `"Ctrl+K S"`. Spaces around a `+` are part of a
+/// step.
+fn sequence_steps(keys: &str) -> Vec<String> {
+	let mut steps: Vec<String> = Vec::new();
+	for token in keys.split_whitespace() {
+		match steps.last_mut() {
+			Some(step) if step.ends_with('+') || token.starts_with('+') => step.push_str(token),
+			_ => steps.push(token.to_owned()),
+		}
+	}
+	steps
+}
+
+/// Whether a single key like `CmdCtrl+S` is the input key with the modifiers
+fn key_matches(complex_key: &str, input_key: &str, modifiers: ModifiersState) -> bool {
+	let parts = complex_key.split('+').map(|s| s.trim().to_lowercase()).collect::<Vec<_>>();
+	let key = match parts.last() {
+		Some(key) => key,
+		None => return false,
+	};
+	if input_key != *key {
+		return false;
+	}
+	let mut has_alt = false;
+	let mut has_ctrl = false;
+	let mut has_logo = false;
+	let mut has_shift = false;
+	for mod_str in parts.iter().take(parts.len() - 1) {
+		match mod_str.as_ref() {
+			"alt" => has_alt = true,
+			"ctrl" => has_ctrl = true,
+			"logo" => has_logo = true,
+			"shift" => has_shift = true,
+			"cmdctrl" => {
+				if cfg!(target_os = "macos") {
+					has_logo = true;
+				} else {
+					has_ctrl = true;
 				}
-				_ => (),
 			}
+			_ => (),
 		}
-		if has_alt == modifiers.alt_key()
-			&& has_ctrl == modifiers.control_key()
-			&& has_logo == modifiers.super_key()
-		{
-			return true;
-		}
 	}
-	false
+	// A Shift that isn't named is accepted for the characters other than letters, because Shift
+	// is part of typing some of them, like `?`, depending on the layout
+	let shift_matches = if has_shift {
+		modifiers.shift_key()
+	} else {
+		!modifiers.shift_key() || is_non_letter_char(key)
+	};
+	has_alt == modifiers.alt_key()
+		&& has_ctrl == modifiers.control_key()
+		&& has_logo == modifiers.super_key()
+		&& shift_matches
+}
+
+/// Whether the input key is a single character that's not a letter, like `1` or `?`
+fn is_non_letter_char(input_key: &str) -> bool {
+	let mut chars = input_key.chars();
+	match (chars.next(), chars.next()) {
+		(Some(c), None) => !c.is_alphabetic(),
+		_ => input_key == "add" || input_key == "subtract",
+	}
+}
+
+/// Bindings may be sequences of keys separated by spaces, like `"G G"`, which are matched against
+/// the `history` of the pressed keys
+pub fn keys_triggered<S: AsRef<str>>(
+	keys: &[S],
+	input_key: &str,
+	modifiers: ModifiersState,
+	history: &KeyHistory,
+) -> bool {
+	keys.iter().any(|keys| {
+		let steps = sequence_steps(keys.as_ref());
+		match steps.as_slice() {
+			[] => false,
+			[key] => key_matches(key, input_key, modifiers),
+			[.., last] => key_matches(last, input_key, modifiers) && history.ends_with(&steps),
+		}
+	})
 }
 
 pub fn action_triggered(
@@ -158,14 +741,150 @@ pub fn action_triggered(
 	action_name: &str,
 	input_key: &str,
 	modifiers: ModifiersState,
+	history: &KeyHistory,
 ) -> bool {
 	let config = config.borrow();
-	let bindings = config.bindings.as_ref();
-	if let Some(Some(keys)) = bindings.map(|b| b.get(action_name)) {
-		keys_triggered(keys.as_slice(), input_key, modifiers)
+	if let Some(keys) = config.bindings_of(action_name) {
+		keys_triggered(keys.as_slice(), input_key, modifiers, history)
 	} else {
 		let empty = Vec::new();
 		let keys = DEFAULT_BINDINGS.get(action_name).unwrap_or(&empty);
-		keys_triggered(keys.as_slice(), input_key, modifiers)
+		keys_triggered(keys.as_slice(), input_key, modifiers, history)
+	}
+}
+
+/// Returns the first key that's bound to the action in a readable form, like `Ctrl+,`
+pub fn action_key_hint(config: &Configuration, action_name: &str) -> Option<String> {
+	let key = match config.bindings_of(action_name) {
+		Some(keys) => keys.first().map(String::as_str),
+		None => DEFAULT_BINDINGS.get(action_name).and_then(|keys| keys.first().copied()),
+	}?;
+	Some(key_hint(key))
+}
+
+/// A key of a binding in a readable form, where `CmdCtrl` is replaced by the modifier that it
+/// stands for on this system
+pub fn key_hint(key: &str) -> String {
+	let cmd_ctrl = if cfg!(target_os = "macos") { "Cmd" } else { "Ctrl" };
+	let parts = key
+		.split('+')
+		.map(str::trim)
+		.map(|part| if part.eq_ignore_ascii_case("cmdctrl") { cmd_ctrl } else { part })
+		.collect::<Vec<_>>();
+	parts.join("+")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn quote_for_sh_keeps_the_value() {
+		assert_eq!(quote_for_sh("a b.jpg"), "'a b.jpg'");
+		assert_eq!(quote_for_sh("it's.jpg"), "'it'\\''s.jpg'");
+		assert_eq!(quote_for_sh("$HOME `id`.jpg"), "'$HOME `id`.jpg'");
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn sh_receives_the_value() {
+		for value in ["a b.jpg", "it's.jpg", "$HOME `id` $(id).jpg", "a\"b\\c.jpg", "-n"] {
+			let line = format!("printf '%s' {}", quote_for_sh(value));
+			let output = Command::new("sh").arg("-c").arg(line).output().unwrap();
+			assert_eq!(String::from_utf8(output.stdout).unwrap(), value);
+		}
+	}
+
+	#[test]
+	fn quote_for_cmd_escapes_variables() {
+		assert_eq!(quote_for_cmd("C:\\a b\\%PATH%.jpg"), "^\"C:\\a b\\^%PATH^%.jpg^\"");
+		assert_eq!(quote_for_cmd("!x!.jpg"), "^\"^!x^!.jpg^\"");
+	}
+
+	#[test]
+	fn quote_for_cmd_escapes_operators() {
+		assert_eq!(quote_for_cmd("a & b | c.jpg"), "^\"a ^& b ^| c.jpg^\"");
+		assert_eq!(quote_for_cmd("a^b (1) <2>.jpg"), "^\"a^^b ^(1^) ^<2^>.jpg^\"");
+	}
+
+	#[test]
+	fn quote_for_cmd_escapes_quotes() {
+		assert_eq!(quote_for_cmd("a\"b"), "^\"a\\^\"b^\"");
+		assert_eq!(quote_for_cmd("a\\\"b"), "^\"a\\\\\\^\"b^\"");
+		// The backslash at the end doesn't escape the closing quote
+		assert_eq!(quote_for_cmd("C:\\dir\\"), "^\"C:\\dir\\\\^\"");
+	}
+
+	#[test]
+	fn key_matches_modifiers() {
+		let none = ModifiersState::empty();
+		assert!(key_matches("X", "x", none));
+		assert!(key_matches("Alt+X", "x", ModifiersState::ALT));
+		assert!(!key_matches("Alt+X", "x", none));
+		assert!(!key_matches("X", "x", ModifiersState::ALT));
+		assert!(key_matches(
+			"Ctrl + Alt + Delete",
+			"delete",
+			ModifiersState::CONTROL | ModifiersState::ALT
+		));
+		let cmd_ctrl =
+			if cfg!(target_os = "macos") { ModifiersState::SUPER } else { ModifiersState::CONTROL };
+		assert!(key_matches("CmdCtrl+S", "s", cmd_ctrl));
+		assert!(!key_matches("Y", "x", none));
+	}
+
+	#[test]
+	fn key_matches_shift() {
+		let none = ModifiersState::empty();
+		let shift = ModifiersState::SHIFT;
+		assert!(key_matches("Shift+X", "x", shift));
+		assert!(!key_matches("Shift+X", "x", none));
+		assert!(!key_matches("X", "x", shift));
+		assert!(key_matches("Shift+F11", "f11", shift));
+		assert!(!key_matches("F11", "f11", shift));
+		// Shift is part of typing these on some layouts
+		assert!(key_matches("1", "1", shift));
+		assert!(key_matches("[", "[", shift));
+		assert!(key_matches("Add", "add", shift));
+		assert!(!key_matches("Space", "space", shift));
+	}
+
+	#[test]
+	fn sequence_steps_split() {
+		assert_eq!(sequence_steps("G G"), ["G", "G"]);
+		assert_eq!(sequence_steps("Ctrl+K S"), ["Ctrl+K", "S"]);
+		assert_eq!(sequence_steps("Ctrl + K  S"), ["Ctrl+K", "S"]);
+		assert_eq!(sequence_steps(" X "), ["X"]);
+		assert!(sequence_steps("").is_empty());
+	}
+
+	#[test]
+	fn key_history_sequences() {
+		let none = ModifiersState::empty();
+		let steps = sequence_steps("G G");
+		let mut history = KeyHistory::default();
+		history.push("g", none);
+		assert!(!history.ends_with(&steps));
+		history.push("g", none);
+		assert!(history.ends_with(&steps));
+		assert!(keys_triggered(&["G G"], "g", none, &history));
+
+		// The press that completed a sequence doesn't start the next one
+		history.push("g", none);
+		assert!(!history.ends_with(&steps));
+		history.push("g", none);
+		assert!(history.ends_with(&steps));
+
+		// The keys have to be pressed soon after each other
+		let mut history = KeyHistory::default();
+		history.push("g", none);
+		history.presses[0].2 -= KEY_SEQUENCE_TIMEOUT * 2;
+		history.push("g", none);
+		assert!(!history.ends_with(&steps));
+
+		let mut history = KeyHistory::default();
+		history.push("k", ModifiersState::CONTROL);
+		history.push("s", none);
+		assert!(history.ends_with(&sequence_steps("Ctrl+K S")));
 	}
 }
@@ -0,0 +1,208 @@
+//! Communication with emulsion from other processes over a local socket (a named pipe on Windows).
+//!
+//! This is what makes the `single_instance` option work: a new process sends its paths to the
+//! instance that's already running and exits instead of opening an other window. With the
+//! `remote_control` option, scripts can drive the viewer too, for example with `emulsion remote`.
+//!
+//! Each request is a JSON object on a single line, like `{"command":"zoom","factor":2.0}`, and
+//! each one is answered with a JSON object on a single line, like `{"ok":true}`.
+
+use std::{
+	env,
+	io::{self, BufRead, BufReader, Write},
+	path::{Path, PathBuf},
+	sync::mpsc,
+	thread,
+	time::Duration,
+};
+
+use gelatin::winit::event_loop::EventLoopProxy;
+use interprocess::local_socket::{
+	prelude::*, GenericFilePath, GenericNamespaced, ListenerOptions, Name, Stream,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::EmulsionEvent;
+
+/// How long a connection waits for the window to handle a request
+const REPLY_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum Request {
+	/// Brings the window to the front and opens `paths` like they were passed on the command
+	/// line. Only brings the window to the front when `paths` is empty.
+	Open {
+		paths: Vec<PathBuf>,
+	},
+	/// Opens `paths` in a new window, see `viewer_window`
+	OpenWindow {
+		paths: Vec<PathBuf>,
+	},
+	Next,
+	Prev,
+	/// Sets the size of an image pixel on the screen, 1 is the original size
+	Zoom {
+		factor: f32,
+	},
+	/// Toggles fullscreen when `enabled` is missing
+	Fullscreen {
+		#[serde(default)]
+		enabled: Option<bool>,
+	},
+	/// Toggles the overlay mode of `toggle_overlay` when `enabled` is missing
+	Overlay {
+		#[serde(default)]
+		enabled: Option<bool>,
+	},
+	/// Answered with the `current` field of the response
+	QueryCurrent,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct Response {
+	pub ok: bool,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub error: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub current: Option<CurrentImage>,
+}
+
+impl Response {
+	pub fn ok() -> Response {
+		Response { ok: true, ..Default::default() }
+	}
+
+	pub fn error(error: String) -> Response {
+		Response { ok: false, error: Some(error), ..Default::default() }
+	}
+}
+
+/// The answer to `Request::QueryCurrent`
+#[derive(Debug, Serialize)]
+pub struct CurrentImage {
+	/// `None` when no image is shown or when the image doesn't come from a file
+	pub path: Option<PathBuf>,
+	/// The index of the image in the folder (or in the list of the browsed files)
+	pub index: Option<usize>,
+	/// The number of images in the folder (or in the list of the browsed files)
+	pub count: Option<usize>,
+	pub zoom: f32,
+	pub fullscreen: bool,
+}
+
+/// A request received from an other process. It's answered by calling `reply`.
+#[derive(Debug)]
+pub struct IncomingRequest {
+	pub request: Request,
+	reply_sender: mpsc::Sender<Response>,
+}
+
+impl IncomingRequest {
+	pub fn reply(&self, response: Response) {
+		// The connection might have timed out already, it's fine to not reply then
+		let _ = self.reply_sender.send(response);
+	}
+}
+
+/// Sends `request` to the window and waits for its response. Returns `None` when the event loop
+/// is gone.
+pub fn dispatch(proxy: &EventLoopProxy<EmulsionEvent>, request: Request) -> Option<Response> {
+	let (reply_sender, reply_receiver) = mpsc::channel();
+	let incoming = IncomingRequest { request, reply_sender };
+	proxy.send_event(EmulsionEvent::IpcRequest(incoming)).ok()?;
+	let response = reply_receiver
+		.recv_timeout(REPLY_TIMEOUT)
+		.unwrap_or_else(|_| Response::error("The request was not handled in time".to_owned()));
+	Some(response)
+}
+
+/// The socket is unique to the user. On Windows it's a named pipe, elsewhere it's a socket file
+/// in `XDG_RUNTIME_DIR`, which only the user can access, or next to the cache file when that's not
+/// set. Unlike an abstract socket, a socket file can't be made by other users under the same name.
+fn socket_name(cache_path: &Path) -> io::Result<Name<'static>> {
+	if cfg!(windows) {
+		let user = env::var("USERNAME").unwrap_or_default();
+		format!("emulsion-{}.sock", user).to_ns_name::<GenericNamespaced>()
+	} else {
+		let path = match env::var_os("XDG_RUNTIME_DIR") {
+			Some(dir) if !dir.is_empty() => PathBuf::from(dir).join("emulsion.sock"),
+			_ => cache_path.with_file_name("emulsion.sock"),
+		};
+		path.to_fs_name::<GenericFilePath>()
+	}
+}
+
+/// Sends `request` to the instance that's already running and returns its response. Fails when
+/// there's no running instance.
+pub fn send(cache_path: &Path, request: &Request) -> io::Result<String> {
+	let stream = Stream::connect(socket_name(cache_path)?)?;
+	let mut stream = BufReader::new(stream);
+	let mut message = serde_json::to_string(request)?;
+	message.push('\n');
+	stream.get_mut().write_all(message.as_bytes())?;
+	let mut response = String::new();
+	stream.read_line(&mut response)?;
+	Ok(response.trim_end().to_owned())
+}
+
+/// Starts a background thread that receives the requests of other processes and sends them
+/// through `proxy`. The thread stops when the event loop is gone. Fails when an other instance
+/// that's running already receives the requests.
+pub fn listen(cache_path: &Path, proxy: EventLoopProxy<EmulsionEvent>) -> io::Result<()> {
+	// The socket of an other instance is only replaced when nothing answers on it, which means
+	// that it was left there by an instance that's not running anymore. An instance that's
+	// running keeps receiving the requests then.
+	if Stream::connect(socket_name(cache_path)?).is_ok() {
+		return Err(io::Error::new(
+			io::ErrorKind::AddrInUse,
+			"an other instance is already receiving the requests",
+		));
+	}
+	let listener =
+		ListenerOptions::new().name(socket_name(cache_path)?).try_overwrite(true).create_sync()?;
+	thread::spawn(move || {
+		for connection in listener.incoming() {
+			match connection {
+				Ok(connection) => {
+					let proxy = proxy.clone();
+					// Each connection has its own thread so that a script that keeps its
+					// connection open doesn't block the others
+					thread::spawn(move || handle_connection(connection, proxy));
+				}
+				Err(e) => log::warn!("Failed to accept a connection from an other process: {}", e),
+			}
+		}
+	});
+	Ok(())
+}
+
+fn handle_connection(connection: Stream, proxy: EventLoopProxy<EmulsionEvent>) {
+	let mut connection = BufReader::new(connection);
+	let mut line = String::new();
+	loop {
+		line.clear();
+		match connection.read_line(&mut line) {
+			Ok(0) | Err(_) => return,
+			Ok(_) => (),
+		}
+		let response = match serde_json::from_str::<Request>(&line) {
+			Ok(request) => match dispatch(&proxy, request) {
+				Some(response) => response,
+				None => return,
+			},
+			Err(e) => Response::error(format!("Invalid request: {}", e)),
+		};
+		let mut message = match serde_json::to_string(&response) {
+			Ok(message) => message,
+			Err(e) => {
+				log::error!("Could not serialize the response {:?}: {}", response, e);
+				return;
+			}
+		};
+		message.push('\n');
+		if connection.get_mut().write_all(message.as_bytes()).is_err() {
+			return;
+		}
+	}
+}
@@ -0,0 +1,152 @@
+//! The share menu of macOS for the `share` action, and the `openFiles` Apple events.
+//!
+//! Finder doesn't pass the images that are opened with emulsion on the command line, it sends them
+//! to the application with an Apple event instead, also when emulsion is already running. Winit
+//! doesn't handle that event, so `application:openFiles:` is added to its application delegate.
+
+use std::{
+	ffi::{CStr, CString, OsStr},
+	os::{raw::c_char, unix::ffi::OsStrExt},
+	path::{Path, PathBuf},
+	sync::{Mutex, OnceLock},
+};
+
+use gelatin::winit::event_loop::EventLoopProxy;
+use objc2::{
+	class, ffi, msg_send,
+	runtime::{AnyClass, AnyObject, Sel},
+	sel, Encode, Encoding,
+};
+
+use crate::EmulsionEvent;
+
+/// The name of the class of the application delegate of winit
+const WINIT_DELEGATE_CLASS: &str = "WinitApplicationDelegate";
+/// `NSMinYEdge`, the picker is shown below the cursor
+const MIN_Y_EDGE: usize = 1;
+/// `NSApplicationDelegateReplySuccess`
+const REPLY_SUCCESS: usize = 0;
+
+/// Where the opened files are sent, see `handle_open_files`
+static OPEN_FILES_PROXY: OnceLock<Mutex<EventLoopProxy<EmulsionEvent>>> = OnceLock::new();
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct CGPoint {
+	x: f64,
+	y: f64,
+}
+
+unsafe impl Encode for CGPoint {
+	const ENCODING: Encoding = Encoding::Struct("CGPoint", &[f64::ENCODING, f64::ENCODING]);
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct CGSize {
+	width: f64,
+	height: f64,
+}
+
+unsafe impl Encode for CGSize {
+	const ENCODING: Encoding = Encoding::Struct("CGSize", &[f64::ENCODING, f64::ENCODING]);
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct CGRect {
+	origin: CGPoint,
+	size: CGSize,
+}
+
+unsafe impl Encode for CGRect {
+	const ENCODING: Encoding = Encoding::Struct("CGRect", &[CGPoint::ENCODING, CGSize::ENCODING]);
+}
+
+/// Shows the share menu of the system for the file at the cursor, over the key window. This has to
+/// be called on the main thread.
+pub fn share(path: &Path) {
+	let path = match CString::new(path.as_os_str().as_bytes()) {
+		Ok(path) => path,
+		Err(e) => {
+			log::error!("Could not share {:?}: {}", path, e);
+			return;
+		}
+	};
+	unsafe {
+		let app: *mut AnyObject = msg_send![class!(NSApplication), sharedApplication];
+		let window: *mut AnyObject = msg_send![app, keyWindow];
+		if window.is_null() {
+			log::warn!("Could not share the file, the window isn't focused");
+			return;
+		}
+		let view: *mut AnyObject = msg_send![window, contentView];
+		// The content view fills the window, so the coordinates of the window are the same
+		let cursor: CGPoint = msg_send![window, mouseLocationOutsideOfEventStream];
+		let anchor = CGRect { origin: cursor, size: CGSize { width: 1.0, height: 1.0 } };
+
+		let string: *mut AnyObject =
+			msg_send![class!(NSString), stringWithUTF8String: path.as_ptr()];
+		let url: *mut AnyObject = msg_send![class!(NSURL), fileURLWithPath: string];
+		let items: *mut AnyObject = msg_send![class!(NSArray), arrayWithObject: url];
+		// The picker isn't released, because it has to outlive the menu, which is closed later
+		let picker: *mut AnyObject = msg_send![class!(NSSharingServicePicker), alloc];
+		let picker: *mut AnyObject = msg_send![picker, initWithItems: items];
+		let _: () =
+			msg_send![picker, showRelativeToRect: anchor, ofView: view, preferredEdge: MIN_Y_EDGE];
+	}
+}
+
+/// Sends `EmulsionEvent::OpenPaths` through `proxy` with the files that Finder opens with
+/// emulsion. This has to be called after the event loop was created, because that's when winit
+/// registers its application delegate.
+pub fn handle_open_files(proxy: EventLoopProxy<EmulsionEvent>) {
+	if OPEN_FILES_PROXY.set(Mutex::new(proxy)).is_err() {
+		return;
+	}
+	let class = match AnyClass::get(WINIT_DELEGATE_CLASS) {
+		Some(class) => class,
+		None => {
+			log::error!(
+				"Could not find the application delegate, files opened in Finder are ignored"
+			);
+			return;
+		}
+	};
+	type OpenFiles = extern "C" fn(&AnyObject, Sel, *mut AnyObject, *mut AnyObject);
+	let open_files: OpenFiles = open_files;
+	// The types are `void` returned, then the receiver, the selector and the two objects
+	let types = b"v@:@@\0";
+	let added = unsafe {
+		ffi::class_addMethod(
+			class as *const AnyClass as *mut ffi::objc_class,
+			sel!(application:openFiles:).as_ptr(),
+			Some(std::mem::transmute::<OpenFiles, unsafe extern "C" fn()>(open_files)),
+			types.as_ptr() as *const c_char,
+		)
+	};
+	if added == ffi::NO {
+		log::error!("Could not handle the files opened in Finder");
+	}
+}
+
+/// `application:openFiles:` of the application delegate, `files` is an `NSArray` of `NSString`
+extern "C" fn open_files(_this: &AnyObject, _sel: Sel, app: *mut AnyObject, files: *mut AnyObject) {
+	let paths = unsafe {
+		let count: usize = msg_send![files, count];
+		(0..count)
+			.map(|i| {
+				let file: *mut AnyObject = msg_send![files, objectAtIndex: i];
+				let utf8: *const c_char = msg_send![file, UTF8String];
+				PathBuf::from(OsStr::from_bytes(CStr::from_ptr(utf8).to_bytes()))
+			})
+			.collect::<Vec<_>>()
+	};
+	if let Some(proxy) = OPEN_FILES_PROXY.get() {
+		// This fails when the event loop is already gone, in which case there's nothing to open
+		let _ = proxy.lock().unwrap().send_event(EmulsionEvent::OpenPaths(paths));
+	}
+	unsafe {
+		let _: () = msg_send![app, replyToOpenOrPrint: REPLY_SUCCESS];
+	}
+}
@@ -2,10 +2,10 @@
 
 use std::cell::{Cell, RefCell};
 use std::f32;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::time::{Duration, Instant};
 
 use directories_next::ProjectDirs;
@@ -15,39 +15,74 @@ use log::{debug, trace};
 
 use gelatin::winit::{
 	dpi::{PhysicalPosition, PhysicalSize},
-	event::WindowEvent,
+	event::{Event, WindowEvent},
+	event_loop::EventLoopProxy,
 	window::Icon,
 };
 use gelatin::{
 	application::*,
 	button::*,
+	drag_area::DragArea,
 	image,
 	label::*,
 	line_layout_container::*,
 	misc::*,
 	picture::*,
+	popup_menu::PopupMenu,
 	window::{Window, WindowDescriptorBuilder},
 	NextUpdate, Widget,
 };
 
+use crate::cmd_line::{ConvertArgs, StartupPaths};
 use crate::configuration::Theme;
-use crate::configuration::{Cache, ConfigWindowSection, Configuration};
+use crate::configuration::{Cache, ConfigWindowSection, Configuration, Insets};
+use crate::image_cache::{directory::BrowseFilter, SharedLoader};
+use crate::playlist::Playlist;
 use crate::version::Version;
 use crate::widgets::{
-	bottom_bar::BottomBar, copy_notification::CopyNotifications, help_screen::*, picture_widget::*,
+	bottom_bar::BottomBar, context_menu::ContextMenu, copy_notification::CopyNotifications,
+	help_screen::*, picture_widget::*,
 };
 
 mod clipboard_handler;
 mod cmd_line;
+mod config_watcher;
 mod configuration;
+#[cfg(target_os = "linux")]
+mod dbus;
+mod doctor;
+mod drag_out;
+mod editing;
+mod file_dialog;
+mod file_manager;
+mod folder_config;
+mod global_hotkeys;
 mod handle_panic;
 mod image_cache;
+mod info;
 mod input_handling;
+mod ipc;
+#[cfg(target_os = "macos")]
+mod macos;
 mod parallel_action;
 mod playback_manager;
+mod playlist;
+mod presenter;
 mod shaders;
+mod shell_integration;
+mod sidecar;
+mod software_viewer;
+mod stats;
+mod system_theme;
+mod text_input;
+#[cfg(target_os = "linux")]
+mod tray;
+mod undo;
 mod utils;
+mod verify;
 mod version;
+mod viewer_window;
+mod wallpaper;
 mod widgets;
 
 lazy_static! {
@@ -62,11 +97,67 @@ static VISIT_SITE: &[u8] = include_bytes!("../resource/visit-site.png");
 static USAGE: &[u8] = include_bytes!("../resource/usage.png");
 static LEFT_TO_PAN: &[u8] = include_bytes!("../resource/use-left-to-pan.png");
 
+/// Shown in place of the file path when the image was read from the standard input
+pub static STDIN_IMAGE_NAME: &str = "[ stdin ]";
+/// Shown in place of the file path when the image was pasted from the clipboard
+pub static CLIPBOARD_IMAGE_NAME: &str = "[ pasted image ]";
+
+/// The folder of the config and cache files can be set with this environment variable
+const CONFIG_DIR_VAR: &str = "EMULSION_CONFIG_DIR";
+
+/// When a file with this name is next to the executable, the config and the cache are stored next
+/// to the executable too, like with `--portable`
+const PORTABLE_MARKER: &str = "portable";
+
+/// Set from the command line, these change what `get_config_and_cache_paths` returns
+static PATH_OVERRIDES: OnceLock<PathOverrides> = OnceLock::new();
+
+#[derive(Debug, Clone, Default)]
+struct PathOverrides {
+	/// Given with `--config`
+	config: Option<PathBuf>,
+	/// Given with `--cache`
+	cache: Option<PathBuf>,
+	portable: bool,
+}
+
+/// How long the notification about a new version is shown when the help screen is hidden
+const UPDATE_TOAST_DURATION: Duration = Duration::from_secs(10);
+/// The height of the strip at the top of a `borderless` window that moves it, in logical pixels
+pub const DRAG_AREA_HEIGHT: f32 = 12.0;
+
 #[derive(Debug)]
 pub enum EmulsionEvent {
 	/// Used to signal the event loop to wake up, because an image was loaded
 	/// and the UI may need to update to display the image
 	ImageLoaded,
+	/// The light or dark appearance of the operating system has changed
+	SystemThemeChanged(Theme),
+	/// The global hotkey for showing the image on the clipboard was pressed
+	ShowClipboardImage,
+	/// The global hotkey of the overlay mode was pressed, see `toggle_overlay`
+	ToggleOverlay,
+	/// The global hotkeys of the slideshow were pressed, see `global_hotkeys`
+	NextImage,
+	PrevImage,
+	ToggleSlideshow,
+	/// Received from an other emulsion process
+	IpcRequest(ipc::IncomingRequest),
+	/// Opens an other window with these paths, see `viewer_window`
+	NewWindow(Vec<PathBuf>),
+	/// Images chosen in the file dialog, see `file_dialog`
+	OpenPaths(Vec<PathBuf>),
+	/// The folder chosen in the folder dialog, see `file_dialog`
+	OpenFolder(PathBuf),
+	/// The playlist chosen in the file dialog, see `file_dialog`
+	OpenPlaylist(Playlist),
+	/// Sent by the menu of the tray icon, see `tray`
+	#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+	ToggleHidden,
+	#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+	Quit,
+	/// The config file was modified, see `config_watcher`
+	ConfigChanged,
 }
 
 // ========================================================
@@ -81,6 +172,89 @@ fn main() {
 	let (config_path, cache_path) = get_config_and_cache_paths();
 
 	let args = cmd_line::parse_args(&config_path, &cache_path);
+	let _ = PATH_OVERRIDES.set(PathOverrides {
+		config: args.config_path.clone(),
+		cache: args.cache_path.clone(),
+		portable: args.portable,
+	});
+	let (config_path, cache_path) = get_config_and_cache_paths();
+
+	if args.doctor {
+		doctor::run(&config_path, &cache_path);
+		return;
+	}
+	if args.register_associations || args.unregister_associations {
+		let result = if args.register_associations {
+			shell_integration::register()
+		} else {
+			shell_integration::unregister()
+		};
+		if let Err(e) = result {
+			eprintln!("Could not change the file associations: {}", e);
+			std::process::exit(1);
+		}
+		return;
+	}
+	if args.write_default_config {
+		if config_path.exists() {
+			eprintln!("{:?} already exists, choose an other file with --config", config_path);
+			std::process::exit(1);
+		}
+		if let Err(e) = std::fs::write(&config_path, configuration::default_file_text()) {
+			eprintln!("Could not write {:?}: {}", config_path, e);
+			std::process::exit(1);
+		}
+		println!("Wrote the default config to {}", config_path.display());
+		return;
+	}
+	if let Some(convert) = args.convert {
+		let ConvertArgs { inputs, output, format, max_size, jpeg_quality } = convert;
+		// Like `cp`, several images are converted into the output folder
+		if inputs.len() > 1 || output.is_dir() {
+			let format = format.as_deref();
+			if !editing::convert_into_folder(&inputs, &output, format, max_size, jpeg_quality) {
+				std::process::exit(1);
+			}
+			return;
+		}
+		if let Err(e) = editing::convert(&inputs[0], &output, max_size, jpeg_quality) {
+			eprintln!("Could not convert {:?}: {}", inputs[0], e);
+			std::process::exit(1);
+		}
+		return;
+	}
+
+	if args.info {
+		let paths = args.file_paths.iter().map(Path::new).collect::<Vec<_>>();
+		if !info::run(&paths) {
+			std::process::exit(1);
+		}
+		return;
+	}
+
+	if let Some(verify) = &args.verify {
+		if !verify::run(&verify.paths, verify.report.as_deref()) {
+			std::process::exit(1);
+		}
+		return;
+	}
+
+	if let Some(request) = args.remote {
+		match ipc::send(&cache_path, &request) {
+			Ok(response) => {
+				println!("{}", response);
+				let value = serde_json::from_str::<serde_json::Value>(&response);
+				if !matches!(value, Ok(value) if value["ok"] == true) {
+					std::process::exit(1);
+				}
+			}
+			Err(e) => {
+				eprintln!("Could not reach a running emulsion window: {}", e);
+				std::process::exit(1);
+			}
+		}
+		return;
+	}
 
 	let cache = Cache::load(&cache_path);
 	let config = Configuration::load(&config_path);
@@ -90,15 +264,59 @@ fn main() {
 
 	let first_launch = cache.is_err();
 	let cache = Arc::new(Mutex::new(cache.unwrap_or_default()));
-	let config = Rc::new(RefCell::new(config.unwrap_or_default()));
+	let config = Rc::new(RefCell::new(config));
 
 	if args.displayed_folders.is_some() {
 		config.borrow_mut().title.get_or_insert_with(Default::default).displayed_folders =
 			args.displayed_folders;
 	}
+	if let Some(profile) = &args.profile {
+		let mut config = config.borrow_mut();
+		if config.profiles.as_ref().is_some_and(|profiles| profiles.contains_key(profile)) {
+			config.profile = Some(profile.clone());
+		} else {
+			log::error!("There's no profile called {:?} in the config", profile);
+		}
+	}
+
+	let startup_image = if args.read_stdin {
+		cmd_line::read_stdin_image().map(|image| (image, STDIN_IMAGE_NAME.to_owned()))
+	} else if args.clipboard {
+		match editing::paste_image_from_clipboard() {
+			Ok(image) => Some((image, CLIPBOARD_IMAGE_NAME.to_owned())),
+			Err(e) => {
+				log::error!("Could not read the image on the clipboard: {}", e);
+				None
+			}
+		}
+	} else {
+		None
+	};
+	let startup_paths = args.startup_paths();
+
+	let single_instance = !args.new_instance
+		&& matches!(
+			&config.borrow().window,
+			Some(ConfigWindowSection { single_instance: Some(true), .. })
+		);
+	let remote_control = matches!(
+		&config.borrow().window,
+		Some(ConfigWindowSection { remote_control: Some(true), .. })
+	);
+	if (single_instance || args.new_window) && startup_image.is_none() {
+		let paths = startup_paths.absolute_paths();
+		let request = if args.new_window {
+			ipc::Request::OpenWindow { paths }
+		} else {
+			ipc::Request::Open { paths }
+		};
+		if ipc::send(&cache_path, &request).is_ok() {
+			return;
+		}
+	}
 
-	let mut application = Application::<()>::new();
-	let window: Rc<Window> = {
+	let mut application = Application::<EmulsionEvent>::new();
+	let window_desc = {
 		let window_cache = &mut cache.lock().unwrap().window;
 		let window_cfg = &config.borrow().window;
 		let window_defaults = configuration::CacheWindowSection::default();
@@ -136,23 +354,71 @@ fn main() {
 // 			}
 // 		}
 
-		let pos = PhysicalPosition::new(window_cache.win_x, window_cache.win_y);
-		let size = PhysicalSize::new(window_cache.win_w, window_cache.win_h);
-		let window_desc = WindowDescriptorBuilder::default()
+		let mut pos = PhysicalPosition::new(window_cache.win_x, window_cache.win_y);
+		let mut size = PhysicalSize::new(window_cache.win_w, window_cache.win_h);
+		let mut maximized = window_cache.maximized || args.maximized;
+		// The command line overrides the window area of the last session and the config
+		if let Some(geometry) = args.geometry {
+			if let Some((w, h)) = geometry.size {
+				size = PhysicalSize::new(w, h);
+			}
+			if let Some((x, y)) = geometry.pos {
+				pos = PhysicalPosition::new(x, y);
+			}
+			maximized = false;
+		}
+		WindowDescriptorBuilder::default()
 			.icon(Some(make_icon()))
-			.maximized(window_cache.maximized)
+			.maximized(maximized)
 			.size(size)
 			.position(Some(pos))
 			.app_id(Some("Emulsion".into()))
+			.decorations(!borderless(&config.borrow()))
+			.always_on_top(always_on_top(&config.borrow()))
+			.opacity(window_opacity(&config.borrow()))
+			.transparent(has_overlay_opacity(&config.borrow()))
 			.build()
-			.unwrap();
-		let window = Window::new(&mut application, window_desc);
-
-		if let Some(ConfigWindowSection { start_fullscreen: Some(true), .. }) = window_cfg {
-			window.set_fullscreen(true);
+			.unwrap()
+	};
+	let window = if args.software {
+		None
+	} else {
+		match Window::try_new(&mut application, window_desc.clone()) {
+			Ok(window) => Some(window),
+			Err(e) => {
+				log::error!("Could not set up OpenGL, falling back to software rendering. {}", e);
+				None
+			}
 		}
-		window
 	};
+	let window = match window {
+		Some(window) => window,
+		None => {
+			let at_exit = {
+				let cache = cache.clone();
+				move || cache.lock().unwrap().save(cache_path).unwrap()
+			};
+			let result = software_viewer::run(
+				application,
+				window_desc,
+				config,
+				cache,
+				startup_paths,
+				startup_image,
+				at_exit,
+			);
+			if let Err(e) = result {
+				log::error!("Could not set up software rendering. {}", e);
+				std::process::exit(1);
+			}
+			return;
+		}
+	};
+	let start_fullscreen = config.borrow().window.as_ref().and_then(|w| w.start_fullscreen);
+	if args.fullscreen || start_fullscreen == Some(true) {
+		let monitor = config.borrow().window.as_ref().and_then(|w| w.fullscreen_monitor);
+		utils::enter_fullscreen(&window, monitor);
+	}
 	add_window_movement_listener(&window, cache.clone());
 
 	let update_label_image = Rc::new(Picture::from_encoded_bytes(NEW_VERSION));
@@ -170,6 +436,8 @@ fn main() {
 	let copy_notifications = CopyNotifications::new(&copy_notifications_widget);
 
 	let bottom_bar = Rc::new(BottomBar::new(&config.borrow()));
+	// The windows share the loader threads and the memory budget of the image cache
+	let loader = Rc::new(SharedLoader::for_system());
 	let picture_widget = make_picture_widget(
 		&window,
 		bottom_bar.clone(),
@@ -177,32 +445,72 @@ fn main() {
 		copy_notifications,
 		config.clone(),
 		cache.clone(),
+		loader.clone(),
 	);
 
-	if let Some(file_path) = args.file_path {
-		picture_widget.jump_to_path(file_path);
+	let context_menu_widget = Rc::new(PopupMenu::new());
+	picture_widget.set_context_menu(ContextMenu::new(&context_menu_widget));
+	viewer_window::forward_new_window(&picture_widget, application.create_loop_proxy());
+	viewer_window::forward_file_dialogs(&picture_widget, application.create_loop_proxy());
+
+	open_startup_paths(&picture_widget, &startup_paths);
+	if let Some(start_at) = args.start_at.clone() {
+		picture_widget.set_start_at(start_at);
+	}
+	if let Some((image, name)) = startup_image {
+		picture_widget.show_memory_image(image, name);
+	}
+	if let Some(interval) = args.slideshow {
+		picture_widget.start_slideshow(interval, args.shuffle);
+	}
+	if args.presenter {
+		let presentation = presenter::open(
+			&mut application,
+			&window,
+			&picture_widget,
+			args.presenter_monitor,
+			&startup_paths,
+			config.clone(),
+			cache.clone(),
+			loader.clone(),
+		);
+		if let Err(e) = presentation {
+			log::error!("Could not open the presentation window: {}", e);
+		}
+	}
+	if let Some(name) = args.name_filter.clone() {
+		// The presentation is filtered too
+		picture_widget.set_browse_filter(BrowseFilter { name: Some(name), ..Default::default() });
 	}
 
 	let picture_area_container = make_picture_area_container();
-	picture_area_container.add_child(picture_widget.clone());
+	picture_area_container.add_child(make_compare_row(&picture_widget));
 	picture_area_container.add_child(copy_notifications_widget);
 	picture_area_container.add_child(left_to_pan_hint);
 	picture_area_container.add_child(help_screen.clone());
 	picture_area_container.add_child(update_notification.clone());
+	picture_area_container.add_child(context_menu_widget);
 
 	let root_container = make_root_container();
-	root_container.add_child(picture_area_container);
+	add_drag_area(&window, &root_container, &config.borrow());
+	root_container.add_child(picture_area_container.clone());
 	root_container.add_child(bottom_bar.widget.clone());
+	if let Some(ConfigWindowSection { snap_to_integer_scale: Some(true), .. }) =
+		&config.borrow().window
+	{
+		add_integer_scale_snapping(&window, picture_widget.clone());
+	}
+	add_fullscreen_insets(&window, picture_area_container, &config.borrow());
 
 	let update_available = Arc::new(AtomicBool::new(false));
 	let update_check_done = Arc::new(AtomicBool::new(false));
+	let latest_version = Arc::new(Mutex::new(None));
 
-	let theme = {
-		Rc::new(Cell::new(match &config.borrow().window {
-			Some(ConfigWindowSection { theme: Some(theme_cfg), .. }) => *theme_cfg,
-			_ => cache.lock().unwrap().theme(),
-		}))
-	};
+	let theme = Rc::new(Cell::new(system_theme::startup_theme(
+		&config.borrow(),
+		&cache.lock().unwrap(),
+	)));
+	let viewer_windows = Rc::new(RefCell::new(Vec::<viewer_window::ViewerWindow>::new()));
 
 	let set_theme = {
 		let update_label = update_label;
@@ -212,58 +520,205 @@ fn main() {
 		let theme = theme.clone();
 		let update_available = update_available.clone();
 		let bottom_bar = bottom_bar.clone();
+		let viewer_windows = viewer_windows.clone();
 
 		Rc::new(move || {
+			set_window_theme(&window, &picture_widget, theme.get());
 			match theme.get() {
 				Theme::Light => {
-					picture_widget.set_bright_shade(0.96);
-					window.set_bg_color([0.85, 0.85, 0.85, 1.0]);
 					update_notification.set_bg_color([0.06, 0.06, 0.06, 1.0]);
 					update_label.set_icon(Some(update_label_image_light.clone()));
 				}
 				Theme::Dark => {
-					picture_widget.set_bright_shade(0.11);
-					window.set_bg_color([0.01, 0.01, 0.01, 1.0]);
 					update_notification.set_bg_color([0.1, 0.1, 0.1, 1.0]);
 					update_label.set_icon(Some(update_label_image.clone()));
 				}
 			}
 			bottom_bar.set_theme(theme.get(), update_available.load(Ordering::SeqCst));
+			let theme = theme.get();
+			viewer_windows.borrow_mut().retain(|viewer_window| viewer_window.set_theme(theme));
 		})
 	};
 	set_theme();
+	if system_theme::follows_system(&config.borrow()) {
+		system_theme::watch(application.create_loop_proxy());
+		let config = config.clone();
+		let theme = theme.clone();
+		let set_theme = set_theme.clone();
+		application.add_global_event_handler(move |event| {
+			if let Event::UserEvent(EmulsionEvent::SystemThemeChanged(new_theme)) = event {
+				// The theme may not follow the system anymore after switching the profile
+				if system_theme::follows_system(&config.borrow()) {
+					theme.set(*new_theme);
+					set_theme();
+				}
+			}
+			NextUpdate::Latest
+		});
+	}
+	config_watcher::watch(config_path.clone(), application.create_loop_proxy());
 	{
+		let config = config.clone();
+		let config_path = config_path.clone();
 		let cache = cache.clone();
+		let picture_widget = picture_widget.clone();
+		let theme = theme.clone();
 		let set_theme = set_theme.clone();
-		bottom_bar.theme_button.set_on_click(move || {
-			let new_theme = theme.get().switch_theme();
-			theme.set(new_theme);
-			cache.lock().unwrap().set_theme(new_theme);
-			set_theme();
+		let displayed_folders = args.displayed_folders;
+		application.add_global_event_handler(move |event| {
+			if let Event::UserEvent(EmulsionEvent::ConfigChanged) = event {
+				match Configuration::reload(&config_path) {
+					Ok(mut reloaded) => {
+						log::info!("Reloaded the config from {:?}", config_path);
+						if displayed_folders.is_some() {
+							let title = reloaded.title.get_or_insert_with(Default::default);
+							title.displayed_folders = displayed_folders;
+						}
+						reloaded.profile = config.borrow().profile.clone();
+						*config.borrow_mut() = reloaded;
+					}
+					Err(e) => {
+						// The previous settings are kept while the error is shown in the title
+						log::error!("Could not parse the config file {:?}\n{}", config_path, e);
+						config.borrow_mut().parse_error = Some(e);
+					}
+				}
+				// The theme follows the config if it's set there
+				if config.borrow().theme_setting().is_some() {
+					let startup_theme =
+						system_theme::startup_theme(&config.borrow(), &cache.lock().unwrap());
+					theme.set(startup_theme);
+					set_theme();
+				}
+				picture_widget.config_changed();
+			}
+			NextUpdate::Latest
 		});
 	}
 	{
-		let slider = bottom_bar.slider.clone();
-		let picture_widget = picture_widget.clone();
-		bottom_bar.slider.set_on_value_change(move || {
-			picture_widget.jump_to_index(slider.value());
+		let config = config.clone();
+		let cache = cache.clone();
+		let theme = theme.clone();
+		let set_theme = set_theme.clone();
+		picture_widget.set_on_profile_changed(move || {
+			theme.set(system_theme::startup_theme(&config.borrow(), &cache.lock().unwrap()));
+			set_theme();
 		});
 	}
+	let _global_hotkeys =
+		global_hotkeys::register(&config.borrow(), application.create_loop_proxy());
 	{
+		let window = window.clone();
 		let picture_widget = picture_widget.clone();
-		bottom_bar.orig_scale_button.set_on_click(move || {
-			picture_widget.set_img_size_to_orig();
+		application.add_global_event_handler(move |event| {
+			match event {
+				Event::UserEvent(EmulsionEvent::ShowClipboardImage) => {
+					window.bring_to_front();
+					picture_widget.show_clipboard_image();
+				}
+				Event::UserEvent(EmulsionEvent::ToggleOverlay) => {
+					picture_widget.set_reference_overlay(!picture_widget.reference_overlay());
+				}
+				Event::UserEvent(EmulsionEvent::NextImage) => picture_widget.next_image(),
+				Event::UserEvent(EmulsionEvent::PrevImage) => picture_widget.prev_image(),
+				Event::UserEvent(EmulsionEvent::ToggleSlideshow) => {
+					picture_widget.toggle_slideshow()
+				}
+				Event::UserEvent(EmulsionEvent::OpenPaths(paths)) => {
+					window.bring_to_front();
+					open_paths(&picture_widget, paths);
+				}
+				Event::UserEvent(EmulsionEvent::OpenFolder(path)) => {
+					window.bring_to_front();
+					picture_widget.open_folder(path);
+				}
+				Event::UserEvent(EmulsionEvent::OpenPlaylist(playlist)) => {
+					window.bring_to_front();
+					picture_widget.open_playlist(playlist.clone());
+				}
+				Event::UserEvent(EmulsionEvent::ToggleHidden) => {
+					window.set_hidden(!window.is_hidden())
+				}
+				Event::UserEvent(EmulsionEvent::Quit) => gelatin::application::request_exit(),
+				_ => (),
+			}
+			NextUpdate::Latest
 		});
 	}
-	{
+	let _tray = start_tray(&window, &config.borrow(), application.create_loop_proxy());
+	#[cfg(target_os = "macos")]
+	macos::handle_open_files(application.create_loop_proxy());
+	if single_instance || remote_control {
+		if let Err(e) = ipc::listen(&cache_path, application.create_loop_proxy()) {
+			log::error!("Could not receive the requests of other processes: {}", e);
+		}
+		let window = window.clone();
 		let picture_widget = picture_widget.clone();
-		bottom_bar.fit_best_button.set_on_click(move || {
-			picture_widget.set_img_size_to_fit(false);
+		let proxy = application.create_loop_proxy();
+		application.add_global_event_handler(move |event| {
+			if let Event::UserEvent(EmulsionEvent::IpcRequest(request)) = event {
+				handle_ipc_request(&window, &picture_widget, &proxy, remote_control, request);
+			}
+			NextUpdate::Latest
 		});
 	}
+	// Kept until the event loop ends, so that files can be opened over D-Bus
+	#[cfg(target_os = "linux")]
+	let _dbus_service = if single_instance || remote_control {
+		let proxy = application.create_loop_proxy();
+		match dbus::DbusService::start(proxy, single_instance, remote_control) {
+			Ok(service) => {
+				if remote_control {
+					let service = service.clone();
+					picture_widget.set_on_image_changed(move |current| {
+						if let Some(path) = &current.path {
+							service.image_changed(path);
+						}
+					});
+				}
+				Some(service)
+			}
+			Err(e) => {
+				log::error!("Could not publish the D-Bus service: {}", e);
+				None
+			}
+		}
+	} else {
+		None
+	};
+	let toggle_theme: Rc<dyn Fn()> = {
+		let cache = cache.clone();
+		let theme = theme.clone();
+		let set_theme = set_theme.clone();
+		Rc::new(move || {
+			let new_theme = theme.get().switch_theme();
+			theme.set(new_theme);
+			cache.lock().unwrap().set_theme(new_theme);
+			set_theme();
+		})
+	};
+	{
+		let toggle_theme = toggle_theme.clone();
+		bottom_bar.theme_button.set_on_click(move || toggle_theme());
+	}
+	connect_bottom_bar(&bottom_bar, &picture_widget);
 	{
-		bottom_bar.fit_stretch_button.set_on_click(move || {
-			picture_widget.set_img_size_to_fit(true);
+		let shared = viewer_window::Shared {
+			config: config.clone(),
+			cache: cache.clone(),
+			loader: loader.clone(),
+			proxy: application.create_loop_proxy(),
+			theme,
+			toggle_theme,
+		};
+		application.add_window_opener(move |event, opener| {
+			if let Event::UserEvent(EmulsionEvent::NewWindow(paths)) = event {
+				match viewer_window::open(opener, paths.clone(), &shared) {
+					Ok(viewer_window) => viewer_windows.borrow_mut().push(viewer_window),
+					Err(e) => log::error!("Could not open a new window: {}", e),
+				}
+			}
+			NextUpdate::Latest
 		});
 	}
 	let help_visible = Cell::new(first_launch);
@@ -287,18 +742,21 @@ fn main() {
 	window.set_root(root_container);
 
 	let check_updates_enabled =
-		config.borrow().updates.as_ref().map(|u| u.check_updates).unwrap_or(true);
+		config.borrow().updates.as_ref().map(|u| u.check_updates).unwrap_or(false);
 
 	let update_checker_join_handle = {
 		let updates = &mut cache.lock().unwrap().updates;
 		let cache = cache.clone();
 		let update_available = update_available.clone();
 		let update_check_done = update_check_done.clone();
+		let latest_version = latest_version.clone();
 
 		if check_updates_enabled && updates.update_check_needed() {
 			// kick off a thread that will check for an update in the background
 			Some(std::thread::spawn(move || {
-				let has_update = update::check_for_updates();
+				let newer_version = update::check_for_updates();
+				let has_update = newer_version.is_some();
+				*latest_version.lock().unwrap() = newer_version;
 				update_available.store(has_update, Ordering::SeqCst);
 				update_check_done.store(true, Ordering::SeqCst);
 				if !has_update {
@@ -310,23 +768,46 @@ fn main() {
 		}
 	};
 
-	let mut nothing_to_do = false;
+	// Without a checker thread there's nothing to wait for, so the event loop isn't woken up
+	let mut nothing_to_do = update_checker_join_handle.is_none();
+	// When an update is found, the notification is shown for a while even if the help screen is
+	// hidden, along with the new version in the title
+	let mut toast_deadline = None;
 	application.add_global_event_handler(move |_| {
 		if nothing_to_do {
 			return NextUpdate::Latest;
 		}
-		if update_check_done.load(Ordering::SeqCst) {
+		if let Some(deadline) = toast_deadline {
+			if Instant::now() < deadline {
+				return NextUpdate::WaitUntil(deadline);
+			}
 			nothing_to_do = true;
+			picture_widget.set_notice(None);
+			update_notification.set_visible(help_screen.visible());
+			return NextUpdate::Latest;
+		}
+		if update_check_done.load(Ordering::SeqCst) {
 			set_theme();
-			if help_screen.visible() && update_available.load(Ordering::SeqCst) {
-				update_notification.set_visible(true);
+			match latest_version.lock().unwrap().as_ref() {
+				Some(version) => {
+					update_notification.set_visible(true);
+					picture_widget.set_notice(Some(format!("Version {} is available", version)));
+					toast_deadline = Some(Instant::now() + UPDATE_TOAST_DURATION);
+				}
+				None => nothing_to_do = true,
 			}
 		}
 		NextUpdate::WaitUntil(Instant::now() + Duration::from_secs(1))
 	});
 
+	let session_start = Instant::now();
+	let record_session = stats::enabled(&config.borrow());
 	application.set_at_exit(Some(move || {
-		cache.lock().unwrap().save(cache_path).unwrap();
+		let mut cache = cache.lock().unwrap();
+		if record_session {
+			cache.stats.record_session(session_start.elapsed());
+		}
+		cache.save(cache_path).unwrap();
 		if let Some(h) = update_checker_join_handle {
 			h.join().unwrap();
 		}
@@ -342,6 +823,174 @@ fn make_icon() -> Icon {
 	Icon::from_rgba(rgba.into_raw(), w, h).unwrap()
 }
 
+/// Without `remote_control` only the requests that open paths are served, which is what
+/// `single_instance` needs
+fn handle_ipc_request(
+	window: &Window,
+	picture_widget: &PictureWidget,
+	proxy: &EventLoopProxy<EmulsionEvent>,
+	remote_control: bool,
+	incoming: &ipc::IncomingRequest,
+) {
+	let response = match &incoming.request {
+		ipc::Request::Open { paths } => {
+			window.bring_to_front();
+			open_paths(picture_widget, paths);
+			ipc::Response::ok()
+		}
+		ipc::Request::OpenWindow { paths } => {
+			// The event loop is running since this request came through it
+			let _ = proxy.send_event(EmulsionEvent::NewWindow(paths.clone()));
+			ipc::Response::ok()
+		}
+		_ if !remote_control => {
+			ipc::Response::error("Enable `remote_control` in the config to use this".to_owned())
+		}
+		ipc::Request::Next => {
+			picture_widget.next_image();
+			ipc::Response::ok()
+		}
+		ipc::Request::Prev => {
+			picture_widget.prev_image();
+			ipc::Response::ok()
+		}
+		ipc::Request::Zoom { factor } if !(*factor > 0.0 && factor.is_finite()) => {
+			ipc::Response::error(format!("The zoom factor must be positive, got {}", factor))
+		}
+		ipc::Request::Zoom { factor } => {
+			picture_widget.set_zoom(*factor);
+			ipc::Response::ok()
+		}
+		ipc::Request::Fullscreen { enabled } => {
+			picture_widget.set_fullscreen(enabled.unwrap_or(!window.fullscreen()));
+			ipc::Response::ok()
+		}
+		ipc::Request::Overlay { enabled } => {
+			picture_widget
+				.set_reference_overlay(enabled.unwrap_or(!picture_widget.reference_overlay()));
+			ipc::Response::ok()
+		}
+		ipc::Request::QueryCurrent => {
+			ipc::Response { current: Some(picture_widget.current_image()), ..ipc::Response::ok() }
+		}
+	};
+	incoming.reply(response);
+}
+
+/// Opens a single image in its folder, or several of them as a playlist
+fn open_paths(picture_widget: &PictureWidget, paths: &[PathBuf]) {
+	match paths {
+		[] => (),
+		[path] => picture_widget.jump_to_path(path),
+		paths => picture_widget.open_playlist(Playlist { paths: paths.to_vec(), current: None }),
+	}
+}
+
+/// Shows the tray icon when it's enabled. With `minimize_to_tray` the window is hidden when it's
+/// minimized, and the icon shows it again.
+#[cfg(target_os = "linux")]
+fn start_tray(
+	window: &Window,
+	config: &Configuration,
+	proxy: EventLoopProxy<EmulsionEvent>,
+) -> Option<tray::Tray> {
+	let minimize_to_tray = config.window.as_ref().and_then(|w| w.minimize_to_tray) == Some(true);
+	if !tray_icon(config) {
+		return None;
+	}
+	let tray = match tray::Tray::start(proxy) {
+		Ok(tray) => tray,
+		Err(e) => {
+			log::error!("Could not show the tray icon: {}", e);
+			return None;
+		}
+	};
+	if minimize_to_tray {
+		window.add_global_event_handler(|window, event| {
+			// There's no event for minimizing, but minimized windows are resized or occluded
+			if let WindowEvent::Resized(_) | WindowEvent::Occluded(true) = event {
+				if window.window_mut().is_minimized() == Some(true) {
+					window.set_hidden(true);
+				}
+			}
+		});
+	}
+	Some(tray)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn start_tray(
+	_window: &Window,
+	config: &Configuration,
+	_proxy: EventLoopProxy<EmulsionEvent>,
+) -> Option<()> {
+	if tray_icon(config) {
+		log::warn!("The tray icon is only available on Linux");
+	}
+	None
+}
+
+/// `minimize_to_tray` needs the tray icon, so it turns it on too
+fn tray_icon(config: &Configuration) -> bool {
+	let window = config.window.as_ref();
+	let enabled = |option: Option<bool>| option == Some(true);
+	window.is_some_and(|w| enabled(w.tray_icon) || enabled(w.minimize_to_tray))
+}
+
+/// Sets the colors of the window and of the shade around the image
+fn set_window_theme(window: &Window, picture_widget: &PictureWidget, theme: Theme) {
+	match theme {
+		Theme::Light => {
+			picture_widget.set_bright_shade(0.96);
+			window.set_bg_color([0.85, 0.85, 0.85, 1.0]);
+		}
+		Theme::Dark => {
+			picture_widget.set_bright_shade(0.11);
+			window.set_bg_color([0.01, 0.01, 0.01, 1.0]);
+		}
+	}
+}
+
+/// Makes the slider and the scale buttons of `bottom_bar` control `picture_widget`. The picture
+/// widget owns the bottom bar, so it's only referenced weakly from the bottom bar.
+fn connect_bottom_bar(bottom_bar: &BottomBar, picture_widget: &Rc<PictureWidget>) {
+	{
+		let slider = Rc::downgrade(&bottom_bar.slider);
+		let picture_widget = Rc::downgrade(picture_widget);
+		bottom_bar.slider.set_on_value_change(move || {
+			if let (Some(slider), Some(picture_widget)) =
+				(slider.upgrade(), picture_widget.upgrade())
+			{
+				picture_widget.jump_to_index(slider.value());
+			}
+		});
+	}
+	{
+		let picture_widget = Rc::downgrade(picture_widget);
+		bottom_bar.orig_scale_button.set_on_click(move || {
+			if let Some(picture_widget) = picture_widget.upgrade() {
+				picture_widget.set_img_size_to_orig();
+			}
+		});
+	}
+	{
+		let picture_widget = Rc::downgrade(picture_widget);
+		bottom_bar.fit_best_button.set_on_click(move || {
+			if let Some(picture_widget) = picture_widget.upgrade() {
+				picture_widget.set_img_size_to_fit(false);
+			}
+		});
+	}
+	{
+		let picture_widget = Rc::downgrade(picture_widget);
+		bottom_bar.fit_stretch_button.set_on_click(move || {
+			if let Some(picture_widget) = picture_widget.upgrade() {
+				picture_widget.set_img_size_to_fit(true);
+			}
+		});
+	}
+}
+
 fn add_window_movement_listener(window: &Window, cache: Arc<Mutex<Cache>>) {
 	window.add_global_event_handler(move |window, event| match event {
 		WindowEvent::Resized(new_size) => {
@@ -360,6 +1009,88 @@ fn add_window_movement_listener(window: &Window, cache: Arc<Mutex<Cache>>) {
 	});
 }
 
+/// Snaps the size of the window when it's resized, see `PictureWidget::integer_scale_window_size`
+fn add_integer_scale_snapping(window: &Window, picture_widget: Rc<PictureWidget>) {
+	window.add_global_event_handler(move |window, event| {
+		if let WindowEvent::Resized(new_size) = event {
+			if window.fullscreen() || window.window_mut().is_maximized() {
+				return;
+			}
+			let dpi_scale = window.window_mut().scale_factor() as f32;
+			if let Some(size) = picture_widget.integer_scale_window_size(*new_size, dpi_scale) {
+				if size != *new_size {
+					let _ = window.window_mut().request_inner_size(size);
+				}
+			}
+		}
+	});
+}
+
+fn borderless(config: &Configuration) -> bool {
+	config.window.as_ref().and_then(|w| w.borderless).unwrap_or(false)
+}
+
+fn always_on_top(config: &Configuration) -> bool {
+	config.window.as_ref().and_then(|w| w.always_on_top).unwrap_or(false)
+}
+
+/// The `opacity` of the window between 0 and 1
+fn window_opacity(config: &Configuration) -> f32 {
+	let opacity = config.window.as_ref().and_then(|w| w.opacity);
+	opacity.unwrap_or(100).min(100) as f32 / 100.0
+}
+
+/// The window needs an alpha channel to become translucent in the overlay mode
+fn has_overlay_opacity(config: &Configuration) -> bool {
+	config.window.as_ref().is_some_and(|w| w.overlay_opacity.is_some())
+}
+
+/// Adds the strip that moves the window to `root_container` if the window is `borderless`. The
+/// strip is hidden in fullscreen.
+fn add_drag_area(
+	window: &Window,
+	root_container: &VerticalLayoutContainer,
+	config: &Configuration,
+) {
+	if !borderless(config) {
+		return;
+	}
+	let drag_area = Rc::new(DragArea::new());
+	drag_area.set_margin_all(0.0);
+	drag_area.set_height(Length::Fixed(DRAG_AREA_HEIGHT));
+	drag_area.set_width(Length::Stretch { min: 0.0, max: f32::INFINITY });
+	// Translucent gray stands out a little from the background of both themes
+	drag_area.set_bg_color([0.5, 0.5, 0.5, 0.2]);
+	root_container.add_child(drag_area.clone());
+	window.add_global_event_handler(move |window, event| {
+		if let WindowEvent::Resized(_) = event {
+			drag_area.set_visible(!window.fullscreen());
+		}
+	});
+}
+
+/// Keeps the picture area and the overlays in it away from the edges that are set in the
+/// `fullscreen_insets` option while the window is fullscreen
+fn add_fullscreen_insets(
+	window: &Window,
+	picture_area_container: Rc<VerticalLayoutContainer>,
+	config: &Configuration,
+) {
+	let insets = match config.window.as_ref().and_then(|w| w.fullscreen_insets) {
+		Some(insets) => insets,
+		None => return,
+	};
+	window.add_global_event_handler(move |window, event| {
+		if let WindowEvent::Resized(_) = event {
+			let insets = if window.fullscreen() { insets } else { Insets::default() };
+			picture_area_container.set_margin_top(insets.top);
+			picture_area_container.set_margin_right(insets.right);
+			picture_area_container.set_margin_bottom(insets.bottom);
+			picture_area_container.set_margin_left(insets.left);
+		}
+	});
+}
+
 fn make_root_container() -> Rc<VerticalLayoutContainer> {
 	let container = Rc::new(VerticalLayoutContainer::new());
 	container.set_margin_all(0.0);
@@ -376,6 +1107,18 @@ fn make_picture_area_container() -> Rc<VerticalLayoutContainer> {
 	picture_area_container
 }
 
+/// Puts `picture_widget` in a row that the pinned image of the side-by-side comparison is shown
+/// in, see `PictureWidget::set_compare_row`
+fn make_compare_row(picture_widget: &Rc<PictureWidget>) -> Rc<HorizontalLayoutContainer> {
+	let row = Rc::new(HorizontalLayoutContainer::new());
+	row.set_margin_all(0.0);
+	row.set_height(Length::Stretch { min: 0.0, max: f32::INFINITY });
+	row.set_width(Length::Stretch { min: 0.0, max: f32::INFINITY });
+	row.add_child(picture_widget.clone());
+	picture_widget.set_compare_row(&row);
+	row
+}
+
 fn make_update_label() -> Rc<Label> {
 	let update_label = Rc::new(Label::new());
 	update_label.set_margin_top(4.0);
@@ -400,7 +1143,9 @@ fn make_update_notification(update_label: Rc<Label>) -> Rc<HorizontalLayoutConta
 	update_button.set_fixed_size(LogicalVector::new(100.0, 24.0));
 	update_button.set_horizontal_align(Alignment::Center);
 	update_button.set_on_click(|| {
-		open::that("https://github.com/ChristophHess/emulsion").unwrap();
+		if let Err(e) = open::that(update::RELEASES_URL) {
+			log::error!("Could not open {}: {}", update::RELEASES_URL, e);
+		}
 	});
 
 	container.add_child(update_label);
@@ -408,6 +1153,14 @@ fn make_update_notification(update_label: Rc<Label>) -> Rc<HorizontalLayoutConta
 	container
 }
 
+fn open_startup_paths(picture_widget: &PictureWidget, startup_paths: &StartupPaths) {
+	match startup_paths {
+		StartupPaths::None => (),
+		StartupPaths::Single(file_path) => picture_widget.jump_to_path(file_path),
+		StartupPaths::List(playlist) => picture_widget.open_playlist(playlist.clone()),
+	}
+}
+
 fn make_picture_widget(
 	window: &Rc<Window>,
 	bottom_bar: Rc<BottomBar>,
@@ -415,6 +1168,7 @@ fn make_picture_widget(
 	copy_notifications: CopyNotifications,
 	config: Rc<RefCell<Configuration>>,
 	cache: Arc<Mutex<Cache>>,
+	loader: Rc<SharedLoader>,
 ) -> Rc<PictureWidget> {
 	let picture_widget = Rc::new(PictureWidget::new(
 		&window.display_mut(),
@@ -424,6 +1178,7 @@ fn make_picture_widget(
 		copy_notifications,
 		config,
 		cache,
+		loader,
 	));
 	picture_widget.set_height(Length::Stretch { min: 0.0, max: f32::INFINITY });
 	picture_widget.set_width(Length::Stretch { min: 0.0, max: f32::INFINITY });
@@ -433,31 +1188,47 @@ fn make_picture_widget(
 pub fn get_config_and_cache_paths() -> (PathBuf, PathBuf) {
 	let config_folder;
 	let cache_folder;
+	let overrides = PATH_OVERRIDES.get().cloned().unwrap_or_default();
+	let exe_folder = || {
+		let exe_path = std::env::current_exe().unwrap();
+		exe_path.parent().unwrap().to_owned()
+	};
 
-	if let Some(ref project_dirs) = *PROJECT_DIRS {
+	if overrides.portable || exe_folder().join(PORTABLE_MARKER).exists() {
+		config_folder = exe_folder();
+		cache_folder = config_folder.clone();
+	} else if let Some(folder) =
+		std::env::var_os(CONFIG_DIR_VAR).filter(|folder| !folder.is_empty())
+	{
+		config_folder = PathBuf::from(folder);
+		cache_folder = config_folder.clone();
+	} else if let Some(ref project_dirs) = *PROJECT_DIRS {
 		config_folder = project_dirs.config_dir().to_owned();
 		cache_folder = project_dirs.cache_dir().to_owned();
 	} else {
-		let exe_path = std::env::current_exe().unwrap();
-		let exe_folder = exe_path.parent().unwrap();
-		config_folder = exe_folder.to_owned();
-		cache_folder = exe_folder.to_owned();
-	}
-	if !config_folder.exists() {
-		std::fs::create_dir_all(&config_folder).unwrap();
+		config_folder = exe_folder();
+		cache_folder = config_folder.clone();
 	}
-	if !cache_folder.exists() {
-		std::fs::create_dir_all(&cache_folder).unwrap();
+	let config_path = overrides.config.unwrap_or_else(|| config_folder.join("cfg.toml"));
+	let cache_path = overrides.cache.unwrap_or_else(|| cache_folder.join("cache.toml"));
+	for folder in [&config_path, &cache_path].iter().filter_map(|path| path.parent()) {
+		if !folder.as_os_str().is_empty() && !folder.exists() {
+			std::fs::create_dir_all(folder).unwrap();
+		}
 	}
 
-	(config_folder.join("cfg.toml"), cache_folder.join("cache.toml"))
+	(config_path, cache_path)
 }
 
 #[cfg(not(feature = "networking"))]
 mod update {
-	/// Always returns false without the `networking` feature.
-	pub fn check_for_updates() -> bool {
-		false
+	use crate::version::Version;
+
+	pub static RELEASES_URL: &str = "https://github.com/ChristophHess/emulsion/releases/latest";
+
+	/// Always returns `None` without the `networking` feature.
+	pub fn check_for_updates() -> Option<Version> {
+		None
 	}
 }
 
@@ -465,6 +1236,8 @@ mod update {
 mod update {
 	use serde::Deserialize;
 
+	use crate::version::Version;
+
 	#[derive(Deserialize)]
 	struct ReleaseInfoJson {
 		tag_name: String,
@@ -472,6 +1245,8 @@ mod update {
 
 	type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
+	pub static RELEASES_URL: &str = "https://github.com/ChristophHess/emulsion/releases/latest";
+
 	/// Tries to fetch latest release tag
 	fn latest_release() -> Result<ReleaseInfoJson> {
 		let url = "https://api.github.com/repos/ChristophHess/emulsion/releases/latest";
@@ -485,9 +1260,8 @@ mod update {
 		}
 	}
 
-	/// Tries to parse version tag and compare against current version
-	fn compare_release(info: &ReleaseInfoJson) -> Result<bool> {
-		use crate::version::Version;
+	/// Tries to parse version tag and returns it if it's newer than the current version
+	fn compare_release(info: &ReleaseInfoJson) -> Result<Option<Version>> {
 		use std::str::FromStr;
 
 		let current = Version::cargo_pkg_version();
@@ -495,25 +1269,25 @@ mod update {
 
 		if latest > current {
 			println!("Current version is {}, latest version is {}", current, latest);
-			Ok(true)
+			Ok(Some(latest))
 		} else {
-			Ok(false)
+			Ok(None)
 		}
 	}
 
-	/// Returns true if updates are available.
-	pub fn check_for_updates() -> bool {
+	/// Returns the latest version if it's newer than the current one.
+	pub fn check_for_updates() -> Option<Version> {
 		match latest_release() {
 			Ok(info) => match compare_release(&info) {
-				Ok(is_newer) => is_newer,
+				Ok(newer) => newer,
 				Err(err) => {
 					eprintln!("Error parsing release tag: {}", err);
-					false
+					None
 				}
 			},
 			Err(err) => {
 				eprintln!("Error checking latest release: {}", err);
-				false
+				None
 			}
 		}
 	}
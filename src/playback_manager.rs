@@ -5,23 +5,29 @@ use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::time::{Duration, Instant};
 
-use gelatin::NextUpdate;
 use rand::seq::SliceRandom;
 use rand::thread_rng;
 
 use log::{debug, trace};
 
+use gelatin::image::RgbaImage;
 use gelatin::window::Window;
 use gelatin::Display;
 
+use crate::folder_config::FolderConfig;
 use crate::image_cache::{
 	self, AnimationFrameTexture, ImageCache, PathResolutionError, PathedTextureResult,
-	TextureResult,
+	SharedLoader, TextureResult,
 };
+use crate::playlist::Playlist;
 
-use image_cache::directory;
+use image_cache::directory::{self, BrowseFilter};
 
 const NANOS_PER_SEC: u64 = 1_000_000_000;
+/// The frame rate of playing the images of the folder when it's not configured
+pub const DEFAULT_FRAME_RATE: u32 = 25;
+/// How long each image is shown during a presentation when it's not set with `--slideshow`
+pub const DEFAULT_PRESENT_INTERVAL: Duration = Duration::from_secs(6);
 
 #[derive(Debug, Eq, PartialEq)]
 pub enum LoadRequest {
@@ -97,9 +103,8 @@ impl Playback for FolderPlayback {
 		image_cache.load_at_index(display, index, None)
 	}
 
-	fn delay_nanos(_player: &ImgSequencePlayer<Self>) -> u64 {
-		const FRAMERATE: u64 = 25;
-		NANOS_PER_SEC / FRAMERATE
+	fn delay_nanos(player: &ImgSequencePlayer<Self>) -> u64 {
+		NANOS_PER_SEC / player.frame_rate.max(1) as u64
 	}
 }
 
@@ -151,33 +156,31 @@ pub struct PlaybackManager {
 	// filename: Option<OsString>,
 	folder_player: ImgSequencePlayer<FolderPlayback>,
 	image_player: ImgSequencePlayer<AnimPlayback>,
+
+	/// An image that doesn't come from a file (for example one pasted from the clipboard) waiting
+	/// to be uploaded to the GPU, along with its name.
+	pending_memory_image: Option<(RgbaImage, String)>,
+	/// While this is set it's displayed instead of the images of the folder.
+	memory_image: Option<AnimationFrameTexture>,
+	/// Stops the frames of the current animation or video from advancing, until an other file is
+	/// shown
+	animation_paused: bool,
+	memory_image_path: LoadedImgPath,
 }
 
 impl PlaybackManager {
-	pub fn new() -> Self {
-		let cache_capacity = match sys_info::mem_info() {
-			Ok(value) => {
-				// value originally reported in KiB
-				((value.total / 8) * 1024) as isize
-			}
-			_ => {
-				eprintln!("Could not get system memory size, using default value");
-				// bytes
-				500_000_000
-			}
-		};
-
-		let thread_count = match sys_info::cpu_num() {
-			Ok(value) => value.clamp(2, 4),
-			_ => 4,
-		};
-
+	/// The images are decoded by `loader`, which the playback managers of all windows share
+	pub fn new(loader: Rc<SharedLoader>) -> Self {
 		PlaybackManager {
 			//playback_state: PlaybackState::Paused,
 			// filename: None,
-			image_cache: ImageCache::new(cache_capacity, thread_count),
+			image_cache: ImageCache::new(loader),
 			folder_player: ImgSequencePlayer::new(),
 			image_player: ImgSequencePlayer::new(),
+			pending_memory_image: None,
+			memory_image: None,
+			animation_paused: false,
+			memory_image_path: LoadedImgPath::NotYetLoaded,
 		}
 	}
 
@@ -212,6 +215,51 @@ impl PlaybackManager {
 		// self.playback_state = PlaybackState::Present;
 	}
 
+	pub fn animation_paused(&self) -> bool {
+		self.animation_paused
+	}
+
+	/// Pauses the frames of the current animation or video, or plays them again
+	pub fn toggle_animation(&mut self) {
+		self.animation_paused = !self.animation_paused;
+		if self.animation_paused {
+			self.image_player.pause_playback();
+		} else {
+			self.image_player.start_playback_forward();
+		}
+	}
+
+	/// Pauses the current animation or video and shows its next (positive `step`) or previous
+	/// frame
+	pub fn step_animation(&mut self, step: isize) {
+		self.animation_paused = true;
+		self.image_player.pause_playback();
+		self.image_player.request_load(LoadRequest::Jump(step as i32));
+	}
+
+	/// The number of images shown per second when the folder is played
+	pub fn frame_rate(&self) -> u32 {
+		self.folder_player.frame_rate
+	}
+
+	pub fn set_frame_rate(&mut self, frame_rate: u32) {
+		self.folder_player.frame_rate = frame_rate.max(1);
+	}
+
+	/// Whether playing the folder starts over after the last image, otherwise it stops there
+	pub fn looping(&self) -> bool {
+		self.folder_player.looping
+	}
+
+	pub fn set_looping(&mut self, looping: bool) {
+		self.folder_player.looping = looping;
+	}
+
+	/// How long each image is shown during a presentation
+	pub fn set_present_interval(&mut self, interval: Duration) {
+		self.folder_player.present_interval = interval.max(Duration::from_millis(100));
+	}
+
 	/// Returns None when the folder hasn't finished filtering
 	pub fn current_file_index(&mut self) -> Option<usize> {
 		self.image_cache.current_file_index()
@@ -222,40 +270,169 @@ impl PlaybackManager {
 		self.image_cache.current_dir_len()
 	}
 
+	/// See `ImageCache::texture_at_index`
+	pub fn texture_at_index(&mut self, index: usize) -> TextureResult<AnimationFrameTexture> {
+		self.image_cache.texture_at_index(index)
+	}
+
 	pub fn update_directory(&mut self) -> directory::Result<()> {
 		debug!("In `update_directory`");
+		if let LoadedImgPath::Removed(_) = self.folder_player.file_path {
+			// Keep the position of the removed file in the folder until an other image is opened
+			return Ok(());
+		}
 		if let LoadRequest::None = self.folder_player.load_request {
 			let curr_path = self.image_cache.current_file_path();
 			debug!("In `update_directory`, current_file_path is: {:?}", curr_path);
 			if curr_path.is_some() {
-				self.image_cache.update_directory()?;
-
-				// The there's no file to open, just request to open the empty path.
-				// This will hide the previously loaded image.
-				// Note that `image_cache.current_file_path()` is used instead of `self.shown_file_path()`
-				let path = self.image_cache.current_file_path().unwrap_or_default();
-				self.request_load(LoadRequest::FilePath(path));
+				// Only reload if the shown image is affected, so that the image doesn't flicker and
+				// animations don't restart whenever the window regains focus (which also happens
+				// when toggling fullscreen on some platforms).
+				let shown_image_changed = self.image_cache.update_directory()?;
+				if shown_image_changed {
+					// The there's no file to open, just request to open the empty path.
+					// This will hide the previously loaded image.
+					// Note that `image_cache.current_file_path()` is used instead of `self.shown_file_path()`
+					let path = self.image_cache.current_file_path().unwrap_or_default();
+					self.request_load(LoadRequest::FilePath(path));
+				}
 			}
 		}
 		Ok(())
 	}
 
+	/// Shows the file at its new path after it was renamed, at its new position in the folder
+	pub fn file_renamed(&mut self, old_path: &Path, new_path: PathBuf) -> directory::Result<()> {
+		self.image_cache.rename_listed_file(old_path, &new_path);
+		self.image_cache.update_directory()?;
+		self.request_load(LoadRequest::FilePath(new_path));
+		Ok(())
+	}
+
+	/// Browses only the images accepted by `filter`, see `Directory::set_filter`
+	pub fn set_browse_filter(&mut self, filter: BrowseFilter) -> directory::Result<()> {
+		self.image_cache.set_browse_filter(filter)
+	}
+
+	pub fn browse_filter(&self) -> &BrowseFilter {
+		self.image_cache.browse_filter()
+	}
+
+	/// The `.emulsion.toml` of the browsed folder, see `folder_config`
+	pub fn folder_config(&self) -> &FolderConfig {
+		self.image_cache.folder_config()
+	}
+
 	pub fn request_load(&mut self, request: LoadRequest) {
+		self.clear_memory_image();
 		self.folder_player.request_load(request);
 		self.image_player.request_load(LoadRequest::Jump(0));
 	}
 
+	/// Browses exactly the files of the playlist (directories are replaced with the files in them)
+	/// instead of the folder of the shown image.
+	pub fn open_playlist(&mut self, playlist: Playlist) -> directory::Result<()> {
+		self.image_cache.open_file_list(playlist.paths)?;
+		match playlist.current.and_then(|path| path.canonicalize().ok()) {
+			Some(current) => self.request_load(LoadRequest::FilePath(current)),
+			None => self.request_load(LoadRequest::LoadAtIndex(0)),
+		}
+		Ok(())
+	}
+
+	/// Returns the images of the folder in their order in the folder. Returns `None` while the
+	/// folder is being filtered.
+	pub fn image_paths(&mut self) -> Option<Vec<PathBuf>> {
+		self.image_cache.image_paths()
+	}
+
+	/// Returns the images in the order they are browsed. During a random presentation the images
+	/// that are still to be shown are listed after the current one, in the order they will be
+	/// shown. Returns `None` while the folder is being filtered.
+	pub fn playlist(&mut self) -> Option<Playlist> {
+		let images = self.image_cache.image_paths()?;
+		let current = self.image_cache.current_file_path();
+		let paths = if self.playback_state() == PlaybackState::RandomPresent {
+			let upcoming = self.folder_player.present_remaining.iter().rev().copied();
+			let current_index = self.image_cache.current_file_index();
+			let mut listed = vec![false; images.len()];
+			let mut order = Vec::with_capacity(images.len());
+			for index in current_index.into_iter().chain(upcoming) {
+				if index < images.len() && !listed[index] {
+					listed[index] = true;
+					order.push(index);
+				}
+			}
+			// The ones that were already shown come first
+			let shown = (0..images.len()).filter(|&index| !listed[index]).collect::<Vec<_>>();
+			order.splice(0..0, shown);
+			order.into_iter().map(|index| images[index].clone()).collect()
+		} else {
+			images
+		};
+		Some(Playlist { paths, current })
+	}
+
+	/// Stops showing the current image because its file was removed, until an other image is
+	/// loaded. Does nothing while an other image is being loaded.
+	pub fn show_removed_placeholder(&mut self) {
+		if self.folder_player.load_request != LoadRequest::None {
+			return;
+		}
+		if let LoadedImgPath::Loaded(path) = &self.folder_player.file_path {
+			self.folder_player.file_path = LoadedImgPath::Removed(path.clone());
+			self.folder_player.image_texture = None;
+			self.image_player.image_texture = None;
+		}
+	}
+
+	/// Displays an image that isn't stored in a file until something else is loaded. `name` is
+	/// shown in place of the file path.
+	pub fn show_memory_image(&mut self, image: RgbaImage, name: String) {
+		self.pending_memory_image = Some((image, name));
+	}
+
+	fn clear_memory_image(&mut self) {
+		self.pending_memory_image = None;
+		self.memory_image = None;
+		self.memory_image_path = LoadedImgPath::NotYetLoaded;
+	}
+
 	pub fn image_texture(&self) -> Option<AnimationFrameTexture> {
+		if self.memory_image.is_some() {
+			return self.memory_image.clone();
+		}
 		self.image_player.image_texture()
 	}
 
 	/// The path to the image file which is currently rendered onto the screen.
 	pub fn shown_file_path(&self) -> &LoadedImgPath {
+		if self.memory_image.is_some() {
+			return &self.memory_image_path;
+		}
 		&self.folder_player.file_path
 	}
 
+	/// The path of the current file of the folder. Unlike `shown_file_path` this is available even
+	/// while an image from memory is shown or if the file failed to load.
+	pub fn current_file_path(&self) -> Option<PathBuf> {
+		self.image_cache.current_file_path()
+	}
+
 	pub fn update_image(&mut self, window: &Window) -> gelatin::NextUpdate {
 		let display = window.display_mut();
+		if let Some((image, name)) = self.pending_memory_image.take() {
+			match AnimationFrameTexture::from_image(&display, image, 0, Default::default()) {
+				Ok(texture) => {
+					self.memory_image = Some(texture);
+					self.memory_image_path = LoadedImgPath::InMemory(name);
+				}
+				Err(e) => log::error!("Failed to upload {} to the GPU: {}", name, e),
+			}
+		}
+		if self.memory_image.is_some() {
+			return gelatin::NextUpdate::Latest;
+		}
 		let prev_file = self.folder_player.image_texture();
 		let next_update = self.folder_player.update_image(&display, &mut self.image_cache);
 		// trace!("Folder player next update: {:?}", next_update);
@@ -265,6 +442,7 @@ impl PlaybackManager {
 			file_changed = !Rc::ptr_eq(&prev.tex_grid, &new.tex_grid);
 		}
 		if file_changed {
+			self.animation_paused = false;
 			self.image_player.start_playback_forward();
 			self.image_player.request_load(LoadRequest::Jump(0));
 		}
@@ -284,6 +462,10 @@ pub enum LoadedImgPath {
 	NotYetLoaded,
 	ErrLoading(PathBuf),
 	Loaded(PathBuf),
+	/// The image doesn't come from a file, the name describes where it comes from
+	InMemory(String),
+	/// The file was removed while it was displayed, see `PlaybackManager::show_removed_placeholder`
+	Removed(PathBuf),
 }
 
 impl LoadedImgPath {
@@ -304,6 +486,14 @@ struct ImgSequencePlayer<P: Playback> {
 	image_texture: Option<AnimationFrameTexture>,
 	file_path: LoadedImgPath,
 
+	/// Only used by `FolderPlayback`, animations play at the delays of their frames
+	frame_rate: u32,
+	/// When this isn't set, playing forward stops at the last image of the folder. Animations
+	/// always loop.
+	looping: bool,
+	/// How long each image is shown during a presentation
+	present_interval: Duration,
+
 	_playback: PhantomData<P>,
 }
 
@@ -318,6 +508,9 @@ impl<P: Playback> ImgSequencePlayer<P> {
 			load_request: LoadRequest::None,
 			image_texture: None,
 			file_path: LoadedImgPath::NotYetLoaded,
+			frame_rate: DEFAULT_FRAME_RATE,
+			looping: true,
+			present_interval: DEFAULT_PRESENT_INTERVAL,
 
 			_playback: PhantomData,
 		}
@@ -374,7 +567,6 @@ impl<P: Playback> ImgSequencePlayer<P> {
 				self.load_request
 			);
 		}
-		self.playback_state = PlaybackState::Paused;
 		let is_paused = matches!(self.playback_state, PlaybackState::Paused);
 		let no_request = matches!(self.load_request, LoadRequest::None);
 		if !self.file_path.is_loaded() && no_request && is_paused {
@@ -388,14 +580,11 @@ impl<P: Playback> ImgSequencePlayer<P> {
 		// function early. And at the same time I want to use it's value as it is at this line.
 		let mut load_request = LoadRequest::None;
 		mem::swap(&mut self.load_request, &mut load_request);
-		let frame_delta_time_nanos;
-		match self.playback_state {
+		let frame_delta_time_nanos = match self.playback_state {
 			PlaybackState::Present | PlaybackState::RandomPresent => {
-				frame_delta_time_nanos = (NANOS_PER_SEC * 6) as i64;
-			}
-			_ => {
-				frame_delta_time_nanos = P::delay_nanos(self) as i64;
+				self.present_interval.as_nanos() as i64
 			}
+			_ => P::delay_nanos(self) as i64,
 		};
 		if self.playback_state == PlaybackState::Paused {
 			if let Err(e) = image_cache.process_prefetched(display) {
@@ -429,14 +618,18 @@ impl<P: Playback> ImgSequencePlayer<P> {
 			// This assumes that the following frames have the same delay but that's okay considering that
 			// if frame step is greater than 1 it almost certainly means that we couldn't load the
 			// next frame quiclky enough so there's not much else to do here.
-			let frame_step;
-			if frame_delta_time_nanos > 0 {
-				frame_step = elapsed_nanos / frame_delta_time_nanos;
-			} else {
-				frame_step = 0;
-			}
+			let frame_step =
+				if frame_delta_time_nanos > 0 { elapsed_nanos / frame_delta_time_nanos } else { 0 };
 			if frame_step > 0 {
+				let at_last_image = match image_cache.current_file_index() {
+					Some(index) => image_cache.current_dir_len() == Some(index + 1),
+					None => false,
+				};
 				load_request = match self.playback_state {
+					PlaybackState::Forward if !self.looping && at_last_image => {
+						self.playback_state = PlaybackState::Paused;
+						LoadRequest::None
+					}
 					PlaybackState::Forward | PlaybackState::Present => {
 						// if we can't load the frames quickly enough,
 						// we won't jump over frames, but instead play the animation slower.
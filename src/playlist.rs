@@ -0,0 +1,100 @@
+//! Saving the browsed images to a file and opening them again.
+//!
+//! A playlist is a text file with one path on each line, so it can be written by hand or
+//! generated by other programs too. Empty lines and lines starting with `#` are skipped, which
+//! makes M3U playlists usable as well. The image that was shown when the playlist was saved is
+//! stored in a `#EMULSION-CURRENT:` line.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const HEADER: &str = "#EMULSION-PLAYLIST";
+const CURRENT_PREFIX: &str = "#EMULSION-CURRENT:";
+
+/// The images to browse, in the order they are browsed
+#[derive(Debug, Default, Clone)]
+pub struct Playlist {
+	pub paths: Vec<PathBuf>,
+	/// The image to show first. The first one is shown if this is `None`.
+	pub current: Option<PathBuf>,
+}
+
+impl Playlist {
+	/// Relative paths are resolved from `base_dir` if it's specified.
+	pub fn parse(text: &str, base_dir: Option<&Path>) -> Playlist {
+		let resolve = |line: &str| match base_dir {
+			Some(base_dir) => base_dir.join(line),
+			None => PathBuf::from(line),
+		};
+		let mut playlist = Playlist::default();
+		// Paths may start or end with spaces, so the lines aren't trimmed
+		for line in text.split('\n').map(|line| line.strip_suffix('\r').unwrap_or(line)) {
+			if let Some(current) = line.strip_prefix(CURRENT_PREFIX) {
+				playlist.current = Some(resolve(current));
+			} else if !line.is_empty() && !line.starts_with('#') {
+				playlist.paths.push(resolve(line));
+			}
+		}
+		playlist
+	}
+
+	/// Reads the playlist at `path`. Relative paths in it are relative to the folder of the
+	/// playlist.
+	pub fn load(path: &Path) -> io::Result<Playlist> {
+		let text = fs::read_to_string(path)?;
+		Ok(Playlist::parse(&text, path.parent()))
+	}
+
+	pub fn save(&self, path: &Path) -> io::Result<()> {
+		let mut text = String::from(HEADER);
+		text.push('\n');
+		if let Some(current) = &self.current {
+			text.push_str(CURRENT_PREFIX);
+			text.push_str(&current.to_string_lossy());
+			text.push('\n');
+		}
+		for path in self.paths.iter() {
+			text.push_str(&path.to_string_lossy());
+			text.push('\n');
+		}
+		fs::write(path, text)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::env;
+
+	use super::*;
+
+	#[test]
+	fn parse_skips_comments() {
+		let text =
+			"#EXTM3U\r\n\r\n#EMULSION-CURRENT:b.png\r\na.png\r\n b c.png \r\n#comment\nsub/d.png";
+		let playlist = Playlist::parse(text, Some(Path::new("base")));
+		let paths = ["base/a.png", "base/ b c.png ", "base/sub/d.png"];
+		assert_eq!(playlist.paths, paths.iter().map(PathBuf::from).collect::<Vec<_>>());
+		assert_eq!(playlist.current, Some(PathBuf::from("base/b.png")));
+
+		let playlist = Playlist::parse("/abs/a.png\n", Some(Path::new("base")));
+		assert_eq!(playlist.paths, vec![PathBuf::from("/abs/a.png")]);
+		assert_eq!(playlist.current, None);
+	}
+
+	#[test]
+	fn save_and_load() {
+		let dir = env::temp_dir().join(format!("emulsion-playlist-{}", std::process::id()));
+		fs::create_dir_all(&dir).unwrap();
+		let path = dir.join("list.txt");
+		let playlist = Playlist {
+			paths: vec![dir.join("a.png"), dir.join(" b.png")],
+			current: Some(dir.join(" b.png")),
+		};
+		playlist.save(&path).unwrap();
+		let loaded = Playlist::load(&path).unwrap();
+		fs::remove_dir_all(&dir).unwrap();
+		assert_eq!(loaded.paths, playlist.paths);
+		assert_eq!(loaded.current, playlist.current);
+	}
+}
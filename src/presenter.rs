@@ -0,0 +1,133 @@
+//! The presenter setup, started with `--presenter`.
+//!
+//! The images are presented as a fullscreen slideshow in a second window on an other monitor,
+//! while the main window shows the image that comes next and the time since the presentation
+//! started. The image and presentation actions of the main window turn the slides of the
+//! presentation, which in turn moves the main window to the image after the presented one.
+
+use std::{
+	cell::{Cell, RefCell},
+	rc::Rc,
+	sync::{Arc, Mutex},
+	time::{Duration, Instant},
+};
+
+use gelatin::{
+	application::Application,
+	label::Label,
+	picture::Picture,
+	renderer::RenderError,
+	window::{Window, WindowDescriptorBuilder},
+	NextUpdate,
+};
+
+use crate::{
+	cmd_line::StartupPaths,
+	configuration::{Cache, Configuration},
+	image_cache::SharedLoader,
+	widgets::{
+		bottom_bar::BottomBar, copy_notification::CopyNotifications, help_screen::HelpScreen,
+		picture_widget::PictureWidget,
+	},
+	EmulsionEvent, LEFT_TO_PAN,
+};
+
+/// Opens the presentation window and starts presenting `startup_paths` in it. `monitor` is the
+/// number of the monitor starting from 1, by default the first monitor that `control_window`
+/// isn't on is used. Returns the widget that shows the presentation.
+#[allow(clippy::too_many_arguments)]
+pub fn open(
+	application: &mut Application<EmulsionEvent>,
+	control_window: &Window,
+	control: &Rc<PictureWidget>,
+	monitor: Option<u32>,
+	startup_paths: &StartupPaths,
+	config: Rc<RefCell<Configuration>>,
+	cache: Arc<Mutex<Cache>>,
+	loader: Rc<SharedLoader>,
+) -> Result<Rc<PictureWidget>, RenderError> {
+	let (monitors, current_monitor) = {
+		let winit_window = control_window.window_mut();
+		(winit_window.available_monitors().collect::<Vec<_>>(), winit_window.current_monitor())
+	};
+	let monitor = match monitor {
+		Some(number) => crate::utils::monitor_by_number(control_window, number),
+		None => monitors.iter().find(|monitor| Some(*monitor) != current_monitor.as_ref()).cloned(),
+	};
+
+	let window_desc = WindowDescriptorBuilder::default()
+		.icon(Some(crate::make_icon()))
+		.position(monitor.as_ref().map(|monitor| monitor.position()))
+		.app_id(Some("Emulsion".into()))
+		.build()
+		.unwrap();
+	let window = Window::try_new(application, window_desc)?;
+	match monitor {
+		Some(monitor) => window.set_fullscreen_on(monitor),
+		None => window.set_fullscreen(true),
+	}
+	window.set_bg_color([0.0, 0.0, 0.0, 1.0]);
+
+	let left_to_pan_hint = Rc::new(HelpScreen::new(Picture::from_encoded_bytes(LEFT_TO_PAN)));
+	let copy_notifications_widget = Rc::new(Label::new());
+	let copy_notifications = CopyNotifications::new(&copy_notifications_widget);
+	// The bottom bar is never shown, the presentation only consists of the images
+	let bottom_bar = Rc::new(BottomBar::new(&config.borrow()));
+	let picture_area_container = crate::make_picture_area_container();
+	crate::add_fullscreen_insets(&window, picture_area_container.clone(), &config.borrow());
+	let audience = crate::make_picture_widget(
+		&window,
+		bottom_bar,
+		left_to_pan_hint.clone(),
+		copy_notifications,
+		config,
+		cache,
+		loader,
+	);
+	audience.set_bright_shade(0.11);
+	picture_area_container.add_child(audience.clone());
+	picture_area_container.add_child(copy_notifications_widget);
+	picture_area_container.add_child(left_to_pan_hint);
+	let root_container = crate::make_root_container();
+	root_container.add_child(picture_area_container);
+	window.set_root(root_container);
+
+	let is_last = Rc::new(Cell::new(false));
+	{
+		let control = control.clone();
+		let is_last = is_last.clone();
+		audience.set_on_image_changed(move |current| match (current.index, current.count) {
+			(Some(index), Some(count)) => {
+				is_last.set(index + 1 >= count);
+				if index + 1 < count {
+					control.jump_to_index(index as u32 + 1);
+				}
+			}
+			// The folder is still being read, the next image isn't known yet
+			_ => {
+				if let Some(path) = &current.path {
+					control.jump_to_path(path);
+				}
+			}
+		});
+	}
+	control.set_audience(audience.clone());
+
+	let started = Instant::now();
+	let mut shown_seconds = None;
+	let control = control.clone();
+	application.add_global_event_handler(move |_| {
+		let seconds = started.elapsed().as_secs();
+		if shown_seconds != Some(seconds) {
+			shown_seconds = Some(seconds);
+			let shown = if is_last.get() { "Last image" } else { "Up next" };
+			let elapsed = format!("{:02}:{:02}", seconds / 60, seconds % 60);
+			control.set_notice(Some(format!("{} : {} elapsed", shown, elapsed)));
+		}
+		NextUpdate::WaitUntil(started + Duration::from_secs(seconds + 1))
+	});
+
+	crate::open_startup_paths(&audience, startup_paths);
+	audience.toggle_presentation();
+	Ok(audience)
+}
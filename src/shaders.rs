@@ -1,2 +1,3 @@
 pub static VERTEX_140: &str = include_str!("shaders/vertex_140.glsl");
 pub static FRAGMENT_140: &str = include_str!("shaders/fragment_140.glsl");
+pub static COMPARE_140: &str = include_str!("shaders/compare_140.glsl");
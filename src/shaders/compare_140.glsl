@@ -0,0 +1,31 @@
+#version 140
+uniform sampler2D tex;
+uniform sampler2D tex_other;
+uniform float lod_level;
+// 0 blends the other image over the image with `opacity`, 1 shows the difference between them
+// multiplied by `gain`, 2 shows the multiplied difference as a heatmap
+uniform int mode;
+uniform float gain;
+uniform float opacity;
+uniform float bright_shade;
+in vec2 v_tex_coords;
+out vec4 f_color;
+void main() {
+    vec4 color = textureLod(tex, v_tex_coords, lod_level);
+    vec4 color_other = textureLod(tex_other, v_tex_coords, lod_level);
+    if (mode == 0) {
+        vec4 shown = mix(vec4(bright_shade), color, color.a);
+        f_color = mix(shown, vec4(color_other.rgb, 1.0), opacity * color_other.a);
+        return;
+    }
+    // Transparent areas are compared as if they were on black
+    vec3 diff = abs(color.rgb * color.a - color_other.rgb * color_other.a) * gain;
+    if (mode == 2) {
+        // From dark blue for no difference through cyan, green and yellow to red
+        float value = clamp(max(diff.r, max(diff.g, diff.b)), 0.0, 1.0);
+        vec3 jet = 1.5 - abs(4.0 * value - vec3(3.0, 2.0, 1.0));
+        f_color = vec4(clamp(jet, 0.0, 1.0), 1.0);
+    } else {
+        f_color = vec4(clamp(diff, 0.0, 1.0), 1.0);
+    }
+}
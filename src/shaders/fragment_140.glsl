@@ -2,13 +2,51 @@
 uniform sampler2D tex;
 uniform float bright_shade;
 uniform float lod_level;
+uniform bool checkerboard;
+// Set for zoomed out images with `downscaling = "high_quality"`. Four samples of the next finer
+// mipmap level are averaged over the screen pixel, which is sharper than sampling the coarser
+// level once and doesn't alias like sampling the finer level once.
+uniform bool supersample;
+// The adjustments of the view, see `Adjustments`. The brightness is added to the channels, the
+// contrast scales them around the middle gray.
+uniform float brightness;
+uniform float contrast;
+uniform float gamma;
+// The view filters, see `ViewFilter`. They are applied after the adjustments.
+uniform bool grayscale;
+uniform bool sepia;
+uniform bool invert;
 in vec2 v_tex_coords;
 out vec4 f_color;
 void main() {
-    vec4 color = textureLod(tex, v_tex_coords, lod_level);
+    vec4 color;
+    if (supersample) {
+        vec2 dx = dFdx(v_tex_coords) * 0.25;
+        vec2 dy = dFdy(v_tex_coords) * 0.25;
+        float lod = max(lod_level - 1.0, 0.0);
+        color = 0.25 * (
+            textureLod(tex, v_tex_coords - dx - dy, lod)
+            + textureLod(tex, v_tex_coords + dx - dy, lod)
+            + textureLod(tex, v_tex_coords - dx + dy, lod)
+            + textureLod(tex, v_tex_coords + dx + dy, lod)
+        );
+    } else {
+        color = textureLod(tex, v_tex_coords, lod_level);
+    }
+    vec3 rgb = pow(color.rgb, vec3(1.0 / gamma));
+    color.rgb = clamp((rgb - 0.5) * contrast + 0.5 + brightness, 0.0, 1.0);
+    if (grayscale || sepia) {
+        // The luma of Rec. 709, like `average_luminance`
+        float luma = dot(color.rgb, vec3(0.2126, 0.7152, 0.0722));
+        color.rgb = sepia ? min(luma * vec3(1.07, 0.87, 0.62) + 0.05, 1.0) : vec3(luma);
+    }
+    if (invert) {
+        color.rgb = 1.0 - color.rgb;
+    }
     const float grid_size = 12.0;
     vec4 grid_color;
-    if ((mod(gl_FragCoord.x, grid_size * 2.0) < grid_size)
+    if (!checkerboard
+        || (mod(gl_FragCoord.x, grid_size * 2.0) < grid_size)
         ^^ (mod(gl_FragCoord.y, grid_size * 2.0) < grid_size)
     ) {
         grid_color = vec4(bright_shade);
@@ -0,0 +1,146 @@
+//! Registering emulsion with the shell of Windows, for `--register-associations` and
+//! `--unregister-associations`, so that a portable copy or a build from source can be set up
+//! without the installer.
+//!
+//! This is only available when emulsion is built for Windows with the `shell-integration`
+//! feature. The keys are written for the current user with `reg.exe`, so no administrator rights
+//! are needed. Like the installer, this registers the `Emulsion.Generic` ProgID for the image
+//! extensions and lists emulsion in the Default Apps settings, where the user can make it the
+//! default. Additionally, an "Open with Emulsion" verb is added to the context menu of images and
+//! folders.
+
+use std::io;
+
+/// Registers the file associations and the context menu verb for the running executable
+#[cfg(all(windows, feature = "shell-integration"))]
+pub fn register() -> io::Result<()> {
+	use registry::*;
+
+	let exe = std::env::current_exe()?;
+	let exe = exe.to_string_lossy();
+	let command = format!("\"{}\" \"%1\"", exe);
+	let icon = format!("\"{}\",0", exe);
+
+	add(&prog_id_key(), None, "Image")?;
+	add(&format!("{}\\DefaultIcon", prog_id_key()), None, &icon)?;
+	add(&format!("{}\\shell\\open\\command", prog_id_key()), None, &command)?;
+	add(CAPABILITIES_KEY, Some("ApplicationName"), "Emulsion")?;
+	add(CAPABILITIES_KEY, Some("ApplicationDescription"), env!("CARGO_PKG_DESCRIPTION"))?;
+	let associations_key = format!("{}\\FileAssociations", CAPABILITIES_KEY);
+	for extension in EXTENSIONS {
+		add(&associations_key, Some(&format!(".{}", extension)), PROG_ID)?;
+		add(&open_with_key(extension), Some(PROG_ID), "")?;
+	}
+	add(REGISTERED_APPLICATIONS_KEY, Some("Emulsion"), CAPABILITIES_PATH)?;
+	for verb_key in verb_keys() {
+		add(&verb_key, None, "Open with Emulsion")?;
+		add(&verb_key, Some("Icon"), &icon)?;
+		add(&format!("{}\\command", verb_key), None, &command)?;
+	}
+	Ok(())
+}
+
+/// Removes what `register` added. The keys that are already missing are skipped.
+#[cfg(all(windows, feature = "shell-integration"))]
+pub fn unregister() -> io::Result<()> {
+	use registry::*;
+
+	delete(&prog_id_key(), None)?;
+	delete(CAPABILITIES_KEY, None)?;
+	delete(REGISTERED_APPLICATIONS_KEY, Some("Emulsion"))?;
+	for extension in EXTENSIONS {
+		delete(&open_with_key(extension), Some(PROG_ID))?;
+	}
+	for verb_key in verb_keys() {
+		delete(&verb_key, None)?;
+	}
+	Ok(())
+}
+
+#[cfg(not(all(windows, feature = "shell-integration")))]
+pub fn register() -> io::Result<()> {
+	Err(unsupported())
+}
+
+#[cfg(not(all(windows, feature = "shell-integration")))]
+pub fn unregister() -> io::Result<()> {
+	Err(unsupported())
+}
+
+#[cfg(not(all(windows, feature = "shell-integration")))]
+fn unsupported() -> io::Error {
+	io::Error::new(
+		io::ErrorKind::Unsupported,
+		"file associations can only be registered on Windows, when emulsion is built with the \
+		shell-integration feature",
+	)
+}
+
+#[cfg(all(windows, feature = "shell-integration"))]
+mod registry {
+	use std::{io, process::Command};
+
+	/// The extensions that emulsion is registered for, like in the installer
+	pub const EXTENSIONS: &[&str] = &[
+		"jpg", "jpeg", "png", "apng", "bmp", "gif", "tga", "avif", "webp", "tif", "tiff", "ico",
+		"hdr", "pbm", "pam", "ppm", "pgm",
+	];
+	pub const PROG_ID: &str = "Emulsion.Generic";
+	const CLASSES_KEY: &str = "HKCU\\Software\\Classes";
+	pub const CAPABILITIES_KEY: &str = "HKCU\\Software\\Emulsion\\Capabilities";
+	/// `CAPABILITIES_KEY` without the root key, as it's listed in `RegisteredApplications`
+	pub const CAPABILITIES_PATH: &str = "Software\\Emulsion\\Capabilities";
+	pub const REGISTERED_APPLICATIONS_KEY: &str = "HKCU\\Software\\RegisteredApplications";
+
+	pub fn prog_id_key() -> String {
+		format!("{}\\{}", CLASSES_KEY, PROG_ID)
+	}
+
+	/// The ProgIDs listed here are offered in the "Open with" menu of the extension
+	pub fn open_with_key(extension: &str) -> String {
+		format!("{}\\.{}\\OpenWithProgids", CLASSES_KEY, extension)
+	}
+
+	/// The keys of the "Open with Emulsion" verb, for every image regardless of the program that
+	/// opens it by default, and for folders
+	pub fn verb_keys() -> [String; 2] {
+		[
+			format!("{}\\SystemFileAssociations\\image\\shell\\Emulsion", CLASSES_KEY),
+			format!("{}\\Directory\\shell\\Emulsion", CLASSES_KEY),
+		]
+	}
+
+	/// Sets the value called `name` in `key`, or the default value of the key when `name` is
+	/// `None`. The key is created when it's missing.
+	pub fn add(key: &str, name: Option<&str>, data: &str) -> io::Result<()> {
+		let mut command = Command::new("reg");
+		command.args(["add", key]);
+		match name {
+			Some(name) => command.args(["/v", name]),
+			None => command.arg("/ve"),
+		};
+		let output = command.args(["/t", "REG_SZ", "/d", data, "/f"]).output()?;
+		if output.status.success() {
+			Ok(())
+		} else {
+			let stderr = String::from_utf8_lossy(&output.stderr);
+			Err(io::Error::other(format!("{}: {}", key, stderr.trim())))
+		}
+	}
+
+	/// Deletes the value called `name` in `key`, or the whole key when `name` is `None`
+	pub fn delete(key: &str, name: Option<&str>) -> io::Result<()> {
+		let mut command = Command::new("reg");
+		command.args(["delete", key]);
+		if let Some(name) = name {
+			command.args(["/v", name]);
+		}
+		// reg.exe fails when the key or the value doesn't exist, which isn't an error here
+		let output = command.arg("/f").output()?;
+		if !output.status.success() {
+			let stderr = String::from_utf8_lossy(&output.stderr);
+			log::debug!("Could not delete {}: {}", key, stderr.trim());
+		}
+		Ok(())
+	}
+}
@@ -0,0 +1,301 @@
+//! Star ratings and tags stored in XMP sidecar files.
+//!
+//! The metadata of `photo.jpg` is written to `photo.jpg.xmp`, the rating as `xmp:Rating` and the
+//! tags as the `dc:subject` keywords, which are understood by most photo managers. The image itself
+//! is never modified. When there's no sidecar, the metadata is read from the XMP packet embedded
+//! in the image, which is where cameras and some editors put it.
+
+use std::{
+	fs::{self, File},
+	io::{self, Read},
+	ops::Range,
+	path::{Path, PathBuf},
+};
+
+pub const MAX_RATING: u8 = 5;
+
+const RATING_PROPERTY: &str = "xmp:Rating";
+const XMP_NAMESPACE: &str = "xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\"";
+const DC_NAMESPACE: &str = "xmlns:dc=\"http://purl.org/dc/elements/1.1/\"";
+const DESCRIPTION_START: &str = "<rdf:Description";
+const SUBJECT_START: &str = "<dc:subject";
+const SUBJECT_END: &str = "</dc:subject>";
+const EMPTY_SIDECAR: &str = "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n \
+	<rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n  \
+	<rdf:Description rdf:about=\"\">\n  \
+	</rdf:Description>\n \
+	</rdf:RDF>\n\
+	</x:xmpmeta>\n";
+/// The embedded XMP packet is near the beginning of the file in the common formats
+const EMBEDDED_SEARCH_LEN: u64 = 128 * 1024;
+
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Metadata {
+	/// `None` if the image isn't rated
+	pub rating: Option<u8>,
+	pub tags: Vec<String>,
+}
+
+impl Metadata {
+	pub fn has_tag(&self, tag: &str) -> bool {
+		self.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))
+	}
+}
+
+/// Returns the existing sidecar of `path`, which is either `photo.jpg.xmp` or `photo.xmp`
+fn find_sidecar(path: &Path) -> Option<PathBuf> {
+	vec![new_sidecar_path(path), path.with_extension("xmp")].into_iter().find(|path| path.is_file())
+}
+
+fn new_sidecar_path(path: &Path) -> PathBuf {
+	let mut full_name = path.as_os_str().to_owned();
+	full_name.push(".xmp");
+	full_name.into()
+}
+
+/// The range of the value of `property` in `xmp`, which may be given as an attribute
+/// (`xmp:Rating="3"`) or as an element (`<xmp:Rating>3</xmp:Rating>`)
+fn find_value(xmp: &str, property: &str) -> Option<Range<usize>> {
+	let mut search_start = 0;
+	while let Some(found) = xmp[search_start..].find(property) {
+		let after_name = search_start + found + property.len();
+		let rest = &xmp[after_name..];
+		let (start, end) = if rest.starts_with("=\"") {
+			(after_name + 2, '"')
+		} else if rest.starts_with("='") {
+			(after_name + 2, '\'')
+		} else if rest.starts_with('>') {
+			(after_name + 1, '<')
+		} else {
+			search_start = after_name;
+			continue;
+		};
+		let len = xmp[start..].find(end)?;
+		return Some(start..start + len);
+	}
+	None
+}
+
+/// The range of the `dc:subject` element, including the start and the end tag
+fn find_subject(xmp: &str) -> Option<Range<usize>> {
+	let start = xmp.find(SUBJECT_START)?;
+	let end = xmp[start..].find(SUBJECT_END)? + start + SUBJECT_END.len();
+	Some(start..end)
+}
+
+fn parse_rating(xmp: &str) -> Option<u8> {
+	let value = &xmp[find_value(xmp, RATING_PROPERTY)?];
+	// -1 means that the image was rejected, which is treated like not being rated
+	let rating = value.trim().parse::<i32>().ok()?;
+	Some(rating.clamp(0, MAX_RATING as i32) as u8)
+}
+
+fn parse_tags(xmp: &str) -> Vec<String> {
+	let subject = match find_subject(xmp) {
+		Some(range) => &xmp[range],
+		None => return Vec::new(),
+	};
+	subject
+		.split("<rdf:li")
+		.skip(1)
+		.filter_map(|item| {
+			let start = item.find('>')? + 1;
+			let end = item.find("</rdf:li>")?;
+			Some(unescape(item.get(start..end)?.trim()))
+		})
+		.filter(|tag| !tag.is_empty())
+		.collect()
+}
+
+fn escape(text: &str) -> String {
+	text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+fn unescape(text: &str) -> String {
+	text.replace("&lt;", "<")
+		.replace("&gt;", ">")
+		.replace("&quot;", "\"")
+		.replace("&apos;", "'")
+		.replace("&amp;", "&")
+}
+
+/// Returns the XMP of the image at `path` from its sidecar or from the image itself
+fn read_xmp(path: &Path) -> Option<String> {
+	if let Some(sidecar) = find_sidecar(path) {
+		return fs::read_to_string(sidecar).ok();
+	}
+	let mut bytes = Vec::new();
+	File::open(path).ok()?.take(EMBEDDED_SEARCH_LEN).read_to_end(&mut bytes).ok()?;
+	Some(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Returns the rating and the tags of the image at `path`
+pub fn read(path: &Path) -> Metadata {
+	match read_xmp(path) {
+		Some(xmp) => Metadata { rating: parse_rating(&xmp), tags: parse_tags(&xmp) },
+		None => Metadata::default(),
+	}
+}
+
+/// Changes the sidecar of the image at `path` with `update`, or creates it. An existing sidecar is
+/// updated in place, so that the metadata written by other programs is kept.
+fn update_sidecar<F>(path: &Path, update: F) -> io::Result<()>
+where
+	F: FnOnce(&mut String, usize),
+{
+	let (sidecar, mut xmp) = match find_sidecar(path) {
+		Some(sidecar) => {
+			let xmp = fs::read_to_string(&sidecar)?;
+			(sidecar, xmp)
+		}
+		None => (new_sidecar_path(path), EMPTY_SIDECAR.to_owned()),
+	};
+	let description = xmp.find(DESCRIPTION_START).ok_or_else(|| {
+		io::Error::new(io::ErrorKind::InvalidData, format!("{:?} is not an XMP file", sidecar))
+	})?;
+	update(&mut xmp, description);
+	fs::write(sidecar, xmp)
+}
+
+/// Declares the namespace as an attribute of the description that starts at `description`
+fn add_namespace(xmp: &mut String, description: usize, namespace: &str) {
+	let prefix = &namespace[..namespace.find('=').unwrap_or(namespace.len())];
+	if !xmp.contains(prefix) {
+		xmp.insert_str(description + DESCRIPTION_START.len(), &format!(" {}", namespace));
+	}
+}
+
+/// Writes `rating` to the sidecar of the image at `path`
+pub fn write_rating(path: &Path, rating: u8) -> io::Result<()> {
+	let rating = rating.min(MAX_RATING).to_string();
+	update_sidecar(path, |xmp, description| match find_value(xmp, RATING_PROPERTY) {
+		Some(range) => xmp.replace_range(range, &rating),
+		None => {
+			let attribute = format!(" {}=\"{}\"", RATING_PROPERTY, rating);
+			xmp.insert_str(description + DESCRIPTION_START.len(), &attribute);
+			add_namespace(xmp, description, XMP_NAMESPACE);
+		}
+	})
+}
+
+/// Replaces the tags in the sidecar of the image at `path` with `tags`
+pub fn write_tags(path: &Path, tags: &[String]) -> io::Result<()> {
+	update_sidecar(path, |xmp, description| {
+		if let Some(range) = find_subject(xmp) {
+			// The indentation of the subject is removed too
+			let start = xmp[..range.start].trim_end().len();
+			xmp.replace_range(start..range.end, "");
+		}
+		if tags.is_empty() {
+			return;
+		}
+		let mut subject = String::from("\n   <dc:subject>\n    <rdf:Bag>\n");
+		for tag in tags {
+			subject.push_str(&format!("     <rdf:li>{}</rdf:li>\n", escape(tag)));
+		}
+		subject.push_str("    </rdf:Bag>\n   </dc:subject>");
+		add_namespace(xmp, description, DC_NAMESPACE);
+		let tag_end = match xmp[description..].find('>') {
+			Some(end) => description + end,
+			None => return,
+		};
+		if xmp[..tag_end].ends_with('/') {
+			// The description is an empty element, which needs an end tag for the subject
+			xmp.replace_range(
+				tag_end - 1..tag_end + 1,
+				&format!(">{}\n  </rdf:Description>", subject),
+			);
+		} else {
+			xmp.insert_str(tag_end + 1, &subject);
+		}
+	})
+}
+
+/// Splits a comma separated list of tags. Empty and repeated tags are left out.
+pub fn parse_tag_list(list: &str) -> Vec<String> {
+	let mut tags: Vec<String> = Vec::new();
+	for tag in list.split(',').map(str::trim).filter(|tag| !tag.is_empty()) {
+		if !tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+			tags.push(tag.to_owned());
+		}
+	}
+	tags
+}
+
+/// The rating as stars, like "★★★☆☆"
+pub fn format_rating(rating: u8) -> String {
+	let rating = rating.min(MAX_RATING) as usize;
+	format!("{}{}", "★".repeat(rating), "☆".repeat(MAX_RATING as usize - rating))
+}
+
+#[cfg(test)]
+mod tests {
+	use std::env;
+
+	use super::*;
+
+	#[test]
+	fn parse_attributes_and_elements() {
+		let xmp = "<rdf:Description xmp:Rating=\"4\"/>";
+		assert_eq!(parse_rating(xmp), Some(4));
+		let xmp = "<rdf:Description xmp:RatingPercent='80'><xmp:Rating>-1</xmp:Rating>";
+		assert_eq!(parse_rating(xmp), Some(0));
+		assert_eq!(parse_rating("<xmp:Rating>9</xmp:Rating>"), Some(MAX_RATING));
+		assert_eq!(parse_rating(EMPTY_SIDECAR), None);
+
+		let xmp = "<dc:subject><rdf:Bag><rdf:li>cats</rdf:li><rdf:li xml:lang='en'> a &amp; b \
+			</rdf:li><rdf:li></rdf:li></rdf:Bag></dc:subject>";
+		assert_eq!(parse_tags(xmp), vec!["cats".to_owned(), "a & b".to_owned()]);
+		assert!(parse_tags(EMPTY_SIDECAR).is_empty());
+	}
+
+	#[test]
+	fn write_and_read() {
+		let dir = env::temp_dir().join(format!("emulsion-sidecar-{}", std::process::id()));
+		fs::create_dir_all(&dir).unwrap();
+		let image = dir.join("photo.jpg");
+		fs::write(&image, b"not an image").unwrap();
+		assert_eq!(read(&image), Metadata::default());
+
+		write_rating(&image, 3).unwrap();
+		write_tags(&image, &["b<c".to_owned(), "d".to_owned()]).unwrap();
+		write_rating(&image, 7).unwrap();
+		let metadata = read(&image);
+		assert_eq!(metadata.rating, Some(MAX_RATING));
+		assert_eq!(metadata.tags, vec!["b<c".to_owned(), "d".to_owned()]);
+		assert!(metadata.has_tag("D"));
+
+		write_tags(&image, &[]).unwrap();
+		let xmp = fs::read_to_string(dir.join("photo.jpg.xmp")).unwrap();
+		assert!(!xmp.contains(SUBJECT_START));
+		assert_eq!(read(&image), Metadata { rating: Some(MAX_RATING), tags: Vec::new() });
+		fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	fn keeps_other_metadata() {
+		let dir = env::temp_dir().join(format!("emulsion-sidecar-other-{}", std::process::id()));
+		fs::create_dir_all(&dir).unwrap();
+		let image = dir.join("photo.jpg");
+		let sidecar = dir.join("photo.xmp");
+		fs::write(
+			&sidecar,
+			"<x:xmpmeta><rdf:RDF><rdf:Description rdf:about=\"\" tiff:Make=\"Cam\"/></rdf:RDF></x:xmpmeta>",
+		)
+		.unwrap();
+		write_tags(&image, &["tag".to_owned()]).unwrap();
+		write_rating(&image, 2).unwrap();
+		let xmp = fs::read_to_string(&sidecar).unwrap();
+		assert!(xmp.contains("tiff:Make=\"Cam\""));
+		assert!(xmp.contains(XMP_NAMESPACE) && xmp.contains(DC_NAMESPACE));
+		assert!(!dir.join("photo.jpg.xmp").exists());
+		assert_eq!(read(&image), Metadata { rating: Some(2), tags: vec!["tag".to_owned()] });
+		fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	fn tag_list() {
+		assert_eq!(parse_tag_list(" a, ,B,b ,c"), vec!["a", "B", "c"]);
+		assert_eq!(format_rating(2), "★★☆☆☆");
+	}
+}
@@ -0,0 +1,376 @@
+//! A bare bones viewer that renders on the CPU.
+//!
+//! This is what runs when emulsion is started with `--software` or when no OpenGL context could
+//! be created (virtual machines, remote desktops, old drivers). It only supports browsing a
+//! folder: the picture fitted to the window, a bottom bar showing the position within the folder
+//! and the basic navigation bindings. Images are loaded on the main thread.
+
+use std::{
+	cell::RefCell,
+	path::{Path, PathBuf},
+	rc::Rc,
+	sync::{Arc, Mutex},
+};
+
+use gelatin::{
+	application::Application,
+	cgmath::{ortho, Matrix4, Vector3},
+	image::RgbaImage,
+	renderer::{BlendMode, RenderError, Renderer, SoftwareRenderer, SoftwareTexture},
+	window::WindowDescriptor,
+	winit::{
+		event::{ElementState, Event, WindowEvent},
+		event_loop::EventLoopWindowTarget,
+		keyboard::ModifiersState,
+		platform::modifier_supplement::KeyEventExtModifierSupplement,
+		window::{Fullscreen, Window},
+	},
+};
+
+use crate::{
+	cmd_line::StartupPaths,
+	configuration::{Cache, Configuration, Theme},
+	image_cache::{
+		directory::Directory,
+		image_loader::{complex_load_image, LoadResult},
+		pixel_ops,
+	},
+	input_handling::*,
+	playback_manager::LoadedImgPath,
+	playlist::Playlist,
+	system_theme,
+	utils::virtual_keycode_to_string,
+	EmulsionEvent,
+};
+
+/// In logical pixels
+const BOTTOM_BAR_HEIGHT: f32 = 32.0;
+/// In logical pixels
+const SLIDER_MARGIN: f32 = 16.0;
+/// In logical pixels
+const SLIDER_THICKNESS: f32 = 4.0;
+
+struct LoadedImage {
+	/// Either `Loaded`, `ErrLoading` (in which case there's no texture) or `InMemory`
+	source: LoadedImgPath,
+	texture: Option<SoftwareTexture>,
+}
+
+struct SoftwareViewer {
+	window: Rc<Window>,
+	renderer: SoftwareRenderer,
+	config: Rc<RefCell<Configuration>>,
+	cache: Arc<Mutex<Cache>>,
+	theme: Theme,
+	show_bottom_bar: bool,
+	fullscreen: bool,
+	modifiers: ModifiersState,
+	key_history: KeyHistory,
+	directory: Directory,
+	image: Option<LoadedImage>,
+}
+
+impl SoftwareViewer {
+	fn open(&mut self, path: &Path) {
+		let folder = path.parent().unwrap_or_else(|| Path::new("."));
+		if let Some(filename) = path.file_name() {
+			if let Err(e) = self.directory.change_directory_with_filename(folder, filename) {
+				log::error!("{}", e);
+			}
+		}
+		self.load(path.to_owned());
+	}
+
+	fn open_playlist(&mut self, playlist: Playlist) {
+		if let Err(e) = self.directory.set_file_list(playlist.paths) {
+			log::error!("{}", e);
+		}
+		if let Some(current) = playlist.current.and_then(|path| path.canonicalize().ok()) {
+			self.directory.select_file(&current);
+		}
+		if let Some(desc) = self.directory.curr_descriptor() {
+			let path = desc.path.clone();
+			self.load(path);
+		}
+	}
+
+	fn jump(&mut self, forward: bool) {
+		if forward {
+			self.directory.jump_to_next();
+		} else {
+			self.directory.jump_to_prev();
+		}
+		if let Some(desc) = self.directory.curr_descriptor() {
+			let path = desc.path.clone();
+			self.load(path);
+		}
+	}
+
+	fn load(&mut self, path: PathBuf) {
+		let mut frame = None;
+		let result = complex_load_image(&path, false, 0, |result| {
+			if let LoadResult::Frame { image, orientation, .. } = result {
+				frame = Some(pixel_ops::bake_orientation(image, orientation));
+			}
+			Ok(())
+		});
+		if let Err(e) = result {
+			log::error!("Failed to load {:?}: {}", path, e);
+		}
+		let texture = frame.and_then(|frame| self.create_texture(&frame));
+		let source = match texture {
+			Some(_) => LoadedImgPath::Loaded(path),
+			None => LoadedImgPath::ErrLoading(path),
+		};
+		self.image = Some(LoadedImage { source, texture });
+		self.update_title();
+		self.window.request_redraw();
+	}
+
+	fn show_memory_image(&mut self, image: RgbaImage, name: String) {
+		let texture = self.create_texture(&image);
+		self.image = Some(LoadedImage { source: LoadedImgPath::InMemory(name), texture });
+		self.update_title();
+		self.window.request_redraw();
+	}
+
+	fn create_texture(&mut self, image: &RgbaImage) -> Option<SoftwareTexture> {
+		match self.renderer.create_texture(image) {
+			Ok(texture) => Some(texture),
+			Err(e) => {
+				log::error!("{}", e);
+				None
+			}
+		}
+	}
+
+	fn update_title(&self) {
+		let config = self.config.borrow();
+		let title_config = config.title.clone().unwrap_or_default();
+		let source = self.image.as_ref().map(|image| &image.source);
+		let name = match source.unwrap_or(&LoadedImgPath::NotYetLoaded) {
+			LoadedImgPath::NotYetLoaded => "[ none ]".into(),
+			LoadedImgPath::ErrLoading(path) => {
+				format!("[ FAILED TO OPEN ] {}", title_config.format_file_path(path)).into()
+			}
+			LoadedImgPath::Loaded(path) => title_config.format_file_path(path),
+			LoadedImgPath::InMemory(name) => format!("{} (unsaved)", name).into(),
+			LoadedImgPath::Removed(path) => {
+				format!("[ FILE REMOVED ] {}", title_config.format_file_path(path)).into()
+			}
+		};
+		let title = format!(
+			"{}{} : Software rendering{}",
+			config.format_parse_error(),
+			name,
+			title_config.format_program_name()
+		);
+		self.window.set_title(&title);
+	}
+
+	fn set_fullscreen(&mut self, fullscreen: bool) {
+		self.fullscreen = fullscreen;
+		let monitor = if fullscreen {
+			Some(Fullscreen::Borderless(self.window.current_monitor()))
+		} else {
+			None
+		};
+		self.window.set_fullscreen(monitor);
+	}
+
+	fn handle_key_input<T>(&mut self, input_key: &str, event_loop: &EventLoopWindowTarget<T>) {
+		let modifiers = self.modifiers;
+		macro_rules! triggered {
+			($action_name:ident) => {
+				action_triggered(
+					&self.config,
+					$action_name,
+					input_key,
+					modifiers,
+					&self.key_history,
+				)
+			};
+		}
+		if triggered!(TOGGLE_FULLSCREEN_NAME) {
+			self.set_fullscreen(!self.fullscreen);
+		}
+		if triggered!(ESCAPE_NAME) {
+			if self.fullscreen {
+				self.set_fullscreen(false);
+			} else {
+				event_loop.exit();
+			}
+		}
+		if triggered!(IMG_PREV_NAME) {
+			self.jump(false);
+		}
+		if triggered!(IMG_NEXT_NAME) {
+			self.jump(true);
+		}
+		if triggered!(EDIT_CONFIG_NAME) {
+			self.config.borrow().open_in_editor();
+		}
+	}
+
+	fn draw(&mut self) {
+		let (bg_color, bar_color) = match self.theme {
+			Theme::Light => ([0.85, 0.85, 0.85, 1.0], [1.0, 1.0, 1.0, 1.0]),
+			Theme::Dark => ([0.01, 0.01, 0.01, 1.0], [0.08, 0.08, 0.08, 1.0]),
+		};
+		let dpi_scale = self.window.scale_factor() as f32;
+		let size = self.renderer.size();
+		let (width, height) = (size.width as f32, size.height as f32);
+		let bar_height = if self.show_bottom_bar && !self.fullscreen {
+			(BOTTOM_BAR_HEIGHT * dpi_scale).round()
+		} else {
+			0.0
+		};
+		let picture_height = height - bar_height;
+
+		self.renderer.begin_frame(bg_color);
+		if let Some(texture) = self.image.as_ref().and_then(|image| image.texture.as_ref()) {
+			// Only shrink the image, just like the "fit best" scaling mode does
+			let (img_w, img_h) = texture.dimensions();
+			let (img_w, img_h) = (img_w as f32, img_h as f32);
+			let scale = (width / img_w).min(picture_height / img_h).min(1.0);
+			let (draw_w, draw_h) = ((img_w * scale).round(), (img_h * scale).round());
+			let x = ((width - draw_w) * 0.5).round();
+			let y = ((picture_height - draw_h) * 0.5).round();
+			let transform = rect_transform(width, height, x, y, draw_w, draw_h);
+			if let Err(e) = self.renderer.draw_texture(transform, texture) {
+				log::error!("{}", e);
+			}
+		}
+		if bar_height > 0.0 {
+			let bar = rect_transform(width, height, 0.0, picture_height, width, bar_height);
+			let _ = self.renderer.draw_color(bar, bar_color, BlendMode::Alpha);
+			let index = self.directory.curr_img_index();
+			let count = self.directory.image_count();
+			if let (Some(index), Some(count)) = (index, count) {
+				let margin = SLIDER_MARGIN * dpi_scale;
+				let thickness = (SLIDER_THICKNESS * dpi_scale).round();
+				let track_w = width - 2.0 * margin;
+				let track_y = (picture_height + (bar_height - thickness) * 0.5).round();
+				let filled_w = track_w * (index as f32 + 1.0) / count.max(1) as f32;
+				let track = rect_transform(width, height, margin, track_y, track_w, thickness);
+				let filled = rect_transform(width, height, margin, track_y, filled_w, thickness);
+				let _ = self.renderer.draw_color(track, [0.5, 0.5, 0.5, 0.3], BlendMode::Alpha);
+				let _ = self.renderer.draw_color(filled, [0.4, 0.4, 0.4, 1.0], BlendMode::Alpha);
+			}
+		}
+		if let Err(e) = self.renderer.end_frame() {
+			log::error!("{}", e);
+		}
+	}
+}
+
+/// Returns the transform that takes the unit quad to the given rectangle of a `frame_w` x
+/// `frame_h` frame. All values are in physical pixels.
+fn rect_transform(frame_w: f32, frame_h: f32, x: f32, y: f32, w: f32, h: f32) -> Matrix4<f32> {
+	let projection = ortho(0.0, frame_w, frame_h, 0.0, -1.0, 1.0);
+	let translation = Matrix4::from_translation(Vector3::new(x, y, 0.0));
+	projection * translation * Matrix4::from_nonuniform_scale(w, h, 1.0)
+}
+
+/// Opens a window that is rendered on the CPU and runs the event loop until the window is closed.
+/// `at_exit` is called right before the event loop exits. Fails when the window or the surface
+/// that it's rendered to can't be created.
+pub fn run<F: FnOnce() + 'static>(
+	application: Application<EmulsionEvent>,
+	window_desc: WindowDescriptor,
+	config: Rc<RefCell<Configuration>>,
+	cache: Arc<Mutex<Cache>>,
+	startup_paths: StartupPaths,
+	startup_image: Option<(RgbaImage, String)>,
+	at_exit: F,
+) -> Result<(), RenderError> {
+	let event_loop = application.event_loop;
+	// The software viewer has no drag area, so it keeps the decorations even when `borderless`
+	let window_builder = window_desc.window_builder(&event_loop).with_decorations(true);
+	let window = window_builder
+		.build(&event_loop)
+		.map_err(|e| RenderError { description: format!("failed to create the window: {}", e) })?;
+	let window = Rc::new(window);
+	let renderer = SoftwareRenderer::new(window.clone())?;
+
+	let (theme, show_bottom_bar, start_fullscreen) = {
+		let config = config.borrow();
+		let window_cfg = config.window.as_ref();
+		let theme = system_theme::startup_theme(&config, &cache.lock().unwrap());
+		if system_theme::follows_system(&config) {
+			system_theme::watch(event_loop.create_proxy());
+		}
+		let show_bottom_bar = window_cfg.and_then(|w| w.show_bottom_bar).unwrap_or(true);
+		let start_fullscreen = window_cfg.and_then(|w| w.start_fullscreen).unwrap_or(false);
+		(theme, show_bottom_bar, start_fullscreen)
+	};
+
+	let mut viewer = SoftwareViewer {
+		window,
+		renderer,
+		config,
+		cache,
+		theme,
+		show_bottom_bar,
+		fullscreen: false,
+		modifiers: ModifiersState::empty(),
+		key_history: Default::default(),
+		directory: Directory::new(),
+		image: None,
+	};
+	viewer.set_fullscreen(start_fullscreen);
+	viewer.update_title();
+	match startup_paths {
+		StartupPaths::None => (),
+		StartupPaths::Single(file_path) => viewer.open(Path::new(&file_path)),
+		StartupPaths::List(playlist) => viewer.open_playlist(playlist),
+	}
+	if let Some((image, name)) = startup_image {
+		viewer.show_memory_image(image, name);
+	}
+
+	let mut at_exit = Some(at_exit);
+	event_loop
+		.run(move |event, event_loop| match event {
+			Event::WindowEvent { event, .. } => match event {
+				WindowEvent::RedrawRequested => viewer.draw(),
+				WindowEvent::CloseRequested => event_loop.exit(),
+				WindowEvent::Resized(size) => {
+					viewer.renderer.resize(size);
+					let mut cache = viewer.cache.lock().unwrap();
+					cache.window.win_w = size.width;
+					cache.window.win_h = size.height;
+					cache.window.maximized = viewer.window.is_maximized();
+					viewer.window.request_redraw();
+				}
+				WindowEvent::Moved(pos) => {
+					let mut cache = viewer.cache.lock().unwrap();
+					cache.window.win_x = pos.x;
+					cache.window.win_y = pos.y;
+				}
+				WindowEvent::ModifiersChanged(modifiers) => viewer.modifiers = modifiers.state(),
+				WindowEvent::KeyboardInput { event, .. }
+					if event.state == ElementState::Pressed =>
+				{
+					let key = event.key_without_modifiers();
+					let input_key_str = virtual_keycode_to_string(&key).to_lowercase();
+					let input_key_str = char_to_input_key(&input_key_str);
+					viewer.key_history.push(&input_key_str, viewer.modifiers);
+					viewer.handle_key_input(input_key_str.as_str(), event_loop);
+				}
+				WindowEvent::DroppedFile(path) => viewer.open(&path),
+				_ => (),
+			},
+			Event::UserEvent(EmulsionEvent::SystemThemeChanged(theme)) => {
+				viewer.theme = theme;
+				viewer.window.request_redraw();
+			}
+			Event::LoopExiting => {
+				if let Some(at_exit) = at_exit.take() {
+					at_exit();
+				}
+			}
+			_ => (),
+		})
+		.map_err(|e| RenderError { description: format!("failed to run the event loop: {}", e) })
+}
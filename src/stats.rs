@@ -0,0 +1,129 @@
+//! Usage statistics that never leave the machine.
+//!
+//! Collecting them is opt-in with `enabled = true` in the `[stats]` section of the config. They
+//! are stored in the `[stats]` section of the cache and the `show_stats` action opens a summary.
+
+use std::{
+	collections::BTreeMap,
+	fmt::Write as _,
+	fs, io,
+	path::{Path, PathBuf},
+	time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::configuration::Configuration;
+
+/// Only the most viewed folders are remembered so that the cache file stays small
+const MAX_FOLDERS: usize = 500;
+/// The number of folders listed in the summary
+const LISTED_FOLDERS: usize = 20;
+
+#[derive(Debug, Default, Eq, PartialEq, Clone, Deserialize)]
+pub struct ConfigStatsSection {
+	pub enabled: Option<bool>,
+}
+
+#[derive(Debug, Default, Eq, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CacheStatsSection {
+	/// Seconds since the Unix epoch when the first statistic was recorded, 0 if none was
+	pub since: u64,
+	pub images_viewed: u64,
+	pub sessions: u64,
+	pub total_session_secs: u64,
+	pub longest_session_secs: u64,
+	/// The number of images viewed in each folder
+	pub folders: BTreeMap<String, u64>,
+}
+
+impl CacheStatsSection {
+	pub fn record_view(&mut self, path: &Path) {
+		self.start_if_needed();
+		self.images_viewed += 1;
+		let folder = match path.parent() {
+			Some(folder) => folder.to_string_lossy().into_owned(),
+			None => return,
+		};
+		*self.folders.entry(folder).or_insert(0) += 1;
+		if self.folders.len() > MAX_FOLDERS {
+			let least_viewed = self.folders.iter().min_by_key(|(_, count)| **count);
+			if let Some(least_viewed) = least_viewed.map(|(folder, _)| folder.clone()) {
+				self.folders.remove(&least_viewed);
+			}
+		}
+	}
+
+	pub fn record_session(&mut self, duration: Duration) {
+		self.start_if_needed();
+		let secs = duration.as_secs();
+		self.sessions += 1;
+		self.total_session_secs += secs;
+		self.longest_session_secs = self.longest_session_secs.max(secs);
+	}
+
+	fn start_if_needed(&mut self) {
+		if self.since == 0 {
+			self.since = now_secs();
+		}
+	}
+
+	/// A plain text summary of the statistics
+	pub fn summary(&self) -> String {
+		let mut summary = String::from("Emulsion usage statistics\n\n");
+		if self.since == 0 {
+			summary.push_str("Nothing has been recorded yet.\n");
+			return summary;
+		}
+		let days = now_secs().saturating_sub(self.since) / (60 * 60 * 24) + 1;
+		let average_secs = self.total_session_secs.checked_div(self.sessions).unwrap_or(0);
+		let _ = writeln!(summary, "Recorded over {} day(s)", days);
+		let _ = writeln!(summary, "Images viewed: {}", self.images_viewed);
+		let _ = writeln!(summary, "Sessions: {}", self.sessions);
+		let _ = writeln!(summary, "Total time: {}", format_duration(self.total_session_secs));
+		let _ = writeln!(summary, "Average session: {}", format_duration(average_secs));
+		let _ =
+			writeln!(summary, "Longest session: {}", format_duration(self.longest_session_secs));
+
+		let mut folders = self.folders.iter().collect::<Vec<_>>();
+		folders.sort_by(|(_, a), (_, b)| b.cmp(a));
+		if !folders.is_empty() {
+			summary.push_str("\nMost viewed folders:\n");
+		}
+		for (folder, count) in folders.into_iter().take(LISTED_FOLDERS) {
+			let _ = writeln!(summary, "{:>8}  {}", count, folder);
+		}
+		summary
+	}
+}
+
+pub fn enabled(config: &Configuration) -> bool {
+	matches!(&config.stats, Some(ConfigStatsSection { enabled: Some(true) }))
+}
+
+/// Writes the summary to `stats.txt` next to the cache file and opens it with the default
+/// program. The temp folder isn't used, because it's shared with the other users.
+pub fn show(stats: &CacheStatsSection) -> io::Result<PathBuf> {
+	let (_, cache_path) = crate::get_config_and_cache_paths();
+	let path = cache_path.with_file_name("stats.txt");
+	if let Some(folder) = path.parent() {
+		fs::create_dir_all(folder)?;
+	}
+	fs::write(&path, stats.summary())?;
+	open::that(&path)?;
+	Ok(path)
+}
+
+fn format_duration(secs: u64) -> String {
+	let (hours, minutes) = (secs / 3600, secs / 60 % 60);
+	if hours > 0 {
+		format!("{}h {}m", hours, minutes)
+	} else {
+		format!("{}m {}s", minutes, secs % 60)
+	}
+}
+
+fn now_secs() -> u64 {
+	SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
@@ -0,0 +1,63 @@
+//! Following the light or dark appearance of the operating system.
+//!
+//! Not every platform notifies applications when the appearance changes, so instead the
+//! appearance is queried periodically on a background thread.
+
+use std::{thread, time::Duration};
+
+use gelatin::winit::event_loop::EventLoopProxy;
+
+use crate::{
+	configuration::{Cache, Configuration, Theme, ThemeSetting},
+	EmulsionEvent,
+};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Returns the appearance of the operating system or `None` if it can't be determined.
+pub fn detect() -> Option<Theme> {
+	match dark_light::detect() {
+		dark_light::Mode::Dark => Some(Theme::Dark),
+		dark_light::Mode::Light => Some(Theme::Light),
+		dark_light::Mode::Default => None,
+	}
+}
+
+/// Returns true if the theme should follow the operating system
+pub fn follows_system(config: &Configuration) -> bool {
+	config.theme_setting() == Some(ThemeSetting::Auto)
+}
+
+/// Returns the theme to start up with. When the config doesn't specify a theme, or when it's set to
+/// `auto` but the appearance of the operating system can't be determined, this is the theme that
+/// was selected last time.
+pub fn startup_theme(config: &Configuration, cache: &Cache) -> Theme {
+	match config.theme_setting() {
+		Some(ThemeSetting::Light) => Theme::Light,
+		Some(ThemeSetting::Dark) => Theme::Dark,
+		Some(ThemeSetting::Auto) => detect().unwrap_or_else(|| cache.theme()),
+		None => cache.theme(),
+	}
+}
+
+/// Starts a background thread that sends `EmulsionEvent::SystemThemeChanged` through `proxy`
+/// whenever the appearance of the operating system changes. The thread stops when the event loop
+/// is gone.
+pub fn watch(proxy: EventLoopProxy<EmulsionEvent>) {
+	thread::spawn(move || {
+		let mut prev = detect();
+		loop {
+			thread::sleep(POLL_INTERVAL);
+			let curr = detect();
+			if curr == prev {
+				continue;
+			}
+			prev = curr;
+			if let Some(theme) = curr {
+				if proxy.send_event(EmulsionEvent::SystemThemeChanged(theme)).is_err() {
+					break;
+				}
+			}
+		}
+	});
+}
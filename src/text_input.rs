@@ -0,0 +1,88 @@
+//! Editing a single line of text with the keyboard, like the new name of a file. Emulsion doesn't
+//! render text, so the edited text is shown in the window title.
+
+use gelatin::winit::{
+	event::KeyEvent,
+	keyboard::{Key, NamedKey},
+};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TextInputEvent {
+	Edited,
+	/// Enter was pressed
+	Confirmed,
+	/// Escape was pressed
+	Cancelled,
+	Ignored,
+}
+
+#[derive(Debug, Clone)]
+pub struct TextInput {
+	text: String,
+	/// A byte index into `text`, always at a character boundary
+	cursor: usize,
+}
+
+impl TextInput {
+	/// The cursor is placed at the `cursor` byte index, or at the end if that's not a character
+	/// boundary
+	pub fn new(text: String, cursor: usize) -> TextInput {
+		let cursor = if text.is_char_boundary(cursor) { cursor } else { text.len() };
+		TextInput { text, cursor }
+	}
+
+	pub fn text(&self) -> &str {
+		&self.text
+	}
+
+	/// The text with a `|` at the cursor
+	pub fn display(&self) -> String {
+		format!("{}|{}", &self.text[..self.cursor], &self.text[self.cursor..])
+	}
+
+	/// Edits the text according to a key press
+	pub fn handle_key(&mut self, input: &KeyEvent) -> TextInputEvent {
+		match &input.logical_key {
+			Key::Named(NamedKey::Enter) => return TextInputEvent::Confirmed,
+			Key::Named(NamedKey::Escape) => return TextInputEvent::Cancelled,
+			Key::Named(NamedKey::Backspace) => {
+				if let Some(prev) = self.prev_boundary() {
+					self.text.replace_range(prev..self.cursor, "");
+					self.cursor = prev;
+				}
+			}
+			Key::Named(NamedKey::Delete) => {
+				if let Some(next) = self.next_boundary() {
+					self.text.replace_range(self.cursor..next, "");
+				}
+			}
+			Key::Named(NamedKey::ArrowLeft) => self.cursor = self.prev_boundary().unwrap_or(0),
+			Key::Named(NamedKey::ArrowRight) => {
+				self.cursor = self.next_boundary().unwrap_or(self.text.len())
+			}
+			Key::Named(NamedKey::Home) => self.cursor = 0,
+			Key::Named(NamedKey::End) => self.cursor = self.text.len(),
+			_ => {
+				let text = match &input.text {
+					Some(text) => text.chars().filter(|c| !c.is_control()).collect::<String>(),
+					None => return TextInputEvent::Ignored,
+				};
+				if text.is_empty() {
+					return TextInputEvent::Ignored;
+				}
+				self.text.insert_str(self.cursor, &text);
+				self.cursor += text.len();
+			}
+		}
+		TextInputEvent::Edited
+	}
+
+	fn prev_boundary(&self) -> Option<usize> {
+		self.text[..self.cursor].char_indices().next_back().map(|(index, _)| index)
+	}
+
+	fn next_boundary(&self) -> Option<usize> {
+		let c = self.text[self.cursor..].chars().next()?;
+		Some(self.cursor + c.len_utf8())
+	}
+}
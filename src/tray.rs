@@ -0,0 +1,276 @@
+//! An icon in the system tray on Linux, with a menu for opening images, pausing the slideshow and
+//! quitting. Clicking the icon hides or shows the window, and with `minimize_to_tray` the window
+//! is hidden when it's minimized, which suits a slideshow that runs all day.
+//!
+//! The icon is a `org.kde.StatusNotifierItem` on the session bus and its menu is a
+//! `com.canonical.dbusmenu`, which is what the panels of KDE and most other desktops show. GNOME
+//! only shows them with the AppIndicator extension.
+
+use std::{collections::HashMap, convert::TryFrom, sync::Mutex};
+
+use gelatin::{image, winit::event_loop::EventLoopProxy};
+use zbus::{
+	blocking::{connection, Connection},
+	fdo, interface,
+	zvariant::{OwnedObjectPath, OwnedValue, Str, Structure, Value},
+};
+
+use crate::{file_dialog, EmulsionEvent};
+
+const ITEM_PATH: &str = "/StatusNotifierItem";
+const MENU_PATH: &str = "/MenuBar";
+
+/// The menu never changes, so the revision of its layout doesn't either
+const MENU_REVISION: u32 = 1;
+
+/// The id of the root of the menu, which contains the entries
+const ROOT_ID: i32 = 0;
+const OPEN_FILE_ID: i32 = 1;
+const TOGGLE_SLIDESHOW_ID: i32 = 2;
+const TOGGLE_HIDDEN_ID: i32 = 3;
+const SEPARATOR_ID: i32 = 4;
+const QUIT_ID: i32 = 5;
+
+/// The entries of the menu with their labels. An empty label makes a separator.
+const MENU_ENTRIES: &[(i32, &str)] = &[
+	(OPEN_FILE_ID, "Open File…"),
+	(TOGGLE_SLIDESHOW_ID, "Pause or Resume Slideshow"),
+	(TOGGLE_HIDDEN_ID, "Show or Hide Window"),
+	(SEPARATOR_ID, ""),
+	(QUIT_ID, "Quit"),
+];
+
+type Properties = HashMap<String, OwnedValue>;
+
+/// Keeps the icon in the tray until it's dropped
+pub struct Tray {
+	_connection: Connection,
+}
+
+impl Tray {
+	/// Publishes the icon and asks the tray of the desktop to show it. Fails when there's no
+	/// session bus or no tray.
+	pub fn start(proxy: EventLoopProxy<EmulsionEvent>) -> zbus::Result<Tray> {
+		// The name follows the convention of the specification, which makes it unique
+		let bus_name = format!("org.kde.StatusNotifierItem-{}-1", std::process::id());
+		let item = Item { proxy: Mutex::new(proxy.clone()), icon: icon_pixmap() };
+		let menu = Menu { proxy: Mutex::new(proxy) };
+		let connection = connection::Builder::session()?
+			.name(bus_name.as_str())?
+			.serve_at(ITEM_PATH, item)?
+			.serve_at(MENU_PATH, menu)?
+			.build()?;
+		connection.call_method(
+			Some("org.kde.StatusNotifierWatcher"),
+			"/StatusNotifierWatcher",
+			Some("org.kde.StatusNotifierWatcher"),
+			"RegisterStatusNotifierItem",
+			&(bus_name.as_str()),
+		)?;
+		Ok(Tray { _connection: connection })
+	}
+}
+
+/// The icon of the window as ARGB pixels in network byte order, like the tray expects it
+fn icon_pixmap() -> Vec<(i32, i32, Vec<u8>)> {
+	let img = image::load_from_memory(include_bytes!("../resource/emulsion48.png")).unwrap();
+	let rgba = img.into_rgba8();
+	let (w, h) = rgba.dimensions();
+	let argb = rgba.pixels().flat_map(|pixel| [pixel[3], pixel[0], pixel[1], pixel[2]]).collect();
+	vec![(w as i32, h as i32, argb)]
+}
+
+fn send(proxy: &Mutex<EventLoopProxy<EmulsionEvent>>, event: EmulsionEvent) {
+	// This fails when the event loop is already gone, in which case there's nothing to do
+	let _ = proxy.lock().unwrap().send_event(event);
+}
+
+struct Item {
+	proxy: Mutex<EventLoopProxy<EmulsionEvent>>,
+	icon: Vec<(i32, i32, Vec<u8>)>,
+}
+
+#[interface(name = "org.kde.StatusNotifierItem")]
+impl Item {
+	/// Clicking the icon
+	fn activate(&self, _x: i32, _y: i32) {
+		send(&self.proxy, EmulsionEvent::ToggleHidden);
+	}
+
+	fn secondary_activate(&self, _x: i32, _y: i32) {}
+
+	/// Only called when the tray doesn't show the menu itself
+	fn context_menu(&self, _x: i32, _y: i32) {}
+
+	fn scroll(&self, _delta: i32, _orientation: &str) {}
+
+	#[zbus(property)]
+	fn category(&self) -> &str {
+		"ApplicationStatus"
+	}
+
+	#[zbus(property)]
+	fn id(&self) -> &str {
+		"emulsion"
+	}
+
+	#[zbus(property)]
+	fn title(&self) -> &str {
+		"Emulsion"
+	}
+
+	#[zbus(property)]
+	fn status(&self) -> &str {
+		"Active"
+	}
+
+	/// Empty because the icon might not be installed in the icon theme, `IconPixmap` is used
+	/// instead
+	#[zbus(property)]
+	fn icon_name(&self) -> &str {
+		""
+	}
+
+	#[zbus(property)]
+	fn icon_pixmap(&self) -> Vec<(i32, i32, Vec<u8>)> {
+		self.icon.clone()
+	}
+
+	#[zbus(property)]
+	fn item_is_menu(&self) -> bool {
+		false
+	}
+
+	#[zbus(property)]
+	fn menu(&self) -> OwnedObjectPath {
+		OwnedObjectPath::try_from(MENU_PATH).unwrap()
+	}
+}
+
+struct Menu {
+	proxy: Mutex<EventLoopProxy<EmulsionEvent>>,
+}
+
+impl Menu {
+	fn properties(id: i32) -> Properties {
+		let mut properties = Properties::new();
+		if id == ROOT_ID {
+			properties.insert("children-display".to_owned(), str_value("submenu"));
+		}
+		match MENU_ENTRIES.iter().find(|(entry_id, _)| *entry_id == id) {
+			Some((_, "")) => {
+				properties.insert("type".to_owned(), str_value("separator"));
+			}
+			Some((_, label)) => {
+				properties.insert("label".to_owned(), str_value(label));
+			}
+			None => (),
+		}
+		properties
+	}
+
+	/// An entry of the menu and its children like they're returned by `GetLayout`
+	fn layout(id: i32) -> (i32, Properties, Vec<OwnedValue>) {
+		let children = if id == ROOT_ID {
+			MENU_ENTRIES.iter().map(|(id, _)| Menu::child_layout(*id)).collect()
+		} else {
+			Vec::new()
+		};
+		(id, Menu::properties(id), children)
+	}
+
+	fn child_layout(id: i32) -> OwnedValue {
+		let (id, properties, children) = Menu::layout(id);
+		// The structure only contains basic values, which can always be owned
+		OwnedValue::try_from(Structure::from((id, properties, children))).unwrap()
+	}
+
+	fn clicked(&self, id: i32) {
+		match id {
+			OPEN_FILE_ID => file_dialog::pick_images(self.proxy.lock().unwrap().clone()),
+			TOGGLE_SLIDESHOW_ID => send(&self.proxy, EmulsionEvent::ToggleSlideshow),
+			TOGGLE_HIDDEN_ID => send(&self.proxy, EmulsionEvent::ToggleHidden),
+			QUIT_ID => send(&self.proxy, EmulsionEvent::Quit),
+			_ => (),
+		}
+	}
+}
+
+fn str_value(text: &str) -> OwnedValue {
+	OwnedValue::from(Str::from(text.to_owned()))
+}
+
+#[interface(name = "com.canonical.dbusmenu")]
+impl Menu {
+	fn get_layout(
+		&self,
+		parent_id: i32,
+		_recursion_depth: i32,
+		_property_names: Vec<String>,
+	) -> (u32, (i32, Properties, Vec<OwnedValue>)) {
+		(MENU_REVISION, Menu::layout(parent_id))
+	}
+
+	fn get_group_properties(
+		&self,
+		ids: Vec<i32>,
+		_property_names: Vec<String>,
+	) -> Vec<(i32, Properties)> {
+		ids.into_iter().map(|id| (id, Menu::properties(id))).collect()
+	}
+
+	fn get_property(&self, id: i32, name: &str) -> fdo::Result<OwnedValue> {
+		let value = Menu::properties(id).remove(name);
+		value.ok_or_else(|| fdo::Error::InvalidArgs(format!("No property {} on {}", name, id)))
+	}
+
+	fn event(&self, id: i32, event_id: &str, _data: Value<'_>, _timestamp: u32) {
+		if event_id == "clicked" {
+			self.clicked(id);
+		}
+	}
+
+	/// Returns the ids that weren't found
+	fn event_group(&self, events: Vec<(i32, String, OwnedValue, u32)>) -> Vec<i32> {
+		let mut not_found = Vec::new();
+		for (id, event_id, _, _) in events {
+			if MENU_ENTRIES.iter().any(|(entry_id, _)| *entry_id == id) {
+				if event_id == "clicked" {
+					self.clicked(id);
+				}
+			} else {
+				not_found.push(id);
+			}
+		}
+		not_found
+	}
+
+	/// The menu doesn't change, so it never has to be updated before it's shown
+	fn about_to_show(&self, _id: i32) -> bool {
+		false
+	}
+
+	fn about_to_show_group(&self, _ids: Vec<i32>) -> (Vec<i32>, Vec<i32>) {
+		(Vec::new(), Vec::new())
+	}
+
+	#[zbus(property)]
+	fn version(&self) -> u32 {
+		3
+	}
+
+	#[zbus(property)]
+	fn text_direction(&self) -> &str {
+		"ltr"
+	}
+
+	#[zbus(property)]
+	fn status(&self) -> &str {
+		"normal"
+	}
+
+	#[zbus(property)]
+	fn icon_theme_path(&self) -> Vec<String> {
+		Vec::new()
+	}
+}
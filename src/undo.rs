@@ -0,0 +1,112 @@
+//! Undoing the deletion and the sorting of files with the `undo` action.
+//!
+//! Of the deleted files only the ones that were moved to the trash can be restored, and restoring
+//! them is only supported where the `trash` crate can list the trash (Windows and Freedesktop
+//! systems). Files that were moved to a sort folder are moved back.
+
+use std::{io, path::PathBuf};
+
+use crate::utils;
+
+/// The number of deletions and moves that can be undone
+const MAX_UNDO_STEPS: usize = 20;
+
+#[derive(Debug)]
+enum UndoStep {
+	/// The original path of a file that was moved to the trash
+	Trashed(PathBuf),
+	Moved {
+		from: PathBuf,
+		to: PathBuf,
+	},
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum UndoError {
+	#[cfg(any(
+		target_os = "windows",
+		all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android"))
+	))]
+	#[error("{0:?} is not in the trash anymore")]
+	NotInTrash(PathBuf),
+
+	#[error("Failed to access the trash: {0}")]
+	Trash(#[from] trash::Error),
+
+	#[error("{0:?} already exists")]
+	Occupied(PathBuf),
+
+	#[error("Failed to move the file back: {0}")]
+	Io(#[from] io::Error),
+
+	#[cfg(not(any(
+		target_os = "windows",
+		all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android"))
+	)))]
+	#[error("Restoring files from the trash is not supported on this system")]
+	Unsupported,
+}
+
+#[derive(Debug, Default)]
+pub struct UndoStack {
+	/// The most recent step is the last
+	steps: Vec<UndoStep>,
+}
+
+impl UndoStack {
+	pub fn push_trashed(&mut self, path: PathBuf) {
+		self.push(UndoStep::Trashed(path));
+	}
+
+	pub fn push_moved(&mut self, from: PathBuf, to: PathBuf) {
+		self.push(UndoStep::Moved { from, to });
+	}
+
+	fn push(&mut self, step: UndoStep) {
+		if self.steps.len() == MAX_UNDO_STEPS {
+			self.steps.remove(0);
+		}
+		self.steps.push(step);
+	}
+
+	/// Restores the most recently trashed or moved file to where it was and returns its path.
+	/// Returns `Ok(None)` when there's nothing to undo.
+	pub fn undo(&mut self) -> Result<Option<PathBuf>, UndoError> {
+		match self.steps.pop() {
+			Some(UndoStep::Trashed(path)) => restore(&path).map(|()| Some(path)),
+			Some(UndoStep::Moved { from, to }) => {
+				if from.exists() {
+					return Err(UndoError::Occupied(from));
+				}
+				utils::move_file(&to, &from)?;
+				Ok(Some(from))
+			}
+			None => Ok(None),
+		}
+	}
+}
+
+#[cfg(any(
+	target_os = "windows",
+	all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android"))
+))]
+fn restore(path: &std::path::Path) -> Result<(), UndoError> {
+	use trash::os_limited;
+
+	// The same path might have been trashed several times, the last one is the one to restore
+	let item = os_limited::list()?
+		.into_iter()
+		.filter(|item| item.original_path() == path)
+		.max_by_key(|item| item.time_deleted)
+		.ok_or_else(|| UndoError::NotInTrash(path.to_owned()))?;
+	os_limited::restore_all([item])?;
+	Ok(())
+}
+
+#[cfg(not(any(
+	target_os = "windows",
+	all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android"))
+)))]
+fn restore(_path: &std::path::Path) -> Result<(), UndoError> {
+	Err(UndoError::Unsupported)
+}
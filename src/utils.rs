@@ -1,6 +1,101 @@
-use gelatin::winit::keyboard::{Key, NamedKey};
+use std::{fs, io, path::Path};
+
+use gelatin::{
+	window::Window,
+	winit::{
+		keyboard::{Key, NamedKey},
+		monitor::MonitorHandle,
+	},
+};
 use log::warn;
 
+/// Renames the file, or copies it and removes the original when it's moved to an other file
+/// system. Other errors of the rename are returned as they are. When the original can't be removed
+/// after copying it, the copy is removed again and the error is returned.
+pub fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+	match fs::rename(from, to) {
+		Err(e) if e.kind() == io::ErrorKind::CrossesDevices => (),
+		result => return result,
+	}
+	fs::copy(from, to)?;
+	if let Err(e) = fs::remove_file(from) {
+		if let Err(e) = fs::remove_file(to) {
+			warn!("Could not remove the copy {:?} of {:?}: {}", to, from, e);
+		}
+		return Err(e);
+	}
+	Ok(())
+}
+
+/// Renames the file unless an other file has the new name already, which is an `AlreadyExists`
+/// error. A new name that only differs in case works on file systems that ignore case too.
+///
+/// Where hard links are supported, the file is linked under the new name before the old name is
+/// removed, which fails instead of replacing a file that was created in the meantime.
+pub fn rename_no_clobber(from: &Path, to: &Path) -> io::Result<()> {
+	match fs::hard_link(from, to) {
+		Ok(()) => {
+			if let Err(e) = fs::remove_file(from) {
+				if let Err(e) = fs::remove_file(to) {
+					warn!("Could not remove the link {:?} to {:?}: {}", to, from, e);
+				}
+				return Err(e);
+			}
+			Ok(())
+		}
+		Err(e) if e.kind() == io::ErrorKind::AlreadyExists && !is_same_name(from, to) => Err(e),
+		// The file systems that ignore case find the file itself under the new name, and some
+		// file systems don't support hard links
+		Err(_) => {
+			if to.symlink_metadata().is_ok() && !is_same_name(from, to) {
+				return Err(io::Error::new(io::ErrorKind::AlreadyExists, "the file exists"));
+			}
+			fs::rename(from, to)
+		}
+	}
+}
+
+/// Whether `to` is `from` with a name that only differs in case, and there's no other entry
+/// with exactly that name in the folder
+fn is_same_name(from: &Path, to: &Path) -> bool {
+	let (from_name, to_name) = match (from.file_name(), to.file_name()) {
+		(Some(from_name), Some(to_name)) => (from_name, to_name),
+		_ => return false,
+	};
+	let same_folder = from.parent() == to.parent();
+	let same_name =
+		from_name.to_string_lossy().to_lowercase() == to_name.to_string_lossy().to_lowercase();
+	if !same_folder || !same_name {
+		return false;
+	}
+	let folder = match to.parent() {
+		Some(folder) if !folder.as_os_str().is_empty() => folder,
+		_ => Path::new("."),
+	};
+	match fs::read_dir(folder) {
+		Ok(entries) => !entries.flatten().any(|entry| entry.file_name() == to_name),
+		Err(_) => false,
+	}
+}
+
+/// The monitor with the number, starting from 1 in the order that the system lists them
+pub fn monitor_by_number(window: &Window, number: u32) -> Option<MonitorHandle> {
+	let monitors = window.window_mut().available_monitors().collect::<Vec<_>>();
+	let monitor = (number as usize).checked_sub(1).and_then(|index| monitors.get(index)).cloned();
+	if monitor.is_none() {
+		warn!("There's no monitor {}, there are {} monitors", number, monitors.len());
+	}
+	monitor
+}
+
+/// Enters fullscreen on the monitor with the number, or on the monitor that the window is on
+pub fn enter_fullscreen(window: &Window, monitor: Option<u32>) {
+	match monitor.and_then(|number| monitor_by_number(window, number)) {
+		Some(monitor) => window.set_fullscreen_on(monitor),
+		None => window.set_fullscreen(true),
+	}
+}
+
 /// Returns the textual name of the key as written in the config file.
 ///
 /// (Right side, eg "Return" is the string that should be used in the config file)
@@ -326,3 +421,61 @@ pub fn virtual_keycode_to_string(key: &Key) -> String {
 		},
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use std::env;
+
+	use super::*;
+
+	#[test]
+	fn move_file_errors() {
+		let dir = env::temp_dir().join(format!("emulsion-move-file-{}", std::process::id()));
+		fs::create_dir_all(&dir).unwrap();
+		let from = dir.join("a.png");
+		fs::write(&from, b"image").unwrap();
+
+		// A missing folder isn't a reason to copy the file
+		let missing = dir.join("missing").join("a.png");
+		let e = move_file(&from, &missing).unwrap_err();
+		assert_eq!(e.kind(), io::ErrorKind::NotFound);
+		assert!(from.exists());
+
+		let to = dir.join("b.png");
+		move_file(&from, &to).unwrap();
+		assert!(!from.exists());
+		assert_eq!(fs::read(&to).unwrap(), b"image");
+		fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	fn rename_without_replacing() {
+		let dir = env::temp_dir().join(format!("emulsion-rename-{}", std::process::id()));
+		fs::create_dir_all(&dir).unwrap();
+		let a = dir.join("a.jpg");
+		let b = dir.join("b.jpg");
+		fs::write(&a, b"a").unwrap();
+		fs::write(&b, b"b").unwrap();
+
+		let e = rename_no_clobber(&a, &b).unwrap_err();
+		assert_eq!(e.kind(), io::ErrorKind::AlreadyExists);
+		assert_eq!(fs::read(&a).unwrap(), b"a");
+		assert_eq!(fs::read(&b).unwrap(), b"b");
+
+		let c = dir.join("c.jpg");
+		rename_no_clobber(&a, &c).unwrap();
+		assert!(!a.exists());
+		assert_eq!(fs::read(&c).unwrap(), b"a");
+
+		// Only the case changes
+		let upper = dir.join("C.jpg");
+		rename_no_clobber(&c, &upper).unwrap();
+		let names = fs::read_dir(&dir)
+			.unwrap()
+			.map(|entry| entry.unwrap().file_name().into_string().unwrap())
+			.collect::<Vec<_>>();
+		assert!(names.contains(&"C.jpg".to_owned()));
+		assert!(!names.contains(&"c.jpg".to_owned()));
+		fs::remove_dir_all(&dir).unwrap();
+	}
+}
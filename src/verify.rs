@@ -0,0 +1,198 @@
+//! Checking images for damage, like the truncated files that are often left after recovering
+//! photos from a failing memory card. Every file is decoded completely, including all the frames
+//! of animations.
+//!
+//! The `verify` action checks the browsed images in the background and opens a report, while
+//! `emulsion --verify PATH...` checks files and folders without opening a window.
+
+use std::{
+	fmt::Write as _,
+	fs, io,
+	path::{Path, PathBuf},
+	sync::{
+		atomic::{AtomicUsize, Ordering},
+		mpsc, Mutex,
+	},
+	thread,
+};
+
+use gelatin::image::ImageFormat;
+
+use crate::image_cache::image_loader::{self, ImgFormat};
+
+#[derive(Debug)]
+pub struct DamagedFile {
+	pub path: PathBuf,
+	pub problem: String,
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+	pub checked: usize,
+	/// Sorted by path
+	pub damaged: Vec<DamagedFile>,
+}
+
+impl Report {
+	/// A plain text report that lists the damaged files
+	pub fn text(&self) -> String {
+		let mut text = String::from("Emulsion image verification\n\n");
+		let _ = writeln!(text, "{}", self.summary());
+		for damaged in &self.damaged {
+			let _ = writeln!(text, "\n{}\n    {}", damaged.path.display(), damaged.problem);
+		}
+		text
+	}
+
+	pub fn summary(&self) -> String {
+		format!("Checked {} image(s), {} damaged", self.checked, self.damaged.len())
+	}
+}
+
+/// Decodes the whole file and returns what's wrong with it
+pub fn verify_file(path: &Path) -> Result<(), String> {
+	image_loader::complex_load_image(path, true, 0, |_| Ok(())).map_err(|e| e.to_string())?;
+	if let Ok(ImgFormat::Image(ImageFormat::Jpeg)) = image_loader::detect_format(path) {
+		let bytes = fs::read(path).map_err(|e| e.to_string())?;
+		if !jpeg_has_end_marker(&bytes) {
+			return Err("The end of the image is missing, the file is probably truncated".into());
+		}
+	}
+	Ok(())
+}
+
+/// Decoders quietly fill the missing part of a truncated JPEG with gray, so this walks the
+/// segments of the file to check that the end of image marker is reached.
+fn jpeg_has_end_marker(bytes: &[u8]) -> bool {
+	// Skip the start of image marker
+	let mut pos = 2;
+	while pos + 1 < bytes.len() {
+		if bytes[pos] != 0xFF {
+			return false;
+		}
+		let marker = bytes[pos + 1];
+		match marker {
+			0xD9 => return true,
+			// A fill byte before a marker
+			0xFF => {
+				pos += 1;
+				continue;
+			}
+			// Markers without a length
+			0x01 | 0xD0..=0xD7 => {
+				pos += 2;
+				continue;
+			}
+			_ => (),
+		}
+		if pos + 3 >= bytes.len() {
+			return false;
+		}
+		let length = u16::from_be_bytes([bytes[pos + 2], bytes[pos + 3]]) as usize;
+		pos += 2 + length;
+		if marker == 0xDA {
+			// Skip the compressed data of the scan, in which 0xFF is only ever followed by 0 or
+			// by a restart marker
+			while pos + 1 < bytes.len()
+				&& (bytes[pos] != 0xFF || matches!(bytes[pos + 1], 0x00 | 0xD0..=0xD7))
+			{
+				pos += 1;
+			}
+		}
+	}
+	false
+}
+
+/// Checks the files on all the available cores
+pub fn verify_all(paths: &[PathBuf]) -> Report {
+	let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+	let next_index = AtomicUsize::new(0);
+	let damaged = Mutex::new(Vec::new());
+	thread::scope(|scope| {
+		for _ in 0..threads.min(paths.len()) {
+			scope.spawn(|| {
+				while let Some(path) = paths.get(next_index.fetch_add(1, Ordering::Relaxed)) {
+					if let Err(problem) = verify_file(path) {
+						damaged.lock().unwrap().push(DamagedFile { path: path.clone(), problem });
+					}
+				}
+			});
+		}
+	});
+	let mut damaged = damaged.into_inner().unwrap();
+	damaged.sort_by(|a, b| a.path.cmp(&b.path));
+	Report { checked: paths.len(), damaged }
+}
+
+/// A verification that runs on a background thread
+pub struct BackgroundVerification {
+	receiver: mpsc::Receiver<Report>,
+}
+
+impl BackgroundVerification {
+	pub fn start(paths: Vec<PathBuf>) -> BackgroundVerification {
+		let (sender, receiver) = mpsc::channel();
+		thread::spawn(move || {
+			let _ = sender.send(verify_all(&paths));
+		});
+		BackgroundVerification { receiver }
+	}
+
+	/// Returns the report once the verification has finished
+	pub fn try_finish(&self) -> Option<Report> {
+		self.receiver.try_recv().ok()
+	}
+}
+
+/// Writes the report to `verify.txt` next to the cache file and opens it with the default
+/// program. The temp folder isn't used, because it's shared with the other users.
+pub fn show(report: &Report) -> io::Result<PathBuf> {
+	let (_, cache_path) = crate::get_config_and_cache_paths();
+	let path = cache_path.with_file_name("verify.txt");
+	if let Some(folder) = path.parent() {
+		fs::create_dir_all(folder)?;
+	}
+	fs::write(&path, report.text())?;
+	open::that(&path)?;
+	Ok(path)
+}
+
+/// The images among `paths` and in the folders of `paths`, including the subfolders
+fn collect_images(paths: &[PathBuf], images: &mut Vec<PathBuf>) {
+	for path in paths {
+		if path.is_dir() {
+			let mut entries = match fs::read_dir(path) {
+				Ok(entries) => entries.filter_map(|e| e.ok().map(|e| e.path())).collect::<Vec<_>>(),
+				Err(e) => {
+					eprintln!("Could not read the folder {:?}: {}", path, e);
+					continue;
+				}
+			};
+			entries.sort();
+			collect_images(&entries, images);
+		} else if !path.exists() {
+			eprintln!("{:?} doesn't exist", path);
+		} else if image_loader::is_file_supported(path) {
+			images.push(path.clone());
+		}
+	}
+}
+
+/// Runs `emulsion --verify`. The report is written to `report_path`, or to the standard output.
+/// Returns false when a file is damaged or the report couldn't be written.
+pub fn run(paths: &[PathBuf], report_path: Option<&Path>) -> bool {
+	let mut images = Vec::new();
+	collect_images(paths, &mut images);
+	let report = verify_all(&images);
+	match report_path {
+		Some(report_path) => {
+			if let Err(e) = fs::write(report_path, report.text()) {
+				eprintln!("Could not write the report to {:?}: {}", report_path, e);
+				return false;
+			}
+			eprintln!("{}", report.summary());
+		}
+		None => print!("{}", report.text()),
+	}
+	report.damaged.is_empty()
+}
@@ -0,0 +1,177 @@
+//! Additional viewer windows in the same process, opened with the `new_window` action or with
+//! `--new-window`, for example to compare two images.
+//!
+//! Each window has its own picture widget, which browses its own folder and keeps its own textures,
+//! because the windows don't share their OpenGL contexts. The image loader threads and the memory
+//! budget of the image cache are shared with the main window, like the configuration and the
+//! cache. Closing an additional window only closes that window, closing the main window exits.
+
+use std::{
+	cell::{Cell, RefCell},
+	path::{Path, PathBuf},
+	rc::{Rc, Weak},
+	sync::{Arc, Mutex},
+};
+
+use gelatin::{
+	application::WindowOpener,
+	label::Label,
+	picture::Picture,
+	popup_menu::PopupMenu,
+	renderer::RenderError,
+	window::{Window, WindowDescriptorBuilder},
+	winit::{dpi::PhysicalSize, event_loop::EventLoopProxy},
+	Widget,
+};
+
+use crate::{
+	cmd_line::StartupPaths,
+	configuration::{Cache, ConfigWindowSection, Configuration, Theme},
+	image_cache::SharedLoader,
+	widgets::{
+		bottom_bar::BottomBar, context_menu::ContextMenu, copy_notification::CopyNotifications,
+		help_screen::HelpScreen, picture_widget::PictureWidget,
+	},
+	EmulsionEvent, LEFT_TO_PAN, USAGE,
+};
+
+/// What the additional windows share with the main window
+pub struct Shared {
+	pub config: Rc<RefCell<Configuration>>,
+	pub cache: Arc<Mutex<Cache>>,
+	pub loader: Rc<SharedLoader>,
+	pub proxy: EventLoopProxy<EmulsionEvent>,
+	pub theme: Rc<Cell<Theme>>,
+	/// Switches the theme of every window, like the theme button of the main window
+	pub toggle_theme: Rc<dyn Fn()>,
+}
+
+/// The widgets of an additional window that change with the theme. They are only referenced
+/// weakly, so that they are dropped when the window is closed.
+pub struct ViewerWindow {
+	window: Weak<Window>,
+	picture_widget: Weak<PictureWidget>,
+	bottom_bar: Weak<BottomBar>,
+}
+
+impl ViewerWindow {
+	/// Returns false when the window was closed
+	pub fn set_theme(&self, theme: Theme) -> bool {
+		match (self.window.upgrade(), self.picture_widget.upgrade(), self.bottom_bar.upgrade()) {
+			(Some(window), Some(picture_widget), Some(bottom_bar)) => {
+				crate::set_window_theme(&window, &picture_widget, theme);
+				bottom_bar.set_theme(theme, false);
+				true
+			}
+			_ => false,
+		}
+	}
+}
+
+/// Makes the `new_window` action of `picture_widget` send `EmulsionEvent::NewWindow`
+pub fn forward_new_window(picture_widget: &PictureWidget, proxy: EventLoopProxy<EmulsionEvent>) {
+	picture_widget.set_on_new_window(move |path| {
+		let paths = path.map(Path::to_owned).into_iter().collect();
+		// Fails only when the event loop is gone, and then there's no window to open anyways
+		let _ = proxy.send_event(EmulsionEvent::NewWindow(paths));
+	});
+}
+
+/// Makes the `open_file`, `open_folder`, `open_playlist` and `save_playlist` actions of
+/// `picture_widget` show the file dialog. The chosen images are opened in the main window, like
+/// the ones that are sent by other processes.
+pub fn forward_file_dialogs(picture_widget: &PictureWidget, proxy: EventLoopProxy<EmulsionEvent>) {
+	let folder_proxy = proxy.clone();
+	let playlist_proxy = proxy.clone();
+	picture_widget.set_on_open_file(move || crate::file_dialog::pick_images(proxy.clone()));
+	picture_widget
+		.set_on_open_folder(move || crate::file_dialog::pick_folder(folder_proxy.clone()));
+	picture_widget
+		.set_on_open_playlist(move || crate::file_dialog::pick_playlist(playlist_proxy.clone()));
+	picture_widget.set_on_save_playlist(crate::file_dialog::save_playlist);
+}
+
+/// Opens a window that shows `paths` like they were passed on the command line
+pub fn open(
+	opener: &mut WindowOpener<EmulsionEvent>,
+	paths: Vec<PathBuf>,
+	shared: &Shared,
+) -> Result<ViewerWindow, RenderError> {
+	let window_desc = {
+		let window_cache = &shared.cache.lock().unwrap().window;
+		WindowDescriptorBuilder::default()
+			.icon(Some(crate::make_icon()))
+			.size(PhysicalSize::new(window_cache.win_w, window_cache.win_h))
+			.app_id(Some("Emulsion".into()))
+			.decorations(!crate::borderless(&shared.config.borrow()))
+			.always_on_top(crate::always_on_top(&shared.config.borrow()))
+			.opacity(crate::window_opacity(&shared.config.borrow()))
+			.transparent(crate::has_overlay_opacity(&shared.config.borrow()))
+			.build()
+			.unwrap()
+	};
+	let window = opener.open(window_desc)?;
+	window.set_close_exits(false);
+
+	let help_screen = Rc::new(HelpScreen::new(Picture::from_encoded_bytes(USAGE)));
+	help_screen.set_visible(false);
+	let left_to_pan_hint = Rc::new(HelpScreen::new(Picture::from_encoded_bytes(LEFT_TO_PAN)));
+	let copy_notifications_widget = Rc::new(Label::new());
+	let copy_notifications = CopyNotifications::new(&copy_notifications_widget);
+	let bottom_bar = Rc::new(BottomBar::new(&shared.config.borrow()));
+	let picture_widget = crate::make_picture_widget(
+		&window,
+		bottom_bar.clone(),
+		left_to_pan_hint.clone(),
+		copy_notifications,
+		shared.config.clone(),
+		shared.cache.clone(),
+		shared.loader.clone(),
+	);
+	let context_menu_widget = Rc::new(PopupMenu::new());
+	picture_widget.set_context_menu(ContextMenu::new(&context_menu_widget));
+	forward_new_window(&picture_widget, shared.proxy.clone());
+	forward_file_dialogs(&picture_widget, shared.proxy.clone());
+	crate::open_startup_paths(&picture_widget, &StartupPaths::from_paths(paths));
+
+	let picture_area_container = crate::make_picture_area_container();
+	picture_area_container.add_child(crate::make_compare_row(&picture_widget));
+	picture_area_container.add_child(copy_notifications_widget);
+	picture_area_container.add_child(left_to_pan_hint);
+	picture_area_container.add_child(help_screen.clone());
+	picture_area_container.add_child(context_menu_widget);
+	let root_container = crate::make_root_container();
+	crate::add_drag_area(&window, &root_container, &shared.config.borrow());
+	root_container.add_child(picture_area_container.clone());
+	root_container.add_child(bottom_bar.widget.clone());
+	if let Some(ConfigWindowSection { snap_to_integer_scale: Some(true), .. }) =
+		&shared.config.borrow().window
+	{
+		crate::add_integer_scale_snapping(&window, picture_widget.clone());
+	}
+	crate::add_fullscreen_insets(&window, picture_area_container, &shared.config.borrow());
+
+	crate::connect_bottom_bar(&bottom_bar, &picture_widget);
+	let toggle_theme = shared.toggle_theme.clone();
+	bottom_bar.theme_button.set_on_click(move || toggle_theme());
+	{
+		// The bottom bar owns the button, so it's only referenced weakly from the button
+		let weak_bottom_bar = Rc::downgrade(&bottom_bar);
+		bottom_bar.help_button.set_on_click(move || {
+			let visible = !help_screen.visible();
+			help_screen.set_visible(visible);
+			if let Some(bottom_bar) = weak_bottom_bar.upgrade() {
+				bottom_bar.set_help_visible(visible);
+			}
+		});
+	}
+	window.set_root(root_container);
+
+	let viewer_window = ViewerWindow {
+		window: Rc::downgrade(&window),
+		picture_widget: Rc::downgrade(&picture_widget),
+		bottom_bar: Rc::downgrade(&bottom_bar),
+	};
+	viewer_window.set_theme(shared.theme.get());
+	Ok(viewer_window)
+}
@@ -0,0 +1,90 @@
+//! Setting an image as the wallpaper of the desktop, for the `set_wallpaper` action.
+//!
+//! There's no common way to do this, so the tools of the system are used: PowerShell on Windows,
+//! AppleScript on macOS, and on Linux the settings of GNOME, Cinnamon and MATE, the
+//! `plasma-apply-wallpaperimage` tool of KDE, or `feh` on other desktops.
+
+use std::{io, path::Path, process::Command, thread};
+
+/// Sets the image at `path` as the wallpaper on all monitors. This doesn't block, the tools are
+/// run in the background and the errors are logged.
+pub fn set(path: &Path) {
+	let path = path.to_owned();
+	thread::spawn(move || {
+		if let Err(e) = path.canonicalize().and_then(|path| set_blocking(&path)) {
+			log::error!("Could not set {:?} as the wallpaper: {}", path, e);
+		}
+	});
+}
+
+/// Runs `cmd` and fails if it doesn't succeed
+fn run(cmd: &mut Command) -> io::Result<()> {
+	let status = cmd.status()?;
+	if !status.success() {
+		let program = cmd.get_program().to_string_lossy().into_owned();
+		return Err(io::Error::other(format!("{} failed with {}", program, status)));
+	}
+	Ok(())
+}
+
+#[cfg(target_os = "windows")]
+fn set_blocking(path: &Path) -> io::Result<()> {
+	// SPI_SETDESKWALLPAPER is 20, SPIF_UPDATEINIFILE | SPIF_SENDCHANGE is 3. The path is passed in
+	// an environment variable so that it doesn't need quoting.
+	const SCRIPT: &str = "Add-Type -TypeDefinition '\
+		using System.Runtime.InteropServices; \
+		public class Wallpaper { \
+			[DllImport(\"user32.dll\", CharSet = CharSet.Unicode)] \
+			public static extern bool SystemParametersInfo(int action, int param, string value, int flags); \
+		}'; \
+		if (-not [Wallpaper]::SystemParametersInfo(20, 0, $env:EMULSION_WALLPAPER, 3)) { exit 1 }";
+	run(Command::new("powershell")
+		.args(["-NoProfile", "-NonInteractive", "-Command", SCRIPT])
+		.env("EMULSION_WALLPAPER", path))
+}
+
+#[cfg(target_os = "macos")]
+fn set_blocking(path: &Path) -> io::Result<()> {
+	run(Command::new("osascript")
+		.args(["-e", "on run argv"])
+		.args(["-e", "tell application \"System Events\" to tell every desktop to set picture to item 1 of argv"])
+		.args(["-e", "end run"])
+		.arg(path))
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn set_blocking(path: &Path) -> io::Result<()> {
+	let desktop = std::env::var("XDG_CURRENT_DESKTOP").unwrap_or_default().to_lowercase();
+	let is_desktop = |names: &[&str]| names.iter().any(|name| desktop.contains(name));
+	if is_desktop(&["kde"]) {
+		run(Command::new("plasma-apply-wallpaperimage").arg(path))
+	} else if is_desktop(&["cinnamon"]) {
+		gsettings_set("org.cinnamon.desktop.background", "picture-uri", &file_uri(path))
+	} else if is_desktop(&["mate"]) {
+		gsettings_set("org.mate.background", "picture-filename", &path.to_string_lossy())
+	} else if is_desktop(&["gnome", "unity", "budgie", "pantheon"]) {
+		let uri = file_uri(path);
+		gsettings_set("org.gnome.desktop.background", "picture-uri", &uri)?;
+		// GNOME 42 and later have a separate wallpaper for the dark style, older versions don't
+		// have this key
+		let _ = gsettings_set("org.gnome.desktop.background", "picture-uri-dark", &uri);
+		Ok(())
+	} else {
+		run(Command::new("feh").arg("--bg-fill").arg(path))
+	}
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn gsettings_set(schema: &str, key: &str, value: &str) -> io::Result<()> {
+	run(Command::new("gsettings").args(["set", schema, key, value]))
+}
+
+#[cfg(target_os = "linux")]
+fn file_uri(path: &Path) -> String {
+	crate::file_manager::file_uri(path)
+}
+
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "linux")))]
+fn file_uri(path: &Path) -> String {
+	format!("file://{}", path.display())
+}
@@ -8,6 +8,7 @@ use gelatin::{
 	picture::Picture,
 	slider::Slider,
 };
+use std::cell::Cell;
 use std::f32;
 use std::rc::Rc;
 
@@ -31,6 +32,9 @@ const SMALL_BUTTON_GAP: f32 = 4.0;
 const BIG_BUTTON_GAP: f32 = 32.0;
 const BUTTON_SIZE: f32 = 24.0;
 
+/// In logical pixels
+pub const BOTTOM_BAR_HEIGHT: f32 = 32.0;
+
 pub struct BottomBar {
 	pub widget: Rc<HorizontalLayoutContainer>,
 	pub orig_scale_button: Rc<Button>,
@@ -41,8 +45,8 @@ pub struct BottomBar {
 	pub help_button: Rc<Button>,
 
 	/// This is false if the configuration requires this to be invisible
-	// and true otherwise.
-	pub should_show: bool,
+	// and true otherwise. The `toggle_bottom_bar` action changes it.
+	pub should_show: Cell<bool>,
 
 	question: Rc<Picture>,
 	question_light: Rc<Picture>,
@@ -76,7 +80,7 @@ impl BottomBar {
 		let widget = Rc::new(HorizontalLayoutContainer::new());
 		widget.set_margin_left(0.0);
 		widget.set_margin_right(0.0);
-		widget.set_height(Length::Fixed(32.0));
+		widget.set_height(Length::Fixed(BOTTOM_BAR_HEIGHT));
 		widget.set_width(Length::Stretch { min: 0.0, max: f32::INFINITY });
 
 		let orig_scale_button = make_icon_button(Alignment::Start);
@@ -115,7 +119,7 @@ impl BottomBar {
 			slider,
 			theme_button,
 			help_button,
-			should_show,
+			should_show: Cell::new(should_show),
 
 			question,
 			question_light,
@@ -168,7 +172,13 @@ impl BottomBar {
 	/// Sets this visible iff both the `visible` parameter is `true` and
 	/// the `should_show` property of this object is `true`
 	pub fn set_visible_if_should_show(&self, visible: bool) {
-		self.widget.set_visible(visible && self.should_show);
+		self.widget.set_visible(visible && self.should_show.get());
+	}
+
+	/// Shows the bar if it's hidden and hides it if it's shown. It's never shown in fullscreen.
+	pub fn toggle(&self, fullscreen: bool) {
+		self.should_show.set(!self.should_show.get());
+		self.set_visible_if_should_show(!fullscreen);
 	}
 
 	pub fn set_help_visible(&self, visible: bool) {
@@ -216,7 +226,7 @@ fn make_slider() -> Rc<Slider> {
 	slider.set_margin_left(BIG_BUTTON_GAP);
 	slider.set_margin_right(BIG_BUTTON_GAP);
 	slider.set_height(Length::Fixed(BUTTON_SIZE));
-	slider.set_width(Length::Stretch { min: 0.0, max: std::f32::INFINITY });
+	slider.set_width(Length::Stretch { min: 0.0, max: f32::INFINITY });
 	slider.set_horizontal_align(Alignment::Center);
 	slider.set_steps(6, 1);
 	slider
@@ -0,0 +1,170 @@
+//! The context menu lists the custom commands and some of the built-in actions along with the keys
+//! that they are bound to. Gelatin doesn't render text, so the labels are rendered with usvg.
+
+use std::{
+	cell::Cell,
+	collections::HashMap,
+	rc::{Rc, Weak},
+};
+
+use gelatin::{image, misc::LogicalVector, picture::Picture, popup_menu::PopupMenu};
+use usvg::fontdb;
+
+use crate::{
+	configuration::Configuration,
+	input_handling::{
+		action_key_hint, key_hint, EDIT_CONFIG_NAME, IMG_COPY_NAME, IMG_DEL_NAME, OPEN_FILE_NAME,
+		OPEN_FOLDER_NAME, OPEN_WITH_EDITOR_NAME, REVEAL_IN_FILE_MANAGER_NAME, SET_WALLPAPER_NAME,
+		SHARE_NAME,
+	},
+};
+
+/// The height of an entry in logical pixels
+const ENTRY_HEIGHT: f32 = 26.0;
+/// The labels are rendered at this multiple of their logical size to stay sharp on high DPI
+/// displays
+const LABEL_SCALE: f32 = 2.0;
+const FONT_SIZE: f32 = 14.0;
+const LABEL_PADDING: f32 = 8.0;
+/// The width of the image that a label is rendered into before it's cropped to the text
+const MAX_LABEL_WIDTH: u32 = 2048;
+/// The first of these that's installed is used for the labels. The generic sans-serif family of
+/// fontdb is Arial, which is missing on many Linux systems.
+const FONT_FAMILIES: &[&str] =
+	&["Segoe UI", "Helvetica Neue", "Arial", "Noto Sans", "DejaVu Sans", "Liberation Sans"];
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum MenuEntry {
+	/// A built-in action by its name, like `IMG_COPY_NAME`
+	Action(&'static str),
+	/// The custom command at this index of the configuration
+	Command(usize),
+}
+
+pub struct ContextMenu {
+	widget: Weak<PopupMenu>,
+	/// The entry that was chosen, which is performed by the picture widget before the next draw
+	chosen: Rc<Cell<Option<MenuEntry>>>,
+	/// Loading the system fonts is slow, so it's only done when the menu is first opened
+	fontdb: Option<fontdb::Database>,
+	labels: HashMap<String, Rc<Picture>>,
+}
+
+impl ContextMenu {
+	pub fn new(widget: &Rc<PopupMenu>) -> ContextMenu {
+		widget.set_item_height(ENTRY_HEIGHT);
+		let chosen = Rc::new(Cell::new(None));
+		ContextMenu { widget: Rc::downgrade(widget), chosen, fontdb: None, labels: HashMap::new() }
+	}
+
+	/// Shows the menu at `pos` with the commands of `config`
+	pub fn open(&mut self, pos: LogicalVector, config: &Configuration) {
+		let widget = match self.widget.upgrade() {
+			Some(widget) => widget,
+			None => return,
+		};
+		let mut builtins = vec![
+			(MenuEntry::Action(OPEN_FILE_NAME), "Open file..."),
+			(MenuEntry::Action(OPEN_FOLDER_NAME), "Open folder..."),
+			(MenuEntry::Action(IMG_COPY_NAME), "Copy image"),
+			(MenuEntry::Action(IMG_DEL_NAME), "Delete"),
+			(MenuEntry::Action(REVEAL_IN_FILE_MANAGER_NAME), "Reveal in file manager"),
+			(MenuEntry::Action(OPEN_WITH_EDITOR_NAME), "Open with editor"),
+			(MenuEntry::Action(SET_WALLPAPER_NAME), "Set as wallpaper"),
+			(MenuEntry::Action(EDIT_CONFIG_NAME), "Edit configuration"),
+		];
+		if cfg!(target_os = "macos") {
+			builtins.insert(5, (MenuEntry::Action(SHARE_NAME), "Share..."));
+		}
+		let mut entries = Vec::new();
+		for (entry, name) in builtins {
+			let key = match entry {
+				MenuEntry::Action(action) => action_key_hint(config, action),
+				_ => None,
+			};
+			entries.push((entry, with_key_hint(name.to_owned(), key)));
+		}
+		for (index, command) in config.commands.iter().flatten().enumerate() {
+			let name = command.name.clone().unwrap_or_else(|| command.program.clone());
+			let key = command.input.first().map(|key| key_hint(key));
+			entries.push((MenuEntry::Command(index), with_key_hint(name, key)));
+		}
+		let labels = entries.iter().map(|(_, text)| self.label(text)).collect();
+		let menu_entries = entries.into_iter().map(|(entry, _)| entry).collect::<Vec<_>>();
+		let chosen = self.chosen.clone();
+		widget.set_on_select(move |index| chosen.set(menu_entries.get(index).copied()));
+		widget.set_items(labels);
+		widget.open(pos);
+	}
+
+	pub fn is_open(&self) -> bool {
+		self.widget.upgrade().is_some_and(|widget| widget.is_open())
+	}
+
+	pub fn take_chosen(&self) -> Option<MenuEntry> {
+		self.chosen.take()
+	}
+
+	fn label(&mut self, text: &str) -> Rc<Picture> {
+		if let Some(label) = self.labels.get(text) {
+			return label.clone();
+		}
+		let fontdb = self.fontdb.get_or_insert_with(load_fonts);
+		let label = Rc::new(Picture::from_image(render_label(text, fontdb)));
+		self.labels.insert(text.to_owned(), label.clone());
+		label
+	}
+}
+
+fn load_fonts() -> fontdb::Database {
+	let mut fontdb = fontdb::Database::new();
+	fontdb.load_system_fonts();
+	let installed = FONT_FAMILIES.iter().find(|family| {
+		fontdb.faces().any(|face| face.families.iter().any(|(name, _)| name == *family))
+	});
+	if let Some(family) = installed {
+		fontdb.set_sans_serif_family(*family);
+	}
+	fontdb
+}
+
+fn with_key_hint(name: String, key: Option<String>) -> String {
+	match key {
+		Some(key) => format!("{}  ({})", name, key),
+		None => name,
+	}
+}
+
+fn escape_xml(text: &str) -> String {
+	text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Renders white text on a transparent background. The image is as wide as the text.
+fn render_label(text: &str, fontdb: &fontdb::Database) -> image::RgbaImage {
+	let height = (ENTRY_HEIGHT * LABEL_SCALE) as u32;
+	let svg = format!(
+		r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}">
+			<text x="{}" y="{}" font-family="sans-serif" font-size="{}" fill="white">{}</text>
+		</svg>"#,
+		MAX_LABEL_WIDTH,
+		height,
+		LABEL_PADDING * LABEL_SCALE,
+		(ENTRY_HEIGHT * 0.5 + FONT_SIZE * 0.35) * LABEL_SCALE,
+		FONT_SIZE * LABEL_SCALE,
+		escape_xml(text),
+	);
+	let tree = match usvg::Tree::from_str(&svg, &usvg::Options::default(), fontdb) {
+		Ok(tree) => tree,
+		Err(e) => {
+			log::error!("Could not render the label {:?}: {}", text, e);
+			return image::RgbaImage::new(1, height);
+		}
+	};
+	let text_right = tree.root().abs_bounding_box().right() + LABEL_PADDING * LABEL_SCALE;
+	let width = (text_right.ceil() as u32).clamp(1, MAX_LABEL_WIDTH);
+	// The dimensions are valid, so creating the pixmap can't fail
+	let mut pixmap = tiny_skia::Pixmap::new(MAX_LABEL_WIDTH, height).unwrap();
+	resvg::render(&tree, tiny_skia::Transform::identity(), &mut pixmap.as_mut());
+	let rendered = image::RgbaImage::from_raw(MAX_LABEL_WIDTH, height, pixmap.take()).unwrap();
+	image::imageops::crop_imm(&rendered, 0, 0, width, height).to_image()
+}
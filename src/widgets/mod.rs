@@ -1,4 +1,5 @@
 pub mod bottom_bar;
+pub mod context_menu;
 pub mod copy_notification;
 pub mod help_screen;
 pub mod picture_widget;
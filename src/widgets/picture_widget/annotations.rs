@@ -0,0 +1,129 @@
+//! The star rating and the tags of the shown file, which are saved in an XMP sidecar next to it,
+//! and browsing only the images with a rating or a tag.
+
+use std::{
+	io,
+	path::{Path, PathBuf},
+};
+
+use crate::{playback_manager::LoadedImgPath, sidecar, text_input::TextInput};
+
+use super::{PictureWidgetData, Prompt};
+
+/// The rating and the tags of the current file of the folder
+pub struct Annotations {
+	/// The file that `metadata` was read from
+	path: Option<PathBuf>,
+	metadata: sidecar::Metadata,
+}
+
+impl Annotations {
+	pub fn new() -> Annotations {
+		Annotations { path: None, metadata: Default::default() }
+	}
+
+	/// Reads the rating and the tags of `path`
+	pub fn load(&mut self, path: Option<&Path>) {
+		self.metadata = path.map(sidecar::read).unwrap_or_default();
+		self.path = path.map(Path::to_owned);
+	}
+
+	fn rate(&mut self, path: &Path, rating: u8) -> io::Result<()> {
+		sidecar::write_rating(path, rating)?;
+		if self.path.as_deref() == Some(path) {
+			self.metadata.rating = Some(rating);
+		}
+		Ok(())
+	}
+
+	fn tag(&mut self, path: &Path, tags: Vec<String>) -> io::Result<()> {
+		sidecar::write_tags(path, &tags)?;
+		if self.path.as_deref() == Some(path) {
+			self.metadata.tags = tags;
+		}
+		Ok(())
+	}
+
+	/// The part of the title that shows the rating and the tags
+	pub fn title(&self) -> String {
+		let rating = match self.metadata.rating {
+			Some(rating) if rating > 0 => format!(" : {}", sidecar::format_rating(rating)),
+			_ => String::new(),
+		};
+		let tags = match self.metadata.tags.as_slice() {
+			[] => String::new(),
+			tags => format!(" : [{}]", tags.join(", ")),
+		};
+		rating + &tags
+	}
+}
+
+impl Default for Annotations {
+	fn default() -> Self {
+		Annotations::new()
+	}
+}
+
+impl PictureWidgetData {
+	/// Gives the shown image `rating` stars, 0 removes the rating
+	pub(super) fn rate_shown_file(&mut self, rating: u8) {
+		let path = match self.playback_manager.shown_file_path() {
+			LoadedImgPath::Loaded(path) => path.clone(),
+			_ => return,
+		};
+		if let Err(e) = self.annotations.rate(&path, rating) {
+			log::error!("Could not save the rating of {:?}: {}", path, e);
+			self.notice = Some(format!("Could not save the rating: {}", e));
+		}
+		self.render_validity.invalidate();
+	}
+
+	/// Starts editing the tags of the shown file
+	pub(super) fn start_tagging(&mut self) {
+		let path = match self.playback_manager.shown_file_path() {
+			LoadedImgPath::Loaded(path) => path,
+			_ => return,
+		};
+		let mut tags = sidecar::read(path).tags.join(", ");
+		if !tags.is_empty() {
+			tags.push_str(", ");
+		}
+		self.prompt = Some((Prompt::Tags, TextInput::new(tags, usize::MAX)));
+		self.render_validity.invalidate();
+	}
+
+	/// Replaces the tags of the shown image with the comma separated `tag_list`
+	pub(super) fn tag_shown_file(&mut self, tag_list: &str) {
+		let path = match self.playback_manager.shown_file_path() {
+			LoadedImgPath::Loaded(path) => path.clone(),
+			_ => return,
+		};
+		let tags = sidecar::parse_tag_list(tag_list);
+		if let Err(e) = self.annotations.tag(&path, tags) {
+			log::error!("Could not save the tags of {:?}: {}", path, e);
+			self.notice = Some(format!("Could not save the tags: {}", e));
+		}
+	}
+
+	/// Starts editing the tag that the browsed images are filtered by
+	pub(super) fn start_tag_filter(&mut self) {
+		let tag = self.playback_manager.browse_filter().tag.clone().unwrap_or_default();
+		self.prompt = Some((Prompt::TagFilter, TextInput::new(tag, usize::MAX)));
+		self.render_validity.invalidate();
+	}
+
+	/// Browses only the images with `tag`, or all images if `tag` is empty
+	pub(super) fn set_tag_filter(&mut self, tag: &str) {
+		let mut filter = self.playback_manager.browse_filter().clone();
+		filter.tag = if tag.is_empty() { None } else { Some(tag.to_owned()) };
+		self.set_browse_filter(filter);
+	}
+
+	/// Raises the minimum rating of the browsed images by one star, or browses all images again
+	/// after five stars
+	pub(super) fn cycle_rating_filter(&mut self) {
+		let mut filter = self.playback_manager.browse_filter().clone();
+		filter.min_rating = (filter.min_rating + 1) % (sidecar::MAX_RATING + 1);
+		self.set_browse_filter(filter);
+	}
+}
@@ -0,0 +1,136 @@
+//! Panning and zooming the image smoothly while the keys of the `pan_*` and `zoom_*` actions are
+//! held down.
+
+use std::time::Instant;
+
+use gelatin::cgmath::Vector2;
+
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum MovementDir {
+	None,
+	Positive,
+	Negative,
+}
+
+impl MovementDir {
+	fn moving(self) -> bool {
+		!matches!(self, MovementDir::None)
+	}
+}
+
+/// The actions that move the camera while their keys are held down
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Movement {
+	ZoomIn,
+	ZoomOut,
+	PanLeft,
+	PanRight,
+	PanUp,
+	PanDown,
+}
+
+/// The held input and the velocity of one kind of movement
+#[derive(Clone, Copy)]
+struct Axis {
+	input: MovementDir,
+	vel: f32,
+}
+
+impl Axis {
+	fn new() -> Axis {
+		Axis { input: MovementDir::None, vel: 0.0 }
+	}
+
+	/// Accelerates towards the held direction, over `dt` seconds
+	fn animate(&mut self, dt: f32) {
+		let dir = match self.input {
+			MovementDir::None => {
+				self.vel = 0.0;
+				return;
+			}
+			MovementDir::Positive => 1.0,
+			MovementDir::Negative => -1.0,
+		};
+		#[allow(clippy::float_cmp)]
+		if self.vel.signum() != dir {
+			self.vel = 0.0;
+		}
+		self.vel += dir * dt * (2.0 / (self.vel.abs() + 1.0));
+	}
+}
+
+/// How far the camera moved since the last step
+pub struct CameraStep {
+	/// The panning in logical pixels, to be multiplied by the panning speed
+	pub pan: Option<Vector2<f32>>,
+	/// The zoom delta, see `Viewport::zoom`
+	pub zoom: Option<f32>,
+}
+
+/// The camera movements of the held keys
+pub struct CameraMovement {
+	hor_pan: Axis,
+	ver_pan: Axis,
+	zoom: Axis,
+	last_move_time: Instant,
+}
+
+impl CameraMovement {
+	pub fn new() -> CameraMovement {
+		CameraMovement {
+			hor_pan: Axis::new(),
+			ver_pan: Axis::new(),
+			zoom: Axis::new(),
+			last_move_time: Instant::now(),
+		}
+	}
+
+	pub fn moving(&self) -> bool {
+		self.hor_pan.input.moving() || self.ver_pan.input.moving() || self.zoom.input.moving()
+	}
+
+	/// Starts `movement` when its key is pressed, and stops it when the key is released
+	pub fn set_moving(&mut self, movement: Movement, pressed: bool) {
+		let was_moving = self.moving();
+		let (axis, dir) = match movement {
+			Movement::ZoomIn => (&mut self.zoom, MovementDir::Positive),
+			Movement::ZoomOut => (&mut self.zoom, MovementDir::Negative),
+			Movement::PanLeft => (&mut self.hor_pan, MovementDir::Positive),
+			Movement::PanRight => (&mut self.hor_pan, MovementDir::Negative),
+			Movement::PanUp => (&mut self.ver_pan, MovementDir::Positive),
+			Movement::PanDown => (&mut self.ver_pan, MovementDir::Negative),
+		};
+		if axis.input == dir && !pressed {
+			axis.input = MovementDir::None;
+			axis.vel = 0.0;
+		}
+		if axis.input != dir && pressed {
+			axis.input = dir;
+			// If there hasn't been any movement in a while, then reset the last update time
+			// to avoid large jumps at the beggining of a move when the delta would be large.
+			if !was_moving {
+				self.last_move_time = Instant::now();
+			}
+		}
+	}
+
+	/// Advances the movements to `now`
+	pub fn step(&mut self, now: Instant) -> CameraStep {
+		let dt_sec = now.duration_since(self.last_move_time).as_secs_f32();
+		self.last_move_time = now;
+		self.hor_pan.animate(dt_sec);
+		self.ver_pan.animate(dt_sec);
+		self.zoom.animate(dt_sec);
+
+		let zoom = Some(self.zoom.vel * dt_sec).filter(|_| self.zoom.input.moving());
+		let panning = self.hor_pan.input.moving() || self.ver_pan.input.moving();
+		let pan = Vector2::new(self.hor_pan.vel, self.ver_pan.vel) * dt_sec;
+		CameraStep { pan: Some(pan).filter(|_| panning), zoom }
+	}
+}
+
+impl Default for CameraMovement {
+	fn default() -> Self {
+		CameraMovement::new()
+	}
+}
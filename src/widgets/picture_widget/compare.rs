@@ -0,0 +1,276 @@
+//! Comparing the shown image with others: the pinned image that's shown next to it, and the image
+//! A that it flickers with or that its difference is computed from.
+
+use std::{
+	rc::{Rc, Weak},
+	time::{Duration, Instant},
+};
+
+use gelatin::{
+	line_layout_container::HorizontalLayoutContainer,
+	misc::LogicalVector,
+	picture_view::{PictureView, ScalingMode},
+	NextUpdate,
+};
+
+use crate::{
+	image_cache::AnimationFrameTexture,
+	input_handling::{action_key_hint, FLICKER_A_B_NAME, PIN_A_NAME},
+	playback_manager::LoadedImgPath,
+};
+
+use super::PictureWidgetData;
+
+/// How long each image is shown while the A/B comparison flickers between them
+const FLICKER_INTERVAL: Duration = Duration::from_millis(250);
+/// The `difference_gain` when it's not configured
+const DEFAULT_DIFFERENCE_GAIN: u32 = 8;
+
+/// How the difference between the image A and the shown image is drawn, see `difference_view`
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum DifferenceView {
+	Off,
+	/// The absolute difference of the colors
+	Absolute,
+	/// The absolute difference multiplied by `difference_gain`
+	Amplified,
+	/// The amplified difference in false colors from blue to red
+	Heatmap,
+}
+
+/// The comparisons of the shown image with other images
+pub struct Compare {
+	/// The row that the pinned image is shown in next to this widget, see
+	/// `PictureWidget::set_compare_row`
+	pub row: Weak<HorizontalLayoutContainer>,
+	/// The view of the pinned image while the side-by-side comparison is on. It draws the textures
+	/// of the image that was shown when it was pinned, and follows the view of this widget.
+	pub pane: Option<Rc<PictureView>>,
+	/// The image pinned with `pin_a` and its name, which `flicker_a_b` alternates with the shown
+	/// image
+	pub image_a: Option<(AnimationFrameTexture, String)>,
+	/// When `flicker_a_b` started being held
+	pub flicker_start: Option<Instant>,
+	/// Whether the image A is drawn in place of the shown image at the moment
+	pub showing_a: bool,
+	pub difference_view: DifferenceView,
+}
+
+impl Compare {
+	pub fn new() -> Compare {
+		Compare {
+			row: Weak::new(),
+			pane: None,
+			image_a: None,
+			flicker_start: None,
+			showing_a: false,
+			difference_view: DifferenceView::Off,
+		}
+	}
+
+	/// Whether the image A is due to be shown at `now`. While `flicker_a_b` is held, the view
+	/// alternates between A and the shown image, starting with A.
+	fn flicker_shows_a(&self, now: Instant) -> bool {
+		match self.flicker_start {
+			Some(start) if self.image_a.is_some() => {
+				let phase = now.duration_since(start).as_millis() / FLICKER_INTERVAL.as_millis();
+				phase.is_multiple_of(2)
+			}
+			_ => false,
+		}
+	}
+
+	/// The image A if the difference from it is shown. Only images of the same size and
+	/// orientation are compared.
+	pub fn difference_image(
+		&self,
+		texture: &AnimationFrameTexture,
+	) -> Option<&AnimationFrameTexture> {
+		if self.difference_view == DifferenceView::Off || self.showing_a {
+			return None;
+		}
+		let (image_a, _) = self.image_a.as_ref()?;
+		let same_size = (image_a.w, image_a.h) == (texture.w, texture.h);
+		Some(image_a).filter(|_| same_size && image_a.orientation == texture.orientation)
+	}
+}
+
+impl Default for Compare {
+	fn default() -> Self {
+		Compare::new()
+	}
+}
+
+impl PictureWidgetData {
+	/// The offset of the pinned image from this widget when `cursor_pos` is over the pinned image
+	pub(super) fn compare_offset(&self, cursor_pos: LogicalVector) -> Option<LogicalVector> {
+		let compare_bounds = self.compare.pane.as_ref()?.drawn_bounds();
+		if !compare_bounds.contains(cursor_pos) {
+			return None;
+		}
+		Some(compare_bounds.pos - self.drawn_bounds.pos)
+	}
+
+	/// Pins the current image next to this widget, or removes the pinned image
+	pub(super) fn toggle_compare(&mut self) {
+		let row = match self.compare.row.upgrade() {
+			Some(row) => row,
+			None => return,
+		};
+		if let Some(pane) = self.compare.pane.take() {
+			row.remove_child(pane);
+		} else if let Some(texture) = self.get_texture() {
+			let pane = Rc::new(PictureView::new());
+			pane.set_interactive(false);
+			// The textures stay alive while they are pinned, even if the cache drops the image
+			pane.set_texture_provider(move |_| Some(texture.picture_textures()));
+			row.add_child(pane.clone());
+			self.compare.pane = Some(pane);
+		} else {
+			self.notice = Some("Open an image to compare with".into());
+		}
+		self.render_validity.invalidate();
+	}
+
+	/// Shows the pinned image with the zoom and the position of this widget. When this widget fits
+	/// the image to it, the pinned image is fitted on its own.
+	pub(super) fn update_compare_view(&self) {
+		let pane = match &self.compare.pane {
+			Some(pane) => pane,
+			None => return,
+		};
+		let mut own = pane.viewport();
+		let viewport = self.viewport;
+		let fixed = viewport.scaling == ScalingMode::Fixed;
+		let moved = own.texel_size != viewport.texel_size || own.pos.vec != viewport.pos.vec;
+		let changed = own.scaling != viewport.scaling || (fixed && moved);
+		if changed {
+			own.scaling = viewport.scaling;
+			if fixed {
+				own.texel_size = viewport.texel_size;
+				own.pos = viewport.pos;
+			}
+			pane.set_viewport(own);
+		}
+	}
+
+	/// Pins the shown image as A for the flicker comparison, or unpins it if it's already A
+	pub(super) fn pin_a(&mut self) {
+		let texture = match self.get_texture() {
+			Some(texture) => texture,
+			None => {
+				self.notice = Some("Open an image to pin as A".into());
+				return;
+			}
+		};
+		if let Some((image_a, name)) = &self.compare.image_a {
+			if Rc::ptr_eq(&image_a.tex_grid, &texture.tex_grid) {
+				self.notice = Some(format!("Unpinned {}", name));
+				self.compare.image_a = None;
+				return;
+			}
+		}
+		let name = match self.playback_manager.shown_file_path() {
+			LoadedImgPath::Loaded(path) => {
+				path.file_name().unwrap_or(path.as_os_str()).to_string_lossy().into_owned()
+			}
+			LoadedImgPath::InMemory(name) => name.clone(),
+			_ => "the image".into(),
+		};
+		let hint = action_key_hint(&self.configuration.borrow(), FLICKER_A_B_NAME);
+		self.notice = Some(match hint {
+			Some(key) => format!("Pinned {} as A, hold {} to compare", name, key),
+			None => format!("Pinned {} as A", name),
+		});
+		self.compare.image_a = Some((texture, name));
+	}
+
+	/// Starts or stops alternating between the image A and the shown image, as `flicker_a_b` is
+	/// pressed or released
+	pub(super) fn set_flicker(&mut self, flicker: bool) {
+		if self.compare.flicker_start.is_some() == flicker {
+			return;
+		}
+		if flicker && self.compare.image_a.is_none() {
+			let hint = action_key_hint(&self.configuration.borrow(), PIN_A_NAME);
+			self.notice = Some(match hint {
+				Some(key) => format!("Pin an image as A with {} first", key),
+				None => "Pin an image as A first".into(),
+			});
+		}
+		let now = Instant::now();
+		self.compare.flicker_start = Some(now).filter(|_| flicker);
+		self.compare.showing_a = self.compare.flicker_shows_a(now);
+		self.render_validity.invalidate();
+	}
+
+	/// Switches between the image A and the shown image when it's time, while flickering
+	pub(super) fn update_flicker(&mut self, now: Instant) {
+		if let Some(start) = self.compare.flicker_start {
+			let showing_a = self.compare.flicker_shows_a(now);
+			if showing_a != self.compare.showing_a {
+				self.compare.showing_a = showing_a;
+				self.render_validity.invalidate();
+			}
+			let phase = now.duration_since(start).as_millis() / FLICKER_INTERVAL.as_millis();
+			let next_switch = start + FLICKER_INTERVAL * (phase as u32 + 1);
+			self.next_update = self.next_update.aggregate(NextUpdate::WaitUntil(next_switch));
+		}
+	}
+
+	/// Switches to the next way of showing the difference from the image A, see `DifferenceView`
+	pub(super) fn cycle_difference_view(&mut self) {
+		self.compare.difference_view = match self.compare.difference_view {
+			DifferenceView::Off => DifferenceView::Absolute,
+			DifferenceView::Absolute => DifferenceView::Amplified,
+			DifferenceView::Amplified => DifferenceView::Heatmap,
+			DifferenceView::Heatmap => DifferenceView::Off,
+		};
+		if self.compare.difference_view != DifferenceView::Off && self.compare.image_a.is_none() {
+			let hint = action_key_hint(&self.configuration.borrow(), PIN_A_NAME);
+			self.notice = Some(match hint {
+				Some(key) => format!("Pin an image as A with {} to see the difference", key),
+				None => "Pin an image as A to see the difference".into(),
+			});
+		}
+		self.render_validity.invalidate();
+	}
+
+	pub(super) fn difference_gain(&self) -> f32 {
+		match self.compare.difference_view {
+			DifferenceView::Off | DifferenceView::Absolute => 1.0,
+			DifferenceView::Amplified | DifferenceView::Heatmap => {
+				let config = self.configuration.borrow();
+				let gain = config.image.as_ref().and_then(|image| image.difference_gain);
+				gain.unwrap_or(DEFAULT_DIFFERENCE_GAIN) as f32
+			}
+		}
+	}
+
+	/// The part of the title that describes the flicker and the difference view
+	pub(super) fn compare_title(&self) -> String {
+		let flicker = match &self.compare.image_a {
+			Some((_, name)) if self.compare.showing_a => format!(" : A ({})", name),
+			Some(_) if self.compare.flicker_start.is_some() => " : B".into(),
+			_ => String::new(),
+		};
+		let difference = match self.compare.difference_view {
+			DifferenceView::Off => String::new(),
+			_ if self.compare.image_a.is_none() => " : Difference (no A)".into(),
+			view => {
+				let shown = self.get_texture();
+				let applies =
+					shown.is_some_and(|shown| self.compare.difference_image(&shown).is_some());
+				match (view, applies) {
+					(_, false) => " : Difference (sizes differ)".into(),
+					(DifferenceView::Absolute, true) => " : Difference".into(),
+					(DifferenceView::Amplified, true) => {
+						format!(" : Difference x{}", self.difference_gain())
+					}
+					(_, true) => format!(" : Heatmap x{}", self.difference_gain()),
+				}
+			}
+		};
+		flicker + &difference
+	}
+}
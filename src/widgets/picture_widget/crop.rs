@@ -0,0 +1,161 @@
+//! The crop mode of the picture widget. While it's on, dragging with the left button selects the
+//! region of the image that `crop_save` saves next to the image and `img_copy` copies.
+
+use gelatin::{
+	cgmath::Vector2,
+	glium::Frame,
+	image,
+	misc::{LogicalRect, LogicalVector},
+	DrawContext,
+};
+
+use crate::{
+	editing::{self, CropTool, TexelRect},
+	playback_manager::LoadedImgPath,
+};
+
+use super::PictureWidgetData;
+
+/// How close the cursor has to be to an edge of the crop selection to grab it, in logical pixels
+const CROP_GRAB_DISTANCE: f32 = 6.0;
+
+/// Whether the crop mode is on, and the selection
+pub struct Crop {
+	pub active: bool,
+	pub tool: CropTool,
+}
+
+impl Crop {
+	pub fn new() -> Crop {
+		Crop { active: false, tool: CropTool::new() }
+	}
+}
+
+impl Default for Crop {
+	fn default() -> Self {
+		Crop::new()
+	}
+}
+
+impl PictureWidgetData {
+	pub(super) fn crop_selection(&self) -> Option<TexelRect> {
+		let (w, h) = self.get_texture()?.oriented_dimensions();
+		self.crop.tool.selection(w, h)
+	}
+
+	pub(super) fn set_cropping(&mut self, cropping: bool) {
+		self.crop.active = cropping;
+		self.crop.tool.clear();
+		self.render_validity.invalidate();
+	}
+
+	/// The part of the title that describes the crop selection
+	pub(super) fn crop_title(&self) -> String {
+		match self.crop_selection() {
+			_ if !self.crop.active => String::new(),
+			Some(rect) => format!(" : Crop {}x{} at {}, {}", rect.w, rect.h, rect.x, rect.y),
+			None => " : Crop (drag to select)".into(),
+		}
+	}
+
+	/// The crop selection in window coordinates, while the crop mode is on
+	pub(super) fn crop_rect(&self) -> Option<LogicalRect> {
+		let rect = self.crop_selection().filter(|_| self.crop.active)?;
+		let top_left = Vector2::new(rect.x as f32, rect.y as f32);
+		let bottom_right = Vector2::new((rect.x + rect.w) as f32, (rect.y + rect.h) as f32);
+		let top_left = self.texel_to_window(top_left)?;
+		let bottom_right = self.texel_to_window(bottom_right)?;
+		Some(LogicalRect { pos: top_left, size: bottom_right - top_left })
+	}
+
+	/// Starts a new selection at `cursor_pos`, or grabs the edge of the selection close to it
+	pub(super) fn begin_crop_drag(&mut self, cursor_pos: LogicalVector) {
+		if let Some(texel_pos) = self.window_to_texel(cursor_pos) {
+			let grab_distance = CROP_GRAB_DISTANCE * self.dpi_scale / self.viewport.texel_size;
+			self.crop.tool.begin_drag(texel_pos, grab_distance);
+		}
+	}
+
+	pub(super) fn crop_drag_to(&mut self, cursor_pos: LogicalVector) {
+		if let Some(texel_pos) = self.window_to_texel(cursor_pos) {
+			self.crop.tool.drag_to(texel_pos);
+		}
+		self.render_validity.invalidate();
+	}
+
+	fn cropped_image(&self) -> Option<image::RgbaImage> {
+		let texture = self.get_texture()?;
+		let rect = self.crop_selection()?;
+		Some(editing::crop(&texture.pixels, texture.orientation, rect))
+	}
+
+	pub(super) fn save_crop(&mut self) {
+		let path = match self.playback_manager.shown_file_path() {
+			LoadedImgPath::Loaded(path) => path.clone(),
+			_ => return,
+		};
+		if let Some(image) = self.cropped_image() {
+			let target = editing::derived_file_path(&path, "cropped");
+			match editing::save_image(image, &target) {
+				Ok(()) => {
+					log::info!("Saved the cropped image to {:?}", target);
+					if let Err(e) = self.playback_manager.update_directory() {
+						eprintln!("Error while updating directory {:?}", e);
+					}
+				}
+				Err(e) => log::error!("Could not save the cropped image to {:?}: {}", target, e),
+			}
+		}
+	}
+
+	pub(super) fn copy_crop(&mut self) {
+		if let Some(image) = self.cropped_image() {
+			self.copy_notifications.set_started();
+			let result = editing::copy_image_to_clipboard(image);
+			if let Err(e) = &result {
+				log::error!("{}", e);
+			}
+			self.copy_notifications.set_finished(result.is_ok());
+		}
+	}
+}
+
+/// Darkens the area of `bounds` outside of `selection` and outlines the selection.
+pub fn draw_crop_overlay(
+	target: &mut Frame,
+	context: &DrawContext,
+	bounds: LogicalRect,
+	selection: LogicalRect,
+) {
+	const SHADE_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.5];
+	const BORDER_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.9];
+	let border = 1.0 / context.dpi_scale_factor;
+
+	let left = selection.left().clamp(bounds.left(), bounds.right());
+	let right = selection.right().clamp(bounds.left(), bounds.right());
+	let top = selection.top().clamp(bounds.top(), bounds.bottom());
+	let bottom = selection.bottom().clamp(bounds.top(), bounds.bottom());
+	let rect = |x0: f32, y0: f32, x1: f32, y1: f32| LogicalRect {
+		pos: LogicalVector::new(x0, y0),
+		size: LogicalVector::new((x1 - x0).max(0.0), (y1 - y0).max(0.0)),
+	};
+
+	let shaded = [
+		rect(bounds.left(), bounds.top(), bounds.right(), top),
+		rect(bounds.left(), bottom, bounds.right(), bounds.bottom()),
+		rect(bounds.left(), top, left, bottom),
+		rect(right, top, bounds.right(), bottom),
+	];
+	for area in shaded {
+		context.clear_color(target, SHADE_COLOR, Some(area));
+	}
+	let outline = [
+		rect(left, top, right, top + border),
+		rect(left, bottom - border, right, bottom),
+		rect(left, top, left + border, bottom),
+		rect(right - border, top, right, bottom),
+	];
+	for line in outline {
+		context.clear_color(target, BORDER_COLOR, Some(line));
+	}
+}
@@ -0,0 +1,174 @@
+//! Exporting the shown image with an export preset, and the images of the folder as an
+//! animation.
+
+use std::{
+	path::Path,
+	time::{Duration, Instant},
+};
+
+use gelatin::NextUpdate;
+
+use crate::{
+	configuration::ExportPreset,
+	editing::{self, animation::BackgroundExport},
+	image_cache::pixel_ops,
+	text_input::TextInput,
+};
+
+use super::{PictureWidgetData, Prompt};
+
+pub struct Export {
+	/// The indices of the first and the last image in the folder that `export_animation` exports,
+	/// marked with `mark_range`. The whole folder is exported when the range isn't complete.
+	range: (Option<usize>, Option<usize>),
+	/// The running `export_animation` action
+	running: Option<BackgroundExport>,
+	/// The name of the export preset that was used last, which `export_as` offers first
+	last_preset: Option<String>,
+}
+
+impl Export {
+	pub fn new() -> Export {
+		Export { range: (None, None), running: None, last_preset: None }
+	}
+
+	/// The part of the title that shows the marked range
+	pub fn range_title(&self) -> String {
+		match self.range {
+			(Some(start), Some(end)) => {
+				format!(" : Range {}-{}", start.min(end) + 1, start.max(end) + 1)
+			}
+			(Some(start), None) => format!(" : Range from {}", start + 1),
+			_ => String::new(),
+		}
+	}
+}
+
+impl Default for Export {
+	fn default() -> Self {
+		Export::new()
+	}
+}
+
+impl PictureWidgetData {
+	/// Starts choosing the export preset, see `export_shown_image`. The last used preset is
+	/// offered first.
+	pub(super) fn start_export_as(&mut self) {
+		if self.get_texture().is_none() {
+			return;
+		}
+		let name = match &self.export.last_preset {
+			Some(name) => name.clone(),
+			None => self.export_presets().into_iter().next().map(|p| p.name).unwrap_or_default(),
+		};
+		self.prompt = Some((Prompt::ExportAs, TextInput::new(name, usize::MAX)));
+		self.render_validity.invalidate();
+	}
+
+	pub(super) fn export_presets(&self) -> Vec<ExportPreset> {
+		let config = self.configuration.borrow();
+		config.export_presets.clone().unwrap_or_else(ExportPreset::defaults)
+	}
+
+	/// Saves the shown image with the export preset called `name` to a new file next to it, like
+	/// "photo-web.jpg"
+	pub(super) fn export_shown_image(&mut self, name: &str) {
+		let preset = self.export_presets().into_iter().find(|p| p.name.eq_ignore_ascii_case(name));
+		let preset = match preset {
+			Some(preset) => preset,
+			None => {
+				self.notice = Some(format!("There's no export preset called {:?}", name));
+				return;
+			}
+		};
+		let texture = match self.get_texture() {
+			Some(texture) => texture,
+			None => return,
+		};
+		let (folder, stem) = match self.playback_manager.current_file_path() {
+			Some(path) => {
+				let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned());
+				(path.parent().map(Path::to_owned).unwrap_or_default(), stem)
+			}
+			None => (std::env::current_dir().unwrap_or_default(), None),
+		};
+		let stem = format!("{}-{}", stem.as_deref().unwrap_or("image"), preset.name);
+		let target = editing::unused_file_path(&folder, &stem, &preset.format);
+		let image = pixel_ops::bake_orientation((*texture.pixels).clone(), texture.orientation);
+		let image = editing::shrink_to_fit(image, preset.max_size);
+		self.export.last_preset = Some(preset.name);
+		match editing::save_image_with_quality(image, &target, preset.quality) {
+			Ok(()) => {
+				log::info!("Exported the image to {:?}", target);
+				self.notice = Some(format!("Exported to {}", target.display()));
+				if let Err(e) = self.playback_manager.update_directory() {
+					eprintln!("Error while updating directory {:?}", e);
+				}
+			}
+			Err(e) => {
+				log::error!("Could not export the image to {:?}: {}", target, e);
+				self.notice = Some(format!("Could not export the image: {}", e));
+			}
+		}
+	}
+
+	/// Marks the current image as the start of the range to export, then as the end of it, and
+	/// clears the range the third time
+	pub(super) fn mark_range(&mut self) {
+		let index = match self.playback_manager.current_file_index() {
+			Some(index) => index,
+			None => return,
+		};
+		self.export.range = match self.export.range {
+			(None, _) => (Some(index), None),
+			(Some(start), None) => (Some(start), Some(index)),
+			(Some(_), Some(_)) => (None, None),
+		};
+		self.render_validity.invalidate();
+	}
+
+	/// Starts writing the marked range of the folder, or the whole folder, to an animation next
+	/// to the images. The animation plays at the frame rate of playing the folder.
+	pub(super) fn start_animation_export(&mut self) {
+		if self.export.running.is_some() {
+			return;
+		}
+		let mut paths = match self.playback_manager.image_paths() {
+			Some(paths) if !paths.is_empty() => paths,
+			_ => return,
+		};
+		if let (Some(start), Some(end)) = self.export.range {
+			let last = paths.len() - 1;
+			paths = paths[start.min(end).min(last)..=start.max(end).min(last)].to_vec();
+		}
+		let folder = paths[0].parent().map(Path::to_owned).unwrap_or_default();
+		let name = folder.file_name().map(|name| name.to_string_lossy().into_owned());
+		let config = self.configuration.borrow();
+		let format = config.sequence.as_ref().and_then(|s| s.export_format).unwrap_or_default();
+		let stem = name.unwrap_or_else(|| "animation".into());
+		let target = editing::unused_file_path(&folder, &stem, format.extension());
+		let fps = self.playback_manager.frame_rate();
+		self.notice = Some(format!("Exporting {} image(s) at {} FPS...", paths.len(), fps));
+		self.export.running = Some(BackgroundExport::start(paths, target, fps, format));
+		self.render_validity.invalidate();
+	}
+
+	/// Reports the result of the animation export once it's finished
+	pub(super) fn poll_export(&mut self, now: Instant) {
+		match self.export.running.as_ref().map(BackgroundExport::try_finish) {
+			Some(Some(result)) => {
+				self.export.running = None;
+				self.notice = Some(match result {
+					Ok(path) => format!("Exported to {}", path.display()),
+					Err(e) => format!("Could not export the animation: {}", e),
+				});
+				self.next_update = NextUpdate::Soonest;
+			}
+			Some(None) => {
+				let next_update = now + Duration::from_millis(200);
+				self.next_update = self.next_update.aggregate(NextUpdate::WaitUntil(next_update));
+			}
+			None => (),
+		}
+	}
+}
@@ -0,0 +1,3205 @@
+use std::{
+	borrow::Cow,
+	cell::RefCell,
+	collections::HashMap,
+	fs, io,
+	path::{Path, PathBuf},
+	rc::{Rc, Weak},
+	sync::{Arc, Mutex},
+	time::{Duration, Instant},
+};
+
+use gelatin::{
+	cgmath::{Matrix4, Vector2, Vector3},
+	glium::{
+		framebuffer::SimpleFrameBuffer,
+		texture::{MipmapsOption, RawImage2d, SrgbTexture2d, UncompressedFloatFormat},
+		uniform,
+		uniforms::{MagnifySamplerFilter, MinifySamplerFilter},
+		BlitTarget, Frame, Program, Surface, Texture2d,
+	},
+	image,
+	shaders::ShaderDescriptor,
+	winit::{
+		dpi::PhysicalSize,
+		event::{ElementState, KeyEvent, MouseButton, TouchPhase},
+		platform::modifier_supplement::KeyEventExtModifierSupplement,
+	},
+};
+
+pub use gelatin::picture_view::ScalingMode;
+use gelatin::{
+	add_common_widget_functions,
+	line_layout_container::HorizontalLayoutContainer,
+	misc::{Alignment, Length, LogicalRect, LogicalVector, WidgetPlacement},
+	picture_view::{Viewport, MAX_ZOOM_FACTOR, MIN_ZOOM_FACTOR},
+	window::{RenderValidity, Window},
+	winit::{keyboard::ModifiersState, monitor::MonitorHandle},
+	Display, DrawContext, Event, EventKind, NextUpdate, Widget, WidgetData, WidgetError,
+};
+
+use crate::{
+	clipboard_handler::ClipboardHandler,
+	configuration::{
+		Antialias, Cache, Configuration, Downscaling, FileRemovedAction, SortFolder, ViewFilter,
+		ViewScaling,
+	},
+	drag_out,
+	editing::{self, TexelRect},
+	file_manager,
+	folder_config::StartMode,
+	image_cache::{
+		directory::{BrowseFilter, NameFilter, StartAt},
+		image_loader::{self, ImageHeader, Orientation},
+		pixel_ops, AnimationFrameTexture, SharedLoader, TextureGridItem,
+	},
+	input_handling::*,
+	ipc,
+	playback_manager::*,
+	playlist::Playlist,
+	shaders, stats,
+	text_input::{TextInput, TextInputEvent},
+	undo::UndoStack,
+	utils::{self, virtual_keycode_to_string},
+	verify::{self, BackgroundVerification},
+	wallpaper, CLIPBOARD_IMAGE_NAME,
+};
+
+use super::{
+	bottom_bar::{BottomBar, BOTTOM_BAR_HEIGHT},
+	context_menu::{ContextMenu, MenuEntry},
+	copy_notification::CopyNotifications,
+	help_screen::HelpScreen,
+};
+
+use self::{
+	annotations::Annotations,
+	camera::{CameraMovement, Movement},
+	compare::{Compare, DifferenceView},
+	crop::{draw_crop_overlay, Crop},
+	export::Export,
+	pages::{OnionSkin, Pages},
+	pointer::{recognize_gesture, Pointer},
+	reading::{Reading, READING_SCROLL_STEP},
+};
+
+mod annotations;
+mod camera;
+mod compare;
+mod crop;
+mod export;
+mod pages;
+mod pointer;
+mod reading;
+
+const AA_TEXEL_SIZE_THRESHOLD: f32 = 4f32;
+
+/// Zooming is considered to be over when there was no zoom input for this long
+const ZOOM_GESTURE_END_DELAY: Duration = Duration::from_millis(150);
+/// How often to check whether the file of the displayed image still exists
+const FILE_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How many mipmap levels coarser the zoomed out images are drawn from while zooming
+const ZOOM_GESTURE_LOD_BIAS: f32 = 1.0;
+/// How much scrolling on a touchpad by one logical pixel zooms while Ctrl is held down
+const TOUCHPAD_ZOOM_SPEED: f32 = 0.05;
+/// The shades around the darkest and brightest images when the background is adaptive. These match
+/// the backgrounds of the dark and the light theme.
+const ADAPTIVE_BG_DARK: f32 = 0.01;
+const ADAPTIVE_BG_LIGHT: f32 = 0.85;
+/// How much darker the blurred copy of the image is drawn with `ambient_background`, so that the
+/// image stands out from it
+const AMBIENT_DIMMING: f32 = 0.3;
+/// The frame rates that `playback_faster` and `playback_slower` step through
+const FRAME_RATES: &[u32] = &[1, 2, 5, 10, 12, 15, 24, 25, 30, 48, 50, 60];
+/// How much one press of `brightness_up`, `contrast_up` or `gamma_up` changes the image, in
+/// percent
+const ADJUSTMENT_STEP: i32 = 10;
+
+/// The brightness, contrast and gamma that the image is drawn with, in percent. These only change
+/// the view, they aren't saved.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct Adjustments {
+	/// Added to each channel
+	brightness: i32,
+	contrast: i32,
+	gamma: i32,
+}
+
+impl Default for Adjustments {
+	fn default() -> Self {
+		Adjustments { brightness: 0, contrast: 100, gamma: 100 }
+	}
+}
+
+impl Adjustments {
+	fn step_brightness(&mut self, steps: i32) {
+		self.brightness = (self.brightness + steps * ADJUSTMENT_STEP).clamp(-100, 100);
+	}
+
+	fn step_contrast(&mut self, steps: i32) {
+		self.contrast = (self.contrast + steps * ADJUSTMENT_STEP).clamp(0, 400);
+	}
+
+	fn step_gamma(&mut self, steps: i32) {
+		self.gamma = (self.gamma + steps * ADJUSTMENT_STEP).clamp(10, 500);
+	}
+
+	/// The adjustments that differ from the default, like "Brightness +20%, Gamma 1.5"
+	fn describe(&self) -> String {
+		let defaults = Adjustments::default();
+		let mut parts = Vec::new();
+		if self.brightness != defaults.brightness {
+			parts.push(format!("Brightness {:+}%", self.brightness));
+		}
+		if self.contrast != defaults.contrast {
+			parts.push(format!("Contrast {}%", self.contrast));
+		}
+		if self.gamma != defaults.gamma {
+			parts.push(format!("Gamma {}", self.gamma as f32 / 100.0));
+		}
+		parts.join(", ")
+	}
+}
+
+/// The state of the window before it became a reference overlay, restored when the overlay mode
+/// is left
+#[derive(Debug, Copy, Clone)]
+struct WindowStateBeforeOverlay {
+	always_on_top: bool,
+	opacity: f32,
+}
+
+/// An other image that the compare shader combines with the shown one
+#[derive(Copy, Clone)]
+enum Overlay<'a> {
+	/// The difference from the image A is shown
+	Difference(&'a AnimationFrameTexture),
+	/// The adjacent image is blended over the shown one
+	OnionSkin(&'a AnimationFrameTexture),
+}
+
+/// The texel under the cursor while the pixel inspector is active
+#[derive(Debug, Copy, Clone)]
+struct InspectedTexel {
+	x: u32,
+	y: u32,
+	color: [u8; 4],
+}
+
+impl InspectedTexel {
+	fn hex(&self) -> String {
+		let [r, g, b, _] = self.color;
+		format!("#{:02x}{:02x}{:02x}", r, g, b)
+	}
+}
+
+/// What triggers the actions of `PictureWidget::handle_input`: a key or a mouse button, or an
+/// action that was chosen from the context menu
+#[derive(Debug, Copy, Clone)]
+enum ActionInput<'a> {
+	Key { input_key: &'a str, modifiers: ModifiersState },
+	Action(&'static str),
+}
+
+/// What the text that's edited in the title is used for
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Prompt {
+	/// The new name of the shown file
+	Rename,
+	/// The tags of the shown file, separated by commas
+	Tags,
+	/// The tag that the browsed images are filtered by
+	TagFilter,
+	/// The file name patterns that the browsed images are filtered by
+	NameFilter,
+	/// The name of the export preset that the shown image is saved with
+	ExportAs,
+}
+
+#[derive(Debug, Clone)]
+enum HoverState {
+	None,
+	ItemHovered { prev_path: LoadedImgPath },
+}
+
+fn orientation_to_matrix(orientation: Orientation) -> Matrix4<f32> {
+	#[rustfmt::skip]
+	let result = match orientation {
+		Orientation::Deg0 => Matrix4::from_scale(1.0),
+		Orientation::Deg0HorFlip => Matrix4::new(
+			-1.0, 0.0, 0.0, 0.0,
+			0.0, 1.0, 0.0, 0.0,
+			0.0, 0.0, 1.0, 0.0,
+			0.0, 0.0, 0.0, 1.0
+		),
+		Orientation::Deg180 => Matrix4::new(
+			-1.0, 0.0, 0.0, 0.0,
+			0.0, -1.0, 0.0, 0.0,
+			0.0, 0.0, 1.0, 0.0,
+			0.0, 0.0, 0.0, 1.0
+		),
+		Orientation::Deg180HorFlip => Matrix4::new(
+			1.0, 0.0, 0.0, 0.0,
+			0.0, -1.0, 0.0, 0.0,
+			0.0, 0.0, 1.0, 0.0,
+			0.0, 0.0, 0.0, 1.0
+		),
+		Orientation::Deg90 => Matrix4::new(
+			0.0, -1.0, 0.0, 0.0,
+			1.0, 0.0, 0.0, 0.0,
+			0.0, 0.0, 1.0, 0.0,
+			0.0, 0.0, 0.0, 1.0
+		),
+		Orientation::Deg90VerFlip => Matrix4::new(
+			0.0, -1.0, 0.0, 0.0,
+			-1.0, 0.0, 0.0, 0.0,
+			0.0, 0.0, 1.0, 0.0,
+			0.0, 0.0, 0.0, 1.0
+		),
+		Orientation::Deg270 => Matrix4::new(
+			0.0, 1.0, 0.0, 0.0,
+			-1.0, 0.0, 0.0, 0.0,
+			0.0, 0.0, 1.0, 0.0,
+			0.0, 0.0, 0.0, 1.0
+		),
+		Orientation::Deg270VerFlip => Matrix4::new(
+			0.0, 1.0, 0.0, 0.0,
+			1.0, 0.0, 0.0, 0.0,
+			0.0, 0.0, 1.0, 0.0,
+			0.0, 0.0, 0.0, 1.0
+		),
+	};
+	result
+}
+
+/// See `PictureWidget::set_on_image_changed`
+type ImageChangedCallback = Box<dyn Fn(&ipc::CurrentImage)>;
+/// See `PictureWidget::set_on_new_window`
+type NewWindowCallback = Box<dyn Fn(Option<&Path>)>;
+/// See `PictureWidget::set_on_open_file`, `PictureWidget::set_on_open_folder` and
+/// `PictureWidget::set_on_open_playlist`
+type OpenDialogCallback = Box<dyn Fn()>;
+/// See `PictureWidget::set_on_save_playlist`
+type SavePlaylistCallback = Box<dyn Fn(Playlist)>;
+/// See `PictureWidget::set_on_profile_changed`
+type ProfileChangedCallback = Rc<dyn Fn()>;
+
+/// The callbacks that the application sets with the `set_on_*` functions of `PictureWidget`
+#[derive(Default)]
+struct Callbacks {
+	on_image_changed: Option<ImageChangedCallback>,
+	on_new_window: Option<NewWindowCallback>,
+	on_open_file: Option<OpenDialogCallback>,
+	on_open_folder: Option<OpenDialogCallback>,
+	on_open_playlist: Option<OpenDialogCallback>,
+	on_save_playlist: Option<SavePlaylistCallback>,
+	on_profile_changed: Option<ProfileChangedCallback>,
+}
+
+struct PictureWidgetData {
+	placement: WidgetPlacement,
+	drawn_bounds: LogicalRect,
+	visible: bool,
+	render_validity: RenderValidity,
+
+	hover: bool,
+
+	configuration: Rc<RefCell<Configuration>>,
+	cache: Arc<Mutex<Cache>>,
+	playback_manager: PlaybackManager,
+	// It's an option to allow manual destruction.
+	clipboard_handler: Option<ClipboardHandler>,
+	clipboard_request_was_pending: bool,
+
+	program: Program,
+	/// Draws the shown image combined with an `Overlay`
+	compare_program: Program,
+	bright_shade: f32,
+	/// Chooses the shade around the image based on `image_luminance`
+	adaptive_background: bool,
+	/// Average luminance of the displayed image, only computed with `adaptive_background`
+	image_luminance: Option<f32>,
+	/// The small blurred copy of the displayed image, only created with `ambient_background`
+	ambient: Option<SrgbTexture2d>,
+	/// The header of the current file of the folder, which might still be loading
+	header: Option<(PathBuf, Option<ImageHeader>)>,
+	/// The rating and the tags of the file of `header`
+	annotations: Annotations,
+	/// A message for the user that's shown in the title
+	notice: Option<String>,
+	/// The monitor chosen with `cycle_monitor`, which overrides the one in the config
+	fullscreen_monitor: Option<MonitorHandle>,
+	/// Set while the window is a reference overlay, see `toggle_overlay`
+	reference_overlay: Option<WindowStateBeforeOverlay>,
+	/// When to check next whether the file of the displayed image still exists
+	next_file_check: Instant,
+	/// The zoom and the position of the image. The position is the center of the displayed
+	/// image, or the center of both pages in a two-page spread.
+	viewport: Viewport,
+	antialiasing: Antialias,
+	/// The antialiasing that was used before the view rule of the current image overrode it
+	antialiasing_before_rule: Option<Antialias>,
+	/// Draw a checkerboard behind transparent images
+	checkerboard: bool,
+	adjustments: Adjustments,
+	/// The filters that the image is drawn with, `Sepia` and `Grayscale` aren't both included
+	view_filters: Vec<ViewFilter>,
+	/// The `background` of the folder config, which replaces the background of the window
+	folder_background: Option<[f32; 4]>,
+	pages: Pages,
+
+	camera: CameraMovement,
+	/// Set while zooming, to draw faster until this time. The image is drawn in full quality
+	/// once it has passed.
+	zoom_gesture_end: Option<Instant>,
+
+	key_history: KeyHistory,
+	pointer: Pointer,
+	last_mouse_pos: LogicalVector,
+	hover_state: HoverState,
+	inspecting: bool,
+	inspected_texel: Option<InspectedTexel>,
+	/// The dpi scale factor of the last draw
+	dpi_scale: f32,
+	crop: Crop,
+
+	first_draw: bool,
+	next_update: NextUpdate,
+	bottom_bar: Rc<BottomBar>,
+	left_to_pan_hint: Rc<HelpScreen>,
+	copy_notifications: CopyNotifications,
+	/// See `PictureWidget::set_context_menu`
+	context_menu: Option<ContextMenu>,
+	window: Weak<Window>,
+	/// The last path passed to `on_image_changed`
+	reported_path: Option<PathBuf>,
+	callbacks: Callbacks,
+	/// The widget that shows the presentation, see `PictureWidget::set_audience`
+	audience: Option<Rc<PictureWidget>>,
+	compare: Compare,
+	undo_stack: UndoStack,
+	/// The views of the images that were shown in this session, with `remember_view`
+	remembered_views: HashMap<PathBuf, Viewport>,
+	/// The zoom and the relative position of the view are kept when an other image is shown
+	zoom_locked: bool,
+	reading: Reading,
+	/// The running `verify` action
+	verification: Option<BackgroundVerification>,
+	export: Export,
+	/// The image to show once the folder of the first image is listed, see `set_start_at`
+	start_at: Option<StartAt>,
+	/// Set by the `snapshot` action. The view is read back and saved when it's drawn next.
+	snapshot_requested: bool,
+	command_messages: CommandMessages,
+	/// The text that's being edited in the title and what it's for
+	prompt: Option<(Prompt, TextInput)>,
+}
+impl WidgetData for PictureWidgetData {
+	fn placement(&mut self) -> &mut WidgetPlacement {
+		&mut self.placement
+	}
+	fn drawn_bounds(&mut self) -> &mut LogicalRect {
+		&mut self.drawn_bounds
+	}
+	fn visible(&mut self) -> &mut bool {
+		&mut self.visible
+	}
+}
+impl PictureWidgetData {
+	/// Zooms and pans the image when two fingers are on the touchscreen and one of them, `id`,
+	/// moves to `pos`
+	fn move_touch(&mut self, id: u64, pos: LogicalVector) {
+		let pinch = match self.pointer.move_touch(id, pos) {
+			Some(pinch) => pinch,
+			None => return,
+		};
+		if let Some(delta) = pinch.zoom {
+			self.zoom_image(pinch.center, delta);
+		}
+		self.viewport.pan(pinch.pan);
+		self.update_scaling_buttons();
+		self.render_validity.invalidate();
+	}
+
+	/// Sets up playing the folder as configured in the `[sequence]` section
+	fn apply_sequence_config(&mut self) {
+		let sequence = self.configuration.borrow().sequence.clone().unwrap_or_default();
+		self.playback_manager.set_frame_rate(sequence.fps.unwrap_or(DEFAULT_FRAME_RATE));
+		self.playback_manager.set_looping(sequence.looping.unwrap_or(true));
+		let interval =
+			sequence.slideshow_seconds.map(|seconds| Duration::from_secs(seconds as u64));
+		self.playback_manager.set_present_interval(interval.unwrap_or(DEFAULT_PRESENT_INTERVAL));
+	}
+
+	/// Starts from the `view_filters` of the `[image]` section
+	fn apply_view_filters_config(&mut self) {
+		let config = self.configuration.borrow();
+		let filters = config.image.as_ref().and_then(|image| image.view_filters.clone());
+		drop(config);
+		self.view_filters.clear();
+		for filter in filters.unwrap_or_default() {
+			if !self.view_filters.contains(&filter) {
+				self.toggle_view_filter(filter);
+			}
+		}
+	}
+
+	fn toggle_view_filter(&mut self, filter: ViewFilter) {
+		if let Some(index) = self.view_filters.iter().position(|f| *f == filter) {
+			self.view_filters.remove(index);
+		} else {
+			// Sepia is a tinted grayscale, so only one of them is shown
+			let replaced = match filter {
+				ViewFilter::Grayscale => Some(ViewFilter::Sepia),
+				ViewFilter::Sepia => Some(ViewFilter::Grayscale),
+				ViewFilter::Invert => None,
+			};
+			self.view_filters.retain(|f| Some(*f) != replaced);
+			self.view_filters.push(filter);
+		}
+		self.render_validity.invalidate();
+	}
+
+	/// Shows the image of `start_at` when the folder of the first image is listed
+	fn jump_to_start_at(&mut self) {
+		let paths = match self.playback_manager.current_file_path() {
+			Some(_) => self.playback_manager.image_paths(),
+			None => None,
+		};
+		let (paths, start_at) = match (paths, self.start_at.take()) {
+			(Some(paths), Some(start_at)) => (paths, start_at),
+			(_, start_at) => {
+				self.start_at = start_at;
+				let next_update = Instant::now() + Duration::from_millis(50);
+				self.next_update = self.next_update.aggregate(NextUpdate::WaitUntil(next_update));
+				return;
+			}
+		};
+		match start_at.find(&paths) {
+			Some(index) => self.playback_manager.request_load(LoadRequest::LoadAtIndex(index)),
+			None => self.notice = Some(format!("No image matches {}", start_at)),
+		}
+		self.render_validity.invalidate();
+	}
+
+	/// Shows the first or the last image of the folder
+	fn jump_to_end(&mut self, last: bool) {
+		let index = match (last, self.playback_manager.current_dir_len()) {
+			(false, _) => 0,
+			(true, Some(len)) if len > 0 => len - 1,
+			(true, _) => return,
+		};
+		self.playback_manager.request_load(LoadRequest::LoadAtIndex(index));
+		self.render_validity.invalidate();
+	}
+
+	/// Switches between fitting the image to the window and showing it at 100%
+	fn toggle_fit(&mut self) {
+		if self.viewport.scaling == ScalingMode::Fixed {
+			let stretch = self.cache.lock().unwrap().image.fit_stretches;
+			self.set_img_size_to_fit(stretch);
+		} else {
+			self.set_img_size_to_orig();
+		}
+	}
+
+	fn zoom_image(&mut self, anchor: LogicalVector, delta: f32) {
+		self.viewport.zoom(anchor, delta);
+		self.zoom_gesture_end = Some(Instant::now() + ZOOM_GESTURE_END_DELAY);
+		self.update_scaling_buttons();
+		self.render_validity.invalidate();
+	}
+
+	fn update_image_transform(&mut self, dpi_scale: f32) {
+		let content = self.content_dimensions();
+		self.viewport.update(self.drawn_bounds.size, content, dpi_scale);
+		if let (true, Some(content)) = (self.reading.active, content) {
+			self.fit_width_for_reading(content, dpi_scale);
+		}
+	}
+
+	fn apply_camera_movement(&mut self, dpi_scale: f32) {
+		let step = self.camera.step(Instant::now());
+		if self.camera.moving() {
+			self.next_update = NextUpdate::Soonest;
+		}
+		if let Some(zoom) = step.zoom {
+			let bounds_size = self.drawn_bounds.size.vec;
+			let anchor = LogicalVector::new(bounds_size.x * 0.5, bounds_size.y * 0.5);
+			self.zoom_image(anchor, zoom);
+		}
+		if let Some(pan) = step.pan {
+			let panning_speed = 400.0 * dpi_scale;
+			self.viewport.scaling = ScalingMode::Fixed;
+			self.update_scaling_buttons();
+			self.viewport.pos.vec += panning_speed * pan;
+		}
+	}
+
+	fn set_window_title_filename(
+		&self,
+		window: &Window,
+		playback_state: PlaybackState,
+		file_path: &LoadedImgPath,
+	) {
+		let playback: Cow<str> = match playback_state {
+			PlaybackState::Forward if self.playback_manager.looping() => {
+				format!(" : Playing at {} FPS", self.playback_manager.frame_rate()).into()
+			}
+			PlaybackState::Forward => {
+				format!(" : Playing Once at {} FPS", self.playback_manager.frame_rate()).into()
+			}
+			PlaybackState::Present => " : Presenting".into(),
+			PlaybackState::RandomPresent => " : Presenting Shuffled".into(),
+			PlaybackState::Paused if self.playback_manager.animation_paused() => {
+				" : Animation Paused".into()
+			}
+			PlaybackState::Paused => "".into(),
+		};
+
+		let config = self.configuration.borrow();
+		let title_config = config.title.clone().unwrap_or_default();
+
+		// The header of the current file is available before the file is decoded. If it belongs
+		// to a different file than the shown one, then that file is still loading.
+		let (header_path, header) = match &self.header {
+			Some((path, header)) => (Some(path), header.as_ref()),
+			None => (None, None),
+		};
+		let loading_path = match (file_path, header_path) {
+			(LoadedImgPath::InMemory(_) | LoadedImgPath::Removed(_), _) => None,
+			(LoadedImgPath::Loaded(path) | LoadedImgPath::ErrLoading(path), Some(header_path))
+				if path == header_path =>
+			{
+				None
+			}
+			(_, header_path) => header_path,
+		};
+		let name = match (loading_path, file_path) {
+			(Some(path), _) => format!("{} : Loading", title_config.format_file_path(path)).into(),
+			(None, LoadedImgPath::NotYetLoaded) => "[ none ]".into(),
+			(None, LoadedImgPath::ErrLoading(path)) => {
+				format!("[ FAILED TO OPEN ] {}", title_config.format_file_path(path)).into()
+			}
+			(None, LoadedImgPath::Loaded(path)) => title_config.format_file_path(path),
+			(None, LoadedImgPath::InMemory(name)) => format!("{} (unsaved)", name).into(),
+			(None, LoadedImgPath::Removed(path)) => {
+				format!("[ FILE REMOVED ] {}", title_config.format_file_path(path)).into()
+			}
+		};
+		let header = match (file_path, header) {
+			(LoadedImgPath::InMemory(_), _) | (_, None) => String::new(),
+			(_, Some(header)) => format!(" : {}", header.describe()),
+		};
+		let annotations = match file_path {
+			LoadedImgPath::Loaded(_) if loading_path.is_none() => self.annotations.title(),
+			_ => String::new(),
+		};
+		let inspected = match self.inspected_texel {
+			Some(texel) if self.inspecting => {
+				let [r, g, b, a] = texel.color;
+				format!(" : {}, {} {} ({}, {}, {}, {})", texel.x, texel.y, texel.hex(), r, g, b, a)
+			}
+			_ => String::new(),
+		};
+		let crop = self.crop_title();
+		let reading = match (self.pages.two_page_spread, self.pages.right_to_left) {
+			(false, false) => "",
+			(true, false) => " : Two Pages",
+			(false, true) => " : Right to Left",
+			(true, true) => " : Two Pages, Right to Left",
+		};
+		let zoom_locked = match (self.reading.active, self.zoom_locked) {
+			(true, _) => " : Reading Mode",
+			(false, true) => " : Zoom Locked",
+			(false, false) => "",
+		};
+		let filter = self.playback_manager.browse_filter();
+		let mut filters = Vec::new();
+		if let Some(name) = &filter.name {
+			filters.push(format!(" : Only {}", name.text()));
+		}
+		if filter.min_rating > 0 {
+			filters.push(format!(" : Rated {}+", filter.min_rating));
+		}
+		if let Some(tag) = &filter.tag {
+			filters.push(format!(" : Tagged {}", tag));
+		}
+		let prompt = match &self.prompt {
+			Some((Prompt::Rename, input)) => {
+				format!(" : Rename to {} (Enter to confirm)", input.display())
+			}
+			Some((Prompt::Tags, input)) => {
+				format!(" : Tags {} (comma separated, Enter to confirm)", input.display())
+			}
+			Some((Prompt::TagFilter, input)) => {
+				format!(" : Show only tag {} (empty shows all, Enter to confirm)", input.display())
+			}
+			Some((Prompt::NameFilter, input)) => {
+				format!(
+					" : Show only files like {} (empty shows all, Enter to confirm)",
+					input.display()
+				)
+			}
+			Some((Prompt::ExportAs, input)) => {
+				let names = self.export_presets().into_iter().map(|preset| preset.name);
+				format!(
+					" : Export as {} ({}, Enter to confirm)",
+					input.display(),
+					names.collect::<Vec<_>>().join(", ")
+				)
+			}
+			None => String::new(),
+		};
+		let notice = match &self.notice {
+			Some(notice) => format!(" : {}", notice),
+			None => String::new(),
+		};
+		let compare = self.compare_title();
+		let range = self.export.range_title();
+		let onion_skin = match self.pages.onion_skin {
+			OnionSkin::Off => "",
+			OnionSkin::Previous => " : Onion Skin of Previous",
+			OnionSkin::Next => " : Onion Skin of Next",
+		};
+		let adjustments = match self.adjustments.describe() {
+			description if description.is_empty() => description,
+			description => format!(" : {}", description),
+		};
+		let view_filters = self.view_filters.iter().map(|filter| match filter {
+			ViewFilter::Grayscale => " : Grayscale",
+			ViewFilter::Sepia => " : Sepia",
+			ViewFilter::Invert => " : Inverted",
+		});
+		let title = format!(
+			"{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}",
+			config.format_parse_error(),
+			name,
+			header,
+			annotations,
+			playback,
+			reading,
+			zoom_locked,
+			filters.concat(),
+			inspected,
+			crop,
+			compare,
+			onion_skin,
+			adjustments,
+			view_filters.collect::<String>(),
+			range,
+			prompt,
+			notice,
+			title_config.format_program_name()
+		);
+		window.set_title(title);
+	}
+
+	/// Finds the texel that is displayed under `cursor_pos` and stores it as the inspected texel.
+	fn update_inspected_texel(&mut self, cursor_pos: LogicalVector) {
+		self.inspected_texel = None;
+		if !self.drawn_bounds.contains(cursor_pos) {
+			return;
+		}
+		if let (Some(texture), Some(texel_pos)) =
+			(self.get_texture(), self.window_to_texel(cursor_pos))
+		{
+			let (x, y) = (texel_pos.x.floor(), texel_pos.y.floor());
+			if x < 0.0 || y < 0.0 {
+				return;
+			}
+			let (x, y) = (x as u32, y as u32);
+			if let Some(color) = texture.oriented_texel(x, y) {
+				self.inspected_texel = Some(InspectedTexel { x, y, color });
+			}
+		}
+	}
+
+	/// Converts a position in the window to texel coordinates of the displayed image. The result
+	/// is not rounded and may lie outside of the image.
+	fn window_to_texel(&self, pos: LogicalVector) -> Option<Vector2<f32>> {
+		let (w, h) = self.get_texture()?.oriented_dimensions();
+		let local_pos = pos - self.drawn_bounds.pos - self.page_positions().0;
+		let texel_pos = local_pos.vec * (self.dpi_scale / self.viewport.texel_size);
+		Some(texel_pos + Vector2::new(w as f32, h as f32) * 0.5)
+	}
+
+	/// The inverse of `window_to_texel`
+	fn texel_to_window(&self, texel_pos: Vector2<f32>) -> Option<LogicalVector> {
+		let (w, h) = self.get_texture()?.oriented_dimensions();
+		let centered = texel_pos - Vector2::new(w as f32, h as f32) * 0.5;
+		let local_pos =
+			LogicalVector { vec: centered * (self.viewport.texel_size / self.dpi_scale) };
+		Some(local_pos + self.page_positions().0 + self.drawn_bounds.pos)
+	}
+
+	/// The size of the displayed image in texels. In a two-page spread this includes the facing
+	/// page, scaled to the height of the current image.
+	fn content_dimensions(&self) -> Option<(f32, f32)> {
+		let (w, h) = self.get_texture()?.oriented_dimensions();
+		let (w, h) = (w as f32, h as f32);
+		match &self.pages.facing_page {
+			Some(facing_page) => {
+				let (facing_w, facing_h) = facing_page.oriented_dimensions();
+				Some((w + facing_w as f32 * h / facing_h as f32, h))
+			}
+			None => Some((w, h)),
+		}
+	}
+
+	/// Moves the shown file to the trash, or deletes it for good when `permanently` is set or when
+	/// `use_trash` is disabled in the config
+	fn delete_shown_file(&mut self, permanently: bool) {
+		let path = match self.playback_manager.shown_file_path() {
+			LoadedImgPath::Loaded(path) => path.clone(),
+			_ => return,
+		};
+		let use_trash = !permanently
+			&& self.configuration.borrow().image.as_ref().and_then(|i| i.use_trash) != Some(false);
+		if use_trash {
+			// The trash remembers the absolute path, which is needed to find the file for `undo`
+			let absolute_path = path.canonicalize().unwrap_or_else(|_| path.clone());
+			match trash::delete(&path) {
+				Ok(()) => self.undo_stack.push_trashed(absolute_path),
+				Err(e) => eprintln!("Error while moving file '{:?}' to trash: {:?}", path, e),
+			}
+		} else if let Err(e) = fs::remove_file(&path) {
+			eprintln!("Error while deleting file '{:?}': {}", path, e);
+		}
+		if let Err(e) = self.playback_manager.update_directory() {
+			eprintln!("Error while updating directory {:?}", e);
+		}
+		self.render_validity.invalidate();
+	}
+
+	fn show_stats(&mut self) {
+		if !stats::enabled(&self.configuration.borrow()) {
+			self.notice = Some("Statistics are off, enable them in the [stats] section".into());
+			self.render_validity.invalidate();
+			return;
+		}
+		let stats = self.cache.lock().unwrap().stats.clone();
+		if let Err(e) = stats::show(&stats) {
+			log::error!("Could not show the statistics: {}", e);
+		}
+	}
+
+	/// Applies the view rule from the config that matches the newly shown image, with the settings
+	/// of the folder config on top. The settings that were overridden by the rule of the previous
+	/// image are restored first.
+	fn apply_view_rule(&mut self, path: &Path) {
+		if let Some(antialiasing) = self.antialiasing_before_rule.take() {
+			self.antialiasing = antialiasing;
+		}
+		self.checkerboard = true;
+		let folder_config = self.playback_manager.folder_config();
+		self.folder_background = folder_config.background_color();
+		let rule = self.configuration.borrow().view_rule(path).cloned();
+		let rule = match folder_config.override_view_rule(rule) {
+			Some(rule) => rule,
+			None => return,
+		};
+		if let Some(antialiasing) = rule.antialiasing {
+			self.antialiasing_before_rule = Some(self.antialiasing);
+			self.antialiasing = antialiasing;
+		}
+		if let Some(checkerboard) = rule.checkerboard {
+			self.checkerboard = checkerboard;
+		}
+		match rule.scaling {
+			// The locked zoom takes precedence
+			_ if self.zoom_locked => (),
+			Some(ViewScaling::Fit) => self.viewport.scaling = ScalingMode::FitMin,
+			Some(ViewScaling::FitStretch) => self.viewport.scaling = ScalingMode::FitStretch,
+			Some(ViewScaling::Original) => {
+				self.viewport.texel_size = 1.0;
+				self.viewport.scaling = ScalingMode::Fixed;
+			}
+			None => (),
+		}
+		self.update_scaling_buttons();
+		self.render_validity.invalidate();
+	}
+
+	/// Starts the `start` mode of the folder config, after an image of an other folder was shown
+	fn apply_folder_start_mode(&mut self) {
+		match self.playback_manager.folder_config().start {
+			Some(StartMode::Fullscreen) => self.set_fullscreen(true),
+			Some(StartMode::Slideshow) => self.playback_manager.start_presentation(),
+			Some(StartMode::Play) => self.playback_manager.start_playback_forward(),
+			None => (),
+		}
+	}
+
+	/// Remembers the view of the previously shown image and restores the view of `path` if it was
+	/// shown before. Only does anything if `remember_view` is enabled in the config.
+	fn restore_remembered_view(&mut self, path: &Path) {
+		let remember_view =
+			self.configuration.borrow().image.as_ref().and_then(|i| i.remember_view);
+		if remember_view != Some(true) {
+			return;
+		}
+		if let Some(prev_path) = self.reported_path.clone() {
+			self.remembered_views.insert(prev_path, self.viewport);
+		}
+		if let Some(viewport) = self.remembered_views.get(path).filter(|_| !self.zoom_locked) {
+			self.viewport = *viewport;
+			self.update_scaling_buttons();
+			self.render_validity.invalidate();
+		}
+	}
+
+	/// Starts editing the name of the shown file, see `handle_prompt_input`
+	fn start_rename(&mut self) {
+		let path = match self.playback_manager.shown_file_path() {
+			LoadedImgPath::Loaded(path) => path,
+			_ => return,
+		};
+		let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+		// The cursor is placed before the extension, because that's rarely changed
+		let cursor = path.file_stem().map_or(name.len(), |stem| stem.to_string_lossy().len());
+		self.prompt = Some((Prompt::Rename, TextInput::new(name, cursor)));
+		self.render_validity.invalidate();
+	}
+
+	/// Starts editing the file name patterns that the browsed images are filtered by
+	fn start_name_filter(&mut self) {
+		let name_filter = self.playback_manager.browse_filter().name.as_ref();
+		let text = name_filter.map_or_else(String::new, |name| name.text().to_owned());
+		self.prompt = Some((Prompt::NameFilter, TextInput::new(text, usize::MAX)));
+		self.render_validity.invalidate();
+	}
+
+	/// Edits the text of the prompt. Enter confirms the text and Escape cancels the prompt.
+	fn handle_prompt_input(&mut self, input: &KeyEvent) {
+		let (prompt, text_input) = match &mut self.prompt {
+			Some((prompt, text_input)) => (*prompt, text_input),
+			None => return,
+		};
+		match text_input.handle_key(input) {
+			TextInputEvent::Edited => (),
+			TextInputEvent::Confirmed => {
+				let text = text_input.text().trim().to_owned();
+				self.prompt = None;
+				match prompt {
+					Prompt::Rename => self.rename_shown_file(&text),
+					Prompt::Tags => self.tag_shown_file(&text),
+					Prompt::TagFilter => self.set_tag_filter(&text),
+					Prompt::NameFilter => self.set_name_filter(&text),
+					Prompt::ExportAs => self.export_shown_image(&text),
+				}
+			}
+			TextInputEvent::Cancelled => self.prompt = None,
+			TextInputEvent::Ignored => return,
+		}
+		self.render_validity.invalidate();
+	}
+
+	fn rename_shown_file(&mut self, new_name: &str) {
+		let path = match self.playback_manager.shown_file_path() {
+			LoadedImgPath::Loaded(path) => path.clone(),
+			_ => return,
+		};
+		if new_name.is_empty() || new_name.contains(['/', '\\']) {
+			self.notice = Some(format!("Invalid file name {:?}", new_name));
+			return;
+		}
+		let new_path = path.with_file_name(new_name);
+		if new_path == path {
+			return;
+		}
+		match utils::rename_no_clobber(&path, &new_path) {
+			Ok(()) => (),
+			Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+				self.notice = Some(format!("{} already exists", new_name));
+				return;
+			}
+			Err(e) => {
+				log::error!("Could not rename {:?} to {:?}: {}", path, new_path, e);
+				self.notice = Some(format!("Could not rename the file: {}", e));
+				return;
+			}
+		}
+		self.notice = None;
+		if let Err(e) = self.playback_manager.file_renamed(&path, new_path) {
+			eprintln!("Error while updating directory {:?}", e);
+		}
+	}
+
+	/// Moves or copies the shown file to `sort_folder` and shows the next image
+	fn sort_shown_file(&mut self, sort_folder: &SortFolder) {
+		let path = match self.playback_manager.shown_file_path() {
+			LoadedImgPath::Loaded(path) => path.clone(),
+			_ => return,
+		};
+		let (folder, file_name) = match (path.parent(), path.file_name()) {
+			(Some(parent), Some(file_name)) => (parent.join(&sort_folder.folder), file_name),
+			_ => return,
+		};
+		let target = folder.join(file_name);
+		if target.exists() {
+			self.notice = Some(format!("{:?} already exists", target));
+			self.render_validity.invalidate();
+			return;
+		}
+		let copy = sort_folder.copy == Some(true);
+		let result = fs::create_dir_all(&folder).and_then(|()| {
+			if copy {
+				fs::copy(&path, &target).map(drop)
+			} else {
+				utils::move_file(&path, &target)
+			}
+		});
+		if let Err(e) = result {
+			log::error!("Could not put {:?} into {:?}: {}", path, folder, e);
+			self.notice = Some(format!("Could not put the file into {:?}: {}", folder, e));
+			self.render_validity.invalidate();
+			return;
+		}
+		let verb = if copy { "Copied" } else { "Moved" };
+		self.notice = Some(format!("{} to {}", verb, folder.display()));
+		if copy {
+			self.load_next_page();
+		} else {
+			self.undo_stack.push_moved(path, target);
+			// The next image takes the place of the moved one
+			if let Err(e) = self.playback_manager.update_directory() {
+				eprintln!("Error while updating directory {:?}", e);
+			}
+			self.render_validity.invalidate();
+		}
+	}
+
+	/// Browses only the files matching the comma separated patterns of `text`, or all images if
+	/// `text` is empty
+	fn set_name_filter(&mut self, text: &str) {
+		let mut filter = self.playback_manager.browse_filter().clone();
+		filter.name = match NameFilter::parse(text) {
+			_ if text.is_empty() => None,
+			Ok(name) => Some(name),
+			Err(e) => {
+				self.notice = Some(format!("Invalid pattern {:?}: {}", text, e));
+				return;
+			}
+		};
+		self.set_browse_filter(filter);
+	}
+
+	/// Browses only the images accepted by `filter`, in the presentation too
+	fn set_browse_filter(&mut self, filter: BrowseFilter) {
+		if let Some(audience) = &self.audience {
+			audience.set_browse_filter(filter.clone());
+		}
+		if let Err(e) = self.playback_manager.set_browse_filter(filter) {
+			eprintln!("Error while updating directory {:?}", e);
+		}
+		self.render_validity.invalidate();
+	}
+
+	/// Restores the most recently trashed or moved file and shows it
+	fn undo_delete(&mut self) {
+		match self.undo_stack.undo() {
+			Ok(Some(path)) => {
+				if let Err(e) = self.playback_manager.update_directory() {
+					eprintln!("Error while updating directory {:?}", e);
+				}
+				self.playback_manager.request_load(LoadRequest::FilePath(path));
+				self.render_validity.invalidate();
+			}
+			Ok(None) => (),
+			Err(e) => log::error!("Could not undo the deletion: {}", e),
+		}
+	}
+
+	/// Lists the folder again and reloads the shown image if it was modified
+	fn refresh_folder(&mut self) {
+		if !self.handle_file_removed() {
+			if let Err(e) = self.playback_manager.update_directory() {
+				eprintln!("{}", e);
+			}
+		}
+		self.render_validity.invalidate();
+	}
+
+	/// Starts checking the browsed images for damage in the background, the report is opened when
+	/// it's done
+	fn start_verification(&mut self) {
+		if self.verification.is_some() {
+			return;
+		}
+		let paths = match self.playback_manager.playlist() {
+			Some(playlist) if !playlist.paths.is_empty() => playlist.paths,
+			_ => return,
+		};
+		self.notice = Some(format!("Verifying {} image(s)...", paths.len()));
+		self.verification = Some(BackgroundVerification::start(paths));
+		self.render_validity.invalidate();
+	}
+
+	/// Handles the removal of the file of the displayed image according to the config. Returns
+	/// false if the file exists.
+	fn handle_file_removed(&mut self) -> bool {
+		match self.playback_manager.shown_file_path() {
+			LoadedImgPath::Loaded(path) if !path.exists() => (),
+			_ => return false,
+		}
+		let action = self.configuration.borrow().image.as_ref().and_then(|i| i.on_file_removed);
+		match action.unwrap_or_default() {
+			FileRemovedAction::Next => {
+				if let Err(e) = self.playback_manager.update_directory() {
+					eprintln!("Error while updating directory {:?}", e);
+				}
+			}
+			FileRemovedAction::Placeholder => {
+				self.set_cropping(false);
+				self.playback_manager.show_removed_placeholder();
+			}
+			FileRemovedAction::Close => {
+				if let Some(window) = self.window.upgrade() {
+					window.close();
+				}
+			}
+		}
+		self.render_validity.invalidate();
+		true
+	}
+
+	/// Returns the current zoom, orientation and the crop selection or the visible region for
+	/// custom commands
+	fn view_state(&self) -> Option<ViewState> {
+		let texture = self.get_texture()?;
+		let (w, h) = texture.oriented_dimensions();
+		let region = match self.crop_selection() {
+			Some(selection) => selection,
+			None => {
+				let top_left = self.window_to_texel(self.drawn_bounds.pos)?;
+				let bottom_right =
+					self.window_to_texel(self.drawn_bounds.pos + self.drawn_bounds.size)?;
+				let x0 = top_left.x.floor().clamp(0.0, w as f32) as u32;
+				let y0 = top_left.y.floor().clamp(0.0, h as f32) as u32;
+				let x1 = bottom_right.x.ceil().clamp(0.0, w as f32) as u32;
+				let y1 = bottom_right.y.ceil().clamp(0.0, h as f32) as u32;
+				TexelRect { x: x0, y: y0, w: x1.saturating_sub(x0), h: y1.saturating_sub(y0) }
+			}
+		};
+		Some(ViewState { zoom: self.viewport.texel_size, orientation: texture.orientation, region })
+	}
+
+	/// Writes the pixels of the view that were read back by `draw` to a new PNG next to the image
+	fn save_snapshot(&mut self, snapshot: image::RgbaImage) {
+		let (folder, stem) = match self.playback_manager.current_file_path() {
+			Some(path) => {
+				let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned());
+				(path.parent().map(Path::to_owned).unwrap_or_default(), stem)
+			}
+			None => (std::env::current_dir().unwrap_or_default(), None),
+		};
+		let stem = format!("{}-snapshot", stem.as_deref().unwrap_or("view"));
+		let target = editing::unused_file_path(&folder, &stem, "png");
+		match editing::save_image(snapshot, &target) {
+			Ok(()) => {
+				log::info!("Saved the snapshot to {:?}", target);
+				self.notice = Some(format!("Saved the view to {}", target.display()));
+				if let Err(e) = self.playback_manager.update_directory() {
+					eprintln!("Error while updating directory {:?}", e);
+				}
+			}
+			Err(e) => {
+				log::error!("Could not save the snapshot to {:?}: {}", target, e);
+				self.notice = Some(format!("Could not save the snapshot: {}", e));
+			}
+		}
+		self.render_validity.invalidate();
+	}
+
+	fn paste(&mut self) {
+		match editing::paste_image_from_clipboard() {
+			Ok(image) => {
+				self.set_cropping(false);
+				self.playback_manager.show_memory_image(image, CLIPBOARD_IMAGE_NAME.into());
+				self.render_validity.invalidate();
+			}
+			Err(e) => log::error!("Could not paste an image: {}", e),
+		}
+	}
+
+	/// Writes the image that was pasted to a new file in the current folder and opens it
+	fn save_memory_image(&mut self) {
+		if !matches!(self.playback_manager.shown_file_path(), LoadedImgPath::InMemory(_)) {
+			return;
+		}
+		let texture = match self.get_texture() {
+			Some(texture) => texture,
+			None => return,
+		};
+		let folder = match self.playback_manager.current_file_path() {
+			Some(path) => path.parent().map(|p| p.to_owned()).unwrap_or_default(),
+			None => std::env::current_dir().unwrap_or_default(),
+		};
+		let target = editing::unused_file_path(&folder, "pasted", "png");
+		let image = pixel_ops::bake_orientation((*texture.pixels).clone(), texture.orientation);
+		match editing::save_image(image, &target) {
+			Ok(()) => {
+				log::info!("Saved the pasted image to {:?}", target);
+				self.playback_manager.request_load(LoadRequest::FilePath(target));
+				self.render_validity.invalidate();
+			}
+			Err(e) => log::error!("Could not save the pasted image to {:?}: {}", target, e),
+		}
+	}
+
+	/// Calls `run` with the shown image as the target of custom commands, if it's a file whose path
+	/// is valid UTF-8
+	fn with_command_target<F: FnOnce(&Self, &CommandTarget)>(&mut self, run: F) {
+		let index = self.playback_manager.current_file_index();
+		let total = self.playback_manager.current_dir_len();
+		if let LoadedImgPath::Loaded(img_path) = self.playback_manager.shown_file_path() {
+			if let Some(folder_path) = img_path.parent() {
+				let img_and_folder = (img_path.to_str(), folder_path.to_str());
+				if let (Some(img_path), Some(folder_path)) = img_and_folder {
+					let view = self.view_state();
+					run(self, &CommandTarget { img_path, folder_path, index, total, view });
+				} else {
+					log::error!("Could not convert the image path to utf8. Path: '{:?}'", img_path);
+				}
+			} else {
+				log::error!("Could not get parent folder for the image path {:?}", img_path);
+			}
+		}
+	}
+
+	fn open_context_menu(&mut self) {
+		self.left_to_pan_hint.set_visible(false);
+		let pos = self.last_mouse_pos;
+		let config = self.configuration.clone();
+		if let Some(context_menu) = &mut self.context_menu {
+			context_menu.open(pos, &config.borrow());
+		}
+	}
+
+	fn reveal_in_file_manager(&self) {
+		if let LoadedImgPath::Loaded(path) = self.playback_manager.shown_file_path() {
+			file_manager::reveal(path);
+		}
+	}
+
+	#[cfg(target_os = "macos")]
+	fn share(&self) {
+		if let LoadedImgPath::Loaded(path) = self.playback_manager.shown_file_path() {
+			crate::macos::share(path);
+		}
+	}
+
+	#[cfg(not(target_os = "macos"))]
+	fn share(&self) {
+		log::warn!("The share menu is only available on macOS");
+	}
+
+	fn set_wallpaper(&self) {
+		if let LoadedImgPath::Loaded(path) = self.playback_manager.shown_file_path() {
+			wallpaper::set(path);
+		}
+	}
+
+	fn copy_path(&mut self) {
+		if let LoadedImgPath::Loaded(path) = self.playback_manager.shown_file_path() {
+			let path = path.to_string_lossy().into_owned();
+			self.copy_notifications.set_started();
+			let result = arboard::Clipboard::new().and_then(|mut c| c.set_text(path));
+			if let Err(e) = &result {
+				log::error!("Failed to copy the path to the clipboard: {}", e);
+			}
+			self.copy_notifications.set_finished(result.is_ok());
+		}
+	}
+
+	fn copy_inspected_texel(&mut self) {
+		if let Some(texel) = self.inspected_texel {
+			self.copy_notifications.set_started();
+			let result = arboard::Clipboard::new().and_then(|mut c| c.set_text(texel.hex()));
+			if let Err(e) = &result {
+				log::error!("Failed to copy the color to the clipboard: {}", e);
+			}
+			self.copy_notifications.set_finished(result.is_ok());
+		}
+	}
+
+	fn get_texture(&self) -> Option<AnimationFrameTexture> {
+		self.playback_manager.image_texture()
+	}
+
+	/// Keeps the zoom when an other image is shown, or stops keeping it. The image is moved so that
+	/// the same part of it is at the center of the view relative to its size, see
+	/// `keep_relative_position`.
+	fn toggle_zoom_lock(&mut self) {
+		self.zoom_locked = !self.zoom_locked;
+		if self.zoom_locked {
+			// Otherwise the next image would be fitted to the window
+			self.viewport.scaling = ScalingMode::Fixed;
+			self.update_scaling_buttons();
+		}
+		self.render_validity.invalidate();
+	}
+
+	/// Moves the image so that the point of the new image at the center of the widget is at the
+	/// same position relative to the size of the image as the point of the previous image was
+	fn keep_relative_position(&mut self, prev_dimensions: (u32, u32), new_dimensions: (u32, u32)) {
+		let prev = (prev_dimensions.0 as f32, prev_dimensions.1 as f32);
+		let new = (new_dimensions.0 as f32, new_dimensions.1 as f32);
+		let center = self.drawn_bounds.size * 0.5;
+		let prev_texel = self.viewport.widget_to_texel(center, prev, self.dpi_scale);
+		let new_texel = Vector2::new(prev_texel.x / prev.0 * new.0, prev_texel.y / prev.1 * new.1);
+		let new_center = self.viewport.texel_to_widget(new_texel, new, self.dpi_scale);
+		self.viewport.pos += center - new_center;
+	}
+
+	pub fn set_img_size_to_orig(&mut self) {
+		self.viewport.texel_size = 1.0;
+		self.viewport.scaling = ScalingMode::Fixed;
+		self.update_scaling_buttons();
+		self.render_validity.invalidate();
+	}
+
+	pub fn set_img_size_to_fit(&mut self, stretch: bool) {
+		{
+			let mut cache = self.cache.lock().unwrap();
+			cache.image.fit_stretches = stretch;
+		}
+		self.viewport.scaling = if stretch { ScalingMode::FitStretch } else { ScalingMode::FitMin };
+		// Fitting each image to the window is the opposite of keeping the zoom
+		self.zoom_locked = false;
+		self.update_scaling_buttons();
+		self.render_validity.invalidate();
+	}
+
+	pub fn toggle_antialias(&mut self) {
+		let aa = match self.antialiasing {
+			Antialias::Auto if self.viewport.texel_size < AA_TEXEL_SIZE_THRESHOLD => {
+				Antialias::Never
+			}
+			Antialias::Auto | Antialias::Never => Antialias::Always,
+			Antialias::Always => Antialias::Never,
+		};
+		self.antialiasing = aa;
+		self.antialiasing_before_rule = None;
+		self.cache.lock().unwrap().image.antialiasing = aa;
+		self.render_validity.invalidate();
+	}
+
+	/// Activates the next profile of the config. Returns false if there are no profiles.
+	fn cycle_profile(&mut self) -> bool {
+		let profile = {
+			let mut config = self.configuration.borrow_mut();
+			if config.profiles.as_ref().is_none_or(|profiles| profiles.is_empty()) {
+				self.notice = Some("There are no profiles in the config".into());
+				self.render_validity.invalidate();
+				return false;
+			}
+			config.profile = config.next_profile();
+			config.active_profile().cloned()
+		};
+		if let Some(antialiasing) = profile.as_ref().and_then(|profile| profile.antialiasing) {
+			self.antialiasing = antialiasing;
+			self.antialiasing_before_rule = None;
+		}
+		self.notice = match &self.configuration.borrow().profile {
+			Some(name) => Some(format!("Profile: {}", name)),
+			None => Some("No profile".into()),
+		};
+		self.render_validity.invalidate();
+		true
+	}
+
+	pub fn set_automatic_antialias(&mut self) {
+		self.antialiasing = Antialias::Auto;
+		self.antialiasing_before_rule = None;
+		self.cache.lock().unwrap().image.antialiasing = Antialias::Auto;
+		self.render_validity.invalidate();
+	}
+
+	fn update_scaling_buttons(&mut self) {
+		self.bottom_bar.update_scaling_buttons(self.viewport.scaling, self.viewport.texel_size);
+	}
+
+	fn current_image(&mut self) -> ipc::CurrentImage {
+		let path = match self.playback_manager.shown_file_path() {
+			LoadedImgPath::Loaded(path) => Some(path.clone()),
+			_ => None,
+		};
+		let fullscreen = self.window.upgrade().is_some_and(|window| window.fullscreen());
+		ipc::CurrentImage {
+			path,
+			index: self.playback_manager.current_file_index(),
+			count: self.playback_manager.current_dir_len(),
+			zoom: self.viewport.texel_size,
+			fullscreen,
+		}
+	}
+
+	/// Pauses playing the folder and shows the next (positive `step`) or the previous image
+	fn step_frame(&mut self, step: i32) {
+		if self.playback_manager.playback_state() == PlaybackState::Forward {
+			self.playback_manager.pause_playback();
+		}
+		let request = if step > 0 { LoadRequest::LoadNext } else { LoadRequest::LoadPrevious };
+		self.playback_manager.request_load(request);
+		self.render_validity.invalidate();
+	}
+
+	/// Switches to the next (positive `step`) or the previous of the common `FRAME_RATES`
+	fn step_frame_rate(&mut self, step: i32) {
+		let current = self.playback_manager.frame_rate();
+		let frame_rate = if step > 0 {
+			FRAME_RATES.iter().find(|rate| **rate > current).copied()
+		} else {
+			FRAME_RATES.iter().rev().find(|rate| **rate < current).copied()
+		};
+		let frame_rate = frame_rate.unwrap_or(current);
+		self.playback_manager.set_frame_rate(frame_rate);
+		self.notice = Some(format!("{} FPS", frame_rate));
+		self.render_validity.invalidate();
+	}
+
+	fn toggle_playback_loop(&mut self) {
+		let looping = !self.playback_manager.looping();
+		self.playback_manager.set_looping(looping);
+		self.notice = Some(if looping { "Looping".into() } else { "Playing once".into() });
+		self.render_validity.invalidate();
+	}
+
+	fn toggle_presentation(&mut self) {
+		match self.playback_manager.playback_state() {
+			PlaybackState::Present => self.playback_manager.pause_playback(),
+			_ => self.playback_manager.start_presentation(),
+		}
+		self.render_validity.invalidate();
+	}
+
+	/// Unlike `toggle_presentation`, a shuffled presentation is paused instead of being restarted
+	/// in order
+	fn toggle_slideshow(&mut self) {
+		match self.playback_manager.playback_state() {
+			PlaybackState::Present | PlaybackState::RandomPresent => {
+				self.playback_manager.pause_playback();
+				self.notice = Some("Slideshow paused".into());
+			}
+			_ => self.playback_manager.start_presentation(),
+		}
+		self.render_validity.invalidate();
+	}
+
+	fn set_fullscreen(&mut self, fullscreen: bool) {
+		if let Some(window) = self.window.upgrade() {
+			if !fullscreen {
+				window.set_fullscreen(false);
+			} else if let Some(monitor) = self.fullscreen_monitor.clone() {
+				window.set_fullscreen_on(monitor);
+			} else {
+				let config = self.configuration.borrow();
+				let monitor = config.window.as_ref().and_then(|w| w.fullscreen_monitor);
+				utils::enter_fullscreen(&window, monitor);
+			}
+			self.bottom_bar.set_visible_if_should_show(!fullscreen);
+		}
+	}
+
+	/// The image that's combined with `texture`, the shown image, when it's drawn
+	fn overlay(&self, texture: &AnimationFrameTexture) -> Option<Overlay<'_>> {
+		if let Some(image_a) = self.compare.difference_image(texture) {
+			return Some(Overlay::Difference(image_a));
+		}
+		let onion_page = self.pages.onion_page.as_ref().filter(|_| !self.compare.showing_a)?;
+		// Images of different sizes are stretched over the shown one, unless they are split into
+		// grids of textures, which wouldn't line up
+		let same_size = (onion_page.w, onion_page.h) == (texture.w, texture.h);
+		let single_cells = onion_page.tex_grid.len() == 1 && texture.tex_grid.len() == 1;
+		let lines_up = same_size || single_cells;
+		Some(Overlay::OnionSkin(onion_page))
+			.filter(|_| lines_up && onion_page.orientation == texture.orientation)
+	}
+
+	fn ambient_background(&self) -> bool {
+		let config = self.configuration.borrow();
+		config.window.as_ref().and_then(|window| window.ambient_background).unwrap_or(false)
+	}
+
+	fn downscaling(&self) -> Downscaling {
+		let config = self.configuration.borrow();
+		config.image.as_ref().and_then(|image| image.downscaling).unwrap_or_default()
+	}
+
+	fn toggle_on_top(&mut self) {
+		let window = match self.window.upgrade() {
+			Some(window) => window,
+			None => return,
+		};
+		let always_on_top = !window.always_on_top();
+		window.set_always_on_top(always_on_top);
+		self.notice = Some(if always_on_top { "Always on top" } else { "Not on top" }.into());
+		self.render_validity.invalidate();
+	}
+
+	/// In the overlay mode the window stays on top at the `overlay_opacity` and the mouse goes
+	/// through it to the windows behind, so that the image can be traced in an other program
+	fn set_reference_overlay(&mut self, enabled: bool) {
+		let window = match self.window.upgrade() {
+			Some(window) => window,
+			None => return,
+		};
+		if enabled == self.reference_overlay.is_some() {
+			return;
+		}
+		if enabled {
+			if window.fullscreen() {
+				self.set_fullscreen(false);
+			}
+			self.reference_overlay = Some(WindowStateBeforeOverlay {
+				always_on_top: window.always_on_top(),
+				opacity: window.opacity(),
+			});
+			window.set_always_on_top(true);
+			if let Some(opacity) = self.overlay_opacity() {
+				window.set_opacity(opacity);
+			}
+			window.set_click_through(true);
+			self.notice = Some("Overlay, the mouse goes through the window".into());
+		} else if let Some(before) = self.reference_overlay.take() {
+			window.set_click_through(false);
+			window.set_always_on_top(before.always_on_top);
+			window.set_opacity(before.opacity);
+			self.notice = Some("Overlay off".into());
+		}
+		self.render_validity.invalidate();
+	}
+
+	/// The `overlay_opacity` between 0 and 1
+	fn overlay_opacity(&self) -> Option<f32> {
+		let config = self.configuration.borrow();
+		let opacity = config.window.as_ref().and_then(|window| window.overlay_opacity)?;
+		Some(opacity.min(100) as f32 / 100.0)
+	}
+
+	fn cycle_monitor(&mut self) {
+		let window = match self.window.upgrade() {
+			Some(window) => window,
+			None => return,
+		};
+		let (monitors, current_monitor) = {
+			let winit_window = window.window_mut();
+			(winit_window.available_monitors().collect::<Vec<_>>(), winit_window.current_monitor())
+		};
+		let current = self.fullscreen_monitor.clone().or(current_monitor);
+		let index = current
+			.and_then(|current| monitors.iter().position(|monitor| *monitor == current))
+			.map_or(0, |index| (index + 1) % monitors.len().max(1));
+		let monitor = match monitors.get(index) {
+			Some(monitor) => monitor.clone(),
+			None => return,
+		};
+		self.fullscreen_monitor = Some(monitor.clone());
+		let number = format!("monitor {} of {}", index + 1, monitors.len());
+		if window.fullscreen() {
+			window.set_fullscreen_on(monitor);
+			self.notice = Some(format!("Fullscreen on {}", number));
+		} else {
+			self.notice = Some(format!("Fullscreen will use {}", number));
+		}
+	}
+}
+
+pub struct PictureWidget {
+	data: RefCell<PictureWidgetData>,
+}
+impl PictureWidget {
+	#[allow(clippy::too_many_arguments)]
+	pub fn new(
+		display: &Display,
+		window: &Rc<Window>,
+		bottom_bar: Rc<BottomBar>,
+		left_to_pan_hint: Rc<HelpScreen>,
+		copy_notifications: CopyNotifications,
+		configuration: Rc<RefCell<Configuration>>,
+		cache: Arc<Mutex<Cache>>,
+		loader: Rc<SharedLoader>,
+	) -> PictureWidget {
+		// let program = program!(display,
+		// 	140 => {
+		// 		vertex: shaders::VERTEX_140,
+		// 		fragment: shaders::FRAGMENT_140
+		// 	},
+		// 	110 => {
+		// 		vertex: shaders::VERTEX_110,
+		// 		fragment: shaders::FRAGMENT_110
+		// 	},
+		// )
+		// .unwrap();
+
+		let program = gelatin::shaders::shader_from_source(
+			display,
+			ShaderDescriptor {
+				vertex_shader: shaders::VERTEX_140,
+				fragment_shader: shaders::FRAGMENT_140,
+				outputs_srgb: false,
+				..Default::default()
+			},
+		)
+		.unwrap();
+		let compare_program = gelatin::shaders::shader_from_source(
+			display,
+			ShaderDescriptor {
+				vertex_shader: shaders::VERTEX_140,
+				fragment_shader: shaders::COMPARE_140,
+				outputs_srgb: false,
+				..Default::default()
+			},
+		)
+		.unwrap();
+
+		let scaling;
+		{
+			let cache = cache.lock().unwrap();
+			if cache.image.fit_stretches {
+				scaling = ScalingMode::FitStretch;
+			} else {
+				scaling = ScalingMode::FitMin;
+			}
+		}
+
+		let (two_page_spread, right_to_left) = {
+			let cache = cache.lock().unwrap();
+			(cache.image.two_page_spread, cache.image.right_to_left)
+		};
+
+		let image_config = configuration.borrow().image.clone().unwrap_or_default();
+		let previous_antialiasing = cache.lock().unwrap().image.antialiasing;
+		let antialiasing = match (image_config.scaling, image_config.antialiasing.as_deref()) {
+			(Some(scaling), _) => scaling,
+			// The choice of `toggle_antialias` is remembered in the cache
+			(None, None | Some("previous")) => previous_antialiasing,
+			(None, Some("auto")) => Antialias::Auto,
+			(None, Some("always")) => Antialias::Always,
+			(None, Some("never")) => Antialias::Never,
+			(None, Some(val)) => {
+				eprintln!("Illegal configuration value {:?} for antialiasing!", val);
+				eprintln!(r#"Allowed values are "auto", "always", "never" and "previous"."#);
+				Antialias::default()
+			}
+		};
+		let profile = configuration.borrow().active_profile().cloned();
+		let antialiasing = profile.and_then(|profile| profile.antialiasing).unwrap_or(antialiasing);
+
+		let adaptive_background = configuration
+			.borrow()
+			.window
+			.as_ref()
+			.and_then(|w| w.adaptive_background)
+			.unwrap_or(false);
+
+		let mut data = PictureWidgetData {
+			placement: Default::default(),
+			drawn_bounds: Default::default(),
+			visible: true,
+			hover: false,
+			configuration,
+			cache,
+			playback_manager: PlaybackManager::new(loader),
+			clipboard_handler: Some(ClipboardHandler::new()),
+			clipboard_request_was_pending: false,
+			render_validity: Default::default(),
+
+			program,
+			compare_program,
+			bright_shade: 0.95,
+			adaptive_background,
+			image_luminance: None,
+			ambient: None,
+			header: None,
+			annotations: Annotations::new(),
+			notice: None,
+			fullscreen_monitor: None,
+			reference_overlay: None,
+			next_file_check: Instant::now(),
+			viewport: Viewport::new(scaling),
+			antialiasing,
+			antialiasing_before_rule: None,
+			checkerboard: true,
+			adjustments: Adjustments::default(),
+			view_filters: Vec::new(),
+			folder_background: None,
+			pages: Pages::new(two_page_spread, right_to_left),
+			camera: CameraMovement::new(),
+			zoom_gesture_end: None,
+			key_history: Default::default(),
+			pointer: Pointer::new(),
+			last_mouse_pos: Default::default(),
+			hover_state: HoverState::None,
+			inspecting: false,
+			inspected_texel: None,
+			dpi_scale: 1.0,
+			crop: Crop::new(),
+			first_draw: true,
+			next_update: NextUpdate::Latest,
+			bottom_bar,
+			left_to_pan_hint,
+			copy_notifications,
+			context_menu: None,
+			window: Rc::downgrade(window),
+			reported_path: None,
+			callbacks: Callbacks::default(),
+			audience: None,
+			compare: Compare::new(),
+			undo_stack: UndoStack::default(),
+			remembered_views: HashMap::new(),
+			zoom_locked: false,
+			reading: Reading::new(),
+			verification: None,
+			export: Export::new(),
+			start_at: None,
+			snapshot_requested: false,
+			command_messages: CommandMessages::new(),
+			prompt: None,
+		};
+		let filter = BrowseFilter::from_config(&data.configuration.borrow());
+		data.set_browse_filter(filter);
+		data.apply_sequence_config();
+		data.apply_view_filters_config();
+		data.update_scaling_buttons();
+		PictureWidget { data: RefCell::new(data) }
+	}
+
+	add_common_widget_functions!(data);
+
+	pub fn set_bright_shade(&self, shade: f32) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.bright_shade = shade;
+		borrowed.render_validity.invalidate();
+	}
+
+	pub fn set_img_size_to_orig(&self) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.set_img_size_to_orig();
+	}
+
+	pub fn set_img_size_to_fit(&self, stretch: bool) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.set_img_size_to_fit(stretch);
+	}
+
+	/// Applies the settings of the config after it was reloaded. The bindings and the commands are
+	/// read from the config when they're used, so those don't need to be applied.
+	pub fn config_changed(&self) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.apply_sequence_config();
+		borrowed.apply_view_filters_config();
+		borrowed.render_validity.invalidate();
+	}
+
+	/// Shows the image of `start_at` instead of the first one once the folder is listed
+	pub fn set_start_at(&self, start_at: StartAt) {
+		self.data.borrow_mut().start_at = Some(start_at);
+	}
+
+	pub fn jump_to_index(&self, index: u32) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.playback_manager.request_load(LoadRequest::LoadAtIndex(index as usize));
+		borrowed.render_validity.invalidate();
+	}
+
+	/// See `PlaybackManager::open_playlist`
+	pub fn open_playlist(&self, playlist: Playlist) {
+		let mut borrowed = self.data.borrow_mut();
+		if let Err(e) = borrowed.playback_manager.open_playlist(playlist) {
+			log::error!("Could not open the file list: {}", e);
+		}
+		borrowed.render_validity.invalidate();
+	}
+
+	/// Browses only the images accepted by `filter`
+	pub fn set_browse_filter(&self, filter: BrowseFilter) {
+		self.data.borrow_mut().set_browse_filter(filter);
+	}
+
+	/// Shows `notice` in the title until it's replaced or removed by passing `None`
+	pub fn set_notice(&self, notice: Option<String>) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.notice = notice;
+		borrowed.render_validity.invalidate();
+	}
+
+	/// Displays the image on the clipboard, like the `paste` action
+	pub fn show_clipboard_image(&self) {
+		self.data.borrow_mut().paste();
+	}
+
+	/// Displays an image that isn't stored in a file, see `PlaybackManager::show_memory_image`
+	pub fn show_memory_image(&self, image: image::RgbaImage, name: String) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.playback_manager.show_memory_image(image, name);
+		borrowed.render_validity.invalidate();
+	}
+
+	/// Starts presenting the images in order, or in a random order if `shuffle` is set, showing
+	/// each one for `interval`
+	pub fn start_slideshow(&self, interval: Duration, shuffle: bool) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.playback_manager.set_present_interval(interval);
+		if shuffle {
+			borrowed.playback_manager.start_random_presentation();
+		} else {
+			borrowed.playback_manager.start_presentation();
+		}
+		borrowed.render_validity.invalidate();
+	}
+
+	/// Browses the images in the folder at `path`, starting at the first one
+	pub fn open_folder(&self, path: &Path) {
+		self.open_playlist(Playlist { paths: vec![path.to_owned()], current: None });
+	}
+
+	pub fn jump_to_path<P: Into<PathBuf>>(&self, path: P) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.playback_manager.request_load(LoadRequest::FilePath(path.into()));
+		borrowed.render_validity.invalidate();
+	}
+
+	/// `callback` is called with the description of the shown image whenever an other file is
+	/// shown
+	pub fn set_on_image_changed<T: Fn(&ipc::CurrentImage) + 'static>(&self, callback: T) {
+		self.data.borrow_mut().callbacks.on_image_changed = Some(Box::new(callback));
+	}
+
+	/// `callback` is called with the path of the shown file when the `new_window` action is
+	/// triggered, it's expected to open a new window that shows the file
+	pub fn set_on_new_window<T: Fn(Option<&Path>) + 'static>(&self, callback: T) {
+		self.data.borrow_mut().callbacks.on_new_window = Some(Box::new(callback));
+	}
+
+	/// `callback` is called when the `open_file` action is triggered, it's expected to let the user
+	/// choose images and open them
+	pub fn set_on_open_file<T: Fn() + 'static>(&self, callback: T) {
+		self.data.borrow_mut().callbacks.on_open_file = Some(Box::new(callback));
+	}
+
+	/// `callback` is called when the `open_folder` action is triggered, it's expected to let the
+	/// user choose a folder and pass it to `open_folder`
+	pub fn set_on_open_folder<T: Fn() + 'static>(&self, callback: T) {
+		self.data.borrow_mut().callbacks.on_open_folder = Some(Box::new(callback));
+	}
+
+	/// `callback` is called when the `open_playlist` action is triggered, it's expected to let the
+	/// user choose a playlist file and pass it to `open_playlist`
+	pub fn set_on_open_playlist<T: Fn() + 'static>(&self, callback: T) {
+		self.data.borrow_mut().callbacks.on_open_playlist = Some(Box::new(callback));
+	}
+
+	/// `callback` is called with the browsed images when the `save_playlist` action is triggered,
+	/// it's expected to let the user choose where the playlist is saved
+	pub fn set_on_save_playlist<T: Fn(Playlist) + 'static>(&self, callback: T) {
+		self.data.borrow_mut().callbacks.on_save_playlist = Some(Box::new(callback));
+	}
+
+	/// `callback` is called after the `cycle_profile` action activated an other profile, to apply
+	/// the settings of the profile that this widget doesn't handle
+	pub fn set_on_profile_changed<T: Fn() + 'static>(&self, callback: T) {
+		self.data.borrow_mut().callbacks.on_profile_changed = Some(Rc::new(callback));
+	}
+
+	/// Makes the image and presentation actions of this widget turn the slides of `audience`
+	/// instead, see `presenter`
+	pub fn set_audience(&self, audience: Rc<PictureWidget>) {
+		self.data.borrow_mut().audience = Some(audience);
+	}
+
+	/// Makes the `compare_side_by_side` action show the pinned image in `row`, next to this
+	/// widget. The view of the pinned image is only created while it's shown.
+	pub fn set_compare_row(&self, row: &Rc<HorizontalLayoutContainer>) {
+		self.data.borrow_mut().compare.row = Rc::downgrade(row);
+	}
+
+	/// The menu that the `context_menu` action opens
+	pub fn set_context_menu(&self, context_menu: ContextMenu) {
+		self.data.borrow_mut().context_menu = Some(context_menu);
+	}
+
+	/// Like the `play_present` action
+	pub fn toggle_presentation(&self) {
+		self.data.borrow_mut().toggle_presentation();
+	}
+
+	/// Pauses the presentation or resumes it, on the audience when there's one. This is for the
+	/// `toggle_slideshow` global hotkey.
+	pub fn toggle_slideshow(&self) {
+		let audience = self.data.borrow().audience.clone();
+		match audience {
+			Some(audience) => audience.toggle_slideshow(),
+			None => self.data.borrow_mut().toggle_slideshow(),
+		}
+	}
+
+	/// Like the `img_next` action
+	pub fn next_image(&self) {
+		self.data.borrow_mut().load_next_page();
+	}
+
+	/// Like the `img_prev` action
+	pub fn prev_image(&self) {
+		self.data.borrow_mut().load_prev_page();
+	}
+
+	/// Sets the size of an image pixel on the screen, 1 is the original size
+	pub fn set_zoom(&self, factor: f32) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.viewport.texel_size = factor.clamp(MIN_ZOOM_FACTOR, MAX_ZOOM_FACTOR);
+		borrowed.viewport.scaling = ScalingMode::Fixed;
+		borrowed.update_scaling_buttons();
+		borrowed.render_validity.invalidate();
+	}
+
+	pub fn set_fullscreen(&self, fullscreen: bool) {
+		self.data.borrow_mut().set_fullscreen(fullscreen);
+	}
+
+	pub fn reference_overlay(&self) -> bool {
+		self.data.borrow().reference_overlay.is_some()
+	}
+
+	/// Like the `toggle_overlay` action
+	pub fn set_reference_overlay(&self, enabled: bool) {
+		self.data.borrow_mut().set_reference_overlay(enabled);
+	}
+
+	/// Returns the window size closest to `window_size` at which the fitted image is shown at a
+	/// whole scale, like 50%, 100% or 200%. Returns `None` when the image isn't fitted to the
+	/// window. See the `snap_to_integer_scale` option.
+	pub fn integer_scale_window_size(
+		&self,
+		window_size: PhysicalSize<u32>,
+		dpi_scale: f32,
+	) -> Option<PhysicalSize<u32>> {
+		let borrowed = self.data.borrow();
+		if borrowed.viewport.scaling == ScalingMode::Fixed {
+			return None;
+		}
+		let (img_w, img_h) = borrowed.content_dimensions()?;
+		let bottom_bar_h = if borrowed.bottom_bar.should_show.get() {
+			(BOTTOM_BAR_HEIGHT * dpi_scale).round()
+		} else {
+			0.0
+		};
+		let drag_area_h = if crate::borderless(&borrowed.configuration.borrow()) {
+			(crate::DRAG_AREA_HEIGHT * dpi_scale).round()
+		} else {
+			0.0
+		};
+		let area_w = window_size.width as f32;
+		let area_h = window_size.height as f32 - bottom_bar_h - drag_area_h;
+		let fit_scale = (area_w / img_w).min(area_h / img_h);
+		let scale = if fit_scale >= 1.0 {
+			// Images that fit in the window are only enlarged when they are stretched
+			match borrowed.viewport.scaling {
+				ScalingMode::FitStretch => fit_scale.round(),
+				_ => 1.0,
+			}
+		} else {
+			1.0 / (1.0 / fit_scale).round()
+		};
+		Some(PhysicalSize::new(
+			(img_w * scale).round() as u32,
+			(img_h * scale + bottom_bar_h).round() as u32,
+		))
+	}
+
+	/// Describes the shown image for `ipc::Request::QueryCurrent`
+	pub fn current_image(&self) -> ipc::CurrentImage {
+		self.data.borrow_mut().current_image()
+	}
+
+	/// Handles the actions that last as long as the key or the mouse button is held down
+	fn handle_held_input(&self, input_key: &str, modifiers: ModifiersState, is_pressed: bool) {
+		let mut borrowed = self.data.borrow_mut();
+		macro_rules! triggered {
+			($action_name:expr) => {
+				action_triggered(
+					&borrowed.configuration,
+					$action_name,
+					input_key,
+					modifiers,
+					&borrowed.key_history,
+				)
+			};
+		}
+		if triggered!(PAN_NAME) {
+			borrowed.pointer.panning_2d = is_pressed;
+		}
+		if triggered!(PAN_VERT_NAME) {
+			borrowed.pointer.panning_vert = is_pressed;
+		}
+		if triggered!(PAN_HOR_NAME) {
+			borrowed.pointer.panning_hor = is_pressed;
+		}
+		if triggered!(PAN_HINT_NAME) {
+			borrowed.left_to_pan_hint.set_visible(is_pressed);
+		}
+		if triggered!(DRAG_OUT_NAME) {
+			borrowed.pointer.drag_out_start = Some(borrowed.last_mouse_pos).filter(|_| is_pressed);
+		}
+		if triggered!(PIXEL_INSPECTOR_NAME) && borrowed.inspecting != is_pressed {
+			borrowed.inspecting = is_pressed;
+			let cursor_pos = borrowed.last_mouse_pos;
+			borrowed.update_inspected_texel(cursor_pos);
+			borrowed.render_validity.invalidate();
+		}
+		if triggered!(FLICKER_A_B_NAME) {
+			borrowed.set_flicker(is_pressed);
+		}
+
+		let movements = [
+			(ZOOM_IN_NAME, Movement::ZoomIn),
+			(ZOOM_OUT_NAME, Movement::ZoomOut),
+			(PAN_LEFT_NAME, Movement::PanLeft),
+			(PAN_RIGHT_NAME, Movement::PanRight),
+			(PAN_UP_NAME, Movement::PanUp),
+			(PAN_DOWN_NAME, Movement::PanDown),
+		];
+		for (name, movement) in movements {
+			if triggered!(name) {
+				borrowed.camera.set_moving(movement, is_pressed);
+			}
+		}
+	}
+
+	fn handle_touch(
+		&self,
+		id: u64,
+		phase: TouchPhase,
+		pos: LogicalVector,
+		modifiers: ModifiersState,
+	) {
+		let mut borrowed = self.data.borrow_mut();
+		let input_key = match phase {
+			TouchPhase::Started => {
+				borrowed.pointer.start_touch(id, pos);
+				None
+			}
+			TouchPhase::Moved => {
+				borrowed.move_touch(id, pos);
+				None
+			}
+			TouchPhase::Ended | TouchPhase::Cancelled => {
+				borrowed.pointer.end_touch(id, pos, phase == TouchPhase::Cancelled)
+			}
+		};
+		drop(borrowed);
+		if let Some(input_key) = input_key {
+			self.handle_key_input(&input_key, modifiers);
+		}
+	}
+
+	fn handle_key_input(&self, input_key: &str, modifiers: ModifiersState) {
+		self.handle_input(ActionInput::Key { input_key, modifiers });
+	}
+
+	fn perform_menu_entry(&self, entry: MenuEntry) {
+		match entry {
+			MenuEntry::Action(action) => self.handle_input(ActionInput::Action(action)),
+			MenuEntry::Command(index) => {
+				self.data.borrow_mut().with_command_target(|data, target| {
+					let config = data.configuration.borrow();
+					execute_command(&config, index, target, &data.command_messages)
+				});
+			}
+		}
+	}
+
+	fn handle_input(&self, input: ActionInput) {
+		let mut borrowed = self.data.borrow_mut();
+		macro_rules! triggered {
+			($action_name:expr) => {
+				match input {
+					ActionInput::Key { input_key, modifiers } => action_triggered(
+						&borrowed.configuration,
+						$action_name,
+						input_key,
+						modifiers,
+						&borrowed.key_history,
+					),
+					ActionInput::Action(action) => action == $action_name,
+				}
+			};
+		}
+		if triggered!(TOGGLE_FULLSCREEN_NAME) {
+			if let Some(window) = borrowed.window.upgrade() {
+				borrowed.set_fullscreen(!window.fullscreen());
+			}
+		}
+		if triggered!(CYCLE_MONITOR_NAME) {
+			borrowed.cycle_monitor();
+		}
+		if triggered!(COMPARE_SIDE_BY_SIDE_NAME) {
+			borrowed.toggle_compare();
+		}
+		if triggered!(PIN_A_NAME) {
+			borrowed.pin_a();
+		}
+		if triggered!(DIFFERENCE_VIEW_NAME) {
+			borrowed.cycle_difference_view();
+		}
+		if triggered!(ONION_SKIN_NAME) {
+			borrowed.cycle_onion_skin();
+		}
+		if triggered!(NEW_WINDOW_NAME) {
+			if let Some(on_new_window) = &borrowed.callbacks.on_new_window {
+				match borrowed.playback_manager.shown_file_path() {
+					LoadedImgPath::Loaded(path) => on_new_window(Some(path)),
+					_ => on_new_window(None),
+				}
+			}
+		}
+		if triggered!(OPEN_FILE_NAME) {
+			if let Some(on_open_file) = &borrowed.callbacks.on_open_file {
+				on_open_file();
+			}
+		}
+		if triggered!(OPEN_FOLDER_NAME) {
+			if let Some(on_open_folder) = &borrowed.callbacks.on_open_folder {
+				on_open_folder();
+			}
+		}
+		if triggered!(CROP_NAME) {
+			let cropping = !borrowed.crop.active;
+			borrowed.set_cropping(cropping);
+		}
+		if triggered!(CROP_SAVE_NAME) && borrowed.crop.active {
+			borrowed.save_crop();
+		} else if triggered!(SAVE_NAME) {
+			borrowed.save_memory_image();
+		}
+		if triggered!(PASTE_NAME) {
+			borrowed.paste();
+		}
+		if triggered!(SAVE_PLAYLIST_NAME) {
+			if let Some(playlist) = borrowed.playback_manager.playlist() {
+				if let Some(on_save_playlist) = &borrowed.callbacks.on_save_playlist {
+					on_save_playlist(playlist);
+				}
+			}
+		}
+		if triggered!(OPEN_PLAYLIST_NAME) {
+			if let Some(on_open_playlist) = &borrowed.callbacks.on_open_playlist {
+				on_open_playlist();
+			}
+		}
+		if triggered!(EDIT_CONFIG_NAME) {
+			borrowed.configuration.borrow().open_in_editor();
+		}
+		if triggered!(SHOW_STATS_NAME) {
+			borrowed.show_stats();
+		}
+		if triggered!(ESCAPE_NAME) && borrowed.crop.active {
+			borrowed.set_cropping(false);
+		} else if triggered!(ESCAPE_NAME) {
+			if let Some(window) = borrowed.window.upgrade() {
+				if window.fullscreen() {
+					borrowed.set_fullscreen(false);
+				} else {
+					window.close();
+				}
+			}
+		}
+		if triggered!(PLAY_ANIM_NAME) {
+			match borrowed.playback_manager.playback_state() {
+				PlaybackState::Forward => borrowed.playback_manager.pause_playback(),
+				_ => borrowed.playback_manager.start_playback_forward(),
+			}
+		}
+		// The left key turns the page forward when reading from right to left
+		let (prev_triggered, next_triggered) =
+			(triggered!(IMG_PREV_NAME), triggered!(IMG_NEXT_NAME));
+		let (prev_triggered, next_triggered) = if borrowed.pages.right_to_left {
+			(next_triggered, prev_triggered)
+		} else {
+			(prev_triggered, next_triggered)
+		};
+		match borrowed.audience.clone() {
+			Some(audience) => {
+				if prev_triggered {
+					audience.prev_image();
+				}
+				if next_triggered {
+					audience.next_image();
+				}
+			}
+			None => {
+				if prev_triggered {
+					borrowed.load_prev_page();
+				}
+				if next_triggered {
+					borrowed.load_next_page();
+				}
+			}
+		}
+		if triggered!(STEP_FORWARD_NAME) {
+			borrowed.step_frame(1);
+		}
+		if triggered!(STEP_BACKWARD_NAME) {
+			borrowed.step_frame(-1);
+		}
+		if triggered!(PLAYBACK_FASTER_NAME) {
+			borrowed.step_frame_rate(1);
+		}
+		if triggered!(PLAYBACK_SLOWER_NAME) {
+			borrowed.step_frame_rate(-1);
+		}
+		if triggered!(TOGGLE_PLAYBACK_LOOP_NAME) {
+			borrowed.toggle_playback_loop();
+		}
+		if triggered!(EXPORT_AS_NAME) {
+			borrowed.start_export_as();
+		}
+		if triggered!(SNAPSHOT_NAME) {
+			borrowed.snapshot_requested = true;
+			borrowed.render_validity.invalidate();
+		}
+		if triggered!(MARK_RANGE_NAME) {
+			borrowed.mark_range();
+		}
+		if triggered!(EXPORT_ANIMATION_NAME) {
+			borrowed.start_animation_export();
+		}
+		if triggered!(TOGGLE_ANIMATION_NAME) {
+			borrowed.playback_manager.toggle_animation();
+			borrowed.render_validity.invalidate();
+		}
+		if triggered!(NEXT_FRAME_NAME) {
+			borrowed.playback_manager.step_animation(1);
+			borrowed.render_validity.invalidate();
+		}
+		if triggered!(PREV_FRAME_NAME) {
+			borrowed.playback_manager.step_animation(-1);
+			borrowed.render_validity.invalidate();
+		}
+		if triggered!(TOGGLE_TWO_PAGE_SPREAD_NAME) {
+			borrowed.toggle_two_page_spread();
+		}
+		if triggered!(TOGGLE_RIGHT_TO_LEFT_NAME) {
+			borrowed.toggle_right_to_left();
+		}
+		if triggered!(IMG_FIT_NAME) {
+			borrowed.set_img_size_to_fit(true);
+		}
+		if triggered!(IMG_FIT_BEST_NAME) {
+			borrowed.set_img_size_to_fit(false);
+		}
+		if triggered!(IMG_FIRST_NAME) {
+			borrowed.jump_to_end(false);
+		}
+		if triggered!(IMG_LAST_NAME) {
+			borrowed.jump_to_end(true);
+		}
+		if triggered!(IMG_ORIG_NAME) {
+			borrowed.set_img_size_to_orig();
+		}
+		if triggered!(TOGGLE_FIT_NAME) {
+			borrowed.toggle_fit();
+		}
+		if triggered!(TOGGLE_ANTIALIAS_NAME) {
+			borrowed.toggle_antialias();
+		}
+		if triggered!(SET_AUTOMATIC_ANTIALIAS_NAME) {
+			borrowed.set_automatic_antialias();
+		}
+		let prev_adjustments = borrowed.adjustments;
+		if triggered!(BRIGHTNESS_UP_NAME) {
+			borrowed.adjustments.step_brightness(1);
+		}
+		if triggered!(BRIGHTNESS_DOWN_NAME) {
+			borrowed.adjustments.step_brightness(-1);
+		}
+		if triggered!(CONTRAST_UP_NAME) {
+			borrowed.adjustments.step_contrast(1);
+		}
+		if triggered!(CONTRAST_DOWN_NAME) {
+			borrowed.adjustments.step_contrast(-1);
+		}
+		if triggered!(GAMMA_UP_NAME) {
+			borrowed.adjustments.step_gamma(1);
+		}
+		if triggered!(GAMMA_DOWN_NAME) {
+			borrowed.adjustments.step_gamma(-1);
+		}
+		if triggered!(RESET_ADJUSTMENTS_NAME) {
+			borrowed.adjustments = Adjustments::default();
+		}
+		if borrowed.adjustments != prev_adjustments {
+			borrowed.render_validity.invalidate();
+		}
+		if triggered!(TOGGLE_GRAYSCALE_NAME) {
+			borrowed.toggle_view_filter(ViewFilter::Grayscale);
+		}
+		if triggered!(TOGGLE_SEPIA_NAME) {
+			borrowed.toggle_view_filter(ViewFilter::Sepia);
+		}
+		if triggered!(TOGGLE_INVERT_NAME) {
+			borrowed.toggle_view_filter(ViewFilter::Invert);
+		}
+		if triggered!(TOGGLE_ON_TOP_NAME) {
+			borrowed.toggle_on_top();
+		}
+		if triggered!(TOGGLE_OVERLAY_NAME) {
+			let enabled = borrowed.reference_overlay.is_none();
+			borrowed.set_reference_overlay(enabled);
+		}
+		if triggered!(PLAY_PRESENT_NAME) {
+			match borrowed.audience.clone() {
+				Some(audience) => audience.toggle_presentation(),
+				None => borrowed.toggle_presentation(),
+			}
+		}
+		if triggered!(PLAY_PRESENT_RND_NAME) {
+			match borrowed.playback_manager.playback_state() {
+				PlaybackState::RandomPresent => borrowed.playback_manager.pause_playback(),
+				_ => borrowed.playback_manager.start_random_presentation(),
+			}
+			borrowed.render_validity.invalidate();
+		}
+		if triggered!(IMG_DEL_NAME) {
+			borrowed.delete_shown_file(false);
+		}
+		if triggered!(IMG_DEL_PERMANENT_NAME) {
+			borrowed.delete_shown_file(true);
+		}
+		if triggered!(UNDO_NAME) {
+			borrowed.undo_delete();
+		}
+		if triggered!(VERIFY_NAME) {
+			borrowed.start_verification();
+		}
+		if triggered!(RENAME_NAME) {
+			borrowed.start_rename();
+		}
+		if let Some(rating) = RATE_NAMES.iter().position(|name| triggered!(*name)) {
+			borrowed.rate_shown_file(rating as u8);
+		}
+		if triggered!(RATING_FILTER_NAME) {
+			borrowed.cycle_rating_filter();
+		}
+		if triggered!(EDIT_TAGS_NAME) {
+			borrowed.start_tagging();
+		}
+		if triggered!(TAG_FILTER_NAME) {
+			borrowed.start_tag_filter();
+		}
+		if triggered!(NAME_FILTER_NAME) {
+			borrowed.start_name_filter();
+		}
+		if triggered!(LOCK_ZOOM_NAME) {
+			borrowed.toggle_zoom_lock();
+		}
+		if triggered!(READING_MODE_NAME) {
+			borrowed.toggle_reading_mode();
+		}
+		if triggered!(IMG_COPY_NAME) && borrowed.crop_selection().is_some() && borrowed.crop.active
+		{
+			borrowed.copy_crop();
+		} else if triggered!(IMG_COPY_NAME) {
+			if let LoadedImgPath::InMemory(_) = borrowed.playback_manager.shown_file_path() {
+				if let Some(texture) = borrowed.get_texture() {
+					borrowed.copy_notifications.set_started();
+					let result = editing::copy_image_to_clipboard((*texture.pixels).clone());
+					if let Err(e) = &result {
+						log::error!("{}", e);
+					}
+					borrowed.copy_notifications.set_finished(result.is_ok());
+				}
+			} else if let LoadedImgPath::Loaded(path) =
+				borrowed.playback_manager.shown_file_path().clone()
+			{
+				let request_started;
+				if let Some(clipboard_handler) = &mut borrowed.clipboard_handler {
+					request_started = true;
+					clipboard_handler.request_copy(path);
+					borrowed.copy_notifications.set_started();
+				} else {
+					request_started = false;
+				}
+				if request_started {
+					borrowed.clipboard_request_was_pending = true;
+				}
+			}
+		}
+		if triggered!(COPY_PATH_NAME) {
+			borrowed.copy_path();
+		}
+		if triggered!(CONTEXT_MENU_NAME) {
+			borrowed.open_context_menu();
+		}
+		if triggered!(REVEAL_IN_FILE_MANAGER_NAME) {
+			borrowed.reveal_in_file_manager();
+		}
+		if triggered!(SET_WALLPAPER_NAME) {
+			borrowed.set_wallpaper();
+		}
+		if triggered!(SHARE_NAME) {
+			borrowed.share();
+		}
+		if triggered!(OPEN_WITH_EDITOR_NAME) {
+			borrowed.with_command_target(|data, target| {
+				open_with_editor(&data.configuration.borrow(), target, &data.command_messages)
+			});
+		}
+		if let ActionInput::Key { input_key, modifiers } = input {
+			borrowed.with_command_target(|data, target| {
+				execute_triggered_commands(
+					data.configuration.clone(),
+					input_key,
+					modifiers,
+					&data.key_history,
+					target,
+					&data.command_messages,
+				)
+			});
+			let sort_folder = {
+				let config = borrowed.configuration.borrow();
+				let mut sort_folders = config.sort_folders.iter().flatten();
+				let history = &borrowed.key_history;
+				sort_folders
+					.find(|folder| keys_triggered(&folder.input, input_key, modifiers, history))
+					.cloned()
+			};
+			if let Some(sort_folder) = sort_folder {
+				borrowed.sort_shown_file(&sort_folder);
+			}
+		}
+		if triggered!(TOGGLE_BOTTOM_BAR_NAME) {
+			let fullscreen = borrowed.window.upgrade().is_some_and(|window| window.fullscreen());
+			borrowed.bottom_bar.toggle(fullscreen);
+		}
+		let (toggle_help, toggle_theme) =
+			(triggered!(TOGGLE_HELP_NAME), triggered!(TOGGLE_THEME_NAME));
+		let on_profile_changed = if triggered!(CYCLE_PROFILE_NAME) && borrowed.cycle_profile() {
+			borrowed.callbacks.on_profile_changed.clone()
+		} else {
+			None
+		};
+		let bottom_bar = borrowed.bottom_bar.clone();
+		// The buttons change other widgets, which may include this one
+		drop(borrowed);
+		if toggle_help {
+			bottom_bar.help_button.click();
+		}
+		if toggle_theme {
+			bottom_bar.theme_button.click();
+		}
+		if let Some(on_profile_changed) = on_profile_changed {
+			on_profile_changed();
+		}
+	}
+}
+
+impl Widget for PictureWidget {
+	fn before_draw(&self, window: &Window) -> NextUpdate {
+		let chosen = self.data.borrow().context_menu.as_ref().and_then(|menu| menu.take_chosen());
+		if let Some(entry) = chosen {
+			self.perform_menu_entry(entry);
+		}
+		let mut data = self.data.borrow_mut();
+		if !data.visible {
+			return NextUpdate::Latest;
+		}
+		if data.first_draw {
+			// Don't block on the main thread and
+			// wait on the image to be loaded on the first draw,
+			// instead let the ui draw itself first and then we can wait.
+			data.first_draw = false;
+			data.next_update = NextUpdate::Soonest;
+			return data.next_update;
+		}
+		let now = Instant::now();
+		let prev_texture = data.playback_manager.image_texture();
+		data.next_update = data.playback_manager.update_image(window);
+		if data.start_at.is_some() {
+			data.jump_to_start_at();
+		}
+		let new_texture = data.playback_manager.image_texture();
+		data.update_pages();
+		let curr_file_index = data.playback_manager.current_file_index();
+		let curr_dir_len = data.playback_manager.current_dir_len();
+		if let (Some(curr_file_index), Some(curr_dir_len)) = (curr_file_index, curr_dir_len) {
+			// dbg!(curr_file_index);
+			// dbg!(curr_dir_len);
+			data.bottom_bar.slider.set_steps(curr_dir_len as u32, curr_file_index as u32);
+		}
+		//data.slider.set_step_bg(data.playback_manager.cached_from_dir());
+		let current_path = data.playback_manager.current_file_path();
+		let header_outdated = match (&data.header, &current_path) {
+			(Some((header_path, _)), Some(current_path)) => header_path != current_path,
+			(None, None) => false,
+			_ => true,
+		};
+		if header_outdated {
+			data.annotations.load(current_path.as_deref());
+			data.header = current_path.map(|path| {
+				let header = image_loader::read_header(&path).ok();
+				(path, header)
+			});
+		}
+		data.update_flicker(now);
+		let playback_state = data.playback_manager.playback_state();
+		data.set_window_title_filename(
+			window,
+			playback_state,
+			data.playback_manager.shown_file_path(),
+		);
+		if let LoadedImgPath::Loaded(path) = data.playback_manager.shown_file_path() {
+			if data.reported_path.as_ref() != Some(path) {
+				let path = path.clone();
+				if stats::enabled(&data.configuration.borrow()) {
+					data.cache.lock().unwrap().stats.record_view(&path);
+				}
+				let prev_folder = data.reported_path.as_deref().and_then(Path::parent);
+				let folder_changed = prev_folder != path.parent();
+				data.apply_view_rule(&path);
+				if folder_changed {
+					data.apply_folder_start_mode();
+				}
+				data.restore_remembered_view(&path);
+				data.reading.image_changed();
+				data.reported_path = Some(path);
+				let current_image = data.current_image();
+				if let Some(on_image_changed) = &data.callbacks.on_image_changed {
+					on_image_changed(&current_image);
+				}
+			}
+		}
+		let texture_changed = match (&prev_texture, &new_texture) {
+			(Some(prev_tex), Some(new_tex)) => !Rc::ptr_eq(&prev_tex.tex_grid, &new_tex.tex_grid),
+			(prev, new) => prev.is_none() != new.is_none(),
+		};
+		if texture_changed {
+			if let (Some(prev_texture), Some(new_texture)) = (&prev_texture, &new_texture) {
+				data.crop.tool.clear();
+				if data.zoom_locked {
+					let prev_dimensions = prev_texture.oriented_dimensions();
+					data.keep_relative_position(prev_dimensions, new_texture.oriented_dimensions());
+				}
+			}
+			if data.adaptive_background {
+				data.image_luminance =
+					new_texture.as_ref().and_then(|tex| pixel_ops::average_luminance(&tex.pixels));
+			}
+			data.render_validity.invalidate();
+		}
+		// The option may also have been switched by reloading the config
+		let ambient_background = data.ambient_background();
+		if !ambient_background {
+			data.ambient = None;
+		} else if texture_changed || (data.ambient.is_none() && new_texture.is_some()) {
+			data.ambient = new_texture.and_then(|tex| create_ambient(&window.display_mut(), &tex));
+			if data.ambient.is_some() {
+				data.render_validity.invalidate();
+			}
+		}
+		if let Some(clipboard_handler) = &data.clipboard_handler {
+			let clipboard_result = clipboard_handler.try_get_result();
+			let request_pending = clipboard_result.is_none();
+			if data.clipboard_request_was_pending != request_pending {
+				match clipboard_result {
+					Some(succeeded) => data.copy_notifications.set_finished(succeeded),
+					None => data.copy_notifications.set_started(),
+				}
+				data.clipboard_request_was_pending = request_pending;
+			} else if request_pending {
+				let next_update = now + Duration::from_millis(100);
+				data.next_update = data.next_update.aggregate(NextUpdate::WaitUntil(next_update));
+			}
+		}
+		while let Some(report) = data.command_messages.try_recv() {
+			if report.message.is_some() {
+				data.notice = report.message;
+			}
+			if report.reload {
+				data.refresh_folder();
+			}
+		}
+		if data.command_messages.is_running() {
+			let next_update = now + Duration::from_millis(200);
+			data.next_update = data.next_update.aggregate(NextUpdate::WaitUntil(next_update));
+		}
+		data.poll_export(now);
+		match data.verification.as_ref().map(BackgroundVerification::try_finish) {
+			Some(Some(report)) => {
+				data.verification = None;
+				data.notice = Some(report.summary());
+				if let Err(e) = verify::show(&report) {
+					log::error!("Could not show the verification report: {}", e);
+				}
+				data.next_update = NextUpdate::Soonest;
+			}
+			Some(None) => {
+				let next_update = now + Duration::from_millis(200);
+				data.next_update = data.next_update.aggregate(NextUpdate::WaitUntil(next_update));
+			}
+			None => (),
+		}
+		if data.camera.moving() {
+			data.render_validity.invalidate();
+			data.next_update = NextUpdate::Soonest;
+		}
+		if now >= data.next_file_check {
+			data.next_file_check = now + FILE_CHECK_INTERVAL;
+			data.handle_file_removed();
+		}
+		let next_file_check = data.next_file_check;
+		data.next_update = data.next_update.aggregate(NextUpdate::WaitUntil(next_file_check));
+		if let Some(gesture_end) = data.zoom_gesture_end {
+			if now >= gesture_end {
+				data.zoom_gesture_end = None;
+				data.render_validity.invalidate();
+			} else {
+				data.next_update = data.next_update.aggregate(NextUpdate::WaitUntil(gesture_end));
+			}
+		}
+		let next_copy_noti_update = data.copy_notifications.update();
+		data.next_update = data.next_update.aggregate(next_copy_noti_update);
+		data.update_compare_view();
+		data.next_update
+	}
+
+	fn draw(&self, target: &mut Frame, context: &DrawContext) -> Result<NextUpdate, WidgetError> {
+		let texture;
+		let mut snapshot = None;
+		{
+			let mut data = self.data.borrow_mut();
+			if !data.visible {
+				return Ok(data.next_update);
+			}
+			data.dpi_scale = context.dpi_scale_factor;
+			data.update_image_transform(context.dpi_scale_factor);
+			data.apply_camera_movement(context.dpi_scale_factor);
+			texture = match &data.compare.image_a {
+				Some((image_a, _)) if data.compare.showing_a => Some(image_a.clone()),
+				_ => data.get_texture(),
+			};
+		}
+		if let Some(texture) = texture {
+			let data = self.data.borrow();
+			let swatch = match data.inspected_texel {
+				Some(texel) if data.inspecting && !data.compare.showing_a => {
+					Some((data.last_mouse_pos, texel.color))
+				}
+				_ => None,
+			};
+			let crop_rect = data.crop_rect();
+			let bounds = data.drawn_bounds;
+			if let Some(color) = data.folder_background {
+				context.clear_color(target, color, Some(bounds));
+			} else if let (true, Some(luminance)) = (data.adaptive_background, data.image_luminance)
+			{
+				let shade = ADAPTIVE_BG_DARK + (ADAPTIVE_BG_LIGHT - ADAPTIVE_BG_DARK) * luminance;
+				context.clear_color(target, [shade, shade, shade, 1.0], Some(bounds));
+			}
+			if let Some(ambient) = &data.ambient {
+				draw_ambient(&data, target, context, ambient);
+			}
+			let (page_pos, facing) = data.page_positions();
+			let texel_size = data.viewport.texel_size;
+			let overlay = data.overlay(&texture);
+			draw_tex_grid(&data, target, context, &texture, page_pos, texel_size, overlay);
+			if let (Some(facing_page), Some((facing_pos, facing_texel_size)), false) =
+				(&data.pages.facing_page, facing, data.compare.showing_a)
+			{
+				draw_tex_grid(
+					&data,
+					target,
+					context,
+					facing_page,
+					facing_pos,
+					facing_texel_size,
+					None,
+				);
+			}
+			// The view is read before the overlays of the tools are drawn over it
+			if data.snapshot_requested {
+				snapshot = Some(read_view(target, context, bounds));
+			}
+			if let Some(crop_rect) = crop_rect {
+				draw_crop_overlay(target, context, bounds, crop_rect);
+			}
+			if let Some((cursor_pos, color)) = swatch {
+				draw_color_swatch(target, context, cursor_pos, color);
+			}
+		}
+		if let Some(snapshot) = snapshot {
+			let mut borrowed = self.data.borrow_mut();
+			borrowed.snapshot_requested = false;
+			match snapshot {
+				Ok(snapshot) => borrowed.save_snapshot(snapshot),
+				Err(e) => log::error!("Could not read the view for the snapshot: {}", e),
+			}
+		}
+		let borrowed = self.data.borrow();
+		Ok(borrowed.next_update)
+	}
+
+	fn layout(&self, available_space: LogicalRect) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.default_layout(available_space);
+		borrowed.hover = borrowed.drawn_bounds.contains(borrowed.last_mouse_pos);
+	}
+
+	fn handle_event(&self, event: &Event) {
+		if !self.data.borrow().visible {
+			return;
+		}
+		// The pinned image is controlled from this widget, as if the cursor was over the same spot
+		// of this widget
+		let offset = self.data.borrow().compare_offset(event.cursor_pos);
+		let translated =
+			offset.map(|offset| Event { cursor_pos: event.cursor_pos - offset, ..event.clone() });
+		let event = translated.as_ref().unwrap_or(event);
+		let menu_open = self.data.borrow().context_menu.as_ref().is_some_and(|menu| menu.is_open());
+		// The input goes to the context menu while it's open
+		if menu_open
+			&& matches!(
+				event.kind,
+				EventKind::MouseButton { .. }
+					| EventKind::MouseScroll { .. }
+					| EventKind::TouchpadScroll { .. }
+					| EventKind::TouchpadMagnify { .. }
+					| EventKind::Touch { .. }
+					| EventKind::KeyInput { .. }
+			) {
+			return;
+		}
+		match event.kind {
+			EventKind::MouseMove => {
+				let mut borrowed = self.data.borrow_mut();
+				borrowed.hover = borrowed.drawn_bounds.contains(event.cursor_pos);
+				if let Some(start) = borrowed.pointer.gesture_start {
+					if recognize_gesture(event.cursor_pos - start).is_some() {
+						// The hint would only be in the way while making a gesture
+						borrowed.left_to_pan_hint.set_visible(false);
+					}
+				}
+				let delta = event.cursor_pos - borrowed.last_mouse_pos;
+				if let Some(delta) = borrowed.pointer.panning_delta(delta) {
+					borrowed.viewport.scaling = ScalingMode::Fixed;
+					borrowed.update_scaling_buttons();
+					borrowed.viewport.pos += delta;
+					borrowed.render_validity.invalidate();
+				}
+				if borrowed.inspecting {
+					borrowed.update_inspected_texel(event.cursor_pos);
+					borrowed.render_validity.invalidate();
+				}
+				if borrowed.pointer.drag_out_moved(event.cursor_pos) {
+					if let LoadedImgPath::Loaded(path) = borrowed.playback_manager.shown_file_path()
+					{
+						drag_out::start(path);
+					}
+				}
+				if borrowed.crop.tool.is_dragging() {
+					borrowed.crop_drag_to(event.cursor_pos);
+				}
+				borrowed.last_mouse_pos = event.cursor_pos;
+			}
+			EventKind::MouseButton { state, button, .. } => {
+				let pressed = state == ElementState::Pressed;
+				let mut borrowed = self.data.borrow_mut();
+				if !pressed {
+					// The modifier of `drag_out` may be released before the button
+					borrowed.pointer.drag_out_start = None;
+				}
+				let mut gesture = None;
+				if button == MouseButton::Right {
+					if pressed {
+						borrowed.pointer.gesture_start =
+							Some(event.cursor_pos).filter(|_| borrowed.hover);
+					} else if let Some(start) = borrowed.pointer.gesture_start.take() {
+						gesture = recognize_gesture(event.cursor_pos - start);
+					}
+				}
+				borrowed.render_validity.invalidate();
+				// The left button is used by the pixel inspector and the crop tool while they're active
+				if button == MouseButton::Left && borrowed.hover && pressed {
+					if borrowed.inspecting {
+						borrowed.copy_inspected_texel();
+						return;
+					}
+					if borrowed.crop.active {
+						borrowed.begin_crop_drag(event.cursor_pos);
+						return;
+					}
+				}
+				if button == MouseButton::Left && !pressed && borrowed.crop.tool.is_dragging() {
+					borrowed.crop.tool.end_drag();
+					return;
+				}
+				let hover = borrowed.hover;
+				if pressed && !hover {
+					return;
+				}
+				let input_key = mouse_button_to_input_key(button);
+				let click = if pressed {
+					borrowed.pointer.press(&input_key, event.cursor_pos);
+					false
+				} else {
+					borrowed.pointer.release(&input_key, event.cursor_pos, gesture.is_some())
+				};
+				let double_click = !pressed && hover && borrowed.pointer.double_click(&input_key);
+				drop(borrowed);
+				if pressed {
+					self.handle_key_input(&input_key, event.modifiers);
+				}
+				self.handle_held_input(&input_key, event.modifiers, pressed);
+				if click {
+					self.handle_key_input(&format!("{}click", input_key), event.modifiers);
+				}
+				if double_click {
+					self.handle_key_input(&format!("{}double", input_key), event.modifiers);
+				}
+				if let Some(gesture) = gesture {
+					self.handle_key_input(&gesture, event.modifiers);
+				}
+			}
+			EventKind::MouseScroll { delta } => {
+				// Actions bound to `WheelUp` and `WheelDown` are triggered once per step of the wheel
+				let steps = self.data.borrow_mut().pointer.wheel_steps(delta.vec.y);
+				let wheel_key = wheel_input_key(steps > 0.0);
+				for _ in 0..steps.abs() as u32 {
+					self.handle_key_input(wheel_key, event.modifiers);
+				}
+				let mut borrowed = self.data.borrow_mut();
+				if !action_triggered(
+					&borrowed.configuration,
+					SCROLL_ZOOM_NAME,
+					WHEEL_INPUT_KEY,
+					event.modifiers,
+					&borrowed.key_history,
+				) {
+					return;
+				}
+				if borrowed.reading.active {
+					borrowed.scroll_reading(delta.vec.y);
+				} else {
+					let delta = delta.vec.y * 0.375;
+					borrowed.zoom_image(event.cursor_pos, delta);
+				}
+			}
+			EventKind::TouchpadScroll { delta } => {
+				let mut borrowed = self.data.borrow_mut();
+				if event.modifiers.control_key() {
+					// Some systems report pinching as scrolling with Ctrl held down
+					borrowed.zoom_image(event.cursor_pos, delta.vec.y * TOUCHPAD_ZOOM_SPEED);
+				} else if borrowed.reading.active {
+					borrowed.scroll_reading(delta.vec.y / READING_SCROLL_STEP);
+				} else {
+					borrowed.viewport.pan(delta);
+					borrowed.update_scaling_buttons();
+					borrowed.render_validity.invalidate();
+				}
+			}
+			EventKind::TouchpadMagnify { delta } => {
+				self.data.borrow_mut().zoom_image(event.cursor_pos, delta);
+			}
+			EventKind::Touch { id, phase, pos } => {
+				self.handle_touch(id, phase, pos, event.modifiers);
+			}
+			EventKind::KeyInput { ref input } => {
+				if self.data.borrow().prompt.is_some() && input.state == ElementState::Pressed {
+					// The keys edit the text instead of triggering actions
+					self.data.borrow_mut().handle_prompt_input(input);
+					return;
+				}
+				let key = input.key_without_modifiers();
+				let is_pressed = input.state == ElementState::Pressed;
+				//println!("Got input for {:?}", key);
+				let input_key_str = virtual_keycode_to_string(&key).to_lowercase();
+				let input_key_str = char_to_input_key(&input_key_str);
+				if is_pressed {
+					self.data.borrow_mut().key_history.push(&input_key_str, event.modifiers);
+					self.handle_key_input(input_key_str.as_str(), event.modifiers);
+				}
+				self.handle_held_input(input_key_str.as_str(), event.modifiers, is_pressed);
+			}
+			EventKind::DroppedFile(ref path) => {
+				let mut borrowed = self.data.borrow_mut();
+				if path.is_dir() {
+					let playlist = Playlist { paths: vec![path.clone()], current: None };
+					if let Err(e) = borrowed.playback_manager.open_playlist(playlist) {
+						log::error!("Could not open the dropped folder: {}", e);
+					}
+				} else {
+					borrowed.playback_manager.request_load(LoadRequest::FilePath(path.clone()));
+				}
+				borrowed.hover_state = HoverState::None;
+				borrowed.render_validity.invalidate();
+			}
+			EventKind::HoveredFile(ref path) => {
+				let mut borrowed = self.data.borrow_mut();
+				match borrowed.hover_state {
+					HoverState::None => {
+						let curr_path = borrowed.playback_manager.shown_file_path().clone();
+						borrowed.hover_state = HoverState::ItemHovered { prev_path: curr_path };
+					}
+					HoverState::ItemHovered { .. } => {}
+				}
+				borrowed.playback_manager.request_load(LoadRequest::FilePath(path.clone()));
+				borrowed.render_validity.invalidate();
+			}
+			EventKind::HoveredFileCancelled => {
+				let mut borrowed = self.data.borrow_mut();
+				match borrowed.hover_state.clone() {
+					HoverState::None => {
+						// Suprisingly this does happen sometimes, so let's just ignore this.
+					}
+					HoverState::ItemHovered { prev_path } => {
+						if let LoadedImgPath::Loaded(prev_path) = prev_path {
+							borrowed
+								.playback_manager
+								.request_load(LoadRequest::FilePath(prev_path));
+						}
+						borrowed.hover_state = HoverState::None;
+					}
+				}
+				borrowed.render_validity.invalidate();
+			}
+			EventKind::Focused(focused) => {
+				if focused {
+					self.data.borrow_mut().refresh_folder();
+				}
+			}
+			EventKind::CloseRequested => {
+				let mut borrowed = self.data.borrow_mut();
+				// Just let it drop.
+				borrowed.clipboard_handler.take();
+			}
+		}
+	}
+
+	// No children for a button
+	fn children(&self, _children: &mut Vec<Rc<dyn Widget>>) {}
+
+	fn placement(&self) -> WidgetPlacement {
+		self.data.borrow().placement
+	}
+
+	fn visible(&self) -> bool {
+		self.data.borrow().visible
+	}
+
+	fn set_valid_ref(&self, render_validity: RenderValidity) {
+		self.data.borrow_mut().render_validity = render_validity;
+	}
+}
+
+impl Drop for PictureWidget {
+	fn drop(&mut self) {
+		// This doesn't work. Would be nice to fix at some point. I think I managed to create a
+		// circular reference with my `Rc`s
+		//println!("Called drop for the picture widget.");
+	}
+}
+
+/// Draws `texture` centered at `img_pos`
+fn draw_tex_grid(
+	data: &PictureWidgetData,
+	target: &mut Frame,
+	context: &DrawContext,
+	texture: &AnimationFrameTexture,
+	img_pos: LogicalVector,
+	img_texel_size: f32,
+	overlay: Option<Overlay>,
+) {
+	let size = data.drawn_bounds.size.vec;
+	let projection_transform = gelatin::cgmath::ortho(0.0, size.x, size.y, 0.0, -1.0, 1.0);
+
+	let viewport_rect = context.logical_rect_to_viewport(&data.drawn_bounds);
+	let image_draw_params =
+		gelatin::glium::DrawParameters { viewport: Some(viewport_rect), ..Default::default() };
+
+	let img_phys_w = texture.w as f32;
+	let img_phys_h = texture.h as f32;
+	let img_height_over_width = img_phys_h / img_phys_w;
+	let image_display_width = img_texel_size * img_phys_w / context.dpi_scale_factor;
+	let image_display_height = image_display_width * img_height_over_width;
+	// Model tranform
+	let img_pyhs_pos = img_pos.vec * context.dpi_scale_factor;
+	let img_phys_siz = {
+		let img_phys_w = image_display_width * context.dpi_scale_factor;
+		let img_phys_h = image_display_height * context.dpi_scale_factor;
+		LogicalVector::new(img_phys_w.ceil(), img_phys_h.ceil())
+	};
+	let img_logical_corner_x =
+		(img_pyhs_pos.x - img_phys_siz.vec.x * 0.5).ceil() / context.dpi_scale_factor;
+	let img_logical_corner_y =
+		(img_pyhs_pos.y - img_phys_siz.vec.y * 0.5).ceil() / context.dpi_scale_factor;
+
+	// This is the display width of the image in logical pixel units
+	let img_adjusted_w = img_phys_siz.vec.x / context.dpi_scale_factor;
+	// This is the display height of the image in logical pixel units
+	let img_adjusted_h = img_phys_siz.vec.y / context.dpi_scale_factor;
+	let img_scaling = Matrix4::from_nonuniform_scale(img_adjusted_w, img_adjusted_h, 1.0);
+	let orientation;
+	{
+		let to_center = Matrix4::from_translation(Vector3::new(
+			-0.5 * img_adjusted_w,
+			-0.5 * img_adjusted_h,
+			0.0,
+		));
+		let orient = orientation_to_matrix(texture.orientation);
+		let to_corner = Matrix4::from_translation(Vector3::new(
+			0.5 * img_adjusted_w,
+			0.5 * img_adjusted_h,
+			0.0,
+		));
+		orientation = to_corner * orient * to_center;
+	}
+	let img_translation =
+		Matrix4::from_translation(Vector3::new(img_logical_corner_x, img_logical_corner_y, 0.0));
+
+	// let img_logical_w = img_w / context.dpi_scale_factor;
+	// let img_logical_h = img_h / context.dpi_scale_factor;
+	let cell_phy_step = texture.cell_step_size;
+	for cell_tex in texture.tex_grid.iter() {
+		let (cell_phys_w, cell_phys_h) = cell_tex.tex.dimensions();
+
+		let cell_phy_offset_x = cell_phy_step * cell_tex.col;
+		let cell_phy_offset_y = cell_phy_step * cell_tex.row;
+		// let cell_logical_offset_x = cell_phy_offset_x as f32 / context.dpi_scale_factor;
+		// let cell_logical_offset_y = cell_phy_offset_y as f32 / context.dpi_scale_factor;
+
+		// The grid is constructed so that it is exactly of size (1, 1) and is located at (0, 0)
+		// This allows to leave most of the image transformation logic unchanged.
+		let cell_scaling = Matrix4::from_nonuniform_scale(
+			cell_phys_w as f32 / img_phys_w,
+			cell_phys_h as f32 / img_phys_h,
+			1.0,
+		);
+		let cell_translation = Matrix4::from_translation(Vector3::new(
+			cell_phy_offset_x as f32 / img_phys_w,
+			cell_phy_offset_y as f32 / img_phys_h,
+			0.0,
+		));
+
+		let transform =
+			img_translation * orientation * img_scaling * cell_translation * cell_scaling;
+		// Projection tranform
+		let transform = projection_transform * transform;
+
+		// Without antialiasing the pixels stay sharp when zoomed out too
+		let minify_filter = match data.antialiasing {
+			Antialias::Never => MinifySamplerFilter::Nearest,
+			Antialias::Auto | Antialias::Always => MinifySamplerFilter::LinearMipmapLinear,
+		};
+		let sampler = cell_tex
+			.tex
+			.sampled()
+			.minify_filter(minify_filter)
+			.wrap_function(gelatin::glium::uniforms::SamplerWrapFunction::Clamp);
+
+		let filter = match data.antialiasing {
+			Antialias::Auto if img_texel_size < AA_TEXEL_SIZE_THRESHOLD => {
+				MagnifySamplerFilter::Linear
+			}
+			Antialias::Auto | Antialias::Never => MagnifySamplerFilter::Nearest,
+			Antialias::Always => MagnifySamplerFilter::Linear,
+		};
+		let sampler = sampler.magnify_filter(filter);
+
+		// building the uniforms
+		let mut lod_level = ((1.0 / img_texel_size).log2().max(0.0) + 0.125).floor();
+		// The mipmaps are generated when the image is uploaded, so while zooming, zoomed out
+		// images are sampled from a coarser level to keep up with the display refresh rate.
+		if lod_level > 0.0 && data.zoom_gesture_end.is_some() {
+			lod_level += ZOOM_GESTURE_LOD_BIAS;
+		}
+		let supersample = img_texel_size < 1.0
+			&& data.zoom_gesture_end.is_none()
+			&& minify_filter != MinifySamplerFilter::Nearest
+			&& data.downscaling() == Downscaling::HighQuality;
+		let heatmap = data.compare.difference_view == DifferenceView::Heatmap;
+		let (other, mode) = match overlay {
+			Some(Overlay::OnionSkin(other)) => (Some(other), 0),
+			Some(Overlay::Difference(other)) if heatmap => (Some(other), 2),
+			Some(Overlay::Difference(other)) => (Some(other), 1),
+			None => (None, 0),
+		};
+		// The grids of images of the same size are split the same way
+		let same_cell =
+			|cell: &&TextureGridItem| cell.col == cell_tex.col && cell.row == cell_tex.row;
+		let other_cell = other.and_then(|other| other.tex_grid.iter().find(same_cell));
+		if let Some(other_cell) = other_cell {
+			let other_sampler = other_cell
+				.tex
+				.sampled()
+				.minify_filter(minify_filter)
+				.wrap_function(gelatin::glium::uniforms::SamplerWrapFunction::Clamp)
+				.magnify_filter(filter);
+			let uniforms = uniform! {
+				matrix: Into::<[[f32; 4]; 4]>::into(transform),
+				tex: sampler,
+				tex_other: other_sampler,
+				lod_level: lod_level,
+				mode: mode,
+				gain: data.difference_gain(),
+				opacity: data.onion_skin_opacity(),
+				bright_shade: data.bright_shade,
+			};
+			target
+				.draw(
+					context.unit_quad_vertices,
+					context.unit_quad_indices,
+					&data.compare_program,
+					&uniforms,
+					&image_draw_params,
+				)
+				.unwrap();
+			continue;
+		}
+		let uniforms = uniform! {
+			matrix: Into::<[[f32; 4]; 4]>::into(transform),
+			bright_shade: data.bright_shade,
+			tex: sampler,
+			lod_level: lod_level,
+			checkerboard: data.checkerboard,
+			supersample: supersample,
+			brightness: data.adjustments.brightness as f32 / 100.0,
+			contrast: data.adjustments.contrast as f32 / 100.0,
+			gamma: data.adjustments.gamma as f32 / 100.0,
+			grayscale: data.view_filters.contains(&ViewFilter::Grayscale),
+			sepia: data.view_filters.contains(&ViewFilter::Sepia),
+			invert: data.view_filters.contains(&ViewFilter::Invert),
+		};
+		target
+			.draw(
+				context.unit_quad_vertices,
+				context.unit_quad_indices,
+				&data.program,
+				&uniforms,
+				&image_draw_params,
+			)
+			.unwrap();
+	}
+}
+
+/// Uploads the small blurred copy of the image for `ambient_background`
+fn create_ambient(
+	display: &gelatin::Display,
+	texture: &AnimationFrameTexture,
+) -> Option<SrgbTexture2d> {
+	let image = pixel_ops::ambient_image(&texture.pixels, texture.orientation);
+	let dimensions = image.dimensions();
+	let raw_image = RawImage2d::from_raw_rgba(image.into_raw(), dimensions);
+	match SrgbTexture2d::with_mipmaps(display, raw_image, MipmapsOption::NoMipmap) {
+		Ok(ambient) => Some(ambient),
+		Err(e) => {
+			log::error!("Could not create the ambient background: {}", e);
+			None
+		}
+	}
+}
+
+/// Draws the blurred copy of the image stretched to cover the whole widget, keeping its aspect
+/// ratio. It's drawn with the adjustments and the filters of the image, but darker.
+fn draw_ambient(
+	data: &PictureWidgetData,
+	target: &mut Frame,
+	context: &DrawContext,
+	ambient: &SrgbTexture2d,
+) {
+	let size = data.drawn_bounds.size.vec;
+	let projection_transform = gelatin::cgmath::ortho(0.0, size.x, size.y, 0.0, -1.0, 1.0);
+	let viewport_rect = context.logical_rect_to_viewport(&data.drawn_bounds);
+	let draw_params =
+		gelatin::glium::DrawParameters { viewport: Some(viewport_rect), ..Default::default() };
+
+	let (w, h) = ambient.dimensions();
+	let scale = (size.x / w as f32).max(size.y / h as f32);
+	let (w, h) = (w as f32 * scale, h as f32 * scale);
+	let translation =
+		Matrix4::from_translation(Vector3::new((size.x - w) * 0.5, (size.y - h) * 0.5, 0.0));
+	let transform = projection_transform * translation * Matrix4::from_nonuniform_scale(w, h, 1.0);
+	let sampler = ambient
+		.sampled()
+		.minify_filter(MinifySamplerFilter::Linear)
+		.magnify_filter(MagnifySamplerFilter::Linear)
+		.wrap_function(gelatin::glium::uniforms::SamplerWrapFunction::Clamp);
+	let uniforms = uniform! {
+		matrix: Into::<[[f32; 4]; 4]>::into(transform),
+		bright_shade: data.bright_shade,
+		tex: sampler,
+		lod_level: 0.0f32,
+		checkerboard: false,
+		supersample: false,
+		brightness: data.adjustments.brightness as f32 / 100.0 - AMBIENT_DIMMING,
+		contrast: data.adjustments.contrast as f32 / 100.0,
+		gamma: data.adjustments.gamma as f32 / 100.0,
+		grayscale: data.view_filters.contains(&ViewFilter::Grayscale),
+		sepia: data.view_filters.contains(&ViewFilter::Sepia),
+		invert: data.view_filters.contains(&ViewFilter::Invert),
+	};
+	target
+		.draw(
+			context.unit_quad_vertices,
+			context.unit_quad_indices,
+			&data.program,
+			&uniforms,
+			&draw_params,
+		)
+		.unwrap();
+}
+
+/// Copies the pixels that were drawn to `bounds` of the window so far. The alpha of the window
+/// isn't meaningful, so the pixels are made opaque.
+fn read_view(
+	target: &Frame,
+	context: &DrawContext,
+	bounds: LogicalRect,
+) -> Result<image::RgbaImage, Box<dyn std::error::Error>> {
+	let rect = context.logical_rect_to_viewport(&bounds);
+	let (w, h) = (rect.width.max(1), rect.height.max(1));
+	let texture = Texture2d::empty_with_format(
+		context.display,
+		UncompressedFloatFormat::U8U8U8U8,
+		MipmapsOption::NoMipmap,
+		w,
+		h,
+	)?;
+	let framebuffer = SimpleFrameBuffer::new(context.display, &texture)?;
+	let whole = BlitTarget { left: 0, bottom: 0, width: w as i32, height: h as i32 };
+	target.blit_color(&rect, &framebuffer, &whole, MagnifySamplerFilter::Nearest);
+	let raw: RawImage2d<u8> = texture.read();
+	let mut pixels = image::RgbaImage::from_raw(raw.width, raw.height, raw.data.into_owned())
+		.ok_or("the size of the pixels doesn't match the view")?;
+	pixels.pixels_mut().for_each(|pixel| pixel[3] = 255);
+	// OpenGL stores the rows from the bottom up
+	Ok(image::imageops::flip_vertical(&pixels))
+}
+
+/// Draws a small square filled with `color` next to the cursor, with a border that stands out
+/// against both the swatch and the image behind it.
+fn draw_color_swatch(
+	target: &mut Frame,
+	context: &DrawContext,
+	cursor_pos: LogicalVector,
+	color: [u8; 4],
+) {
+	const SIZE: f32 = 24.0;
+	const OFFSET: f32 = 16.0;
+	const BORDER: f32 = 2.0;
+
+	let color = color.map(|c| c as f32 / 255.0);
+	let luminance = 0.2126 * color[0] + 0.7152 * color[1] + 0.0722 * color[2];
+	let border_color = if luminance > 0.5 { [0.0, 0.0, 0.0, 1.0] } else { [1.0, 1.0, 1.0, 1.0] };
+
+	let pos = cursor_pos + LogicalVector::new(OFFSET, OFFSET);
+	let border_rect = LogicalRect { pos, size: LogicalVector::new(SIZE, SIZE) };
+	let inner_rect = LogicalRect {
+		pos: pos + LogicalVector::new(BORDER, BORDER),
+		size: LogicalVector::new(SIZE - 2.0 * BORDER, SIZE - 2.0 * BORDER),
+	};
+	context.clear_color(target, border_color, Some(border_rect));
+	context.clear_color(target, [color[0], color[1], color[2], 1.0], Some(inner_rect));
+}
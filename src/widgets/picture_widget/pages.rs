@@ -0,0 +1,204 @@
+//! Showing the following image next to the current one, like the pages of an open book, and
+//! blending the previous or the next image over the current one like an onion skin.
+
+use std::{
+	rc::Rc,
+	time::{Duration, Instant},
+};
+
+use gelatin::{misc::LogicalVector, NextUpdate};
+
+use crate::{
+	image_cache::{AnimationFrameTexture, TextureError},
+	playback_manager::LoadRequest,
+};
+
+use super::PictureWidgetData;
+
+/// The `onion_skin_opacity` when it's not configured, in percent
+const DEFAULT_ONION_SKIN_OPACITY: u32 = 50;
+
+/// Which adjacent image is blended over the shown one, see `onion_skin`
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum OnionSkin {
+	Off,
+	Previous,
+	Next,
+}
+
+pub struct Pages {
+	/// Show the following image next to the current one, like the pages of an open book
+	pub two_page_spread: bool,
+	/// The pages are read from right to left, like manga
+	pub right_to_left: bool,
+	/// The image displayed next to the current one in a two-page spread
+	pub facing_page: Option<AnimationFrameTexture>,
+	pub onion_skin: OnionSkin,
+	/// The adjacent image that's blended over the current one, see `onion_skin`
+	pub onion_page: Option<AnimationFrameTexture>,
+}
+
+impl Pages {
+	pub fn new(two_page_spread: bool, right_to_left: bool) -> Pages {
+		Pages {
+			two_page_spread,
+			right_to_left,
+			facing_page: None,
+			onion_skin: OnionSkin::Off,
+			onion_page: None,
+		}
+	}
+}
+
+impl PictureWidgetData {
+	/// Finds the facing page and the onion skin page of the current image, and redraws the image
+	/// when they changed
+	pub(super) fn update_pages(&mut self) {
+		let prev_facing_page = self.pages.facing_page.take();
+		self.pages.facing_page = self.find_facing_page();
+		let facing_page_changed = match (&prev_facing_page, &self.pages.facing_page) {
+			(Some(prev), Some(new)) => !Rc::ptr_eq(&prev.tex_grid, &new.tex_grid),
+			(prev, new) => prev.is_none() != new.is_none(),
+		};
+		let prev_onion_page = self.pages.onion_page.take();
+		self.pages.onion_page = self.find_onion_page();
+		let onion_page_changed = match (&prev_onion_page, &self.pages.onion_page) {
+			(Some(prev), Some(new)) => !Rc::ptr_eq(&prev.tex_grid, &new.tex_grid),
+			(prev, new) => prev.is_none() != new.is_none(),
+		};
+		if facing_page_changed || onion_page_changed {
+			self.render_validity.invalidate();
+		}
+	}
+
+	/// Returns the center of the current image, and the center and texel size of the facing page
+	/// in a two-page spread.
+	pub(super) fn page_positions(&self) -> (LogicalVector, Option<(LogicalVector, f32)>) {
+		let (texture, facing_page) = match (self.get_texture(), &self.pages.facing_page) {
+			(Some(texture), Some(facing_page)) => (texture, facing_page),
+			_ => return (self.viewport.pos, None),
+		};
+		let (w, h) = texture.oriented_dimensions();
+		let (facing_w, facing_h) = facing_page.oriented_dimensions();
+		let facing_texel_size = self.viewport.texel_size * h as f32 / facing_h as f32;
+		let page_w = w as f32 * self.viewport.texel_size / self.dpi_scale;
+		let facing_page_w = facing_w as f32 * facing_texel_size / self.dpi_scale;
+		// The current page is on the left when reading from left to right
+		let dir = if self.pages.right_to_left { 1.0 } else { -1.0 };
+		let page_pos = self.viewport.pos + LogicalVector::new(dir * facing_page_w * 0.5, 0.0);
+		let facing_pos = self.viewport.pos - LogicalVector::new(dir * page_w * 0.5, 0.0);
+		(page_pos, Some((facing_pos, facing_texel_size)))
+	}
+
+	/// Finds the image to show next to the current one. Returns `None` if there's no facing page
+	/// or if it's still loading, in which case `next_update` is set to check it again.
+	fn find_facing_page(&mut self) -> Option<AnimationFrameTexture> {
+		let texture = self.get_texture()?;
+		let index = self.playback_manager.current_file_index()?;
+		let len = self.playback_manager.current_dir_len()?;
+		if !self.pages.two_page_spread
+			|| index + 1 >= len
+			|| self.page_stands_alone(index, &texture)
+		{
+			return None;
+		}
+		match self.loaded_texture_at_index(index + 1)? {
+			facing_page if is_double_page(&facing_page) => None,
+			facing_page => Some(facing_page),
+		}
+	}
+
+	/// The image before or after the current one for `onion_skin`
+	fn find_onion_page(&mut self) -> Option<AnimationFrameTexture> {
+		let index = self.playback_manager.current_file_index()?;
+		let index = match self.pages.onion_skin {
+			OnionSkin::Off => return None,
+			OnionSkin::Previous => index.checked_sub(1)?,
+			OnionSkin::Next => index + 1,
+		};
+		if index >= self.playback_manager.current_dir_len()? {
+			return None;
+		}
+		self.loaded_texture_at_index(index)
+	}
+
+	/// The texture of the image at `index` of the folder. If it's still loading, the widget is
+	/// updated again soon.
+	fn loaded_texture_at_index(&mut self, index: usize) -> Option<AnimationFrameTexture> {
+		match self.playback_manager.texture_at_index(index) {
+			Ok(texture) => Some(texture),
+			Err(TextureError::WaitingOnLoader) => {
+				let next_update = Instant::now() + Duration::from_millis(50);
+				self.next_update = self.next_update.aggregate(NextUpdate::WaitUntil(next_update));
+				None
+			}
+			Err(_) => None,
+		}
+	}
+
+	/// The cover (the first image) and double pages are never shown next to an other page
+	fn page_stands_alone(&self, index: usize, texture: &AnimationFrameTexture) -> bool {
+		index == 0 || is_double_page(texture)
+	}
+
+	pub(super) fn load_next_page(&mut self) {
+		let request = match self.pages.facing_page {
+			Some(_) => LoadRequest::Jump(2),
+			None => LoadRequest::LoadNext,
+		};
+		self.playback_manager.request_load(request);
+		self.render_validity.invalidate();
+	}
+
+	/// Turns back to the previous spread. Pages that haven't been loaded yet are assumed to be
+	/// single pages.
+	pub(super) fn load_prev_page(&mut self) {
+		let index = self.playback_manager.current_file_index().unwrap_or(0);
+		let mut request = LoadRequest::LoadPrevious;
+		if self.pages.two_page_spread && index >= 2 {
+			let mut stands_alone = |index| match self.playback_manager.texture_at_index(index) {
+				Ok(texture) => self.page_stands_alone(index, &texture),
+				Err(_) => index == 0,
+			};
+			if !stands_alone(index - 2) && !stands_alone(index - 1) {
+				request = LoadRequest::Jump(-2);
+			}
+		}
+		self.playback_manager.request_load(request);
+		self.render_validity.invalidate();
+	}
+
+	pub(super) fn toggle_two_page_spread(&mut self) {
+		self.pages.two_page_spread = !self.pages.two_page_spread;
+		self.cache.lock().unwrap().image.two_page_spread = self.pages.two_page_spread;
+		self.render_validity.invalidate();
+	}
+
+	pub(super) fn toggle_right_to_left(&mut self) {
+		self.pages.right_to_left = !self.pages.right_to_left;
+		self.cache.lock().unwrap().image.right_to_left = self.pages.right_to_left;
+		self.render_validity.invalidate();
+	}
+
+	/// Blends the previous or the next image over the shown one, or stops blending
+	pub(super) fn cycle_onion_skin(&mut self) {
+		self.pages.onion_skin = match self.pages.onion_skin {
+			OnionSkin::Off => OnionSkin::Previous,
+			OnionSkin::Previous => OnionSkin::Next,
+			OnionSkin::Next => OnionSkin::Off,
+		};
+		self.render_validity.invalidate();
+	}
+
+	pub(super) fn onion_skin_opacity(&self) -> f32 {
+		let config = self.configuration.borrow();
+		let opacity = config.image.as_ref().and_then(|image| image.onion_skin_opacity);
+		opacity.unwrap_or(DEFAULT_ONION_SKIN_OPACITY).min(100) as f32 / 100.0
+	}
+}
+
+/// Landscape images are usually scans of two pages, so they are shown on their own
+fn is_double_page(texture: &AnimationFrameTexture) -> bool {
+	let (w, h) = texture.oriented_dimensions();
+	w > h
+}
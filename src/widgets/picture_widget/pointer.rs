@@ -0,0 +1,215 @@
+//! The state of the mouse and the touchscreen, for recognizing clicks, double clicks, mouse
+//! gestures, swipes and taps.
+
+use std::{
+	collections::HashMap,
+	time::{Duration, Instant},
+};
+
+use gelatin::{cgmath::Vector2, misc::LogicalVector};
+
+use crate::input_handling::{gesture_input_key, swipe_input_key, DOUBLE_TAP_INPUT_KEY};
+
+/// The longest time between the clicks of a double click
+const DOUBLE_CLICK_TIME: Duration = Duration::from_millis(250);
+/// How far the cursor has to move with the right button held down to make a mouse gesture, in
+/// logical pixels
+const GESTURE_MIN_DISTANCE: f32 = 50.0;
+/// How far a finger has to move on a touchscreen to swipe, in logical pixels
+const SWIPE_MIN_DISTANCE: f32 = 80.0;
+/// How far a finger may move on a touchscreen while tapping, in logical pixels
+const TAP_MAX_DISTANCE: f32 = 10.0;
+/// The longest time between the taps of a double tap
+const DOUBLE_TAP_TIME: Duration = Duration::from_millis(300);
+/// How far the cursor may move between pressing and releasing a mouse button for a click, in
+/// logical pixels
+const CLICK_MAX_DISTANCE: f32 = 5.0;
+
+/// How two fingers on the touchscreen moved the image
+pub struct Pinch {
+	/// The point between the fingers
+	pub center: LogicalVector,
+	/// The zoom delta, see `Viewport::zoom`
+	pub zoom: Option<f32>,
+	pub pan: LogicalVector,
+}
+
+pub struct Pointer {
+	last_click_time: Instant,
+	/// The input key of the mouse button that was last clicked, for detecting double clicks
+	last_click_key: String,
+	/// The input key of the mouse button that was last pressed over the image and where
+	last_press: Option<(String, LogicalVector)>,
+	/// The scrolling that didn't add up to a whole step of the wheel yet
+	wheel_steps: f32,
+	/// Where the right button was pressed, for recognizing mouse gestures
+	pub gesture_start: Option<LogicalVector>,
+	/// Where `drag_out` was pressed, the file is dragged once the cursor moves away from there
+	pub drag_out_start: Option<LogicalVector>,
+	/// The positions of the fingers on a touchscreen by their ids
+	touches: HashMap<u64, LogicalVector>,
+	/// Where the only finger on the touchscreen started touching it, for recognizing swipes and taps
+	touch_start: Option<LogicalVector>,
+	last_tap_time: Option<Instant>,
+	pub panning_2d: bool,
+	pub panning_vert: bool,
+	pub panning_hor: bool,
+}
+
+impl Pointer {
+	pub fn new() -> Pointer {
+		Pointer {
+			last_click_time: Instant::now() - Duration::from_secs(10),
+			last_click_key: String::new(),
+			last_press: None,
+			wheel_steps: 0.0,
+			gesture_start: None,
+			drag_out_start: None,
+			touches: HashMap::new(),
+			touch_start: None,
+			last_tap_time: None,
+			panning_2d: false,
+			panning_vert: false,
+			panning_hor: false,
+		}
+	}
+
+	/// How far the image is panned when the cursor moves by `delta` with a panning action held down
+	pub fn panning_delta(&self, mut delta: LogicalVector) -> Option<LogicalVector> {
+		if !self.panning_2d && !self.panning_hor && !self.panning_vert {
+			return None;
+		}
+		if !self.panning_2d {
+			if !self.panning_hor {
+				// only vertical panning
+				delta.vec.x = 0.0;
+			}
+			if !self.panning_vert {
+				// only horzontal panning
+				delta.vec.y = 0.0;
+			}
+		}
+		Some(delta)
+	}
+
+	/// Returns true when the cursor moved far enough from where `drag_out` was pressed to start
+	/// dragging the file
+	pub fn drag_out_moved(&mut self, cursor_pos: LogicalVector) -> bool {
+		match self.drag_out_start {
+			Some(start) if distance(start, cursor_pos) >= CLICK_MAX_DISTANCE => {
+				self.drag_out_start = None;
+				true
+			}
+			_ => false,
+		}
+	}
+
+	pub fn press(&mut self, input_key: &str, cursor_pos: LogicalVector) {
+		self.last_press = Some((input_key.to_owned(), cursor_pos));
+	}
+
+	/// Whether releasing the button of `input_key` at `cursor_pos` ends a click. It's not a click
+	/// if the cursor moved away or made a mouse gesture.
+	pub fn release(&mut self, input_key: &str, cursor_pos: LogicalVector, gesture: bool) -> bool {
+		self.last_press.take().is_some_and(|(key, pos)| {
+			key == input_key && !gesture && distance(pos, cursor_pos) < CLICK_MAX_DISTANCE
+		})
+	}
+
+	/// Whether this click of `input_key` is the second one of a double click
+	pub fn double_click(&mut self, input_key: &str) -> bool {
+		let now = Instant::now();
+		let double_click = self.last_click_key == input_key
+			&& now.duration_since(self.last_click_time) < DOUBLE_CLICK_TIME;
+		// A third click starts a new double click
+		self.last_click_key = if double_click { String::new() } else { input_key.to_owned() };
+		self.last_click_time = now;
+		double_click
+	}
+
+	/// Adds up the scrolling and returns the whole steps of the wheel, negative when scrolling
+	/// down
+	pub fn wheel_steps(&mut self, delta: f32) -> f32 {
+		if self.wheel_steps * delta < 0.0 {
+			self.wheel_steps = 0.0;
+		}
+		self.wheel_steps += delta;
+		let steps = self.wheel_steps.trunc();
+		self.wheel_steps -= steps;
+		steps
+	}
+
+	pub fn start_touch(&mut self, id: u64, pos: LogicalVector) {
+		self.touches.insert(id, pos);
+		// Swiping and tapping are done with a single finger
+		self.touch_start = Some(pos).filter(|_| self.touches.len() == 1);
+	}
+
+	/// Returns how the image is zoomed and panned when two fingers are on the touchscreen and one
+	/// of them, `id`, moves to `pos`
+	pub fn move_touch(&mut self, id: u64, pos: LogicalVector) -> Option<Pinch> {
+		let prev_pos = self.touches.insert(id, pos)?;
+		if self.touches.len() != 2 {
+			return None;
+		}
+		let (_, &other) = self.touches.iter().find(|(&other_id, _)| other_id != id)?;
+		let prev_center = (prev_pos + other) / 2.0f32;
+		let center = (pos + other) / 2.0f32;
+		let prev_distance = distance(prev_pos, other);
+		let zoom = Some(prev_distance).filter(|d| *d > 1.0).map(|prev_distance| {
+			let ratio = distance(pos, other) / prev_distance;
+			if ratio >= 1.0 {
+				ratio - 1.0
+			} else {
+				1.0 - 1.0 / ratio
+			}
+		});
+		Some(Pinch { center, zoom, pan: center - prev_center })
+	}
+
+	/// Returns the input key of the swipe or the double tap that ended at `pos`. A cancelled touch
+	/// isn't a gesture.
+	pub fn end_touch(&mut self, id: u64, pos: LogicalVector, cancelled: bool) -> Option<String> {
+		self.touches.remove(&id);
+		let start = self.touch_start.take().filter(|_| !cancelled)?;
+		let Vector2 { x, y } = (pos - start).vec;
+		let movement = x.abs().max(y.abs());
+		if movement >= SWIPE_MIN_DISTANCE {
+			return Some(swipe_input_key(x, y));
+		}
+		if movement > TAP_MAX_DISTANCE {
+			return None;
+		}
+		let now = Instant::now();
+		match self.last_tap_time.take() {
+			Some(last_tap) if now.duration_since(last_tap) < DOUBLE_TAP_TIME => {
+				Some(DOUBLE_TAP_INPUT_KEY.to_owned())
+			}
+			_ => {
+				self.last_tap_time = Some(now);
+				None
+			}
+		}
+	}
+}
+
+impl Default for Pointer {
+	fn default() -> Self {
+		Pointer::new()
+	}
+}
+
+/// Returns the input key of the mouse gesture of moving the cursor by `movement`, or `None` if it
+/// moved too little to be a gesture
+pub fn recognize_gesture(movement: LogicalVector) -> Option<String> {
+	let Vector2 { x, y } = movement.vec;
+	if x.abs().max(y.abs()) < GESTURE_MIN_DISTANCE {
+		return None;
+	}
+	Some(gesture_input_key(x, y))
+}
+
+fn distance(a: LogicalVector, b: LogicalVector) -> f32 {
+	let Vector2 { x, y } = (a - b).vec;
+	(x * x + y * y).sqrt()
+}
@@ -0,0 +1,103 @@
+//! The reading mode for long strips of comics. The images are fitted to the width of the window,
+//! and scrolling past the bottom or the top of an image shows the next or the previous one.
+
+use std::mem;
+
+use super::{PictureWidgetData, ScalingMode};
+
+/// How far one line of scrolling moves the image in the reading mode, in logical pixels
+pub const READING_SCROLL_STEP: f32 = 100.0;
+
+/// Which part of the image is shown first in the reading mode
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum ReadingStart {
+	Top,
+	Bottom,
+}
+
+pub struct Reading {
+	pub active: bool,
+	/// The image was left by scrolling above its top, so the previous one is shown from the bottom
+	scrolled_to_prev: bool,
+	/// Where to place the newly shown image at the next draw
+	start: Option<ReadingStart>,
+}
+
+impl Reading {
+	pub fn new() -> Reading {
+		Reading { active: false, scrolled_to_prev: false, start: None }
+	}
+
+	/// Shows the new image from the top, or from the bottom when it was reached by scrolling up
+	pub fn image_changed(&mut self) {
+		if self.active {
+			let start_at_bottom = mem::take(&mut self.scrolled_to_prev);
+			let start = if start_at_bottom { ReadingStart::Bottom } else { ReadingStart::Top };
+			self.start = Some(start);
+		}
+	}
+}
+
+impl Default for Reading {
+	fn default() -> Self {
+		Reading::new()
+	}
+}
+
+impl PictureWidgetData {
+	/// Fits the image to the width of the widget and keeps it covering the height of the widget
+	pub(super) fn fit_width_for_reading(&mut self, (w, h): (f32, f32), dpi_scale: f32) {
+		let size = self.drawn_bounds.size.vec;
+		self.viewport.scaling = ScalingMode::Fixed;
+		self.viewport.texel_size = size.x * dpi_scale / w;
+		let img_h = h * self.viewport.texel_size / dpi_scale;
+		let pos = &mut self.viewport.pos.vec;
+		pos.x = size.x * 0.5;
+		match self.reading.start.take() {
+			Some(ReadingStart::Top) => pos.y = img_h * 0.5,
+			Some(ReadingStart::Bottom) => pos.y = size.y - img_h * 0.5,
+			None => (),
+		}
+		if img_h > size.y {
+			pos.y = pos.y.clamp(size.y - img_h * 0.5, img_h * 0.5);
+		} else {
+			pos.y = size.y * 0.5;
+		}
+	}
+
+	/// Scrolls the image in the reading mode, `lines` is positive when scrolling up. Scrolling past
+	/// the bottom or the top of the image shows the next or the previous image.
+	pub(super) fn scroll_reading(&mut self, lines: f32) {
+		let (_, h) = match self.content_dimensions() {
+			Some(content) => content,
+			None => return,
+		};
+		let img_h = h * self.viewport.texel_size / self.dpi_scale;
+		let top = self.viewport.pos.vec.y - img_h * 0.5;
+		let bottom = top + img_h;
+		// Allow for rounding errors
+		if lines < 0.0 && bottom <= self.drawn_bounds.size.vec.y + 0.5 {
+			self.reading.scrolled_to_prev = false;
+			self.load_next_page();
+		} else if lines > 0.0 && top >= -0.5 {
+			self.reading.scrolled_to_prev = true;
+			self.load_prev_page();
+		} else {
+			self.viewport.pos.vec.y += lines * READING_SCROLL_STEP;
+			self.render_validity.invalidate();
+		}
+	}
+
+	pub(super) fn toggle_reading_mode(&mut self) {
+		self.reading.active = !self.reading.active;
+		if self.reading.active {
+			self.reading.start = Some(ReadingStart::Top);
+		} else {
+			let stretch = self.cache.lock().unwrap().image.fit_stretches;
+			self.viewport.scaling =
+				if stretch { ScalingMode::FitStretch } else { ScalingMode::FitMin };
+		}
+		self.update_scaling_buttons();
+		self.render_validity.invalidate();
+	}
+}
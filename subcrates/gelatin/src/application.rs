@@ -12,7 +12,11 @@ use winit::{
 	window::WindowId,
 };
 
-use crate::{window::Window, NextUpdate};
+use crate::{
+	renderer::RenderError,
+	window::{Window, WindowDescriptor},
+	NextUpdate,
+};
 
 // const MAX_SLEEP_DURATION: std::time::Duration = std::time::Duration::from_millis(4);
 static EXIT_REQUESTED: AtomicBool = AtomicBool::new(false);
@@ -43,11 +47,9 @@ fn aggregate_control_flow<E>(event_loop: &EventLoopWindowTarget<E>, new: Control
 			return true;
 		}
 		ControlFlow::WaitUntil(new_time) => match original {
-			ControlFlow::WaitUntil(orig_time) => {
-				if new_time < orig_time {
-					set_control_flow(event_loop, new);
-					return true;
-				}
+			ControlFlow::WaitUntil(orig_time) if new_time < orig_time => {
+				set_control_flow(event_loop, new);
+				return true;
 			}
 			ControlFlow::Wait => {
 				set_control_flow(event_loop, new);
@@ -71,6 +73,23 @@ fn sanitize_control_flow<E>(event_loop: &EventLoopWindowTarget<E>) {
 }
 
 pub type EventHandler<UserEvent> = dyn FnMut(&Event<UserEvent>) -> NextUpdate;
+pub type WindowOpenerHandler<UserEvent> =
+	dyn FnMut(&Event<UserEvent>, &mut WindowOpener<UserEvent>) -> NextUpdate;
+
+/// Opens windows while the event loop is running, see `Application::add_window_opener`
+pub struct WindowOpener<'a, UserEvent: 'static> {
+	event_loop: &'a EventLoopWindowTarget<UserEvent>,
+	opened: Vec<Rc<Window>>,
+}
+
+impl<'a, UserEvent: 'static> WindowOpener<'a, UserEvent> {
+	/// Like `Window::try_new`, the window receives events once the handler returns
+	pub fn open(&mut self, desc: WindowDescriptor) -> Result<Rc<Window>, RenderError> {
+		let window = Window::build(self.event_loop, desc)?;
+		self.opened.push(window.clone());
+		Ok(window)
+	}
+}
 
 pub struct Application<UserEvent>
 where
@@ -79,6 +98,7 @@ where
 	pub event_loop: EventLoop<UserEvent>,
 	windows: HashMap<WindowId, Rc<Window>>,
 	global_handlers: Vec<Box<EventHandler<UserEvent>>>,
+	window_openers: Vec<Box<WindowOpenerHandler<UserEvent>>>,
 	at_exit: Option<Box<dyn FnOnce()>>,
 }
 
@@ -91,6 +111,7 @@ where
 			event_loop: EventLoopBuilder::<UserEvent>::with_user_event().build().unwrap(),
 			windows: HashMap::new(),
 			global_handlers: Vec::new(),
+			window_openers: Vec::new(),
 			at_exit: None,
 		}
 	}
@@ -113,6 +134,14 @@ where
 		self.global_handlers.push(Box::new(fun));
 	}
 
+	/// Like `add_global_event_handler`, but the handler can open new windows
+	pub fn add_window_opener<F>(&mut self, fun: F)
+	where
+		F: FnMut(&Event<UserEvent>, &mut WindowOpener<UserEvent>) -> NextUpdate + 'static,
+	{
+		self.window_openers.push(Box::new(fun));
+	}
+
 	pub fn create_loop_proxy(&self) -> EventLoopProxy<UserEvent> {
 		self.event_loop.create_proxy()
 	}
@@ -121,6 +150,7 @@ where
 		let mut windows: HashMap<WindowId, Rc<Window>> = self.windows;
 		let mut at_exit = self.at_exit;
 		let mut global_handlers = self.global_handlers;
+		let mut window_openers = self.window_openers;
 		#[cfg(feature = "benchmark")]
 		let mut last_draw_time = std::time::Instant::now();
 		#[cfg(feature = "benchmark")]
@@ -152,6 +182,16 @@ where
 					let handler_next_update = handler(&event);
 					aggregate_control_flow(event_loop, handler_next_update.into());
 				}
+				if !window_openers.is_empty() {
+					let mut opener = WindowOpener { event_loop, opened: Vec::new() };
+					for handler in window_openers.iter_mut() {
+						let handler_next_update = handler(&event, &mut opener);
+						aggregate_control_flow(event_loop, handler_next_update.into());
+					}
+					for window in opener.opened {
+						windows.insert(window.get_id(), window);
+					}
+				}
 				// dbg!(&event);
 				match event {
 					Event::NewEvents(_) => {
@@ -162,16 +202,22 @@ where
 						}
 					}
 					Event::WindowEvent { event, window_id } => {
+						// Closed windows are dropped, but they might still receive some events
+						let window = match windows.get(&window_id) {
+							Some(window) => window.clone(),
+							None => return,
+						};
 						if let WindowEvent::RedrawRequested = event {
-							let new_control_flow = windows.get(&window_id).unwrap().redraw().into();
+							let new_control_flow = window.redraw().into();
 							aggregate_control_flow(event_loop, new_control_flow);
 							#[cfg(feature = "benchmark")]
 							update_draw_dt();
 						}
 						if let WindowEvent::CloseRequested = event {
-							// This actually wouldn't be okay for a general pupose ui toolkit,
-							// but gelatin is specifically made for emulsion so this is fine hehe
-							request_exit();
+							// This exits unless the window was told otherwise. It actually
+							// wouldn't be okay for a general pupose ui toolkit, but gelatin is
+							// specifically made for emulsion so this is fine hehe
+							window.close();
 						}
 						let destroyed;
 						if let WindowEvent::Destroyed = event {
@@ -179,8 +225,8 @@ where
 						} else {
 							destroyed = false;
 						}
-						windows.get(&window_id).unwrap().process_event(event, event_loop);
-						if destroyed {
+						window.process_event(event, event_loop);
+						if destroyed || window.is_closed() {
 							windows.remove(&window_id);
 						}
 					}
@@ -189,6 +235,7 @@ where
 							event_loop.exit();
 							return;
 						}
+						windows.retain(|_, window| !window.is_closed());
 						for window in windows.values() {
 							window.main_events_cleared();
 							if window.redraw_needed() {
@@ -67,6 +67,14 @@ impl Button {
 		borrowed.on_click = Some(Rc::new(callback));
 	}
 
+	/// Calls the callback as if the button was clicked
+	pub fn click(&self) {
+		let on_click = self.data.borrow().on_click.clone();
+		if let Some(callback) = on_click {
+			callback();
+		}
+	}
+
 	pub fn set_icon(&self, img: Option<Rc<Picture>>) {
 		let mut borrowed = self.data.borrow_mut();
 		borrowed.icon = img;
@@ -0,0 +1,224 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use glium::Frame;
+use winit::{
+	dpi::{PhysicalPosition, PhysicalSize},
+	event::{ElementState, MouseButton},
+	monitor::MonitorHandle,
+};
+
+use crate::add_common_widget_functions;
+use crate::misc::{Alignment, Length, LogicalRect, LogicalVector, WidgetPlacement};
+use crate::window::{RenderValidity, Window};
+use crate::NextUpdate;
+use crate::{DrawContext, Event, EventKind, Widget, WidgetData, WidgetError};
+
+/// The longest time between the clicks of a double click
+const DOUBLE_CLICK_TIME: Duration = Duration::from_millis(400);
+
+struct DragAreaData {
+	placement: WidgetPlacement,
+	drawn_bounds: LogicalRect,
+	visible: bool,
+
+	bg_color: [f32; 4],
+	hover: bool,
+	/// Where the cursor grabbed the window while it's being dragged, relative to the window
+	grab: Option<LogicalVector>,
+	/// The position of the cursor that the window hasn't been moved to yet
+	pending_move: Option<LogicalVector>,
+	last_press_time: Option<Instant>,
+	toggle_maximized: bool,
+	/// The window snaps to the edges of the monitor that are closer than this, in logical pixels
+	snap_distance: f32,
+
+	render_validity: RenderValidity,
+}
+impl WidgetData for DragAreaData {
+	fn placement(&mut self) -> &mut WidgetPlacement {
+		&mut self.placement
+	}
+	fn drawn_bounds(&mut self) -> &mut LogicalRect {
+		&mut self.drawn_bounds
+	}
+	fn visible(&mut self) -> &mut bool {
+		&mut self.visible
+	}
+}
+
+/// An area that moves the window when it's dragged, like the title bar of a window without
+/// decorations. Double clicking it maximizes or restores the window.
+///
+/// The window is moved by the widget so that it snaps to the edges of the monitor. Where the
+/// position of the window can't be set, like on Wayland, the system is asked to move it instead.
+pub struct DragArea {
+	data: RefCell<DragAreaData>,
+}
+
+impl DragArea {
+	pub fn new() -> DragArea {
+		DragArea {
+			data: RefCell::new(DragAreaData {
+				placement: Default::default(),
+				drawn_bounds: Default::default(),
+				visible: true,
+				bg_color: [0.0, 0.0, 0.0, 0.0],
+				hover: false,
+				grab: None,
+				pending_move: None,
+				last_press_time: None,
+				toggle_maximized: false,
+				snap_distance: 16.0,
+				render_validity: Default::default(),
+			}),
+		}
+	}
+
+	add_common_widget_functions!(data);
+
+	pub fn set_bg_color(&self, color: [f32; 4]) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.bg_color = color;
+		borrowed.render_validity.invalidate();
+	}
+
+	/// Zero turns snapping off
+	pub fn set_snap_distance(&self, distance: f32) {
+		self.data.borrow_mut().snap_distance = distance;
+	}
+}
+
+impl Default for DragArea {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Widget for DragArea {
+	fn before_draw(&self, window: &Window) -> NextUpdate {
+		let mut borrowed = self.data.borrow_mut();
+		if borrowed.toggle_maximized {
+			borrowed.toggle_maximized = false;
+			let winit_window = window.window_mut();
+			winit_window.set_maximized(!winit_window.is_maximized());
+		}
+		let (grab, cursor_pos) = match (borrowed.grab, borrowed.pending_move.take()) {
+			(Some(grab), Some(cursor_pos)) => (grab, cursor_pos),
+			_ => return NextUpdate::Latest,
+		};
+		let winit_window = window.window_mut();
+		let pos = match winit_window.outer_position() {
+			Ok(pos) => pos,
+			Err(_) => {
+				borrowed.grab = None;
+				if let Err(e) = winit_window.drag_window() {
+					log::warn!("Could not move the window: {}", e);
+				}
+				return NextUpdate::Latest;
+			}
+		};
+		let scale = winit_window.scale_factor() as f32;
+		let delta = (cursor_pos - grab).vec * scale;
+		let mut new_pos =
+			PhysicalPosition::new(pos.x + delta.x.round() as i32, pos.y + delta.y.round() as i32);
+		if let Some(monitor) = winit_window.current_monitor() {
+			let distance = (borrowed.snap_distance * scale).round() as i32;
+			new_pos = snap_to_edges(new_pos, winit_window.outer_size(), &monitor, distance);
+		}
+		if new_pos != pos {
+			winit_window.set_outer_position(new_pos);
+		}
+		NextUpdate::Latest
+	}
+
+	fn draw(&self, target: &mut Frame, context: &DrawContext) -> Result<NextUpdate, WidgetError> {
+		let borrowed = self.data.borrow();
+		if borrowed.visible && borrowed.bg_color[3] > 0.0 {
+			context.clear_color(target, borrowed.bg_color, Some(borrowed.drawn_bounds));
+		}
+		Ok(NextUpdate::Latest)
+	}
+
+	fn layout(&self, available_space: LogicalRect) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.default_layout(available_space);
+	}
+
+	fn handle_event(&self, event: &Event) {
+		let mut borrowed = self.data.borrow_mut();
+		match event.kind {
+			EventKind::MouseMove => {
+				borrowed.hover = borrowed.drawn_bounds.contains(event.cursor_pos);
+				if borrowed.grab.is_some() {
+					borrowed.pending_move = Some(event.cursor_pos);
+					borrowed.render_validity.invalidate();
+				}
+			}
+			EventKind::MouseButton { state, button: MouseButton::Left } => match state {
+				ElementState::Pressed if borrowed.hover && borrowed.visible => {
+					let now = Instant::now();
+					let double_click = borrowed
+						.last_press_time
+						.is_some_and(|time| now.duration_since(time) < DOUBLE_CLICK_TIME);
+					if double_click {
+						borrowed.last_press_time = None;
+						borrowed.toggle_maximized = true;
+					} else {
+						borrowed.last_press_time = Some(now);
+						borrowed.grab = Some(event.cursor_pos);
+					}
+					borrowed.render_validity.invalidate();
+				}
+				ElementState::Pressed => (),
+				ElementState::Released => {
+					borrowed.grab = None;
+					borrowed.pending_move = None;
+				}
+			},
+			_ => (),
+		}
+	}
+
+	// No children for a drag area
+	fn children(&self, _children: &mut Vec<Rc<dyn Widget>>) {}
+
+	fn placement(&self) -> WidgetPlacement {
+		self.data.borrow().placement
+	}
+
+	fn visible(&self) -> bool {
+		self.data.borrow().visible
+	}
+
+	fn set_valid_ref(&self, render_validity: RenderValidity) {
+		self.data.borrow_mut().render_validity = render_validity;
+	}
+}
+
+/// Moves the window at `pos` to the edges of the monitor that its edges are closer to than
+/// `distance`
+fn snap_to_edges(
+	pos: PhysicalPosition<i32>,
+	size: PhysicalSize<u32>,
+	monitor: &MonitorHandle,
+	distance: i32,
+) -> PhysicalPosition<i32> {
+	let monitor_pos = monitor.position();
+	let monitor_size = monitor.size();
+	let snap = |start: i32, len: u32, min: i32, max_len: u32| {
+		let (len, max) = (len as i32, min + max_len as i32);
+		if (start - min).abs() < distance {
+			min
+		} else if (start + len - max).abs() < distance {
+			max - len
+		} else {
+			start
+		}
+	};
+	PhysicalPosition::new(
+		snap(pos.x, size.width, monitor_pos.x, monitor_size.width),
+		snap(pos.y, size.height, monitor_pos.y, monitor_size.height),
+	)
+}
@@ -10,25 +10,30 @@ use std::{
 };
 
 use winit::{
-	event::{ElementState, KeyEvent, MouseButton},
+	event::{ElementState, KeyEvent, MouseButton, TouchPhase},
 	event_loop::ControlFlow,
 	keyboard::ModifiersState,
 };
 
 use cgmath::{Matrix4, Vector3};
 use glium::{
-	glutin::surface::WindowSurface, implement_vertex, uniform, Blend, BlendingFunction, Frame,
-	IndexBuffer, LinearBlendingFactor, Program, Rect, Surface, VertexBuffer,
+	glutin::surface::WindowSurface, implement_vertex, Frame, IndexBuffer, Program, Rect,
+	VertexBuffer,
 };
 
 use misc::*;
+use renderer::{BlendMode, GliumRenderer, Renderer};
 
 pub mod application;
 pub mod button;
+pub mod drag_area;
 pub mod label;
 pub mod line_layout_container;
 pub mod misc;
 pub mod picture;
+pub mod picture_view;
+pub mod popup_menu;
+pub mod renderer;
 pub mod shaders;
 pub mod slider;
 pub mod window;
@@ -323,6 +328,7 @@ macro_rules! add_common_widget_functions {
 	};
 }
 
+#[derive(Clone)]
 pub struct Event {
 	/// The position of the cursor in virtual pixels
 	/// relative to the top left corner of the window.
@@ -330,10 +336,19 @@ pub struct Event {
 	pub modifiers: ModifiersState,
 	pub kind: EventKind,
 }
+/// `MouseScroll` is scrolling with a mouse wheel in lines. `TouchpadScroll` is smooth scrolling in
+/// logical pixels, which usually comes from swiping with two fingers on a touchpad.
+/// `TouchpadMagnify` is pinching on a touchpad, its `delta` is the relative change of the scale and
+/// it's only reported on macOS. `Touch` is a finger touching a touchscreen at `pos`, which is in
+/// logical pixels like `Event::cursor_pos`.
+#[derive(Clone)]
 pub enum EventKind {
 	MouseMove,
 	MouseButton { state: ElementState, button: MouseButton },
 	MouseScroll { delta: LogicalVector },
+	TouchpadScroll { delta: LogicalVector },
+	TouchpadMagnify { delta: f32 },
+	Touch { id: u64, phase: TouchPhase, pos: LogicalVector },
 	KeyInput { input: KeyEvent },
 	DroppedFile(PathBuf),
 	HoveredFile(PathBuf),
@@ -388,28 +403,6 @@ impl<'a> DrawContext<'a> {
 			let scale = Matrix4::from_scale(2.0);
 			transform = Matrix4::from_translation(Vector3::new(-1.0, -1.0, 0.0)) * scale;
 		}
-		let image_draw_params = glium::DrawParameters {
-			blend: Blend {
-				color: BlendingFunction::Addition {
-					source: LinearBlendingFactor::SourceAlpha,
-					destination: LinearBlendingFactor::OneMinusSourceAlpha,
-				},
-				..Default::default()
-			},
-			..Default::default()
-		};
-		let uniforms = uniform! {
-			matrix: Into::<[[f32; 4]; 4]>::into(transform),
-			color: color,
-		};
-		target
-			.draw(
-				self.unit_quad_vertices,
-				self.unit_quad_indices,
-				self.colored_program,
-				&uniforms,
-				&image_draw_params,
-			)
-			.unwrap();
+		GliumRenderer::new(target, self).draw_color(transform, color, BlendMode::Alpha).unwrap();
 	}
 }
@@ -0,0 +1,518 @@
+//! Showing a picture that can be zoomed and panned.
+//!
+//! `Viewport` holds the zoom and the position of the picture, and implements fitting the picture
+//! to the widget, zooming around a point and keeping the picture within reach. It doesn't care how
+//! the picture is stored or drawn, so widgets that draw their own textures can use it too.
+//! `PictureView` is a ready-made widget that draws the textures returned by its texture provider.
+
+use std::cell::RefCell;
+use std::f32::consts::FRAC_PI_2;
+use std::rc::Rc;
+
+use cgmath::{Matrix4, Rad, Vector2, Vector3};
+use glium::{
+	texture::SrgbTexture2d,
+	uniform,
+	uniforms::{MagnifySamplerFilter, MinifySamplerFilter, SamplerWrapFunction},
+	Frame, Surface,
+};
+use winit::event::{ElementState, MouseButton};
+
+use crate::add_common_widget_functions;
+use crate::misc::{Alignment, Length, LogicalRect, LogicalVector, WidgetPlacement};
+use crate::window::RenderValidity;
+use crate::{Display, DrawContext, Event, EventKind, NextUpdate, Widget, WidgetData, WidgetError};
+
+pub const MIN_ZOOM_FACTOR: f32 = 0.0001;
+pub const MAX_ZOOM_FACTOR: f32 = 10000.0;
+/// Pictures are drawn without smoothing when a texel is at least this many pixels wide
+const NEAREST_TEXEL_SIZE_THRESHOLD: f32 = 4.0;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ScalingMode {
+	/// The zoom is kept when the widget is resized
+	Fixed,
+	/// The picture is shrunk or enlarged to fit the widget
+	FitStretch,
+	/// The picture is shrunk to fit the widget, but never enlarged
+	FitMin,
+}
+
+/// The zoom and the position of a picture within a widget. The size of the content is passed in
+/// texels to the functions that need it, rotated if the picture is rotated.
+#[derive(Debug, Copy, Clone)]
+pub struct Viewport {
+	/// The size of a texel in physical display pixels. It's 1 until the picture is fitted or
+	/// zoomed, so it's never zero.
+	pub texel_size: f32,
+	/// The center of the picture relative to the top left corner of the widget
+	pub pos: LogicalVector,
+	pub scaling: ScalingMode,
+	/// The size of the widget at the last `update`
+	prev_widget_size: LogicalVector,
+}
+
+impl Viewport {
+	pub fn new(scaling: ScalingMode) -> Viewport {
+		Viewport {
+			texel_size: 1.0,
+			pos: Default::default(),
+			scaling,
+			prev_widget_size: Default::default(),
+		}
+	}
+
+	/// Centers the picture and sizes it to fit the widget. Unless `stretch` is set, pictures that
+	/// already fit are shown at their original size.
+	pub fn fit(
+		&mut self,
+		widget_size: LogicalVector,
+		content: (f32, f32),
+		dpi_scale: f32,
+		stretch: bool,
+	) {
+		let size = widget_size.vec;
+		let (img_phys_w, img_pyhs_h) = content;
+		let panel_aspect = size.x / size.y;
+		let img_aspect = img_phys_w / img_pyhs_h;
+
+		let texel_size_to_fit_width = size.x / img_phys_w;
+		let img_texel_size = if img_aspect > panel_aspect {
+			// The image is relatively wider than the panel
+			texel_size_to_fit_width
+		} else {
+			texel_size_to_fit_width * (img_aspect / panel_aspect)
+		};
+		let widget_phys_size = size * dpi_scale;
+		let fits_in_widget = widget_phys_size.x >= img_phys_w && widget_phys_size.y >= img_pyhs_h;
+		self.pos = LogicalVector::new(size.x * 0.5, size.y * 0.5);
+		if fits_in_widget && !stretch {
+			self.texel_size = 1.0;
+		} else {
+			self.texel_size = img_texel_size * dpi_scale;
+		}
+		self.scaling = if stretch { ScalingMode::FitStretch } else { ScalingMode::FitMin };
+	}
+
+	/// Zooms in (positive `delta`) or out around `anchor`, which stays at the same place of the
+	/// picture. Zooming close to the original size snaps to it.
+	pub fn zoom(&mut self, anchor: LogicalVector, mut delta: f32) {
+		delta = if delta > 0.0 { delta + 1.0 } else { 1.0 / (delta.abs() + 1.0) };
+		let mut texel_size = (self.texel_size * delta).max(0.0);
+		if (texel_size - 1.0).abs() < 0.01 {
+			texel_size = 1.0;
+		} else {
+			texel_size = texel_size.clamp(MIN_ZOOM_FACTOR, MAX_ZOOM_FACTOR)
+		}
+		self.pos = (texel_size / self.texel_size) * (self.pos - anchor) + anchor;
+		self.texel_size = texel_size;
+		self.scaling = ScalingMode::Fixed;
+	}
+
+	pub fn pan(&mut self, delta: LogicalVector) {
+		self.pos += delta;
+		self.scaling = ScalingMode::Fixed;
+	}
+
+	/// Ensures that the picture is within the widget, or at least touches an edge of the widget
+	pub fn keep_in_bounds(
+		&mut self,
+		widget_size: LogicalVector,
+		content: (f32, f32),
+		dpi_scale: f32,
+	) {
+		let (w, h) = content;
+		let img_w = w * self.texel_size / dpi_scale;
+		let img_h = h * self.texel_size / dpi_scale;
+		let widget_size = widget_size.vec;
+		let pos = self.pos.vec;
+
+		if pos.x < -img_w / 2.0 {
+			self.pos.vec.x = -img_w / 2.0;
+		}
+		if pos.y < -img_h / 2.0 {
+			self.pos.vec.y = -img_h / 2.0;
+		}
+		if pos.x > widget_size.x + img_w / 2.0 {
+			self.pos.vec.x = (widget_size.x + img_w / 2.0).ceil();
+		}
+		if pos.y > widget_size.y + img_h / 2.0 {
+			self.pos.vec.y = (widget_size.y + img_h / 2.0).ceil();
+		}
+	}
+
+	/// Applies the scaling mode before drawing. With a fixed zoom, the picture stays at the same
+	/// place relative to the center of the widget when the widget is resized.
+	pub fn update(
+		&mut self,
+		widget_size: LogicalVector,
+		content: Option<(f32, f32)>,
+		dpi_scale: f32,
+	) {
+		if let Some(content) = content {
+			match self.scaling {
+				ScalingMode::Fixed => {
+					self.pos += (widget_size - self.prev_widget_size) * 0.5f32;
+					self.keep_in_bounds(widget_size, content, dpi_scale);
+				}
+				ScalingMode::FitStretch => self.fit(widget_size, content, dpi_scale, true),
+				ScalingMode::FitMin => self.fit(widget_size, content, dpi_scale, false),
+			}
+		} else if self.scaling == ScalingMode::Fixed {
+			self.pos += (widget_size - self.prev_widget_size) * 0.5f32;
+		}
+		self.prev_widget_size = widget_size;
+	}
+
+	/// Converts a position relative to the top left corner of the widget to texel coordinates
+	pub fn widget_to_texel(
+		&self,
+		pos: LogicalVector,
+		content: (f32, f32),
+		dpi_scale: f32,
+	) -> Vector2<f32> {
+		let (w, h) = content;
+		(pos - self.pos).vec * (dpi_scale / self.texel_size) + Vector2::new(w, h) * 0.5
+	}
+
+	/// The inverse of `widget_to_texel`
+	pub fn texel_to_widget(
+		&self,
+		texel_pos: Vector2<f32>,
+		content: (f32, f32),
+		dpi_scale: f32,
+	) -> LogicalVector {
+		let (w, h) = content;
+		let centered = texel_pos - Vector2::new(w, h) * 0.5;
+		LogicalVector { vec: centered * (self.texel_size / dpi_scale) } + self.pos
+	}
+}
+
+/// The textures of a picture and how the picture is turned. Large pictures are split into several
+/// textures, because the size of a texture is limited.
+#[derive(Clone)]
+pub struct PictureTextures {
+	/// The textures with the position of their top left corner in the picture, in texels
+	pub tiles: Vec<(Rc<SrgbTexture2d>, (u32, u32))>,
+	/// The size of the whole picture in texels, before it's turned
+	pub size: (u32, u32),
+	/// The number of clockwise quarter turns the picture is rotated by
+	pub quarter_turns: u32,
+	/// The picture is mirrored horizontally before it's rotated
+	pub mirrored: bool,
+}
+
+impl PictureTextures {
+	/// A picture that's a single texture
+	pub fn single(texture: Rc<SrgbTexture2d>) -> PictureTextures {
+		let size = texture.dimensions();
+		PictureTextures { tiles: vec![(texture, (0, 0))], size, quarter_turns: 0, mirrored: false }
+	}
+
+	/// The size of the picture as it's shown, after it's turned
+	fn turned_size(&self) -> (f32, f32) {
+		let (w, h) = (self.size.0 as f32, self.size.1 as f32);
+		if self.quarter_turns % 2 == 1 {
+			(h, w)
+		} else {
+			(w, h)
+		}
+	}
+}
+
+/// Returns the picture to show, it's called whenever the view is drawn
+pub type TextureProvider = Box<dyn Fn(&Display) -> Option<PictureTextures>>;
+
+struct PictureViewData {
+	placement: WidgetPlacement,
+	drawn_bounds: LogicalRect,
+	visible: bool,
+	render_validity: RenderValidity,
+
+	viewport: Viewport,
+	texture_provider: Option<TextureProvider>,
+	/// The view is zoomed and panned with the mouse and the touchpad
+	interactive: bool,
+	/// The turned size of the last drawn picture
+	content: Option<(f32, f32)>,
+	dpi_scale: f32,
+	panning: bool,
+	last_mouse_pos: LogicalVector,
+}
+
+impl WidgetData for PictureViewData {
+	fn placement(&mut self) -> &mut WidgetPlacement {
+		&mut self.placement
+	}
+	fn drawn_bounds(&mut self) -> &mut LogicalRect {
+		&mut self.drawn_bounds
+	}
+	fn visible(&mut self) -> &mut bool {
+		&mut self.visible
+	}
+}
+
+impl PictureViewData {
+	/// Keeps the zoomed or panned picture in view and redraws it
+	fn keep_in_bounds(&mut self) {
+		if let Some(content) = self.content {
+			self.viewport.keep_in_bounds(self.drawn_bounds.size, content, self.dpi_scale);
+		}
+		self.render_validity.invalidate();
+	}
+}
+
+/// A widget that shows a picture which is zoomed with the mouse wheel and panned by dragging it.
+/// On a touchpad it's panned by swiping with two fingers and zoomed by pinching.
+pub struct PictureView {
+	data: RefCell<PictureViewData>,
+}
+
+impl PictureView {
+	pub fn new() -> PictureView {
+		PictureView {
+			data: RefCell::new(PictureViewData {
+				placement: WidgetPlacement {
+					width: Length::Stretch { min: 0.0, max: f32::INFINITY },
+					height: Length::Stretch { min: 0.0, max: f32::INFINITY },
+					horizontal_align: Alignment::Center,
+					vertical_align: Alignment::Center,
+					..Default::default()
+				},
+				drawn_bounds: Default::default(),
+				visible: true,
+				render_validity: Default::default(),
+				viewport: Viewport::new(ScalingMode::FitMin),
+				texture_provider: None,
+				interactive: true,
+				content: None,
+				dpi_scale: 1.0,
+				panning: false,
+				last_mouse_pos: Default::default(),
+			}),
+		}
+	}
+
+	add_common_widget_functions!(data);
+
+	pub fn set_texture_provider<F>(&self, texture_provider: F)
+	where
+		F: Fn(&Display) -> Option<PictureTextures> + 'static,
+	{
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.texture_provider = Some(Box::new(texture_provider));
+		borrowed.render_validity.invalidate();
+	}
+
+	/// Redraws the view, for example because the texture provider returns an other picture
+	pub fn invalidate(&self) {
+		self.data.borrow().render_validity.invalidate();
+	}
+
+	/// When it's not interactive, the view ignores the input and it's only changed with
+	/// `set_viewport`, for example to follow an other view
+	pub fn set_interactive(&self, interactive: bool) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.interactive = interactive;
+		borrowed.panning = false;
+	}
+
+	/// The area of the window that the view was last laid out to
+	pub fn drawn_bounds(&self) -> LogicalRect {
+		self.data.borrow().drawn_bounds
+	}
+
+	pub fn viewport(&self) -> Viewport {
+		self.data.borrow().viewport
+	}
+
+	pub fn set_viewport(&self, viewport: Viewport) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.viewport = viewport;
+		borrowed.render_validity.invalidate();
+	}
+
+	pub fn set_scaling(&self, scaling: ScalingMode) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.viewport.scaling = scaling;
+		borrowed.render_validity.invalidate();
+	}
+}
+
+impl Default for PictureView {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Widget for PictureView {
+	fn draw(&self, target: &mut Frame, context: &DrawContext) -> Result<NextUpdate, WidgetError> {
+		let mut borrowed = self.data.borrow_mut();
+		if !borrowed.visible {
+			return Ok(NextUpdate::Latest);
+		}
+		let dpi_scale = context.dpi_scale_factor;
+		borrowed.dpi_scale = dpi_scale;
+		let picture =
+			borrowed.texture_provider.as_ref().and_then(|provider| provider(context.display));
+		let picture = match picture {
+			Some(picture) if picture.size.0 > 0 && picture.size.1 > 0 => picture,
+			_ => {
+				borrowed.content = None;
+				return Ok(NextUpdate::Latest);
+			}
+		};
+		let content = picture.turned_size();
+		borrowed.content = Some(content);
+		let widget_size = borrowed.drawn_bounds.size;
+		borrowed.viewport.update(widget_size, Some(content), dpi_scale);
+		let viewport = borrowed.viewport;
+
+		let size = widget_size.vec;
+		let projection = cgmath::ortho(0.0, size.x, size.y, 0.0, -1.0, 1.0);
+		let (pic_w, pic_h) = (picture.size.0 as f32, picture.size.1 as f32);
+		let (display_w, display_h) =
+			(pic_w * viewport.texel_size / dpi_scale, pic_h * viewport.texel_size / dpi_scale);
+		let rotation = Matrix4::from_angle_z(Rad(FRAC_PI_2 * picture.quarter_turns as f32));
+		let mirror = if picture.mirrored { -1.0 } else { 1.0 };
+		// The picture is drawn onto the unit quad, centered on the position of the viewport
+		let picture_transform = projection
+			* Matrix4::from_translation(viewport.pos.vec.extend(0.0))
+			* rotation
+			* Matrix4::from_nonuniform_scale(mirror * display_w, display_h, 1.0)
+			* Matrix4::from_translation(Vector3::new(-0.5, -0.5, 0.0));
+
+		let magnify_filter = if viewport.texel_size < NEAREST_TEXEL_SIZE_THRESHOLD {
+			MagnifySamplerFilter::Linear
+		} else {
+			MagnifySamplerFilter::Nearest
+		};
+		let draw_params = glium::DrawParameters {
+			viewport: Some(context.logical_rect_to_viewport(&borrowed.drawn_bounds)),
+			blend: glium::Blend::alpha_blending(),
+			..Default::default()
+		};
+		for (texture, (x, y)) in picture.tiles.iter() {
+			let (tile_w, tile_h) = texture.dimensions();
+			// Each tile covers its part of the unit quad
+			let offset = Vector3::new(*x as f32 / pic_w, *y as f32 / pic_h, 0.0);
+			let scale = (tile_w as f32 / pic_w, tile_h as f32 / pic_h);
+			let transform = picture_transform
+				* Matrix4::from_translation(offset)
+				* Matrix4::from_nonuniform_scale(scale.0, scale.1, 1.0);
+			let sampler = texture
+				.sampled()
+				.wrap_function(SamplerWrapFunction::Clamp)
+				.minify_filter(MinifySamplerFilter::Linear)
+				.magnify_filter(magnify_filter);
+			let uniforms = uniform! {
+				matrix: Into::<[[f32; 4]; 4]>::into(transform),
+				tex: sampler,
+				color: [1.0f32, 1.0, 1.0, 1.0],
+				bg_color: [0.0f32, 0.0, 0.0, 0.0],
+				texture_size: [display_w, display_h],
+				brighten: 0.0f32,
+				shadow_color: [0.0f32, 0.0, 0.0],
+				shadow_offset: 1.0f32,
+			};
+			target
+				.draw(
+					context.unit_quad_vertices,
+					context.unit_quad_indices,
+					context.textured_program,
+					&uniforms,
+					&draw_params,
+				)
+				.map_err(|e| WidgetError::Custom(Box::new(e)))?;
+		}
+		Ok(NextUpdate::Latest)
+	}
+
+	fn layout(&self, available_space: LogicalRect) {
+		self.data.borrow_mut().default_layout(available_space);
+	}
+
+	fn handle_event(&self, event: &Event) {
+		let mut borrowed = self.data.borrow_mut();
+		if !borrowed.visible || !borrowed.interactive {
+			return;
+		}
+		let local_pos = event.cursor_pos - borrowed.drawn_bounds.pos;
+		match event.kind {
+			EventKind::MouseMove => {
+				if borrowed.panning {
+					let delta = event.cursor_pos - borrowed.last_mouse_pos;
+					borrowed.viewport.pan(delta);
+					borrowed.render_validity.invalidate();
+				}
+				borrowed.last_mouse_pos = event.cursor_pos;
+			}
+			EventKind::MouseButton { state, button: MouseButton::Left } => {
+				borrowed.panning = state == ElementState::Pressed
+					&& borrowed.drawn_bounds.contains(event.cursor_pos);
+			}
+			EventKind::MouseScroll { delta }
+				if borrowed.drawn_bounds.contains(event.cursor_pos) =>
+			{
+				borrowed.viewport.zoom(local_pos, delta.vec.y * 0.375);
+				borrowed.keep_in_bounds();
+			}
+			EventKind::TouchpadScroll { delta }
+				if borrowed.drawn_bounds.contains(event.cursor_pos) =>
+			{
+				borrowed.viewport.pan(delta);
+				borrowed.keep_in_bounds();
+			}
+			EventKind::TouchpadMagnify { delta }
+				if borrowed.drawn_bounds.contains(event.cursor_pos) =>
+			{
+				borrowed.viewport.zoom(local_pos, delta);
+				borrowed.keep_in_bounds();
+			}
+			_ => (),
+		}
+	}
+
+	fn children(&self, _children: &mut Vec<Rc<dyn Widget>>) {}
+
+	fn placement(&self) -> WidgetPlacement {
+		self.data.borrow().placement
+	}
+
+	fn visible(&self) -> bool {
+		self.data.borrow().visible
+	}
+
+	fn set_valid_ref(&self, render_validity: RenderValidity) {
+		self.data.borrow_mut().render_validity = render_validity;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn zoom_before_fit() {
+		let mut viewport = Viewport::new(ScalingMode::Fixed);
+		viewport.zoom(LogicalVector::new(10.0, 20.0), 1.0);
+		assert_eq!(viewport.texel_size, 2.0);
+		assert!(viewport.pos.vec.x.is_finite() && viewport.pos.vec.y.is_finite());
+		let texel = viewport.widget_to_texel(LogicalVector::new(10.0, 20.0), (100.0, 50.0), 1.0);
+		assert!(texel.x.is_finite() && texel.y.is_finite());
+	}
+
+	#[test]
+	fn zoom_keeps_the_anchor() {
+		let mut viewport = Viewport::new(ScalingMode::FitMin);
+		let widget_size = LogicalVector::new(200.0, 100.0);
+		viewport.update(widget_size, Some((400.0, 100.0)), 1.0);
+		assert_eq!(viewport.texel_size, 0.5);
+		let anchor = LogicalVector::new(150.0, 40.0);
+		let before = viewport.widget_to_texel(anchor, (400.0, 100.0), 1.0);
+		viewport.zoom(anchor, 0.5);
+		let after = viewport.widget_to_texel(anchor, (400.0, 100.0), 1.0);
+		assert!((before - after).x.abs() < 1e-3 && (before - after).y.abs() < 1e-3);
+		assert_eq!(viewport.scaling, ScalingMode::Fixed);
+	}
+}
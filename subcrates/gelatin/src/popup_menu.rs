@@ -0,0 +1,291 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use cgmath::Matrix4;
+use glium::Frame;
+use winit::{
+	event::{ElementState, MouseButton},
+	keyboard::{Key, NamedKey},
+};
+
+use crate::add_common_widget_functions;
+use crate::misc::{Alignment, Length, LogicalRect, LogicalVector, WidgetPlacement};
+use crate::picture::Picture;
+use crate::renderer::{BlendMode, GliumRenderer, Renderer};
+use crate::window::RenderValidity;
+use crate::NextUpdate;
+use crate::{DrawContext, Event, EventKind, Widget, WidgetData, WidgetError};
+
+/// The space around the items in logical pixels
+const PADDING: f32 = 4.0;
+
+struct PopupMenuData {
+	placement: WidgetPlacement,
+	drawn_bounds: LogicalRect,
+	visible: bool,
+
+	/// Where the top left corner of the menu is placed if it fits into the window
+	open_pos: LogicalVector,
+	items: Vec<Rc<Picture>>,
+	item_height: f32,
+	hover: Option<usize>,
+	bg_color: [f32; 4],
+	highlight_color: [f32; 4],
+	on_select: Option<Rc<dyn Fn(usize)>>,
+
+	render_validity: RenderValidity,
+}
+impl WidgetData for PopupMenuData {
+	fn placement(&mut self) -> &mut WidgetPlacement {
+		&mut self.placement
+	}
+	fn drawn_bounds(&mut self) -> &mut LogicalRect {
+		&mut self.drawn_bounds
+	}
+	fn visible(&mut self) -> &mut bool {
+		&mut self.visible
+	}
+}
+
+impl PopupMenuData {
+	fn item_bounds(&self, index: usize) -> LogicalRect {
+		let pos = self.drawn_bounds.pos
+			+ LogicalVector::new(PADDING, PADDING + index as f32 * self.item_height);
+		let width = self.drawn_bounds.size.vec.x - 2.0 * PADDING;
+		LogicalRect { pos, size: LogicalVector::new(width, self.item_height) }
+	}
+
+	fn item_at(&self, pos: LogicalVector) -> Option<usize> {
+		(0..self.items.len()).find(|index| self.item_bounds(*index).contains(pos))
+	}
+
+	fn close(&mut self) {
+		self.visible = false;
+		self.hover = None;
+		self.render_validity.invalidate();
+	}
+}
+
+/// A vertical list of items that pops up at the cursor, like a context menu. Each item is shown
+/// as a picture, which is scaled to the item height. The menu closes when an item is selected
+/// or when a mouse button is pressed outside of it.
+pub struct PopupMenu {
+	data: RefCell<PopupMenuData>,
+}
+
+impl PopupMenu {
+	pub fn new() -> PopupMenu {
+		let placement = WidgetPlacement { ignore_layout: true, ..Default::default() };
+		PopupMenu {
+			data: RefCell::new(PopupMenuData {
+				placement,
+				drawn_bounds: Default::default(),
+				visible: false,
+				open_pos: Default::default(),
+				items: Vec::new(),
+				item_height: 24.0,
+				hover: None,
+				bg_color: [0.1, 0.1, 0.1, 0.92],
+				highlight_color: [1.0, 1.0, 1.0, 0.15],
+				on_select: None,
+				render_validity: Default::default(),
+			}),
+		}
+	}
+
+	add_common_widget_functions!(data);
+
+	/// The callback receives the index of the selected item
+	pub fn set_on_select<T: Fn(usize) + 'static>(&self, callback: T) {
+		self.data.borrow_mut().on_select = Some(Rc::new(callback));
+	}
+
+	pub fn set_items(&self, items: Vec<Rc<Picture>>) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.items = items;
+		borrowed.hover = None;
+		borrowed.render_validity.invalidate();
+	}
+
+	pub fn set_item_height(&self, item_height: f32) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.item_height = item_height;
+		borrowed.render_validity.invalidate();
+	}
+
+	pub fn set_colors(&self, bg_color: [f32; 4], highlight_color: [f32; 4]) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.bg_color = bg_color;
+		borrowed.highlight_color = highlight_color;
+		borrowed.render_validity.invalidate();
+	}
+
+	/// Shows the menu at `pos`, which is moved if the menu wouldn't fit into the window there
+	pub fn open(&self, pos: LogicalVector) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.open_pos = pos;
+		borrowed.visible = true;
+		borrowed.hover = None;
+		borrowed.render_validity.invalidate();
+	}
+
+	pub fn close(&self) {
+		self.data.borrow_mut().close();
+	}
+
+	pub fn is_open(&self) -> bool {
+		self.data.borrow().visible
+	}
+
+	fn select(&self, index: usize) {
+		let on_select = {
+			let mut borrowed = self.data.borrow_mut();
+			borrowed.close();
+			borrowed.on_select.clone()
+		};
+		if let Some(callback) = on_select {
+			callback(index);
+		}
+	}
+}
+
+impl Default for PopupMenu {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Widget for PopupMenu {
+	fn draw(&self, target: &mut Frame, context: &DrawContext) -> Result<NextUpdate, WidgetError> {
+		let borrowed = self.data.borrow();
+		if !borrowed.visible {
+			return Ok(NextUpdate::Latest);
+		}
+		let mut renderer = GliumRenderer::new(target, context);
+		let transform_of = |rect: LogicalRect| {
+			let rect = rect.align_to_pixels(context.dpi_scale_factor);
+			let scale = Matrix4::from_nonuniform_scale(rect.size.vec.x, rect.size.vec.y, 1.0);
+			context.projection_transform
+				* Matrix4::from_translation(rect.pos.vec.extend(0.0))
+				* scale
+		};
+		let draw_error = |e| WidgetError::Custom(Box::new(e));
+		renderer
+			.draw_color(transform_of(borrowed.drawn_bounds), borrowed.bg_color, BlendMode::Alpha)
+			.map_err(draw_error)?;
+		if let Some(hover) = borrowed.hover {
+			let bounds = borrowed.item_bounds(hover);
+			renderer
+				.draw_color(transform_of(bounds), borrowed.highlight_color, BlendMode::Alpha)
+				.map_err(draw_error)?;
+		}
+		for (index, item) in borrowed.items.iter().enumerate() {
+			let mut bounds = borrowed.item_bounds(index);
+			let metadata = item.get_metadata()?;
+			bounds.size.vec.x = metadata.width as f32 * bounds.size.vec.y / metadata.height as f32;
+			let texture = item.texture(context.display)?;
+			renderer.draw_texture(transform_of(bounds), &texture).map_err(draw_error)?;
+		}
+		Ok(NextUpdate::Latest)
+	}
+
+	fn layout(&self, available_space: LogicalRect) {
+		let mut borrowed = self.data.borrow_mut();
+		let item_height = borrowed.item_height;
+		let widest = borrowed
+			.items
+			.iter()
+			.filter_map(|item| item.get_metadata().ok())
+			.map(|metadata| metadata.width as f32 * item_height / metadata.height as f32)
+			.fold(0.0, f32::max);
+		let size = LogicalVector::new(
+			widest + 2.0 * PADDING,
+			borrowed.items.len() as f32 * item_height + 2.0 * PADDING,
+		);
+		// The menu is flipped to the other side of the cursor when it would go out of the window
+		let mut pos = borrowed.open_pos;
+		if pos.vec.x + size.vec.x > available_space.right() {
+			pos.vec.x -= size.vec.x;
+		}
+		if pos.vec.y + size.vec.y > available_space.bottom() {
+			pos.vec.y -= size.vec.y;
+		}
+		pos.vec.x = pos.vec.x.max(available_space.left());
+		pos.vec.y = pos.vec.y.max(available_space.top());
+		borrowed.placement.width = Length::Fixed(size.vec.x);
+		borrowed.placement.height = Length::Fixed(size.vec.y);
+		borrowed.placement.horizontal_align = Alignment::Start;
+		borrowed.placement.vertical_align = Alignment::Start;
+		borrowed.drawn_bounds = LogicalRect { pos, size };
+	}
+
+	fn handle_event(&self, event: &Event) {
+		if !self.data.borrow().visible {
+			return;
+		}
+		match &event.kind {
+			EventKind::MouseMove => {
+				let mut borrowed = self.data.borrow_mut();
+				let hover = borrowed.item_at(event.cursor_pos);
+				if hover != borrowed.hover {
+					borrowed.hover = hover;
+					borrowed.render_validity.invalidate();
+				}
+			}
+			EventKind::MouseButton { state, button } => {
+				let (inside, hover) = {
+					let borrowed = self.data.borrow();
+					(borrowed.drawn_bounds.contains(event.cursor_pos), borrowed.hover)
+				};
+				match state {
+					ElementState::Pressed if !inside => self.close(),
+					ElementState::Released if *button == MouseButton::Left => {
+						if let Some(index) = hover {
+							self.select(index);
+						}
+					}
+					_ => (),
+				}
+			}
+			EventKind::KeyInput { input } if input.state == ElementState::Pressed => {
+				let hover = self.data.borrow().hover;
+				let item_count = self.data.borrow().items.len();
+				let hover = match &input.logical_key {
+					Key::Named(NamedKey::ArrowDown) => hover.map_or(0, |h| (h + 1) % item_count),
+					Key::Named(NamedKey::ArrowUp) => {
+						hover.unwrap_or(0).checked_sub(1).unwrap_or(item_count.saturating_sub(1))
+					}
+					Key::Named(NamedKey::Enter) => {
+						if let Some(index) = hover {
+							self.select(index);
+						}
+						return;
+					}
+					Key::Named(NamedKey::Escape) => {
+						self.close();
+						return;
+					}
+					_ => return,
+				};
+				let mut borrowed = self.data.borrow_mut();
+				borrowed.hover = Some(hover).filter(|_| item_count > 0);
+				borrowed.render_validity.invalidate();
+			}
+			_ => (),
+		}
+	}
+
+	fn children(&self, _children: &mut Vec<Rc<dyn Widget>>) {}
+
+	fn placement(&self) -> WidgetPlacement {
+		self.data.borrow().placement
+	}
+
+	fn visible(&self) -> bool {
+		self.data.borrow().visible
+	}
+
+	fn set_valid_ref(&self, render_validity: RenderValidity) {
+		self.data.borrow_mut().render_validity = render_validity;
+	}
+}
@@ -0,0 +1,99 @@
+use cgmath::Matrix4;
+use glium::{
+	texture::{MipmapsOption, RawImage2d, SrgbTexture2d},
+	uniform, Blend, BlendingFunction, Frame, LinearBlendingFactor, Surface,
+};
+use image::RgbaImage;
+
+use super::{BlendMode, RenderError, Renderer};
+use crate::DrawContext;
+
+/// Draws into a frame that a `Window` is currently rendering.
+pub struct GliumRenderer<'a> {
+	target: &'a mut Frame,
+	context: &'a DrawContext<'a>,
+}
+
+impl<'a> GliumRenderer<'a> {
+	pub fn new(target: &'a mut Frame, context: &'a DrawContext<'a>) -> Self {
+		GliumRenderer { target, context }
+	}
+}
+
+fn blend_for(mode: BlendMode) -> Blend {
+	match mode {
+		BlendMode::Alpha => Blend {
+			color: BlendingFunction::Addition {
+				source: LinearBlendingFactor::SourceAlpha,
+				destination: LinearBlendingFactor::OneMinusSourceAlpha,
+			},
+			..Default::default()
+		},
+		BlendMode::Max => Blend {
+			color: BlendingFunction::Max,
+			alpha: BlendingFunction::Max,
+			..Default::default()
+		},
+	}
+}
+
+impl<'a> Renderer for GliumRenderer<'a> {
+	type Texture = SrgbTexture2d;
+
+	fn create_texture(&mut self, image: &RgbaImage) -> Result<Self::Texture, RenderError> {
+		let raw_image = RawImage2d::from_raw_rgba(image.as_raw().clone(), image.dimensions());
+		SrgbTexture2d::with_mipmaps(self.context.display, raw_image, MipmapsOption::NoMipmap)
+			.map_err(|e| RenderError { description: format!("glium texture error: {}", e) })
+	}
+
+	fn draw_color(
+		&mut self,
+		transform: Matrix4<f32>,
+		color: [f32; 4],
+		blend: BlendMode,
+	) -> Result<(), RenderError> {
+		let draw_params = glium::DrawParameters { blend: blend_for(blend), ..Default::default() };
+		let uniforms = uniform! {
+			matrix: Into::<[[f32; 4]; 4]>::into(transform),
+			color: color,
+		};
+		self.target
+			.draw(
+				self.context.unit_quad_vertices,
+				self.context.unit_quad_indices,
+				self.context.colored_program,
+				&uniforms,
+				&draw_params,
+			)
+			.map_err(|e| RenderError { description: format!("glium draw error: {}", e) })
+	}
+
+	fn draw_texture(
+		&mut self,
+		transform: Matrix4<f32>,
+		texture: &Self::Texture,
+	) -> Result<(), RenderError> {
+		let draw_params =
+			glium::DrawParameters { blend: blend_for(BlendMode::Alpha), ..Default::default() };
+		let (w, h) = texture.dimensions();
+		// The shadow offset is set so that no shadow gets drawn along the edges
+		let uniforms = uniform! {
+			matrix: Into::<[[f32; 4]; 4]>::into(transform),
+			tex: texture.sampled(),
+			texture_size: [w as f32, h as f32],
+			brighten: 0.0f32,
+			shadow_color: [0.0f32, 0.0, 0.0],
+			bg_color: [0.0f32, 0.0, 0.0, 0.0],
+			shadow_offset: 1.0f32,
+		};
+		self.target
+			.draw(
+				self.context.unit_quad_vertices,
+				self.context.unit_quad_indices,
+				self.context.textured_program,
+				&uniforms,
+				&draw_params,
+			)
+			.map_err(|e| RenderError { description: format!("glium draw error: {}", e) })
+	}
+}
@@ -0,0 +1,61 @@
+//! Backend independent drawing primitives.
+//!
+//! Widgets still draw through glium directly, but new drawing code should target the `Renderer`
+//! trait so that it keeps working once the widgets are ported over. The glium backend draws into
+//! the frame of the window. The software backend rasterizes on the CPU and is used as a fallback
+//! when there's no working OpenGL.
+
+use std::{error::Error, fmt};
+
+use cgmath::Matrix4;
+use image::RgbaImage;
+
+mod glium_backend;
+pub use glium_backend::GliumRenderer;
+
+mod software_backend;
+pub use software_backend::{SoftwareRenderer, SoftwareTexture};
+
+#[derive(Debug)]
+pub struct RenderError {
+	pub description: String,
+}
+impl fmt::Display for RenderError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "RenderError: {}", self.description)
+	}
+}
+impl Error for RenderError {}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BlendMode {
+	/// Regular alpha blending
+	Alpha,
+
+	/// Takes the per-channel maximum of the source and the destination. This is also applied to
+	/// the alpha channel.
+	Max,
+}
+
+/// The primitives are all drawn onto the unit quad ((0, 0) to (1, 1)) which `transform` then
+/// takes to clip space. This is the same convention that the `matrix` uniform in
+/// `vertex_140.glsl` follows.
+pub trait Renderer {
+	type Texture;
+
+	/// Uploads an image whose colors are in the sRGB color space.
+	fn create_texture(&mut self, image: &RgbaImage) -> Result<Self::Texture, RenderError>;
+
+	fn draw_color(
+		&mut self,
+		transform: Matrix4<f32>,
+		color: [f32; 4],
+		blend: BlendMode,
+	) -> Result<(), RenderError>;
+
+	fn draw_texture(
+		&mut self,
+		transform: Matrix4<f32>,
+		texture: &Self::Texture,
+	) -> Result<(), RenderError>;
+}
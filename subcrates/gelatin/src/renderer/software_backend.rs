@@ -0,0 +1,154 @@
+use std::{num::NonZeroU32, rc::Rc};
+
+use cgmath::Matrix4;
+use image::RgbaImage;
+use tiny_skia::{
+	FillRule, FilterQuality, Paint, Path, PathBuilder, Pixmap, PixmapPaint, Rect, Transform,
+};
+use winit::{dpi::PhysicalSize, window::Window};
+
+use super::{BlendMode, RenderError, Renderer};
+
+type Surface = softbuffer::Surface<Rc<Window>, Rc<Window>>;
+
+pub struct SoftwareTexture {
+	pixmap: Pixmap,
+}
+
+impl SoftwareTexture {
+	pub fn dimensions(&self) -> (u32, u32) {
+		(self.pixmap.width(), self.pixmap.height())
+	}
+}
+
+/// Rasterizes on the CPU and presents the result in a winit window.
+///
+/// This is meant for systems where no usable OpenGL context can be created (virtual machines,
+/// remote desktops, broken drivers) so it favours robustness over speed. The frame is built in an
+/// offscreen pixmap and copied to the window by `end_frame`.
+pub struct SoftwareRenderer {
+	surface: Surface,
+	pixmap: Pixmap,
+	unit_quad: Path,
+}
+
+impl SoftwareRenderer {
+	pub fn new(window: Rc<Window>) -> Result<Self, RenderError> {
+		let context = softbuffer::Context::new(window.clone()).map_err(|e| RenderError {
+			description: format!("failed to create software context: {}", e),
+		})?;
+		let size = window.inner_size();
+		let surface = Surface::new(&context, window).map_err(|e| RenderError {
+			description: format!("failed to create software surface: {}", e),
+		})?;
+		let unit_quad = PathBuilder::from_rect(Rect::from_xywh(0.0, 0.0, 1.0, 1.0).unwrap());
+		let mut renderer =
+			SoftwareRenderer { surface, pixmap: Pixmap::new(1, 1).unwrap(), unit_quad };
+		renderer.resize(size);
+		Ok(renderer)
+	}
+
+	pub fn resize(&mut self, size: PhysicalSize<u32>) {
+		let (width, height) = match (NonZeroU32::new(size.width), NonZeroU32::new(size.height)) {
+			(Some(width), Some(height)) => (width, height),
+			_ => return,
+		};
+		if let Err(e) = self.surface.resize(width, height) {
+			log::error!("Failed to resize the software surface: {}", e);
+			return;
+		}
+		self.pixmap = Pixmap::new(size.width, size.height).unwrap();
+	}
+
+	pub fn size(&self) -> PhysicalSize<u32> {
+		PhysicalSize::new(self.pixmap.width(), self.pixmap.height())
+	}
+
+	pub fn begin_frame(&mut self, clear_color: [f32; 4]) {
+		self.pixmap.fill(to_skia_color(clear_color));
+	}
+
+	pub fn end_frame(&mut self) -> Result<(), RenderError> {
+		let mut buffer = self.surface.buffer_mut().map_err(|e| RenderError {
+			description: format!("failed to get the software buffer: {}", e),
+		})?;
+		// The framebuffer is always presented as opaque, see `Window::set_alpha_to_1`
+		for (dst, src) in buffer.iter_mut().zip(self.pixmap.pixels()) {
+			*dst = (src.red() as u32) << 16 | (src.green() as u32) << 8 | src.blue() as u32;
+		}
+		buffer.present().map_err(|e| RenderError {
+			description: format!("failed to present the software buffer: {}", e),
+		})
+	}
+
+	/// Converts a transform that takes the unit quad to clip space into one that takes it to
+	/// pixel coordinates of the frame.
+	fn to_pixel_transform(&self, transform: Matrix4<f32>) -> Transform {
+		let half_w = self.pixmap.width() as f32 * 0.5;
+		let half_h = self.pixmap.height() as f32 * 0.5;
+		Transform::from_row(
+			half_w * transform.x.x,
+			-half_h * transform.x.y,
+			half_w * transform.y.x,
+			-half_h * transform.y.y,
+			half_w * (transform.w.x + 1.0),
+			half_h * (1.0 - transform.w.y),
+		)
+	}
+}
+
+fn to_skia_color(color: [f32; 4]) -> tiny_skia::Color {
+	let [r, g, b, a] = color.map(|c| c.clamp(0.0, 1.0));
+	tiny_skia::Color::from_rgba(r, g, b, a).unwrap()
+}
+
+impl Renderer for SoftwareRenderer {
+	type Texture = SoftwareTexture;
+
+	fn create_texture(&mut self, image: &RgbaImage) -> Result<Self::Texture, RenderError> {
+		let (width, height) = image.dimensions();
+		let size = tiny_skia::IntSize::from_wh(width, height).ok_or_else(|| RenderError {
+			description: format!("{}x{} is not a valid texture size", width, height),
+		})?;
+		// Pixmaps store premultiplied colors
+		let mut data = image.as_raw().clone();
+		for pixel in data.chunks_exact_mut(4) {
+			let alpha = pixel[3] as u16;
+			for channel in &mut pixel[..3] {
+				*channel = ((*channel as u16 * alpha + 127) / 255) as u8;
+			}
+		}
+		let pixmap = Pixmap::from_vec(data, size).unwrap();
+		Ok(SoftwareTexture { pixmap })
+	}
+
+	fn draw_color(
+		&mut self,
+		transform: Matrix4<f32>,
+		color: [f32; 4],
+		blend: BlendMode,
+	) -> Result<(), RenderError> {
+		let mut paint = Paint::default();
+		paint.set_color(to_skia_color(color));
+		paint.blend_mode = match blend {
+			BlendMode::Alpha => tiny_skia::BlendMode::SourceOver,
+			BlendMode::Max => tiny_skia::BlendMode::Lighten,
+		};
+		let transform = self.to_pixel_transform(transform);
+		self.pixmap.fill_path(&self.unit_quad, &paint, FillRule::Winding, transform, None);
+		Ok(())
+	}
+
+	fn draw_texture(
+		&mut self,
+		transform: Matrix4<f32>,
+		texture: &Self::Texture,
+	) -> Result<(), RenderError> {
+		let (width, height) = texture.dimensions();
+		let transform =
+			self.to_pixel_transform(transform).pre_scale(1.0 / width as f32, 1.0 / height as f32);
+		let paint = PixmapPaint { quality: FilterQuality::Bilinear, ..Default::default() };
+		self.pixmap.draw_pixmap(0, 0, texture.pixmap.as_ref(), &paint, transform, None);
+		Ok(())
+	}
+}
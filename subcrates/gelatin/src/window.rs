@@ -7,17 +7,17 @@ use glium::{
 		display::{GetGlDisplay, GlDisplay},
 		surface::{GlSurface, WindowSurface},
 	},
-	uniform, Blend, BlendingFunction, Display, Frame, IndexBuffer, Program, Rect, Surface,
-	VertexBuffer,
+	CapabilitiesSource, Display, Frame, IndexBuffer, Program, Rect, Surface, VertexBuffer,
 };
 use log::{debug, error, warn};
 use raw_window_handle::HasRawWindowHandle;
 use winit::{
 	dpi::{PhysicalPosition, PhysicalSize},
 	event::WindowEvent,
-	event_loop::{EventLoop, EventLoopWindowTarget},
+	event_loop::{EventLoopBuilder, EventLoopWindowTarget},
 	keyboard::ModifiersState,
-	window::{CursorIcon, Fullscreen, Icon, WindowBuilder, WindowId},
+	monitor::MonitorHandle,
+	window::{CursorIcon, Fullscreen, Icon, WindowBuilder, WindowId, WindowLevel},
 };
 
 #[cfg(not(any(target_os = "macos", windows)))]
@@ -41,7 +41,8 @@ use derive_builder::Builder;
 
 use crate::shaders;
 use crate::{
-	application::Application,
+	application::{request_exit, Application},
+	renderer::{BlendMode, GliumRenderer, RenderError, Renderer},
 	shaders::{shader_from_source, ShaderDescriptor},
 };
 use crate::{
@@ -138,6 +139,25 @@ pub struct WindowDescriptor {
 	#[builder(default)]
 	position: Option<PhysicalPosition<i32>>,
 
+	/// Without the decorations the window has no title bar and no borders, see `DragArea`
+	#[builder(default = "true")]
+	decorations: bool,
+
+	/// The window floats above the other windows, see `Window::set_always_on_top`
+	#[builder(default)]
+	always_on_top: bool,
+
+	/// Below 1 the windows behind this one show through it. The window can only be made
+	/// translucent with `Window::set_opacity` if it's created with an opacity below 1 or with
+	/// `transparent`.
+	#[builder(default = "1.0")]
+	opacity: f32,
+
+	/// Creates the window with an alpha channel even when its `opacity` is 1, so that it can be
+	/// made translucent later
+	#[builder(default)]
+	transparent: bool,
+
 	/// Only relevant on Wayland.
 	/// See: https://docs.rs/winit/0.24.0/winit/platform/unix/trait.WindowBuilderExtUnix.html#tymethod.with_app_id
 	#[builder(default)]
@@ -145,6 +165,71 @@ pub struct WindowDescriptor {
 	app_id: Option<String>,
 }
 
+impl WindowDescriptor {
+	/// Creates a winit window builder that follows this descriptor. The minimum size is applied
+	/// and the position is dropped if it would place the window outside of every monitor.
+	pub fn window_builder<UserEvent>(
+		&self,
+		event_loop: &EventLoopWindowTarget<UserEvent>,
+	) -> WindowBuilder {
+		const MINIMUM_WINDOW_SIZE: u32 = 200;
+		let mut size = self.size;
+		if size.width < MINIMUM_WINDOW_SIZE {
+			warn!("Window width was specified to be zero. Defaulting to {MINIMUM_WINDOW_SIZE} instead");
+			size.width = MINIMUM_WINDOW_SIZE;
+		}
+		if size.height < MINIMUM_WINDOW_SIZE {
+			warn!("Window height was specified to be zero. Defaulting to {MINIMUM_WINDOW_SIZE} instead");
+			size.height = MINIMUM_WINDOW_SIZE;
+		}
+
+		let mut window_builder = WindowBuilder::new()
+			.with_title("Loading")
+			.with_fullscreen(None)
+			.with_window_icon(self.icon.clone())
+			.with_maximized(self.maximized)
+			.with_decorations(self.decorations)
+			.with_transparent(self.transparent || self.opacity < 1.0)
+			.with_window_level(if self.always_on_top {
+				WindowLevel::AlwaysOnTop
+			} else {
+				WindowLevel::Normal
+			});
+
+		if !self.maximized {
+			window_builder = window_builder.with_inner_size(size);
+			if let Some(window_pos) = self.position {
+				// Check if the window would be placed outside of the screen
+				// (This can happen when using two displays, then disconnecting
+				// one of the displays and starting up emulsion)
+				let in_bounds = event_loop.available_monitors().any(|monitor| {
+					debug!("Monitor pos: {:?}", monitor.position());
+					debug!("Monitor size: {:?}", monitor.size());
+					is_in_bounds(monitor.position(), monitor.size(), window_pos)
+				});
+				if in_bounds {
+					window_builder = window_builder.with_position(window_pos);
+				}
+			}
+		}
+
+		#[cfg(not(any(target_os = "macos", windows)))]
+		let window_builder = if let Some(app_id) = &self.app_id {
+			let is_wayland = std::env::var("XDG_SESSION_TYPE")
+				.is_ok_and(|var| var.to_lowercase().contains("wayland"));
+			if is_wayland {
+				WindowBuilderExtWayland::with_name(window_builder, app_id, app_id.to_lowercase())
+			} else {
+				WindowBuilderExtX11::with_name(window_builder, app_id, app_id.to_lowercase())
+			}
+		} else {
+			window_builder
+		};
+
+		window_builder
+	}
+}
+
 pub type EventHandler = dyn FnMut(&Window, &WindowEvent);
 
 struct WindowData {
@@ -153,6 +238,11 @@ struct WindowData {
 
 	size_before_fullscreen: PhysicalSize<u32>,
 	fullscreen: bool,
+	always_on_top: bool,
+	/// Whether the window was created with an alpha channel, the opacity is ignored otherwise
+	transparent: bool,
+	opacity: f32,
+	click_through: bool,
 	last_mouse_move_update_time: std::time::Instant,
 	unprocessed_move_event: Option<Event>,
 	last_event_invalidated: bool,
@@ -167,6 +257,12 @@ struct WindowData {
 
 	global_event_handlers: Vec<Box<EventHandler>>,
 
+	/// See `Window::set_close_exits`
+	close_exits: bool,
+	closed: bool,
+	/// Hidden with `set_hidden`, which doesn't close the window
+	hidden: bool,
+
 	// Draw data
 	unit_quad_vertices: VertexBuffer<Vertex>,
 	unit_quad_indices: IndexBuffer<u16>,
@@ -190,62 +286,65 @@ impl PartialEq for Window {
 }
 impl Eq for Window {}
 
+/// Information about the OpenGL implementation, see `probe_opengl`
+#[derive(Debug, Clone)]
+pub struct OpenGlInfo {
+	pub vendor: String,
+	pub renderer: String,
+	pub version: String,
+	pub max_texture_size: u32,
+}
+
+/// Creates an invisible window with the same kind of OpenGL context that `Window::try_new`
+/// creates and returns information about the context.
+///
+/// This is meant for diagnostics. It creates its own event loop, and winit only allows creating
+/// one event loop in a process, so no `Application` can be created afterwards.
+pub fn probe_opengl() -> Result<OpenGlInfo, RenderError> {
+	let event_loop = EventLoopBuilder::new().build().map_err(|e| RenderError {
+		description: format!("failed to create the event loop: {}", e),
+	})?;
+	let builder =
+		WindowBuilder::new().with_visible(false).with_inner_size(PhysicalSize::new(64, 64));
+	let (_window, display) = Window::build_winit_window(builder, &event_loop)?;
+	Ok(OpenGlInfo {
+		vendor: display.get_opengl_vendor_string().to_owned(),
+		renderer: display.get_opengl_renderer_string().to_owned(),
+		version: display.get_opengl_version_string().to_owned(),
+		max_texture_size: display.get_capabilities().max_texture_size as u32,
+	})
+}
+
 impl Window {
 	pub fn new<UserEvent: Debug>(
 		application: &mut Application<UserEvent>,
-		mut desc: WindowDescriptor,
+		desc: WindowDescriptor,
 	) -> Rc<Self> {
-		//use glium::glutin::window::Icon;
-		//let exe_parent = std::env::current_exe().unwrap().parent().unwrap().to_owned();
-
-		const MINIMUM_WINDOW_SIZE: u32 = 200;
-		if desc.size.width < MINIMUM_WINDOW_SIZE {
-			warn!("Window width was specified to be zero. Defaulting to {MINIMUM_WINDOW_SIZE} instead");
-			desc.size.width = MINIMUM_WINDOW_SIZE;
-		}
-		if desc.size.height < MINIMUM_WINDOW_SIZE {
-			warn!("Window height was specified to be zero. Defaulting to {MINIMUM_WINDOW_SIZE} instead");
-			desc.size.height = MINIMUM_WINDOW_SIZE;
-		}
+		Self::try_new(application, desc).unwrap()
+	}
 
-		let mut window_builder = WindowBuilder::new()
-			.with_title("Loading")
-			.with_fullscreen(None)
-			.with_window_icon(desc.icon)
-			.with_maximized(desc.maximized);
+	/// Like `new` but returns an error instead of panicking when no suitable OpenGL context can be
+	/// created for the window.
+	pub fn try_new<UserEvent: Debug>(
+		application: &mut Application<UserEvent>,
+		desc: WindowDescriptor,
+	) -> Result<Rc<Self>, RenderError> {
+		let window = Self::build(&application.event_loop, desc)?;
+		application.register_window(window.clone());
+		Ok(window)
+	}
 
-		if !desc.maximized {
-			window_builder = window_builder.with_inner_size(desc.size);
-			if let Some(window_pos) = desc.position {
-				// Check if the window would be placed outside of the screen
-				// (This can happen when using two displays, then disconnecting
-				// one of the displays and starting up emulsion)
-				let in_bounds = application.event_loop.available_monitors().any(|monitor| {
-					debug!("Monitor pos: {:?}", monitor.position());
-					debug!("Monitor size: {:?}", monitor.size());
-					is_in_bounds(monitor.position(), monitor.size(), window_pos)
-				});
-				if in_bounds {
-					window_builder = window_builder.with_position(window_pos);
-				}
-			}
-		}
+	/// Creates the window without registering it with the application, see `WindowOpener`
+	pub(crate) fn build<UserEvent>(
+		event_loop: &EventLoopWindowTarget<UserEvent>,
+		desc: WindowDescriptor,
+	) -> Result<Rc<Self>, RenderError> {
+		//use glium::glutin::window::Icon;
+		//let exe_parent = std::env::current_exe().unwrap().parent().unwrap().to_owned();
 
-		#[cfg(not(any(target_os = "macos", windows)))]
-		let window_builder = if let Some(app_id) = desc.app_id {
-			let is_wayland = std::env::var("XDG_SESSION_TYPE")
-				.map_or(false, |var| var.to_lowercase().contains("wayland"));
-			if is_wayland {
-				WindowBuilderExtWayland::with_name(window_builder, &app_id, app_id.to_lowercase())
-			} else {
-				WindowBuilderExtX11::with_name(window_builder, &app_id, app_id.to_lowercase())
-			}
-		} else {
-			window_builder
-		};
+		let window_builder = desc.window_builder(event_loop);
 
-		// let window = window.build(&application.event_loop).unwrap();
-		let (window, display) = Self::build_winit_window(window_builder, &application.event_loop);
+		let (window, display) = Self::build_winit_window(window_builder, event_loop)?;
 
 		window.set_cursor_icon(CursorIcon::Default);
 
@@ -278,7 +377,7 @@ impl Window {
 				..Default::default()
 			},
 		)
-		.unwrap();
+		.map_err(|e| RenderError { description: format!("failed to compile shaders: {}", e) })?;
 
 		let colored_shadowed_program = shader_from_source(
 			&display,
@@ -289,7 +388,7 @@ impl Window {
 				..Default::default()
 			},
 		)
-		.unwrap();
+		.map_err(|e| RenderError { description: format!("failed to compile shaders: {}", e) })?;
 
 		let colored_program = shader_from_source(
 			&display,
@@ -300,7 +399,7 @@ impl Window {
 				..Default::default()
 			},
 		)
-		.unwrap();
+		.map_err(|e| RenderError { description: format!("failed to compile shaders: {}", e) })?;
 
 		let resulting_window = Rc::new(Window {
 			data: RefCell::new(WindowData {
@@ -308,6 +407,10 @@ impl Window {
 				window,
 				size_before_fullscreen: desc.size,
 				fullscreen: false,
+				always_on_top: desc.always_on_top,
+				transparent: desc.transparent || desc.opacity < 1.0,
+				opacity: desc.opacity.clamp(0.0, 1.0),
+				click_through: false,
 				last_mouse_move_update_time: std::time::Instant::now(),
 				unprocessed_move_event: None,
 				last_event_invalidated: true,
@@ -320,6 +423,10 @@ impl Window {
 
 				global_event_handlers: Vec::new(),
 
+				close_exits: true,
+				closed: false,
+				hidden: false,
+
 				unit_quad_vertices: vertex_buffer,
 				unit_quad_indices: index_buffer,
 				textured_program,
@@ -328,17 +435,21 @@ impl Window {
 			}),
 		});
 
-		application.register_window(resulting_window.clone());
-		resulting_window
+		Ok(resulting_window)
 	}
 
 	/// This is mostly copy-pasted from `glutin::SimpleWindowBuilder::build`
 	/// but I use some custom configuration settings here
 	fn build_winit_window<UserEvent>(
 		builder: WindowBuilder,
-		event_loop: &EventLoop<UserEvent>,
-	) -> (winit::window::Window, Display<WindowSurface>) {
+		event_loop: &EventLoopWindowTarget<UserEvent>,
+	) -> Result<(winit::window::Window, Display<WindowSurface>), RenderError> {
+		fn context_error(stage: &str, error: impl std::fmt::Display) -> RenderError {
+			RenderError { description: format!("failed to {}: {}", stage, error) }
+		}
+
 		// let is_maximized = builder.m
+		let transparent = builder.transparent();
 		// First we start by opening a new Window
 		let display_builder =
 			glutin_winit::DisplayBuilder::new().with_window_builder(Some(builder));
@@ -346,21 +457,24 @@ impl Window {
 		let config_template_builder = glutin::config::ConfigTemplateBuilder::new()
 			.prefer_hardware_accelerated(Some(true))
 			.with_surface_type(ConfigSurfaceTypes::WINDOW)
+			.with_transparency(transparent)
 			.with_api(Api::OPENGL);
 		let (window, gl_config) = display_builder
 			.build(event_loop, config_template_builder, |mut configs| {
-				// Pick the first srgb capable config
+				// Pick the first srgb capable config, which also has to support transparency for
+				// a transparent window
 				let mut target = configs.next().unwrap();
 				for cfg in configs {
-					if target.srgb_capable() {
+					let supports_transparency = target.supports_transparency().unwrap_or(false);
+					if target.srgb_capable() && (supports_transparency || !transparent) {
 						break;
 					}
 					target = cfg;
 				}
 				target
 			})
-			.unwrap();
-		let window = window.unwrap();
+			.map_err(|e| context_error("create the OpenGL display", e))?;
+		let window = window.ok_or_else(|| context_error("create the window", "no window"))?;
 
 		// Now we get the window size to use as the initial size of the Surface
 		let (width, height): (u32, u32) = window.inner_size().into();
@@ -374,8 +488,8 @@ impl Window {
 				);
 
 		// Finally we can create a Surface, use it to make a PossiblyCurrentContext and create the glium Display
-		let surface =
-			unsafe { gl_config.display().create_window_surface(&gl_config, &attrs).unwrap() };
+		let surface = unsafe { gl_config.display().create_window_surface(&gl_config, &attrs) }
+			.map_err(|e| context_error("create the window surface", e))?;
 
 		let context_attributes = glutin::context::ContextAttributesBuilder::new()
 			.with_profile(GlProfile::Core) // requires OpenGL 3.3
@@ -386,10 +500,10 @@ impl Window {
 			gl_config
 				.display()
 				.create_context(&gl_config, &context_attributes)
-				.expect("failed to create context")
+				.map_err(|e| context_error("create the OpenGL 3.3 context", e))?
 		}
 		.make_current(&surface)
-		.unwrap();
+		.map_err(|e| context_error("make the OpenGL context current", e))?;
 
 		// Set up vsync
 		let swap_interval = glutin::surface::SwapInterval::Wait(NonZeroU32::new(1).unwrap());
@@ -397,9 +511,10 @@ impl Window {
 			error!("Failed to set vsync, error returned by set_swap_interval: {}", error);
 		}
 
-		let display = Display::from_context_surface(current_context, surface).unwrap();
+		let display = Display::from_context_surface(current_context, surface)
+			.map_err(|e| context_error("initialize glium", e))?;
 
-		(window, display)
+		Ok((window, display))
 	}
 
 	pub fn add_global_event_handler<F: FnMut(&Window, &WindowEvent) + 'static>(&self, fun: F) {
@@ -496,17 +611,39 @@ impl Window {
 					}
 				}
 				WindowEvent::MouseWheel { delta: native_delta, .. } => {
-					let delta = match native_delta {
-						MouseScrollDelta::LineDelta(x, y) => LogicalVector::new(x, y),
-						MouseScrollDelta::PixelDelta(native_pos) => LogicalVector::new(
-							native_pos.x as f32 / 13.0,
-							native_pos.y as f32 / 8.0,
-						),
+					let kind = match native_delta {
+						MouseScrollDelta::LineDelta(x, y) => {
+							EventKind::MouseScroll { delta: LogicalVector::new(x, y) }
+						}
+						MouseScrollDelta::PixelDelta(native_pos) => {
+							let scaling = borrowed.window.scale_factor() as f32;
+							let delta = LogicalVector::from_physical(native_pos, scaling);
+							EventKind::TouchpadScroll { delta }
+						}
 					};
 					event = Some(Event {
 						cursor_pos: borrowed.cursor_pos,
 						modifiers: borrowed.modifiers,
-						kind: EventKind::MouseScroll { delta },
+						kind,
+					});
+				}
+				WindowEvent::Touch(touch) => {
+					let scaling = borrowed.window.scale_factor() as f32;
+					event = Some(Event {
+						cursor_pos: borrowed.cursor_pos,
+						modifiers: borrowed.modifiers,
+						kind: EventKind::Touch {
+							id: touch.id,
+							phase: touch.phase,
+							pos: LogicalVector::from_physical(touch.location, scaling),
+						},
+					});
+				}
+				WindowEvent::TouchpadMagnify { delta, .. } => {
+					event = Some(Event {
+						cursor_pos: borrowed.cursor_pos,
+						modifiers: borrowed.modifiers,
+						kind: EventKind::TouchpadMagnify { delta: delta as f32 },
 					});
 				}
 				WindowEvent::MouseInput { state, button, .. } => {
@@ -668,6 +805,9 @@ impl Window {
 		// the alpha values into account and blends the framebuffer set by applications
 		// with the rest of the desktop.
 		self.set_alpha_to_1(&mut target, &draw_context);
+		if borrowed.transparent && borrowed.opacity < 1.0 {
+			self.apply_opacity(&mut target, &draw_context, borrowed.opacity);
+		}
 
 		target.finish().unwrap();
 		borrowed.render_validity.make_valid();
@@ -694,34 +834,147 @@ impl Window {
 		borrowed.window.set_fullscreen(monitor);
 	}
 
+	/// Like `set_fullscreen(true)` but on `monitor` instead of the monitor the window is on
+	pub fn set_fullscreen_on(&self, monitor: MonitorHandle) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.fullscreen = true;
+		borrowed.size_before_fullscreen = borrowed.window.inner_size();
+		borrowed.window.set_fullscreen(Some(Fullscreen::Borderless(Some(monitor))));
+	}
+
 	pub fn set_maximized(&self, maximized: bool) {
 		self.data.borrow_mut().window.set_maximized(maximized);
 	}
 
+	pub fn opacity(&self) -> f32 {
+		self.data.borrow().opacity
+	}
+
+	/// See `WindowDescriptor::opacity`
+	pub fn set_opacity(&self, opacity: f32) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.opacity = opacity.clamp(0.0, 1.0);
+		borrowed.render_validity.invalidate();
+	}
+
+	/// False when the window can't be made translucent, see `WindowDescriptor::transparent`
+	pub fn is_transparent(&self) -> bool {
+		self.data.borrow().transparent
+	}
+
+	pub fn click_through(&self) -> bool {
+		self.data.borrow().click_through
+	}
+
+	/// A click-through window ignores the mouse, which goes to the windows behind it instead. It
+	/// can still be controlled with the keyboard while it's focused.
+	pub fn set_click_through(&self, click_through: bool) {
+		let mut borrowed = self.data.borrow_mut();
+		if let Err(e) = borrowed.window.set_cursor_hittest(!click_through) {
+			warn!("Could not make the window ignore the mouse: {}", e);
+			return;
+		}
+		borrowed.click_through = click_through;
+	}
+
+	pub fn always_on_top(&self) -> bool {
+		self.data.borrow().always_on_top
+	}
+
+	/// Asks the window manager to keep the window above the other windows. Some window managers
+	/// ignore this, for example the ones of Wayland.
+	pub fn set_always_on_top(&self, always_on_top: bool) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.always_on_top = always_on_top;
+		let level = if always_on_top { WindowLevel::AlwaysOnTop } else { WindowLevel::Normal };
+		borrowed.window.set_window_level(level);
+	}
+
+	/// When `close_exits` is false, closing the window only closes this window instead of exiting
+	/// the application. It's true by default.
+	pub fn set_close_exits(&self, close_exits: bool) {
+		self.data.borrow_mut().close_exits = close_exits;
+	}
+
+	/// Closes the window, or exits the application unless `set_close_exits(false)` was called. The
+	/// window is hidden right away and the application drops it after the current event.
+	pub fn close(&self) {
+		let mut borrowed = self.data.borrow_mut();
+		if borrowed.close_exits {
+			request_exit();
+		} else {
+			borrowed.closed = true;
+			borrowed.window.set_visible(false);
+		}
+	}
+
+	pub fn is_closed(&self) -> bool {
+		self.data.borrow().closed
+	}
+
+	/// Restores the window if it's minimized and gives it the keyboard focus
+	pub fn bring_to_front(&self) {
+		let borrowed = self.data.borrow();
+		borrowed.window.set_minimized(false);
+		borrowed.window.focus_window();
+	}
+
+	pub fn is_hidden(&self) -> bool {
+		self.data.borrow().hidden
+	}
+
+	/// Hides the window without closing it, for example to minimize it to the tray. Showing it
+	/// again brings it to the front. Some window managers ignore this, like the ones of Wayland.
+	pub fn set_hidden(&self, hidden: bool) {
+		{
+			let mut borrowed = self.data.borrow_mut();
+			borrowed.hidden = hidden;
+			borrowed.window.set_visible(!hidden);
+		}
+		if !hidden {
+			self.bring_to_front();
+		}
+	}
+
 	/// Sets the alpha values by drawing a quad covering the entire framebuffer
 	/// with a blending mode set to max and a shader that draws (0,0,0,1) values
 	fn set_alpha_to_1(&self, target: &mut Frame, context: &DrawContext) {
 		let transform = Matrix4::from_scale(2.0);
 		let transform = Matrix4::from_translation(Vector3::new(-1.0, -1.0, 0.0)) * transform;
-		let image_draw_params = glium::DrawParameters {
-			blend: Blend {
-				color: BlendingFunction::Max,
-				alpha: BlendingFunction::Max,
-				..Default::default()
+		GliumRenderer::new(target, context)
+			.draw_color(transform, [0.0, 0.0, 0.0, 1.0], BlendMode::Max)
+			.unwrap();
+	}
+
+	/// Multiplies the colors and the alpha values of the framebuffer by `opacity`. The colors are
+	/// multiplied too because the compositors expect them to be premultiplied by the alpha.
+	fn apply_opacity(&self, target: &mut Frame, context: &DrawContext, opacity: f32) {
+		use glium::{Blend, BlendingFunction, LinearBlendingFactor};
+		let transform = Matrix4::from_scale(2.0);
+		let transform = Matrix4::from_translation(Vector3::new(-1.0, -1.0, 0.0)) * transform;
+		let blend = Blend {
+			color: BlendingFunction::Addition {
+				source: LinearBlendingFactor::Zero,
+				destination: LinearBlendingFactor::SourceColor,
+			},
+			alpha: BlendingFunction::Addition {
+				source: LinearBlendingFactor::Zero,
+				destination: LinearBlendingFactor::SourceAlpha,
 			},
 			..Default::default()
 		};
-		let uniforms = uniform! {
+		let uniforms = glium::uniform! {
 			matrix: Into::<[[f32; 4]; 4]>::into(transform),
-			color: [0.0f32, 0.0, 0.0, 1.0],
+			color: [opacity; 4],
 		};
+		let draw_params = glium::DrawParameters { blend, ..Default::default() };
 		target
 			.draw(
 				context.unit_quad_vertices,
 				context.unit_quad_indices,
 				context.colored_program,
 				&uniforms,
-				&image_draw_params,
+				&draw_params,
 			)
 			.unwrap();
 	}